@@ -199,6 +199,19 @@ pub async fn export_accounts(account_ids: Vec<String>) -> Result<AccountExportRe
         .unwrap_or_else(|_| Err("Task panicked".to_string()))
 }
 
+/// 导出口令加密的便携账号数据（可在不同设备间导入）
+#[tauri::command]
+pub async fn export_accounts_encrypted(
+    account_ids: Vec<String>,
+    passphrase: String,
+) -> Result<String, String> {
+    tokio::task::spawn_blocking(move || {
+        modules::account_transfer::export_accounts(&account_ids, &passphrase)
+    })
+    .await
+    .unwrap_or_else(|_| Err("Task panicked".to_string()))
+}
+
 /// 内部辅助功能：在添加或导入账号后自动刷新一次额度
 async fn internal_refresh_account_quota(
     app: &tauri::AppHandle,
@@ -399,70 +412,16 @@ pub async fn save_config(
     proxy_state: tauri::State<'_, crate::commands::proxy::ProxyServiceState>,
     config: AppConfig,
 ) -> Result<(), String> {
+    // 托管字段 (环境变量 / 声明式配置文件) 以托管值为准
+    let config = modules::managed_config::apply(config)?;
     modules::save_app_config(&config)?;
 
     // 通知托盘配置已更新
     let _ = app.emit("config://updated", ());
 
-    // 热更新正在运行的服务
-    let instance_lock = proxy_state.instance.read().await;
-    if let Some(instance) = instance_lock.as_ref() {
-        // 更新模型映射
-        instance.axum_server.update_mapping(&config.proxy).await;
-        // 更新仅暴露真实配额模型开关
-        instance
-            .axum_server
-            .update_only_raw_quota_models(config.proxy.only_raw_quota_models)
-            .await;
-        // 更新上游代理
-        instance
-            .axum_server
-            .update_proxy(config.proxy.upstream_proxy.clone())
-            .await;
-        // 更新安全策略 (auth)
-        instance.axum_server.update_security(&config.proxy).await;
-        // 更新 z.ai 配置
-        instance.axum_server.update_zai(&config.proxy).await;
-        // 更新实验性配置
-        instance
-            .axum_server
-            .update_experimental(&config.proxy)
-            .await;
-        // 更新调试日志配置
-        instance
-            .axum_server
-            .update_debug_logging(&config.proxy)
-            .await;
-        // [NEW] 更新 User-Agent 配置
-        instance.axum_server.update_user_agent(&config.proxy).await;
-        // 更新 Thinking Budget 配置
-        crate::proxy::update_thinking_budget_config(config.proxy.thinking_budget.clone());
-        // [NEW] 更新全局系统提示词配置
-        crate::proxy::update_global_system_prompt_config(config.proxy.global_system_prompt.clone());
-        // [NEW] 更新全局图像思维模式配置
-        crate::proxy::update_image_thinking_mode(config.proxy.image_thinking_mode.clone());
-        // [NEW] 更新全局压缩等级配置
-        crate::proxy::config::update_global_compression_level(
-            config.proxy.experimental.compression_level.clone(),
-            config.proxy.experimental.enable_usage_scaling,
-        );
-        crate::proxy::config::update_global_thresholds(
-            config.proxy.experimental.context_compression_threshold_l1,
-            config.proxy.experimental.context_compression_threshold_l2,
-            config.proxy.experimental.context_compression_threshold_l3,
-        );
-        // 更新代理池配置
-        instance
-            .axum_server
-            .update_proxy_pool(config.proxy.proxy_pool.clone())
-            .await;
-        // 更新熔断配置
-        instance
-            .token_manager
-            .update_circuit_breaker_config(config.circuit_breaker.clone())
-            .await;
-        tracing::debug!("已同步热更新反代服务配置");
-    }
+    // 热更新服务 (管理服务器常驻，未启动反代时同样同步，监听地址变化时重新绑定)
+    crate::commands::proxy::apply_saved_config(&proxy_state, config).await?;
+    tracing::debug!("已同步热更新反代服务配置");
 
     Ok(())
 }
@@ -576,6 +535,29 @@ pub async fn import_v1_accounts(
     Ok(accounts)
 }
 
+#[tauri::command]
+pub async fn import_accounts_encrypted(
+    app: tauri::AppHandle,
+    proxy_state: tauri::State<'_, crate::commands::proxy::ProxyServiceState>,
+    data: String,
+    passphrase: String,
+) -> Result<modules::account_transfer::PortableImportResult, String> {
+    let result = tokio::task::spawn_blocking(move || {
+        modules::account_transfer::import_accounts(&data, &passphrase)
+    })
+    .await
+    .unwrap_or_else(|_| Err("Task panicked".to_string()))?;
+
+    // 导入已写入代理池配置，重新加载后热更新代理池与绑定
+    let app_config = modules::load_app_config()?;
+    crate::commands::proxy::apply_saved_config(&proxy_state, app_config).await?;
+
+    crate::modules::tray::update_tray_menus(&app);
+    let _ = crate::commands::proxy::reload_proxy_accounts(proxy_state).await;
+
+    Ok(result)
+}
+
 #[tauri::command]
 pub async fn import_from_db(
     app: tauri::AppHandle,
@@ -1210,6 +1192,17 @@ pub async fn get_token_stats_account_trend_daily(
     crate::modules::token_stats::get_account_trend_daily(days)
 }
 
+#[tauri::command]
+pub async fn get_token_cost_report(
+    query: crate::modules::chargeback::CostQuery,
+) -> Result<crate::modules::chargeback::CostReport, String> {
+    let plan = crate::modules::chargeback::plan(&query)?;
+    let pricing = crate::modules::config::load_app_config()?.proxy.pricing;
+    tokio::task::spawn_blocking(move || crate::modules::chargeback::cost_report(&plan, &pricing))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
 #[tauri::command]
 pub async fn query_transit_info(url: String, key: String) -> Result<String, String> {
     let client = reqwest::Client::builder()
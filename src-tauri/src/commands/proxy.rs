@@ -169,7 +169,8 @@ pub async fn internal_start_proxy_service(
     if active_accounts == 0 {
         let zai_enabled = config.zai.enabled
             && !matches!(config.zai.dispatch_mode, crate::proxy::ZaiDispatchMode::Off);
-        if !zai_enabled {
+        let providers_enabled = config.providers.iter().any(|p| p.enabled);
+        if !zai_enabled && !providers_enabled {
             tracing::warn!("沒有可用賬號，反代邏輯將暫停，請通過管理界面添加。");
             return Ok(ProxyStatus {
                 running: false,
@@ -240,23 +241,19 @@ pub async fn ensure_admin_server(
     // [NEW] 加载账号数据，否则管理界面统计为 0
     let _ = token_manager.load_accounts().await;
 
+    // 完整配置快照 (供热更新比较与分发)
+    let mut app_config = crate::modules::config::load_app_config()
+        .unwrap_or_else(|_| crate::models::AppConfig::new());
+    app_config.proxy = config.clone();
+
     let (axum_server, server_handle) = match crate::proxy::AxumServer::start(
         config.get_bind_address().to_string(),
         config.port,
         token_manager,
-        config.custom_mapping.clone(),
-        config.request_timeout,
-        config.upstream_proxy.clone(),
-        config.user_agent_override.clone(),
-        crate::proxy::ProxySecurityConfig::from_proxy_config(&config),
-        config.zai.clone(),
         monitor,
-        config.experimental.clone(),
-        config.debug_logging.clone(),
         integration.clone(),
         cloudflared_state,
-        config.proxy_pool.clone(),
-        config.only_raw_quota_models,
+        app_config,
     )
     .await
     {
@@ -280,6 +277,8 @@ pub async fn ensure_admin_server(
         config.experimental.compression_level.clone(),
         config.experimental.enable_usage_scaling,
     );
+    // [NEW] 初始化 OpenTelemetry 链路追踪导出
+    crate::proxy::telemetry::apply_config(&config.telemetry);
 
     Ok(())
 }
@@ -297,6 +296,8 @@ pub async fn stop_proxy_service(state: State<'_, ProxyServiceState>) -> Result<(
     if let Some(instance) = instance_lock.take() {
         instance.token_manager.abort_background_tasks().await;
         instance.axum_server.set_running(false).await;
+        // 导出已缓冲的链路 span (阻塞调用)
+        let _ = tokio::task::spawn_blocking(crate::proxy::telemetry::force_flush).await;
         // 已移除 instance.axum_server.stop() 调用，防止杀死 Admin Server
     }
 
@@ -429,6 +430,22 @@ pub async fn export_proxy_logs(file_path: String) -> Result<usize, String> {
     Ok(count)
 }
 
+/// 按条件流式导出审计日志到文件 (JSONL / CSV)，返回行数
+#[tauri::command]
+pub async fn export_audit_log(
+    query: crate::modules::audit_export::ExportQuery,
+    file_path: String,
+) -> Result<u64, String> {
+    tokio::task::spawn_blocking(move || {
+        let plan = crate::modules::audit_export::plan(&query)?;
+        let file = std::fs::File::create(&file_path)
+            .map_err(|e| format!("Failed to create file: {}", e))?;
+        crate::modules::audit_export::write_export(&plan, &mut std::io::BufWriter::new(file))
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
 /// 导出指定的日志JSON到文件
 #[tauri::command]
 pub async fn export_proxy_logs_json(file_path: String, json_data: String) -> Result<usize, String> {
@@ -501,23 +518,41 @@ pub async fn update_model_mapping(
     config: ProxyConfig,
     state: State<'_, ProxyServiceState>,
 ) -> Result<(), String> {
-    let instance_lock = state.instance.read().await;
-
-    // 1. 如果服务正在运行，立即更新内存中的映射 (这里目前只更新了 anthropic_mapping 的 RwLock,
-    // 后续可以根据需要让 resolve_model_route 直接读取全量 config)
-    if let Some(instance) = instance_lock.as_ref() {
-        instance.axum_server.update_mapping(&config).await;
-        tracing::debug!("后端服务已接收全量模型映射配置");
-    }
-
-    // 2. 无论是否运行，都保存到全局配置持久化
+    // 1. 无论是否运行，都保存到全局配置持久化
     let mut app_config = crate::modules::config::load_app_config().map_err(|e| e)?;
     app_config.proxy.custom_mapping = config.custom_mapping;
+    app_config.proxy.model_fallback_chains = config.model_fallback_chains;
     crate::modules::config::save_app_config(&app_config).map_err(|e| e)?;
 
+    // 2. 热更新运行中的服务 (原子替换配置快照)
+    apply_saved_config(&state, app_config).await?;
+    tracing::debug!("后端服务已接收全量模型映射配置");
+
     Ok(())
 }
 
+/// 将已保存的配置热更新到常驻服务
+///
+/// 管理服务器常驻，未启动反代时同样同步；监听地址变化时重新绑定，失败原因作为错误返回
+pub async fn apply_saved_config(
+    state: &ProxyServiceState,
+    config: crate::models::AppConfig,
+) -> Result<(), String> {
+    let admin_lock = state.admin_server.read().await;
+    let Some(admin) = admin_lock.as_ref() else {
+        return Ok(());
+    };
+    let proxy_config = config.proxy.clone();
+    let report = admin.axum_server.apply_config(config).await?;
+    if let Some(instance) = state.instance.write().await.as_mut() {
+        instance.config = proxy_config;
+    }
+    match report.rebind_error {
+        Some(error) => Err(error),
+        None => Ok(()),
+    }
+}
+
 fn join_base_url(base: &str, path: &str) -> String {
     let base = base.trim_end_matches('/');
     let path = if path.starts_with('/') {
@@ -769,14 +804,11 @@ pub async fn check_proxy_health(
 ) -> Result<ProxyPoolConfig, String> {
     let instance_lock = state.instance.read().await;
     if let Some(instance) = instance_lock.as_ref() {
-        let pool_state = instance.axum_server.proxy_pool_state.clone();
-        let manager = crate::proxy::proxy_pool::ProxyPoolManager::new(pool_state.clone());
-
+        let manager = &instance.axum_server.proxy_pool_manager;
         manager.health_check().await?;
 
         // Return the updated config from memory
-        let config = pool_state.read().await;
-        Ok(config.clone())
+        Ok(manager.pool_config())
     } else {
         Err("服务未运行".to_string())
     }
@@ -789,8 +821,7 @@ pub async fn get_proxy_pool_config(
 ) -> Result<ProxyPoolConfig, String> {
     let instance_lock = state.instance.read().await;
     if let Some(instance) = instance_lock.as_ref() {
-        let config = instance.axum_server.proxy_pool_state.read().await;
-        Ok(config.clone())
+        Ok(instance.axum_server.proxy_pool_manager.pool_config())
     } else {
         Err("服务未运行".to_string())
     }
@@ -1,4 +1,4 @@
-use crate::modules::user_token_db::{self, TokenIpBinding, UserToken};
+use crate::modules::user_token_db::{self, TokenConsumption, TokenIpBinding, UserToken};
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -10,6 +10,14 @@ pub struct CreateTokenRequest {
     pub curfew_start: Option<String>,
     pub curfew_end: Option<String>,
     pub custom_expires_at: Option<i64>, // 自定义过期时间戳 (秒)
+    #[serde(default)]
+    pub rpm_limit: Option<i32>,
+    #[serde(default)]
+    pub daily_token_limit: Option<i64>,
+    #[serde(default)]
+    pub monthly_token_limit: Option<i64>,
+    #[serde(default)]
+    pub allowed_models: Option<Vec<String>>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -20,20 +28,60 @@ pub struct UpdateTokenRequest {
     pub max_ips: Option<i32>,
     pub curfew_start: Option<Option<String>>,
     pub curfew_end: Option<Option<String>>,
+    // 限额字段: 缺省 = 保持不变, null = 清除 (不限)
+    #[serde(default, deserialize_with = "double_option")]
+    pub rpm_limit: Option<Option<i32>>,
+    #[serde(default, deserialize_with = "double_option")]
+    pub daily_token_limit: Option<Option<i64>>,
+    #[serde(default, deserialize_with = "double_option")]
+    pub monthly_token_limit: Option<Option<i64>>,
+    #[serde(default, deserialize_with = "double_option")]
+    pub allowed_models: Option<Option<Vec<String>>>,
+}
+
+/// 区分 "字段缺省" 与 "显式 null"：出现的字段 (含 null) 包装为 Some
+fn double_option<'de, T, D>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    T: Deserialize<'de>,
+    D: serde::Deserializer<'de>,
+{
+    Option::<T>::deserialize(deserializer).map(Some)
+}
+
+/// 令牌信息 + 当前消耗 (用于展示用量与限额对比)
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UserTokenWithUsage {
+    #[serde(flatten)]
+    pub token: UserToken,
+    pub usage: TokenConsumption,
 }
 
 // 命令实现
 
-/// 列出所有令牌
+/// 列出所有令牌 (附带当前消耗)
+#[tauri::command]
+pub async fn list_user_tokens() -> Result<Vec<UserTokenWithUsage>, String> {
+    let tokens = user_token_db::list_tokens()?;
+    let mut result = Vec::with_capacity(tokens.len());
+    for token in tokens {
+        let usage = user_token_db::get_token_consumption(&token.id).unwrap_or_default();
+        result.push(UserTokenWithUsage { token, usage });
+    }
+    Ok(result)
+}
+
+/// 获取单个令牌的限额与当前消耗
 #[tauri::command]
-pub async fn list_user_tokens() -> Result<Vec<UserToken>, String> {
-    user_token_db::list_tokens()
+pub async fn get_user_token_usage(id: String) -> Result<UserTokenWithUsage, String> {
+    let token = user_token_db::get_token_by_id(&id)?.ok_or("Token not found")?;
+    let usage = user_token_db::get_token_consumption(&id)?;
+    Ok(UserTokenWithUsage { token, usage })
 }
 
 /// 创建新令牌
 #[tauri::command]
 pub async fn create_user_token(request: CreateTokenRequest) -> Result<UserToken, String> {
-    user_token_db::create_token(
+    let token = user_token_db::create_token(
         request.username,
         request.expires_type,
         request.description,
@@ -41,7 +89,23 @@ pub async fn create_user_token(request: CreateTokenRequest) -> Result<UserToken,
         request.curfew_start,
         request.curfew_end,
         request.custom_expires_at,
-    )
+    )?;
+
+    if request.rpm_limit.is_none()
+        && request.daily_token_limit.is_none()
+        && request.monthly_token_limit.is_none()
+        && request.allowed_models.is_none()
+    {
+        return Ok(token);
+    }
+    user_token_db::update_token_limits(
+        &token.id,
+        request.rpm_limit.map(Some),
+        request.daily_token_limit.map(Some),
+        request.monthly_token_limit.map(Some),
+        request.allowed_models.map(Some),
+    )?;
+    user_token_db::get_token_by_id(&token.id)?.ok_or_else(|| "Token not found".to_string())
 }
 
 /// 更新令牌
@@ -55,6 +119,13 @@ pub async fn update_user_token(id: String, request: UpdateTokenRequest) -> Resul
         request.max_ips,
         request.curfew_start,
         request.curfew_end,
+    )?;
+    user_token_db::update_token_limits(
+        &id,
+        request.rpm_limit,
+        request.daily_token_limit,
+        request.monthly_token_limit,
+        request.allowed_models,
     )
 }
 
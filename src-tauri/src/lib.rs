@@ -210,6 +210,11 @@ pub fn run() {
     // Initialize logger
     logger::init_logger();
 
+    // 命令行备份/恢复 (--backup <file> / --restore <file>)，执行后直接退出
+    if let Some(code) = modules::backup::run_cli(&args) {
+        std::process::exit(code);
+    }
+
     #[cfg(target_os = "linux")]
     configure_linux_gdk_backend();
 
@@ -218,6 +223,11 @@ pub fn run() {
         error!("Failed to initialize token stats database: {}", e);
     }
 
+    // Initialize quota history database
+    if let Err(e) = modules::quota_history::init_db() {
+        error!("Failed to initialize quota history database: {}", e);
+    }
+
     // Initialize security database
     if let Err(e) = modules::security_db::init_db() {
         error!("Failed to initialize security database: {}", e);
@@ -228,6 +238,14 @@ pub fn run() {
         error!("Failed to initialize user token database: {}", e);
     }
 
+    // 命令行子命令 (accounts / tokens / stats / proxy / config)
+    let serve_overrides = match modules::cli::run_cli(&args) {
+        Some(modules::cli::CliOutcome::Exit(code)) => std::process::exit(code),
+        Some(modules::cli::CliOutcome::Serve(overrides)) => Some(overrides),
+        None => None,
+    };
+    let is_headless = is_headless || serve_overrides.is_some();
+
     if is_headless {
         info!("Starting in HEADLESS mode...");
 
@@ -349,7 +367,18 @@ pub fn run() {
                         }
                     }
 
+                    // CLI `proxy start --port/--bind` 覆盖项仅对本次运行生效，不写回配置
+                    if let Some(overrides) = serve_overrides.as_ref().filter(|o| !o.is_empty()) {
+                        overrides.apply(&mut config.proxy);
+                        info!(
+                            "Command-line override: listening on {}:{}",
+                            config.proxy.get_bind_address(),
+                            config.proxy.port
+                        );
+                    }
+
                     // Start proxy service
+                    let control_addr = modules::cli::control_address(&config.proxy);
                     if let Err(e) = commands::proxy::internal_start_proxy_service(
                         config.proxy,
                         &proxy_state,
@@ -361,6 +390,7 @@ pub fn run() {
                     }
 
                     info!("Headless proxy service is running.");
+                    modules::cli::write_pid_file(&control_addr);
 
                     // Start smart scheduler for 7-day weekly reset warmup
                     modules::scheduler::start_scheduler(None, proxy_state.clone());
@@ -372,9 +402,15 @@ pub fn run() {
                 }
             }
 
-            // Wait for Ctrl-C
-            tokio::signal::ctrl_c().await.ok();
+            // Wait for Ctrl-C / SIGTERM / 管理接口停止请求 (`proxy stop`)
+            modules::cli::wait_for_shutdown_signal().await;
+            modules::cli::remove_pid_file();
             info!("Headless mode shutting down");
+            // 排空进行中的请求 (SSE / WebSocket) 后再退出
+            let admin_lock = proxy_state.admin_server.read().await;
+            if let Some(admin) = admin_lock.as_ref() {
+                admin.axum_server.drain_and_stop().await;
+            }
         });
         return;
     }
@@ -529,6 +565,7 @@ pub fn run() {
             commands::reorder_accounts,
             commands::switch_account,
             commands::export_accounts,
+            commands::export_accounts_encrypted,
             // Device fingerprint
             commands::get_device_profiles,
             commands::bind_device_profile,
@@ -558,6 +595,7 @@ pub fn run() {
             commands::set_active_oauth_client,
             commands::import_v1_accounts,
             commands::import_from_db,
+            commands::import_accounts_encrypted,
             commands::import_custom_db,
             commands::sync_account_from_db,
             commands::save_text_file,
@@ -592,6 +630,7 @@ pub fn run() {
             commands::proxy::get_proxy_logs_count,
             commands::proxy::export_proxy_logs,
             commands::proxy::export_proxy_logs_json,
+            commands::proxy::export_audit_log,
             commands::proxy::get_proxy_logs_count_filtered,
             commands::proxy::get_proxy_logs_filtered,
             commands::proxy::set_proxy_monitor_enabled,
@@ -636,6 +675,7 @@ pub fn run() {
             commands::get_token_stats_model_trend_daily,
             commands::get_token_stats_account_trend_hourly,
             commands::get_token_stats_account_trend_daily,
+            commands::get_token_cost_report,
             proxy::cli_sync::get_cli_sync_status,
             proxy::cli_sync::execute_cli_sync,
             proxy::cli_sync::execute_cli_restore,
@@ -687,6 +727,7 @@ pub fn run() {
             commands::user_token::renew_user_token,
             commands::user_token::get_token_ip_bindings,
             commands::user_token::get_user_token_summary,
+            commands::user_token::get_user_token_usage,
             commands::query_transit_info,
             // Patch commands
             commands::patch_agy_binary,
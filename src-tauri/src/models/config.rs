@@ -32,6 +32,9 @@ pub struct AppConfig {
     pub hidden_menu_items: Vec<String>, // Hidden menu item path list
     #[serde(default)]
     pub cloudflared: CloudflaredConfig, // [NEW] Cloudflared configuration
+    /// 由环境变量 / 声明式配置文件托管的字段 (只读，不写入 gui_config.json)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub managed_fields: Vec<String>,
 }
 
 /// Scheduled warmup configuration
@@ -192,6 +195,7 @@ impl AppConfig {
             circuit_breaker: CircuitBreakerConfig::default(),
             hidden_menu_items: Vec::new(),
             cloudflared: CloudflaredConfig::default(),
+            managed_fields: Vec::new(),
         }
     }
 }
@@ -417,6 +417,13 @@ mod tests {
 /// Global account write lock to prevent corruption during concurrent operations
 static ACCOUNT_INDEX_LOCK: Lazy<Mutex<()>> = Lazy::new(|| Mutex::new(()));
 
+/// Hold the account write lock (used by backup/restore to snapshot accounts consistently)
+pub(crate) fn lock_accounts() -> Result<std::sync::MutexGuard<'static, ()>, String> {
+    ACCOUNT_INDEX_LOCK
+        .lock()
+        .map_err(|e| format!("failed_to_acquire_lock: {}", e))
+}
+
 // ... existing constants ...
 const DATA_DIR: &str = ".antigravity_tools";
 const ACCOUNTS_INDEX: &str = "accounts.json";
@@ -1569,6 +1576,18 @@ pub fn update_account_quota(account_id: &str, quota: QuotaData) -> Result<(), St
     // Save account first
     save_account(&account)?;
 
+    // 记录配额历史快照 (用于消耗速率与耗尽预测)
+    if let Some(ref q) = account.quota {
+        if let Err(e) =
+            crate::modules::quota_history::record_snapshot(&account.id, &account.email, q)
+        {
+            crate::modules::logger::log_warn(&format!(
+                "[Quota] Failed to record quota history for {}: {}",
+                account.email, e
+            ));
+        }
+    }
+
     // [FIX] 同时更新索引文件中的摘要信息，确保列表页图标即时刷新
     {
         let _lock = ACCOUNT_INDEX_LOCK
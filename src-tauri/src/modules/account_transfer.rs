@@ -0,0 +1,362 @@
+//! 跨设备账号迁移 (口令加密的便携导出格式)
+//!
+//! `utils::crypto` 的字段加密密钥与本机 `machine_uid` 绑定，无法在设备间迁移。
+//! 这里将账号 (Token、设备指纹、标签、代理绑定) 及其绑定的代理条目序列化为带版本号的
+//! JSON，使用用户口令 (Argon2id 派生) + AES-256-GCM 加密后以 Base64 文本传输，
+//! GCM 认证标签同时作为完整性校验。代理密码在加密载荷内以明文保存，导入后再按
+//! 目标机器的密钥重新加密落盘。
+
+use base64::{engine::general_purpose, Engine as _};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::models::{DeviceProfile, DeviceProfileVersion, TokenData};
+use crate::modules::{account, config};
+use crate::proxy::config::{ProxyAuth, ProxyEntry, ProxyPoolConfig};
+use crate::utils::crypto;
+
+/// 当前导出格式版本
+pub const PORTABLE_FORMAT_VERSION: u32 = 1;
+
+/// 导出的单个账号
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PortableAccount {
+    pub email: String,
+    pub name: Option<String>,
+    pub token: TokenData,
+    #[serde(default)]
+    pub device_profile: Option<DeviceProfile>,
+    #[serde(default)]
+    pub device_history: Vec<DeviceProfileVersion>,
+    #[serde(default)]
+    pub custom_label: Option<String>,
+    /// 绑定的代理 ID (对应 `PortableBundle::proxies`)
+    #[serde(default)]
+    pub proxy_id: Option<String>,
+    #[serde(default)]
+    pub proxy_bound_at: Option<i64>,
+}
+
+/// 导出的代理条目 (密码为明文，仅存在于加密载荷内)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PortableProxy {
+    pub id: String,
+    pub name: String,
+    pub url: String,
+    #[serde(default)]
+    pub username: Option<String>,
+    #[serde(default)]
+    pub password: Option<String>,
+    pub enabled: bool,
+    pub priority: i32,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub max_accounts: Option<usize>,
+    #[serde(default)]
+    pub health_check_url: Option<String>,
+}
+
+impl From<&ProxyEntry> for PortableProxy {
+    fn from(entry: &ProxyEntry) -> Self {
+        Self {
+            id: entry.id.clone(),
+            name: entry.name.clone(),
+            url: entry.url.clone(),
+            username: entry.auth.as_ref().map(|a| a.username.clone()),
+            password: entry.auth.as_ref().map(|a| a.password.clone()),
+            enabled: entry.enabled,
+            priority: entry.priority,
+            tags: entry.tags.clone(),
+            max_accounts: entry.max_accounts,
+            health_check_url: entry.health_check_url.clone(),
+        }
+    }
+}
+
+impl From<PortableProxy> for ProxyEntry {
+    fn from(proxy: PortableProxy) -> Self {
+        let auth = proxy.username.map(|username| ProxyAuth {
+            username,
+            password: proxy.password.unwrap_or_default(),
+        });
+        Self {
+            id: proxy.id,
+            name: proxy.name,
+            url: proxy.url,
+            auth,
+            enabled: proxy.enabled,
+            priority: proxy.priority,
+            tags: proxy.tags,
+            max_accounts: proxy.max_accounts,
+            health_check_url: proxy.health_check_url,
+            last_check_time: None,
+            is_healthy: true,
+            latency: None,
+        }
+    }
+}
+
+/// 加密前的导出内容
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PortableBundle {
+    pub format_version: u32,
+    pub app_version: String,
+    pub exported_at: i64,
+    pub accounts: Vec<PortableAccount>,
+    #[serde(default)]
+    pub proxies: Vec<PortableProxy>,
+}
+
+/// 导入结果
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PortableImportResult {
+    pub format_version: u32,
+    pub exported_at: i64,
+    /// 导入 (新增或更新) 后的本机账号 ID
+    pub account_ids: Vec<String>,
+    pub proxies_added: usize,
+    pub bindings_restored: usize,
+}
+
+/// 根据账号与代理池配置构建导出内容，只包含被选中账号实际绑定的代理
+pub fn build_bundle(accounts: &[crate::models::Account], pool: &ProxyPoolConfig) -> PortableBundle {
+    let mut proxies: Vec<PortableProxy> = Vec::new();
+    let accounts = accounts
+        .iter()
+        .map(|acc| {
+            let proxy_id = pool
+                .account_bindings
+                .get(&acc.id)
+                .cloned()
+                .or_else(|| acc.proxy_id.clone())
+                .filter(|id| pool.proxies.iter().any(|p| &p.id == id));
+            if let Some(id) = &proxy_id {
+                if !proxies.iter().any(|p| &p.id == id) {
+                    if let Some(entry) = pool.proxies.iter().find(|p| &p.id == id) {
+                        proxies.push(PortableProxy::from(entry));
+                    }
+                }
+            }
+            PortableAccount {
+                email: acc.email.clone(),
+                name: acc.name.clone(),
+                token: acc.token.clone(),
+                device_profile: acc.device_profile.clone(),
+                device_history: acc.device_history.clone(),
+                custom_label: acc.custom_label.clone(),
+                proxy_bound_at: proxy_id.as_ref().and(acc.proxy_bound_at),
+                proxy_id,
+            }
+        })
+        .collect();
+
+    PortableBundle {
+        format_version: PORTABLE_FORMAT_VERSION,
+        app_version: env!("CARGO_PKG_VERSION").to_string(),
+        exported_at: chrono::Utc::now().timestamp(),
+        accounts,
+        proxies,
+    }
+}
+
+/// 序列化并用口令加密，返回 Base64 文本
+pub fn seal_bundle(bundle: &PortableBundle, passphrase: &str) -> Result<String, String> {
+    if passphrase.is_empty() {
+        return Err("Passphrase must not be empty".to_string());
+    }
+    let json = serde_json::to_vec(bundle)
+        .map_err(|e| format!("Failed to serialize account bundle: {}", e))?;
+    let sealed = crypto::encrypt_with_passphrase(&json, passphrase)?;
+    Ok(general_purpose::STANDARD.encode(sealed))
+}
+
+/// 是否为口令加密的导出文本 (Base64 编码的加密数据)
+pub fn is_sealed_bundle(data: &str) -> bool {
+    general_purpose::STANDARD
+        .decode(data.trim())
+        .is_ok_and(|sealed| crypto::is_passphrase_encrypted(&sealed))
+}
+
+/// 解码、校验并解密导出文本
+pub fn open_bundle(data: &str, passphrase: &str) -> Result<PortableBundle, String> {
+    let sealed = general_purpose::STANDARD
+        .decode(data.trim())
+        .map_err(|e| format!("Invalid export data (not base64): {}", e))?;
+    if !crypto::is_passphrase_encrypted(&sealed) {
+        return Err("Invalid export data: not a passphrase-encrypted account export".to_string());
+    }
+    let json = crypto::decrypt_with_passphrase(&sealed, passphrase)?;
+    let bundle: PortableBundle =
+        serde_json::from_slice(&json).map_err(|e| format!("Invalid account bundle: {}", e))?;
+    if bundle.format_version > PORTABLE_FORMAT_VERSION {
+        return Err(format!(
+            "Account export format version {} is newer than supported version {}; please upgrade",
+            bundle.format_version, PORTABLE_FORMAT_VERSION
+        ));
+    }
+    Ok(bundle)
+}
+
+/// 导出指定账号为口令加密文本
+pub fn export_accounts(account_ids: &[String], passphrase: &str) -> Result<String, String> {
+    let accounts: Vec<_> = account::list_accounts()?
+        .into_iter()
+        .filter(|acc| account_ids.contains(&acc.id))
+        .collect();
+    if accounts.is_empty() {
+        return Err("No matching accounts to export".to_string());
+    }
+    let app_config = config::load_app_config()?;
+    let bundle = build_bundle(&accounts, &app_config.proxy.proxy_pool);
+
+    crate::modules::logger::log_info(&format!(
+        "Exported {} accounts ({} proxies) with passphrase encryption",
+        bundle.accounts.len(),
+        bundle.proxies.len()
+    ));
+    seal_bundle(&bundle, passphrase)
+}
+
+/// 导入口令加密的账号导出文本：按邮箱新增或更新账号，合并缺失的代理条目并恢复绑定
+pub fn import_accounts(data: &str, passphrase: &str) -> Result<PortableImportResult, String> {
+    let bundle = open_bundle(data, passphrase)?;
+    let mut app_config = config::load_app_config()?;
+    let pool = &mut app_config.proxy.proxy_pool;
+
+    // 本机已有同 ID 代理时保留本机配置
+    let mut proxies_added = 0;
+    for proxy in bundle.proxies {
+        if !pool.proxies.iter().any(|p| p.id == proxy.id) {
+            pool.proxies.push(proxy.into());
+            proxies_added += 1;
+        }
+    }
+
+    let mut account_ids = Vec::with_capacity(bundle.accounts.len());
+    let mut bindings: HashMap<String, String> = HashMap::new();
+    for item in bundle.accounts {
+        let mut acc = account::upsert_account(item.email, item.name, item.token)?;
+        if item.device_profile.is_some() {
+            acc.device_profile = item.device_profile;
+        }
+        if !item.device_history.is_empty() {
+            acc.device_history = item.device_history;
+        }
+        if item.custom_label.is_some() {
+            acc.custom_label = item.custom_label;
+        }
+        if let Some(proxy_id) = item.proxy_id {
+            if pool.proxies.iter().any(|p| p.id == proxy_id) {
+                acc.proxy_id = Some(proxy_id.clone());
+                acc.proxy_bound_at = item.proxy_bound_at;
+                bindings.insert(acc.id.clone(), proxy_id);
+            }
+        }
+        account::save_account(&acc)?;
+        account_ids.push(acc.id);
+    }
+
+    let bindings_restored = bindings.len();
+    pool.account_bindings.extend(bindings);
+    if proxies_added > 0 || bindings_restored > 0 {
+        config::save_app_config(&app_config)?;
+    }
+
+    crate::modules::logger::log_info(&format!(
+        "Imported {} accounts from encrypted export ({} proxies added, {} bindings restored)",
+        account_ids.len(),
+        proxies_added,
+        bindings_restored
+    ));
+
+    Ok(PortableImportResult {
+        format_version: bundle.format_version,
+        exported_at: bundle.exported_at,
+        account_ids,
+        proxies_added,
+        bindings_restored,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::Account;
+
+    fn sample_pool() -> ProxyPoolConfig {
+        let mut pool = ProxyPoolConfig::default();
+        pool.proxies.push(ProxyEntry {
+            id: "proxy-1".to_string(),
+            name: "US".to_string(),
+            url: "socks5://10.0.0.1:1080".to_string(),
+            auth: Some(ProxyAuth {
+                username: "user".to_string(),
+                password: "secret".to_string(),
+            }),
+            enabled: true,
+            priority: 1,
+            tags: vec![],
+            max_accounts: None,
+            health_check_url: None,
+            last_check_time: Some(1),
+            is_healthy: false,
+            latency: Some(10),
+        });
+        pool.proxies.push(ProxyEntry {
+            id: "proxy-unused".to_string(),
+            ..pool.proxies[0].clone()
+        });
+        pool.account_bindings
+            .insert("acc-1".to_string(), "proxy-1".to_string());
+        pool
+    }
+
+    #[test]
+    fn test_bundle_roundtrip_with_binding() {
+        let token = TokenData::new(
+            "access".to_string(),
+            "refresh-token".to_string(),
+            3600,
+            Some("a@example.com".to_string()),
+            None,
+            None,
+            false,
+            None,
+        );
+        let mut acc = Account::new("acc-1".to_string(), "a@example.com".to_string(), token);
+        acc.custom_label = Some("work".to_string());
+
+        let bundle = build_bundle(&[acc], &sample_pool());
+        assert_eq!(bundle.proxies.len(), 1);
+        assert_eq!(bundle.accounts[0].proxy_id.as_deref(), Some("proxy-1"));
+
+        let sealed = seal_bundle(&bundle, "correct horse").unwrap();
+        assert!(!sealed.contains("refresh-token"));
+
+        let opened = open_bundle(&sealed, "correct horse").unwrap();
+        assert_eq!(opened.format_version, PORTABLE_FORMAT_VERSION);
+        assert_eq!(opened.accounts[0].token.refresh_token, "refresh-token");
+        assert_eq!(opened.accounts[0].custom_label.as_deref(), Some("work"));
+        assert_eq!(opened.proxies[0].password.as_deref(), Some("secret"));
+
+        let entry: ProxyEntry = opened.proxies[0].clone().into();
+        assert_eq!(entry.auth.unwrap().password, "secret");
+    }
+
+    #[test]
+    fn test_open_rejects_wrong_passphrase_and_tampering() {
+        let bundle = build_bundle(&[], &ProxyPoolConfig::default());
+        let sealed = seal_bundle(&bundle, "pass").unwrap();
+        assert!(open_bundle(&sealed, "wrong").is_err());
+
+        let mut raw = general_purpose::STANDARD.decode(&sealed).unwrap();
+        let last = raw.len() - 1;
+        raw[last] ^= 0x01;
+        let tampered = general_purpose::STANDARD.encode(raw);
+        assert!(open_bundle(&tampered, "pass").is_err());
+        assert!(open_bundle("not base64!", "pass").is_err());
+        assert!(seal_bundle(&bundle, "").is_err());
+    }
+}
@@ -0,0 +1,557 @@
+//! 审计日志导出 (JSONL / CSV)
+//!
+//! 逐行读取 SQLite 并写出，不在内存中聚合整张表，适合按月导出给财务做分摊。
+//! 支持的数据集：
+//! - `request_logs`：请求日志 (`proxy_logs.db`)
+//! - `token_usage`：按账号记录的 Token 用量 (`token_stats.db`)
+//! - `user_token_usage`：按用户令牌记录的 Token 用量 (`user_tokens.db`)
+
+use bytes::Bytes;
+use chrono::{DateTime, Datelike, NaiveDate, TimeZone, Utc};
+use rusqlite::types::{Value as SqlValue, ValueRef};
+use rusqlite::Connection;
+use serde::Deserialize;
+use std::io::Write;
+
+/// 流式输出时每次发送的块大小
+const CHUNK_SIZE: usize = 64 * 1024;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExportDataset {
+    #[default]
+    RequestLogs,
+    TokenUsage,
+    UserTokenUsage,
+}
+
+impl ExportDataset {
+    fn name(self) -> &'static str {
+        match self {
+            Self::RequestLogs => "request_logs",
+            Self::TokenUsage => "token_usage",
+            Self::UserTokenUsage => "user_token_usage",
+        }
+    }
+
+    fn connect(self) -> Result<Connection, String> {
+        match self {
+            Self::RequestLogs => super::proxy_db::connect_db(),
+            Self::TokenUsage => super::token_stats::connect_db(),
+            Self::UserTokenUsage => super::user_token_db::connect_db(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    #[default]
+    Jsonl,
+    Csv,
+}
+
+/// 导出参数 (同时用于 `GET /api/logs/export` 的查询串与 CLI)
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct ExportQuery {
+    pub dataset: ExportDataset,
+    pub format: ExportFormat,
+    /// 起始时间 (含)：RFC 3339、`YYYY-MM-DD` 或 Unix 时间戳
+    pub from: Option<String>,
+    /// 结束时间 (不含)；仅日期时包含当天
+    pub to: Option<String>,
+    /// 自然月 (`YYYY-MM`, UTC)，与 from/to 互斥
+    pub month: Option<String>,
+    pub username: Option<String>,
+    pub account: Option<String>,
+    /// 匹配请求模型或映射后的模型
+    pub model: Option<String>,
+    pub protocol: Option<String>,
+    /// 状态码 (`429`)、状态类别 (`5xx`)、`success` 或 `error`
+    pub status: Option<String>,
+    /// 不导出请求体与响应体
+    pub redact: bool,
+}
+
+/// 已校验的导出计划
+#[derive(Debug, Clone)]
+pub struct ExportPlan {
+    pub dataset: ExportDataset,
+    pub format: ExportFormat,
+    sql: String,
+    params: Vec<SqlValue>,
+}
+
+impl ExportPlan {
+    pub fn content_type(&self) -> &'static str {
+        match self.format {
+            ExportFormat::Jsonl => "application/x-ndjson",
+            ExportFormat::Csv => "text/csv; charset=utf-8",
+        }
+    }
+
+    pub fn file_name(&self) -> String {
+        let ext = match self.format {
+            ExportFormat::Jsonl => "jsonl",
+            ExportFormat::Csv => "csv",
+        };
+        format!(
+            "{}_{}.{}",
+            self.dataset.name(),
+            Utc::now().format("%Y%m%d%H%M%S"),
+            ext
+        )
+    }
+}
+
+/// 数据集的列与可过滤字段
+struct DatasetSpec {
+    select: &'static str,
+    bodies: &'static str,
+    from: &'static str,
+    /// 时间列及其单位 (1 = 秒, 1000 = 毫秒)
+    time_column: &'static str,
+    time_scale: i64,
+    username: Option<&'static str>,
+    account: Option<&'static str>,
+    models: &'static [&'static str],
+    protocol: Option<&'static str>,
+    status: Option<&'static str>,
+}
+
+fn spec(dataset: ExportDataset) -> DatasetSpec {
+    match dataset {
+        ExportDataset::RequestLogs => DatasetSpec {
+            select: "id, timestamp, strftime('%Y-%m-%dT%H:%M:%SZ', timestamp / 1000, 'unixepoch') AS time,
+                method, url, status, duration AS duration_ms, model, mapped_model, protocol,
+                account_email, username, client_ip, input_tokens, output_tokens, cached_tokens, reasoning_tokens, error",
+            bodies: ", request_body, response_body",
+            from: "request_logs",
+            time_column: "timestamp",
+            time_scale: 1000,
+            username: Some("username"),
+            account: Some("account_email"),
+            models: &["model", "mapped_model"],
+            protocol: Some("protocol"),
+            status: Some("status"),
+        },
+        ExportDataset::TokenUsage => DatasetSpec {
+            select: "id, timestamp, strftime('%Y-%m-%dT%H:%M:%SZ', timestamp, 'unixepoch') AS time,
+                account_email, model, input_tokens, output_tokens, cached_tokens, total_tokens",
+            bodies: "",
+            from: "token_usage",
+            time_column: "timestamp",
+            time_scale: 1,
+            username: None,
+            account: Some("account_email"),
+            models: &["model"],
+            protocol: None,
+            status: None,
+        },
+        ExportDataset::UserTokenUsage => DatasetSpec {
+            select: "l.id, l.request_time AS timestamp,
+                strftime('%Y-%m-%dT%H:%M:%SZ', l.request_time, 'unixepoch') AS time,
+                l.token_id, t.username, l.ip_address, l.model, l.input_tokens, l.output_tokens,
+                COALESCE(l.input_tokens, 0) + COALESCE(l.output_tokens, 0) AS total_tokens, l.status",
+            bodies: "",
+            from: "token_usage_logs l LEFT JOIN user_tokens t ON t.id = l.token_id",
+            time_column: "l.request_time",
+            time_scale: 1,
+            username: Some("t.username"),
+            account: None,
+            models: &["l.model"],
+            protocol: None,
+            status: Some("l.status"),
+        },
+    }
+}
+
+/// 解析时间参数，返回 Unix 秒；`end_of_day` 为真时仅日期的输入取次日零点
+fn parse_time(value: &str, end_of_day: bool) -> Result<i64, String> {
+    let value = value.trim();
+    if let Ok(n) = value.parse::<i64>() {
+        // 13 位视为毫秒
+        return Ok(if n.abs() >= 100_000_000_000 {
+            n / 1000
+        } else {
+            n
+        });
+    }
+    if let Ok(dt) = DateTime::parse_from_rfc3339(value) {
+        return Ok(dt.timestamp());
+    }
+    if let Ok(date) = NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        let date = if end_of_day {
+            date.succ_opt().ok_or("date out of range")?
+        } else {
+            date
+        };
+        return Ok(Utc
+            .from_utc_datetime(&date.and_hms_opt(0, 0, 0).expect("midnight is valid"))
+            .timestamp());
+    }
+    Err(format!(
+        "Invalid time '{}': expected RFC 3339, YYYY-MM-DD or a Unix timestamp",
+        value
+    ))
+}
+
+/// `YYYY-MM` -> [月初, 次月初)
+fn month_range(value: &str) -> Result<(i64, i64), String> {
+    let start = NaiveDate::parse_from_str(&format!("{}-01", value.trim()), "%Y-%m-%d")
+        .map_err(|_| format!("Invalid month '{}': expected YYYY-MM", value))?;
+    let end = if start.month() == 12 {
+        NaiveDate::from_ymd_opt(start.year() + 1, 1, 1)
+    } else {
+        NaiveDate::from_ymd_opt(start.year(), start.month() + 1, 1)
+    }
+    .ok_or("month out of range")?;
+    let ts = |d: NaiveDate| {
+        Utc.from_utc_datetime(&d.and_hms_opt(0, 0, 0).expect("midnight is valid"))
+            .timestamp()
+    };
+    Ok((ts(start), ts(end)))
+}
+
+/// `month` 或 `from`/`to` -> [起, 止) 的 Unix 秒，未指定的一端为 None
+pub(crate) fn resolve_period(
+    month: Option<&str>,
+    from: Option<&str>,
+    to: Option<&str>,
+) -> Result<(Option<i64>, Option<i64>), String> {
+    match month {
+        Some(_) if from.is_some() || to.is_some() => {
+            Err("Use either 'month' or 'from'/'to', not both".to_string())
+        }
+        Some(month) => {
+            let (from, to) = month_range(month)?;
+            Ok((Some(from), Some(to)))
+        }
+        None => Ok((
+            from.map(|v| parse_time(v, false)).transpose()?,
+            to.map(|v| parse_time(v, true)).transpose()?,
+        )),
+    }
+}
+
+/// 状态过滤 -> SQL 条件
+fn status_clause(column: &str, value: &str, params: &mut Vec<SqlValue>) -> Result<String, String> {
+    let value = value.trim().to_ascii_lowercase();
+    match value.as_str() {
+        "success" => Ok(format!("{} < 400", column)),
+        "error" => Ok(format!("{} >= 400", column)),
+        v if v.len() == 3 && v.ends_with("xx") => {
+            let class: i64 = v[..1]
+                .parse()
+                .map_err(|_| format!("Invalid status filter: {}", value))?;
+            params.push(SqlValue::Integer(class * 100));
+            params.push(SqlValue::Integer(class * 100 + 100));
+            Ok(format!("{col} >= ? AND {col} < ?", col = column))
+        }
+        v => {
+            let code: i64 = v
+                .parse()
+                .map_err(|_| format!("Invalid status filter: {}", value))?;
+            params.push(SqlValue::Integer(code));
+            Ok(format!("{} = ?", column))
+        }
+    }
+}
+
+/// 校验参数并生成查询
+pub fn plan(query: &ExportQuery) -> Result<ExportPlan, String> {
+    let spec = spec(query.dataset);
+    let dataset = query.dataset.name();
+    let unsupported = |field: &str| format!("Filter '{}' is not supported for {}", field, dataset);
+
+    let mut clauses = Vec::new();
+    let mut params = Vec::new();
+
+    let (from, to) = resolve_period(
+        query.month.as_deref(),
+        query.from.as_deref(),
+        query.to.as_deref(),
+    )?;
+    if let Some(from) = from {
+        clauses.push(format!("{} >= ?", spec.time_column));
+        params.push(SqlValue::Integer(from * spec.time_scale));
+    }
+    if let Some(to) = to {
+        clauses.push(format!("{} < ?", spec.time_column));
+        params.push(SqlValue::Integer(to * spec.time_scale));
+    }
+
+    for (field, value, column) in [
+        ("username", &query.username, spec.username),
+        ("account", &query.account, spec.account),
+        ("protocol", &query.protocol, spec.protocol),
+    ] {
+        let Some(value) = value.as_deref().filter(|v| !v.is_empty()) else {
+            continue;
+        };
+        let column = column.ok_or_else(|| unsupported(field))?;
+        clauses.push(format!("{} = ? COLLATE NOCASE", column));
+        params.push(SqlValue::Text(value.to_string()));
+    }
+
+    if let Some(model) = query.model.as_deref().filter(|v| !v.is_empty()) {
+        let any = spec
+            .models
+            .iter()
+            .map(|c| format!("{} = ?", c))
+            .collect::<Vec<_>>()
+            .join(" OR ");
+        clauses.push(format!("({})", any));
+        params.extend(
+            spec.models
+                .iter()
+                .map(|_| SqlValue::Text(model.to_string())),
+        );
+    }
+
+    if let Some(status) = query.status.as_deref().filter(|v| !v.is_empty()) {
+        let column = spec.status.ok_or_else(|| unsupported("status"))?;
+        clauses.push(status_clause(column, status, &mut params)?);
+    }
+
+    let bodies = if query.redact { "" } else { spec.bodies };
+    let mut sql = format!("SELECT {}{} FROM {}", spec.select, bodies, spec.from);
+    if !clauses.is_empty() {
+        sql.push_str(" WHERE ");
+        sql.push_str(&clauses.join(" AND "));
+    }
+    sql.push_str(&format!(" ORDER BY {} ASC", spec.time_column));
+
+    Ok(ExportPlan {
+        dataset: query.dataset,
+        format: query.format,
+        sql,
+        params,
+    })
+}
+
+fn csv_field(value: &ValueRef<'_>) -> String {
+    let text = match value {
+        ValueRef::Null => return String::new(),
+        ValueRef::Integer(i) => return i.to_string(),
+        ValueRef::Real(f) => return f.to_string(),
+        ValueRef::Text(t) => String::from_utf8_lossy(t).into_owned(),
+        ValueRef::Blob(b) => String::from_utf8_lossy(b).into_owned(),
+    };
+    if text.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text
+    }
+}
+
+fn json_field(value: &ValueRef<'_>) -> serde_json::Value {
+    match value {
+        ValueRef::Null => serde_json::Value::Null,
+        ValueRef::Integer(i) => (*i).into(),
+        ValueRef::Real(f) => serde_json::Number::from_f64(*f)
+            .map(serde_json::Value::Number)
+            .unwrap_or(serde_json::Value::Null),
+        ValueRef::Text(t) | ValueRef::Blob(t) => String::from_utf8_lossy(t).into_owned().into(),
+    }
+}
+
+/// 在指定连接上执行导出，返回写出的行数
+pub fn write_rows(
+    conn: &Connection,
+    plan: &ExportPlan,
+    out: &mut dyn Write,
+) -> Result<u64, String> {
+    let mut stmt = conn.prepare(&plan.sql).map_err(|e| e.to_string())?;
+    let columns: Vec<String> = stmt.column_names().into_iter().map(String::from).collect();
+    let io_err = |e: std::io::Error| format!("Failed to write export: {}", e);
+
+    if plan.format == ExportFormat::Csv {
+        writeln!(out, "{}", columns.join(",")).map_err(io_err)?;
+    }
+
+    let mut rows = stmt
+        .query(rusqlite::params_from_iter(plan.params.iter()))
+        .map_err(|e| e.to_string())?;
+    let mut count = 0u64;
+    while let Some(row) = rows.next().map_err(|e| e.to_string())? {
+        match plan.format {
+            ExportFormat::Csv => {
+                let line = (0..columns.len())
+                    .map(|i| row.get_ref(i).map(|v| csv_field(&v)))
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(|e| e.to_string())?
+                    .join(",");
+                writeln!(out, "{}", line).map_err(io_err)?;
+            }
+            ExportFormat::Jsonl => {
+                let mut obj = serde_json::Map::with_capacity(columns.len());
+                for (i, name) in columns.iter().enumerate() {
+                    let value = row.get_ref(i).map_err(|e| e.to_string())?;
+                    obj.insert(name.clone(), json_field(&value));
+                }
+                serde_json::to_writer(&mut *out, &obj).map_err(|e| e.to_string())?;
+                out.write_all(b"\n").map_err(io_err)?;
+            }
+        }
+        count += 1;
+    }
+    out.flush().map_err(io_err)?;
+    Ok(count)
+}
+
+/// 打开数据集所在的数据库并导出
+pub fn write_export(plan: &ExportPlan, out: &mut dyn Write) -> Result<u64, String> {
+    let conn = plan.dataset.connect()?;
+    write_rows(&conn, plan, out)
+}
+
+/// 将导出内容分块发送到异步通道 (HTTP 流式响应)；接收端关闭时写入失败，导出随之中止
+pub struct ChannelWriter {
+    tx: tokio::sync::mpsc::Sender<Result<Bytes, std::io::Error>>,
+    buf: Vec<u8>,
+}
+
+impl ChannelWriter {
+    pub fn new(tx: tokio::sync::mpsc::Sender<Result<Bytes, std::io::Error>>) -> Self {
+        Self {
+            tx,
+            buf: Vec::with_capacity(CHUNK_SIZE),
+        }
+    }
+
+    fn send_buffer(&mut self) -> std::io::Result<()> {
+        if self.buf.is_empty() {
+            return Ok(());
+        }
+        let chunk = Bytes::from(std::mem::replace(
+            &mut self.buf,
+            Vec::with_capacity(CHUNK_SIZE),
+        ));
+        self.tx.blocking_send(Ok(chunk)).map_err(|_| {
+            std::io::Error::new(std::io::ErrorKind::BrokenPipe, "export stream closed")
+        })
+    }
+}
+
+impl Write for ChannelWriter {
+    fn write(&mut self, data: &[u8]) -> std::io::Result<usize> {
+        self.buf.extend_from_slice(data);
+        if self.buf.len() >= CHUNK_SIZE {
+            self.send_buffer()?;
+        }
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.send_buffer()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn seeded_request_logs() -> Connection {
+        let mut conn = Connection::open_in_memory().unwrap();
+        crate::modules::proxy_db::migrate(&mut conn).unwrap();
+        for (id, ts, status, user, model, body) in [
+            (
+                "a",
+                1_756_684_800_000i64,
+                200,
+                "alice",
+                "gpt-4o",
+                "{\"q\":\"hi, \\\"there\\\"\"}",
+            ),
+            ("b", 1_756_771_200_000, 429, "bob", "gemini-2.5-pro", "{}"),
+            ("c", 1_759_276_800_000, 200, "alice", "gpt-4o", "{}"),
+        ] {
+            conn.execute(
+                "INSERT INTO request_logs (id, timestamp, method, url, status, duration, model, username, protocol, request_body)
+                 VALUES (?1, ?2, 'POST', '/v1/chat/completions', ?3, 10, ?4, ?5, 'openai', ?6)",
+                rusqlite::params![id, ts, status, model, user, body],
+            )
+            .unwrap();
+        }
+        conn
+    }
+
+    fn export(conn: &Connection, query: ExportQuery) -> String {
+        let mut out = Vec::new();
+        write_rows(conn, &plan(&query).unwrap(), &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_month_and_username_filters() {
+        let conn = seeded_request_logs();
+        let out = export(
+            &conn,
+            ExportQuery {
+                month: Some("2025-09".to_string()),
+                username: Some("ALICE".to_string()),
+                ..Default::default()
+            },
+        );
+        let rows: Vec<serde_json::Value> = out
+            .lines()
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect();
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0]["id"], "a");
+        assert_eq!(rows[0]["time"], "2025-09-01T00:00:00Z");
+        assert!(rows[0].get("request_body").is_some());
+
+        let errors = export(
+            &conn,
+            ExportQuery {
+                status: Some("4xx".to_string()),
+                redact: true,
+                ..Default::default()
+            },
+        );
+        let row: serde_json::Value = serde_json::from_str(errors.trim()).unwrap();
+        assert_eq!(row["id"], "b");
+        assert!(row.get("request_body").is_none());
+    }
+
+    #[test]
+    fn test_csv_escaping_and_header() {
+        let conn = seeded_request_logs();
+        let out = export(
+            &conn,
+            ExportQuery {
+                format: ExportFormat::Csv,
+                to: Some("2025-09-01".to_string()),
+                ..Default::default()
+            },
+        );
+        let mut lines = out.lines();
+        let header = lines.next().unwrap();
+        assert!(header.starts_with("id,timestamp,time,"));
+        assert!(header.ends_with(",request_body,response_body"));
+        let row = lines.next().unwrap();
+        assert!(row.contains("\"{\"\"q\"\":\"\"hi, \\\"\"there\\\"\"\"\"}\""));
+        assert!(lines.next().is_none());
+    }
+
+    #[test]
+    fn test_plan_rejects_unsupported_filters() {
+        let query = ExportQuery {
+            dataset: ExportDataset::TokenUsage,
+            username: Some("alice".to_string()),
+            ..Default::default()
+        };
+        assert!(plan(&query).unwrap_err().contains("username"));
+        assert!(plan(&ExportQuery {
+            month: Some("2025-09".to_string()),
+            from: Some("2025-09-01".to_string()),
+            ..Default::default()
+        })
+        .is_err());
+        assert!(parse_time("yesterday", false).is_err());
+        assert_eq!(parse_time("1756684800000", false).unwrap(), 1_756_684_800);
+    }
+}
@@ -0,0 +1,729 @@
+//! 数据目录整体备份与恢复
+//!
+//! 备份为 gzip 压缩的 tar 归档 (可选口令加密)，包含：
+//! - `manifest.json`：格式版本、应用版本、各数据库结构版本与文件清单
+//! - `databases/*.db`：通过 SQLite 在线备份 API 获取的一致性快照
+//! - `files/*`：数据目录顶层的 JSON 配置 (`gui_config.json`、`accounts.json` 等) 与 `accounts/` 账号文件
+//!
+//! 恢复时先完整解包到暂存目录，并对每个数据库副本执行结构迁移 (旧备份自动升级，
+//! 更新版本写入的备份直接拒绝)，全部校验通过后才写回数据库与 JSON 文件。
+//!
+//! 代理池认证密码在 `gui_config.json` 中以本机密钥加密，换机后无法解密：口令加密的备份
+//! 中以明文保存 (受备份口令保护)，恢复时再用目标机器的密钥重新加密。
+
+use std::fs;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use rusqlite::backup::Progress;
+use rusqlite::{Connection, DatabaseName};
+use serde::{Deserialize, Serialize};
+
+use crate::modules::{account, db_migrations};
+use crate::utils::crypto;
+
+/// 备份归档格式版本
+const FORMAT_VERSION: u32 = 1;
+const MANIFEST_FILE: &str = "manifest.json";
+const DATABASES_DIR: &str = "databases";
+const FILES_DIR: &str = "files";
+const ACCOUNTS_DIR: &str = "accounts";
+const CONFIG_FILE: &str = "gui_config.json";
+
+/// 备份口令的环境变量 (CLI 模式)
+const PASSPHRASE_ENV: &str = "ABV_BACKUP_PASSPHRASE";
+
+/// 纳入备份的 SQLite 数据库
+struct DatabaseSpec {
+    file: &'static str,
+    /// 不在数据目录中的数据库 (返回 None 表示不可用)
+    external_path: Option<fn() -> Option<PathBuf>>,
+    migrate: fn(&mut Connection) -> Result<u32, String>,
+}
+
+const DATABASES: &[DatabaseSpec] = &[
+    DatabaseSpec {
+        file: "proxy_logs.db",
+        external_path: None,
+        migrate: crate::modules::proxy_db::migrate,
+    },
+    DatabaseSpec {
+        file: "token_stats.db",
+        external_path: None,
+        migrate: crate::modules::token_stats::migrate,
+    },
+    DatabaseSpec {
+        file: "user_tokens.db",
+        external_path: None,
+        migrate: crate::modules::user_token_db::migrate,
+    },
+    DatabaseSpec {
+        file: "security.db",
+        external_path: None,
+        migrate: crate::modules::security_db::migrate,
+    },
+    DatabaseSpec {
+        file: "quota_history.db",
+        external_path: None,
+        migrate: crate::modules::quota_history::migrate,
+    },
+    DatabaseSpec {
+        file: crate::proxy::rate_limit_store::RATE_LIMIT_DB_FILE,
+        external_path: None,
+        migrate: crate::proxy::rate_limit_store::migrate,
+    },
+    DatabaseSpec {
+        file: crate::proxy::signature_cache::SIGNATURE_CACHE_DB_FILE,
+        external_path: None,
+        migrate: crate::proxy::signature_cache::migrate,
+    },
+    DatabaseSpec {
+        file: "tool_artifacts.db",
+        external_path: Some(crate::proxy::adapters::artifact_store::default_db_path),
+        migrate: crate::proxy::adapters::artifact_store::migrate,
+    },
+];
+
+impl DatabaseSpec {
+    fn path(&self, data_dir: &Path) -> Option<PathBuf> {
+        match self.external_path {
+            Some(external) => external(),
+            None => Some(data_dir.join(self.file)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackupDatabaseEntry {
+    pub file: String,
+    pub schema_version: u32,
+}
+
+/// 备份清单
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackupManifest {
+    pub format_version: u32,
+    pub app_version: String,
+    pub created_at: i64,
+    pub databases: Vec<BackupDatabaseEntry>,
+    /// 相对数据目录的文件路径
+    pub files: Vec<String>,
+}
+
+/// 临时目录，离开作用域时删除
+struct StagingDir(PathBuf);
+
+impl StagingDir {
+    fn create(data_dir: &Path, prefix: &str) -> Result<Self, String> {
+        let path = data_dir.join(format!(".{}-{}", prefix, uuid::Uuid::new_v4()));
+        fs::create_dir_all(&path).map_err(|e| format!("Failed to create staging dir: {}", e))?;
+        Ok(Self(path))
+    }
+}
+
+impl Drop for StagingDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+/// 仅允许数据目录顶层的 JSON 文件与 `accounts/` 下的 JSON 文件
+fn is_backup_file(rel: &str) -> bool {
+    let parts: Vec<&str> = rel.split('/').collect();
+    let name = match parts.as_slice() {
+        [name] => name,
+        [dir, name] if *dir == ACCOUNTS_DIR => name,
+        _ => return false,
+    };
+    name.ends_with(".json") && !name.starts_with('.') && !name.contains(".tmp")
+}
+
+fn collect_files(data_dir: &Path) -> Result<Vec<(String, Vec<u8>)>, String> {
+    let mut files = Vec::new();
+    for (dir, prefix) in [
+        (data_dir.to_path_buf(), String::new()),
+        (data_dir.join(ACCOUNTS_DIR), format!("{}/", ACCOUNTS_DIR)),
+    ] {
+        let entries = match fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(format!("Failed to read {}: {}", dir.display(), e)),
+        };
+        for entry in entries.flatten() {
+            if !entry.file_type().map(|t| t.is_file()).unwrap_or(false) {
+                continue;
+            }
+            let rel = format!("{}{}", prefix, entry.file_name().to_string_lossy());
+            if !is_backup_file(&rel) {
+                continue;
+            }
+            let data = fs::read(entry.path())
+                .map_err(|e| format!("Failed to read {}: {}", entry.path().display(), e))?;
+            files.push((rel, data));
+        }
+    }
+    files.sort_by(|a, b| a.0.cmp(&b.0));
+    Ok(files)
+}
+
+/// 改写配置文件中的代理池认证密码 (`f` 返回 None 表示保持原值)
+///
+/// 无法解析的配置文件原样返回，由恢复后的加载流程给出错误
+fn map_proxy_passwords(
+    data: &[u8],
+    f: impl Fn(&str) -> Result<Option<String>, String>,
+) -> Result<Vec<u8>, String> {
+    let Ok(mut config) = serde_json::from_slice::<serde_json::Value>(data) else {
+        return Ok(data.to_vec());
+    };
+    let proxies = config
+        .pointer_mut("/proxy/proxy_pool/proxies")
+        .and_then(|v| v.as_array_mut());
+    let mut changed = false;
+    for proxy in proxies.into_iter().flatten() {
+        let Some(password) = proxy.pointer_mut("/auth/password") else {
+            continue;
+        };
+        let Some(current) = password.as_str().filter(|p| !p.is_empty()) else {
+            continue;
+        };
+        if let Some(mapped) = f(current)? {
+            *password = serde_json::Value::String(mapped);
+            changed = true;
+        }
+    }
+    if !changed {
+        return Ok(data.to_vec());
+    }
+    serde_json::to_vec_pretty(&config).map_err(|e| e.to_string())
+}
+
+fn append_bytes<W: Write>(
+    builder: &mut tar::Builder<W>,
+    path: &str,
+    data: &[u8],
+    mtime: i64,
+) -> Result<(), String> {
+    let mut header = tar::Header::new_gnu();
+    header.set_size(data.len() as u64);
+    header.set_mode(0o600);
+    header.set_mtime(mtime.max(0) as u64);
+    header.set_cksum();
+    builder
+        .append_data(&mut header, path, data)
+        .map_err(|e| format!("Failed to write {} to archive: {}", path, e))
+}
+
+/// 为 `data_dir` 生成备份归档 (返回归档字节与清单)
+pub fn create_backup_in(
+    data_dir: &Path,
+    passphrase: Option<&str>,
+) -> Result<(Vec<u8>, BackupManifest), String> {
+    let created_at = chrono::Utc::now().timestamp();
+    let staging = StagingDir::create(data_dir, "backup")?;
+    let mut builder = tar::Builder::new(GzEncoder::new(Vec::new(), Compression::default()));
+
+    // 1. 数据库在线快照
+    let mut databases = Vec::new();
+    for spec in DATABASES {
+        let Some(source) = spec.path(data_dir).filter(|p| p.exists()) else {
+            continue;
+        };
+        let snapshot = staging.0.join(spec.file);
+        let src = Connection::open(&source)
+            .map_err(|e| format!("Failed to open {}: {}", spec.file, e))?;
+        src.busy_timeout(std::time::Duration::from_secs(5))
+            .map_err(|e| e.to_string())?;
+        src.backup(DatabaseName::Main, &snapshot, None)
+            .map_err(|e| format!("Failed to snapshot {}: {}", spec.file, e))?;
+        drop(src);
+
+        let schema_version = Connection::open(&snapshot)
+            .and_then(|conn| db_migrations::schema_version(&conn))
+            .map_err(|e| e.to_string())?;
+        let data = fs::read(&snapshot).map_err(|e| e.to_string())?;
+        append_bytes(
+            &mut builder,
+            &format!("{}/{}", DATABASES_DIR, spec.file),
+            &data,
+            created_at,
+        )?;
+        databases.push(BackupDatabaseEntry {
+            file: spec.file.to_string(),
+            schema_version,
+        });
+    }
+
+    // 2. JSON 文件 (持有账号锁，避免与账号写入交错)
+    let files = {
+        let _lock = account::lock_accounts()?;
+        collect_files(data_dir)?
+    };
+    for (rel, data) in &files {
+        // 口令加密的备份可能在其他机器恢复，代理密码改用明文 (由备份口令保护)
+        let data = if rel == CONFIG_FILE && passphrase.is_some() {
+            map_proxy_passwords(data, |password| {
+                if !crypto::is_machine_encrypted(password) {
+                    return Ok(None);
+                }
+                Ok(crypto::decrypt_string(password).ok())
+            })?
+        } else {
+            data.clone()
+        };
+        append_bytes(
+            &mut builder,
+            &format!("{}/{}", FILES_DIR, rel),
+            &data,
+            created_at,
+        )?;
+    }
+
+    let manifest = BackupManifest {
+        format_version: FORMAT_VERSION,
+        app_version: env!("CARGO_PKG_VERSION").to_string(),
+        created_at,
+        databases,
+        files: files.into_iter().map(|(rel, _)| rel).collect(),
+    };
+    let manifest_json = serde_json::to_vec_pretty(&manifest).map_err(|e| e.to_string())?;
+    append_bytes(&mut builder, MANIFEST_FILE, &manifest_json, created_at)?;
+
+    let archive = builder
+        .into_inner()
+        .and_then(|gz| gz.finish())
+        .map_err(|e| format!("Failed to finish archive: {}", e))?;
+
+    let archive = match passphrase {
+        Some(passphrase) => crypto::encrypt_with_passphrase(&archive, passphrase)?,
+        None => archive,
+    };
+    Ok((archive, manifest))
+}
+
+/// 先写临时文件再重命名，保证目标文件要么是旧内容要么是完整的新内容
+fn write_atomic(path: &Path, data: &[u8]) -> Result<(), String> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+    let tmp = path.with_file_name(format!(
+        ".{}.{}.tmp",
+        path.file_name().unwrap_or_default().to_string_lossy(),
+        uuid::Uuid::new_v4()
+    ));
+    fs::write(&tmp, data).map_err(|e| format!("Failed to write {}: {}", tmp.display(), e))?;
+    fs::rename(&tmp, path).map_err(|e| {
+        let _ = fs::remove_file(&tmp);
+        format!("Failed to replace {}: {}", path.display(), e)
+    })
+}
+
+/// 已解包并校验的归档: (暂存目录, 清单, 待写回的数据库 (文件名, 暂存副本, 目标路径))
+type StagedArchive = (
+    StagingDir,
+    BackupManifest,
+    Vec<(&'static str, PathBuf, PathBuf)>,
+);
+
+/// 解包归档到暂存目录，校验清单并迁移暂存的数据库副本 (不触碰 `data_dir` 中的数据)
+fn stage_archive(data_dir: &Path, archive: &[u8]) -> Result<StagedArchive, String> {
+    // 1. 解包到暂存目录 (tar 会拒绝越出目标目录的路径)
+    let staging = StagingDir::create(data_dir, "restore")?;
+    tar::Archive::new(GzDecoder::new(archive))
+        .unpack(&staging.0)
+        .map_err(|e| format!("Invalid backup archive: {}", e))?;
+
+    let mut manifest_json = String::new();
+    fs::File::open(staging.0.join(MANIFEST_FILE))
+        .and_then(|mut f| f.read_to_string(&mut manifest_json))
+        .map_err(|e| format!("Backup manifest missing: {}", e))?;
+    let manifest: BackupManifest = serde_json::from_str(&manifest_json)
+        .map_err(|e| format!("Invalid backup manifest: {}", e))?;
+    if manifest.format_version > FORMAT_VERSION {
+        return Err(format!(
+            "Backup format version {} is newer than supported version {}",
+            manifest.format_version, FORMAT_VERSION
+        ));
+    }
+
+    // 2. 校验并迁移暂存的数据库副本
+    let mut restores = Vec::new();
+    for entry in &manifest.databases {
+        let Some(spec) = DATABASES.iter().find(|s| s.file == entry.file) else {
+            tracing::warn!(
+                "[Backup] Skipping unknown database in backup: {}",
+                entry.file
+            );
+            continue;
+        };
+        let staged = staging.0.join(DATABASES_DIR).join(spec.file);
+        let mut conn = Connection::open(&staged)
+            .map_err(|e| format!("Failed to open staged {}: {}", spec.file, e))?;
+        (spec.migrate)(&mut conn)?;
+        drop(conn);
+        if let Some(target) = spec.path(data_dir) {
+            restores.push((spec.file, staged, target));
+        }
+    }
+    for rel in &manifest.files {
+        if !is_backup_file(rel) || !staging.0.join(FILES_DIR).join(rel).is_file() {
+            return Err(format!("Invalid file entry in backup: {}", rel));
+        }
+    }
+
+    // 3. 明文保存的代理密码使用本机密钥重新加密
+    if manifest.files.iter().any(|rel| rel == CONFIG_FILE) {
+        let path = staging.0.join(FILES_DIR).join(CONFIG_FILE);
+        let data = fs::read(&path).map_err(|e| e.to_string())?;
+        let data = map_proxy_passwords(&data, |password| {
+            if crypto::is_machine_encrypted(password) {
+                return Ok(None);
+            }
+            crypto::encrypt_string(password).map(Some)
+        })?;
+        fs::write(&path, data).map_err(|e| e.to_string())?;
+    }
+
+    Ok((staging, manifest, restores))
+}
+
+/// 将暂存内容写回 `data_dir`
+fn apply_staged(data_dir: &Path, staged: &StagedArchive) -> Result<(), String> {
+    let (staging, manifest, restores) = staged;
+
+    // 写回数据库 (在线恢复 API，已打开的连接可直接看到新内容)
+    for (file, staged, target) in restores {
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+        }
+        let mut dest =
+            Connection::open(target).map_err(|e| format!("Failed to open {}: {}", file, e))?;
+        dest.busy_timeout(std::time::Duration::from_secs(5))
+            .map_err(|e| e.to_string())?;
+        dest.restore(DatabaseName::Main, staged, None::<fn(Progress)>)
+            .map_err(|e| format!("Failed to restore {}: {}", file, e))?;
+    }
+
+    // 写回 JSON 文件，并移除备份中不存在的账号文件
+    let _lock = account::lock_accounts()?;
+    let accounts_dir = data_dir.join(ACCOUNTS_DIR);
+    if let Ok(entries) = fs::read_dir(&accounts_dir) {
+        for entry in entries.flatten() {
+            let rel = format!("{}/{}", ACCOUNTS_DIR, entry.file_name().to_string_lossy());
+            if is_backup_file(&rel) && !manifest.files.contains(&rel) {
+                let _ = fs::remove_file(entry.path());
+            }
+        }
+    }
+    for rel in &manifest.files {
+        let data = fs::read(staging.0.join(FILES_DIR).join(rel)).map_err(|e| e.to_string())?;
+        write_atomic(&data_dir.join(rel), &data)?;
+    }
+
+    Ok(())
+}
+
+/// 从恢复前快照回滚 `data_dir`，并删除本次恢复新建的数据库文件
+fn rollback_restore(data_dir: &Path, snapshot: &[u8], created: &[PathBuf]) -> Result<(), String> {
+    let staged = stage_archive(data_dir, snapshot)?;
+    apply_staged(data_dir, &staged)?;
+    for path in created {
+        for suffix in ["", "-wal", "-shm"] {
+            let mut file = path.clone().into_os_string();
+            file.push(suffix);
+            let _ = fs::remove_file(PathBuf::from(file));
+        }
+    }
+    Ok(())
+}
+
+/// 从备份归档恢复 `data_dir`
+///
+/// 所有数据库副本校验通过后才写入；写入前先为当前数据做快照，任一步失败都会整体回滚
+pub fn restore_backup_in(
+    data_dir: &Path,
+    archive: &[u8],
+    passphrase: Option<&str>,
+) -> Result<BackupManifest, String> {
+    let decrypted;
+    let archive = if crypto::is_passphrase_encrypted(archive) {
+        let passphrase = passphrase
+            .ok_or_else(|| "Backup is encrypted; a passphrase is required".to_string())?;
+        decrypted = crypto::decrypt_with_passphrase(archive, passphrase)?;
+        decrypted.as_slice()
+    } else {
+        archive
+    };
+
+    let staged = stage_archive(data_dir, archive)?;
+
+    let created: Vec<PathBuf> = staged
+        .2
+        .iter()
+        .filter(|(_, _, target)| !target.exists())
+        .map(|(_, _, target)| target.clone())
+        .collect();
+    let (snapshot, _) = create_backup_in(data_dir, None)
+        .map_err(|e| format!("Failed to snapshot current data before restore: {}", e))?;
+
+    if let Err(e) = apply_staged(data_dir, &staged) {
+        tracing::error!("[Backup] Restore failed, rolling back: {}", e);
+        return Err(match rollback_restore(data_dir, &snapshot, &created) {
+            Ok(()) => format!("Restore failed, previous data was rolled back: {}", e),
+            Err(rollback_err) => format!(
+                "Restore failed: {}; rollback also failed: {}",
+                e, rollback_err
+            ),
+        });
+    }
+
+    Ok(staged.1)
+}
+
+/// 备份当前数据目录到 `output`
+pub fn create_backup(output: &Path, passphrase: Option<&str>) -> Result<BackupManifest, String> {
+    let data_dir = account::get_data_dir()?;
+    let (archive, manifest) = create_backup_in(&data_dir, passphrase)?;
+    write_atomic(output, &archive)?;
+    Ok(manifest)
+}
+
+/// 从 `input` 恢复当前数据目录
+pub fn restore_backup(input: &Path, passphrase: Option<&str>) -> Result<BackupManifest, String> {
+    let data_dir = account::get_data_dir()?;
+    let archive = fs::read(input).map_err(|e| format!("Failed to read backup: {}", e))?;
+    restore_backup_in(&data_dir, &archive, passphrase)
+}
+
+/// 默认备份文件名
+pub fn default_file_name(encrypted: bool) -> String {
+    format!(
+        "antigravity-backup-{}.tar.gz{}",
+        chrono::Local::now().format("%Y%m%d-%H%M%S"),
+        if encrypted { ".enc" } else { "" }
+    )
+}
+
+/// 命令行模式：`--backup <file>` / `--restore <file>` (口令通过 `--passphrase-file <file|->` 或
+/// `ABV_BACKUP_PASSPHRASE` 提供，不接受命令行明文)。未指定相关参数时返回 None，否则返回进程退出码。
+pub fn run_cli(args: &[String]) -> Option<i32> {
+    let value_of = |flag: &str| {
+        args.iter()
+            .position(|a| a == flag)
+            .and_then(|i| args.get(i + 1))
+            .cloned()
+    };
+    let backup = value_of("--backup");
+    let restore = value_of("--restore");
+    if backup.is_none() && restore.is_none() {
+        return None;
+    }
+    if args.iter().any(|a| a == "--passphrase") {
+        tracing::error!(
+            "[Backup] --passphrase is not accepted on the command line (visible in ps and shell history); use --passphrase-file <file|-> or {}",
+            PASSPHRASE_ENV
+        );
+        return Some(2);
+    }
+    let passphrase = match crate::modules::cli::read_secret(
+        value_of("--passphrase-file").as_deref(),
+        PASSPHRASE_ENV,
+        false,
+    ) {
+        Ok(passphrase) => passphrase,
+        Err(e) => {
+            tracing::error!("[Backup] {}", e);
+            return Some(2);
+        }
+    };
+
+    let result = match (backup, restore) {
+        (Some(output), None) => create_backup(Path::new(&output), passphrase.as_deref()).map(|m| {
+            format!(
+                "Backup written to {} ({} databases, {} files)",
+                output,
+                m.databases.len(),
+                m.files.len()
+            )
+        }),
+        (None, Some(input)) => restore_backup(Path::new(&input), passphrase.as_deref()).map(|m| {
+            format!(
+                "Restored {} databases and {} files from {} (created by v{})",
+                m.databases.len(),
+                m.files.len(),
+                input,
+                m.app_version
+            )
+        }),
+        _ => Err("--backup and --restore cannot be used together".to_string()),
+    };
+    match result {
+        Ok(message) => {
+            tracing::info!("[Backup] {}", message);
+            Some(0)
+        }
+        Err(e) => {
+            tracing::error!("[Backup] {}", e);
+            Some(1)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn seed_data_dir(dir: &Path) {
+        fs::create_dir_all(dir.join(ACCOUNTS_DIR)).unwrap();
+        fs::write(dir.join("gui_config.json"), r#"{"language":"en"}"#).unwrap();
+        fs::write(dir.join("accounts.json"), r#"{"accounts":["a"]}"#).unwrap();
+        fs::write(dir.join(ACCOUNTS_DIR).join("a.json"), r#"{"id":"a"}"#).unwrap();
+        fs::create_dir_all(dir.join("logs")).unwrap();
+        fs::write(dir.join("logs").join("app.log"), "not backed up").unwrap();
+
+        let mut conn = Connection::open(dir.join("token_stats.db")).unwrap();
+        crate::modules::token_stats::migrate(&mut conn).unwrap();
+        conn.execute(
+            "INSERT INTO token_usage (timestamp, account_email, model) VALUES (1, 'a@test.com', 'm')",
+            [],
+        )
+        .unwrap();
+    }
+
+    fn usage_rows(dir: &Path) -> i64 {
+        Connection::open(dir.join("token_stats.db"))
+            .unwrap()
+            .query_row("SELECT COUNT(*) FROM token_usage", [], |r| r.get(0))
+            .unwrap()
+    }
+
+    #[test]
+    fn test_encrypted_backup_round_trip() {
+        let source = tempfile::tempdir().unwrap();
+        seed_data_dir(source.path());
+
+        let (archive, manifest) = create_backup_in(source.path(), Some("secret")).unwrap();
+        assert!(crypto::is_passphrase_encrypted(&archive));
+        assert_eq!(manifest.databases.len(), 1);
+        assert_eq!(
+            manifest.files,
+            vec!["accounts.json", "accounts/a.json", "gui_config.json"]
+        );
+
+        // 恢复到另一台机器上已有不同数据的目录
+        let target = tempfile::tempdir().unwrap();
+        fs::create_dir_all(target.path().join(ACCOUNTS_DIR)).unwrap();
+        fs::write(target.path().join(ACCOUNTS_DIR).join("stale.json"), "{}").unwrap();
+
+        assert!(restore_backup_in(target.path(), &archive, None).is_err());
+        assert!(restore_backup_in(target.path(), &archive, Some("wrong")).is_err());
+        restore_backup_in(target.path(), &archive, Some("secret")).unwrap();
+
+        assert_eq!(usage_rows(target.path()), 1);
+        assert_eq!(
+            fs::read_to_string(target.path().join(ACCOUNTS_DIR).join("a.json")).unwrap(),
+            r#"{"id":"a"}"#
+        );
+        assert!(!target.path().join(ACCOUNTS_DIR).join("stale.json").exists());
+        assert!(target.path().join("gui_config.json").exists());
+        assert!(!target.path().join("logs").exists());
+    }
+
+    #[test]
+    fn test_proxy_passwords_survive_machine_change() {
+        let source = tempfile::tempdir().unwrap();
+        seed_data_dir(source.path());
+        let encrypted = crypto::encrypt_string("proxy-pw").unwrap();
+        let config = serde_json::json!({
+            "proxy": { "proxy_pool": { "proxies": [
+                { "id": "p1", "auth": { "username": "u", "password": encrypted } },
+                { "id": "p2" }
+            ] } }
+        });
+        fs::write(source.path().join(CONFIG_FILE), config.to_string()).unwrap();
+
+        // 归档内为明文 (由备份口令保护)，不依赖源机器的密钥
+        let (archive, _) = create_backup_in(source.path(), Some("secret")).unwrap();
+        let plain = crypto::decrypt_with_passphrase(&archive, "secret").unwrap();
+        let mut entries = tar::Archive::new(GzDecoder::new(plain.as_slice()));
+        let mut archived = String::new();
+        entries
+            .entries()
+            .unwrap()
+            .map(|e| e.unwrap())
+            .find(|e| e.path().unwrap().ends_with(CONFIG_FILE))
+            .unwrap()
+            .read_to_string(&mut archived)
+            .unwrap();
+        assert!(archived.contains("proxy-pw"), "{}", archived);
+
+        // 恢复后使用本机密钥重新加密
+        let target = tempfile::tempdir().unwrap();
+        restore_backup_in(target.path(), &archive, Some("secret")).unwrap();
+        let restored: serde_json::Value =
+            serde_json::from_slice(&fs::read(target.path().join(CONFIG_FILE)).unwrap()).unwrap();
+        let password = restored["proxy"]["proxy_pool"]["proxies"][0]["auth"]["password"]
+            .as_str()
+            .unwrap();
+        assert!(crypto::is_machine_encrypted(password));
+        assert_eq!(crypto::decrypt_string(password).unwrap(), "proxy-pw");
+    }
+
+    #[test]
+    fn test_restore_refuses_newer_schema_without_touching_data() {
+        let source = tempfile::tempdir().unwrap();
+        seed_data_dir(source.path());
+        Connection::open(source.path().join("token_stats.db"))
+            .unwrap()
+            .pragma_update(None, "user_version", 99)
+            .unwrap();
+        let (archive, _) = create_backup_in(source.path(), None).unwrap();
+
+        let target = tempfile::tempdir().unwrap();
+        seed_data_dir(target.path());
+        fs::write(target.path().join("gui_config.json"), "{}").unwrap();
+
+        let err = restore_backup_in(target.path(), &archive, None).unwrap_err();
+        assert!(err.contains("newer"), "{}", err);
+        assert_eq!(
+            fs::read_to_string(target.path().join("gui_config.json")).unwrap(),
+            "{}"
+        );
+        assert_eq!(usage_rows(target.path()), 1);
+    }
+
+    #[test]
+    fn test_failed_restore_rolls_back_databases_and_files() {
+        let source = tempfile::tempdir().unwrap();
+        seed_data_dir(source.path());
+        fs::write(source.path().join(ACCOUNTS_DIR).join("b.json"), "{}").unwrap();
+        Connection::open(source.path().join("token_stats.db"))
+            .unwrap()
+            .execute(
+                "INSERT INTO token_usage (timestamp, account_email, model) VALUES (2, 'b@test.com', 'm')",
+                [],
+            )
+            .unwrap();
+        let (archive, _) = create_backup_in(source.path(), None).unwrap();
+
+        // gui_config.json 在目标上是非空目录，写回 JSON 时必然失败 (数据库已先写回)
+        let target = tempfile::tempdir().unwrap();
+        seed_data_dir(target.path());
+        fs::remove_file(target.path().join("gui_config.json")).unwrap();
+        fs::create_dir_all(target.path().join("gui_config.json").join("blocker")).unwrap();
+        fs::write(target.path().join("accounts.json"), "{}").unwrap();
+
+        let err = restore_backup_in(target.path(), &archive, None).unwrap_err();
+        assert!(err.contains("rolled back"), "{}", err);
+        assert_eq!(usage_rows(target.path()), 1);
+        assert_eq!(
+            fs::read_to_string(target.path().join("accounts.json")).unwrap(),
+            "{}"
+        );
+        assert!(target.path().join(ACCOUNTS_DIR).join("a.json").exists());
+        assert!(!target.path().join(ACCOUNTS_DIR).join("b.json").exists());
+    }
+}
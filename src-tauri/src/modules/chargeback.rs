@@ -0,0 +1,427 @@
+//! 成本分摊报表
+//!
+//! 按用户令牌 / 账号 / 模型汇总 `token_usage` (`token_stats.db`) 中的用量，并按
+//! `proxy.pricing` 标价表 (每百万 Token 单价) 估算标价成本，便于向共用反代的各团队分摊。
+//! 计价使用实际路由后的模型名 (`mapped_model`)，未记录时回退到请求的模型名。
+
+use crate::proxy::common::model_mapping::wildcard_match;
+use crate::proxy::config::{ModelPrice, PricingConfig};
+use rusqlite::types::Value as SqlValue;
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+
+const TOKENS_PER_PRICE_UNIT: f64 = 1_000_000.0;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CostGroupBy {
+    /// 按用户令牌 (username)
+    #[default]
+    User,
+    Account,
+    Model,
+}
+
+impl CostGroupBy {
+    fn column(self) -> &'static str {
+        match self {
+            CostGroupBy::User => "username",
+            CostGroupBy::Account => "account_email",
+            CostGroupBy::Model => "COALESCE(mapped_model, model)",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct CostQuery {
+    pub group_by: CostGroupBy,
+    /// 起始时间 (RFC 3339 / YYYY-MM-DD / Unix 时间戳)，含
+    pub from: Option<String>,
+    /// 结束时间，不含；仅日期时包含当天
+    pub to: Option<String>,
+    /// 整月 (YYYY-MM)，与 from/to 互斥
+    pub month: Option<String>,
+    pub username: Option<String>,
+    pub account: Option<String>,
+    /// 匹配请求的模型名或路由后的模型名
+    pub model: Option<String>,
+}
+
+/// 校验后的查询
+#[derive(Debug, Clone)]
+pub struct CostPlan {
+    group_by: CostGroupBy,
+    from: Option<i64>,
+    to: Option<i64>,
+    sql: String,
+    params: Vec<SqlValue>,
+}
+
+/// 一组用量及其估算成本
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct CostLine {
+    /// 分组键；按用户令牌分组时，未使用用户令牌的请求为 null
+    pub key: Option<String>,
+    pub request_count: u64,
+    /// 按输入单价计费的 Token (不含缓存命中)
+    pub uncached_input_tokens: u64,
+    pub cached_tokens: u64,
+    /// 输出 Token (含推理 Token)
+    pub output_tokens: u64,
+    pub reasoning_tokens: u64,
+    pub total_tokens: u64,
+    pub cost: f64,
+    /// 标价表中没有的模型，其用量不计入 cost
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub unpriced_models: Vec<String>,
+}
+
+impl CostLine {
+    fn add(&mut self, other: &CostLine) {
+        self.request_count += other.request_count;
+        self.uncached_input_tokens += other.uncached_input_tokens;
+        self.cached_tokens += other.cached_tokens;
+        self.output_tokens += other.output_tokens;
+        self.reasoning_tokens += other.reasoning_tokens;
+        self.total_tokens += other.total_tokens;
+        self.cost += other.cost;
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CostReport {
+    pub group_by: CostGroupBy,
+    pub currency: String,
+    /// 统计区间 [from, to) 的 Unix 秒，未限定时为 null
+    pub from: Option<i64>,
+    pub to: Option<i64>,
+    /// 按成本降序
+    pub rows: Vec<CostLine>,
+    pub total: CostLine,
+}
+
+/// 查找模型的标价：精确匹配优先，其次是最具体的通配符规则
+pub fn find_price<'a>(pricing: &'a PricingConfig, model: &str) -> Option<&'a ModelPrice> {
+    if let Some(price) = pricing.models.get(model) {
+        return Some(price);
+    }
+    let specificity = |pattern: &str| pattern.chars().count() - pattern.matches('*').count();
+    pricing
+        .models
+        .iter()
+        .filter(|(pattern, _)| pattern.contains('*') && wildcard_match(pattern, model))
+        // 同等具体时按规则名取最小者，保证结果稳定
+        .max_by(|(a, _), (b, _)| specificity(a).cmp(&specificity(b)).then_with(|| b.cmp(a)))
+        .map(|(_, price)| price)
+}
+
+/// 按标价计算一组用量的成本；推理 Token 从输出中拆出单独计价
+fn usage_cost(price: &ModelPrice, usage: &CostLine) -> f64 {
+    let plain_output = usage.output_tokens.saturating_sub(usage.reasoning_tokens);
+    (usage.uncached_input_tokens as f64 * price.input
+        + usage.cached_tokens as f64 * price.cached_input.unwrap_or(price.input)
+        + plain_output as f64 * price.output
+        + usage.reasoning_tokens as f64 * price.reasoning.unwrap_or(price.output))
+        / TOKENS_PER_PRICE_UNIT
+}
+
+/// 校验参数并生成查询
+pub fn plan(query: &CostQuery) -> Result<CostPlan, String> {
+    let (from, to) = crate::modules::audit_export::resolve_period(
+        query.month.as_deref(),
+        query.from.as_deref(),
+        query.to.as_deref(),
+    )?;
+
+    let mut clauses = Vec::new();
+    let mut params = Vec::new();
+    if let Some(from) = from {
+        clauses.push("timestamp >= ?".to_string());
+        params.push(SqlValue::Integer(from));
+    }
+    if let Some(to) = to {
+        clauses.push("timestamp < ?".to_string());
+        params.push(SqlValue::Integer(to));
+    }
+    for (column, value) in [
+        ("username", &query.username),
+        ("account_email", &query.account),
+    ] {
+        if let Some(value) = value.as_deref().filter(|v| !v.is_empty()) {
+            clauses.push(format!("{} = ? COLLATE NOCASE", column));
+            params.push(SqlValue::Text(value.to_string()));
+        }
+    }
+    if let Some(model) = query.model.as_deref().filter(|v| !v.is_empty()) {
+        clauses.push("(model = ? OR mapped_model = ?)".to_string());
+        params.push(SqlValue::Text(model.to_string()));
+        params.push(SqlValue::Text(model.to_string()));
+    }
+
+    // 成本按 (分组键, 计价模型) 计算后再合并到分组键
+    let mut sql = format!(
+        "SELECT {} AS group_key, COALESCE(mapped_model, model) AS priced_model,
+            COUNT(*), SUM(uncached_input_tokens), SUM(cached_tokens), SUM(output_tokens),
+            SUM(MIN(reasoning_tokens, output_tokens)), SUM(total_tokens)
+         FROM token_usage",
+        query.group_by.column()
+    );
+    if !clauses.is_empty() {
+        sql.push_str(" WHERE ");
+        sql.push_str(&clauses.join(" AND "));
+    }
+    sql.push_str(" GROUP BY group_key, priced_model");
+
+    Ok(CostPlan {
+        group_by: query.group_by,
+        from,
+        to,
+        sql,
+        params,
+    })
+}
+
+/// 在指定连接上执行查询并估算成本
+pub fn build_report(
+    conn: &Connection,
+    plan: &CostPlan,
+    pricing: &PricingConfig,
+) -> Result<CostReport, String> {
+    let mut stmt = conn.prepare(&plan.sql).map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map(rusqlite::params_from_iter(plan.params.iter()), |row| {
+            Ok((
+                row.get::<_, Option<String>>(1)?,
+                CostLine {
+                    key: row.get(0)?,
+                    request_count: row.get(2)?,
+                    uncached_input_tokens: row.get(3)?,
+                    cached_tokens: row.get(4)?,
+                    output_tokens: row.get(5)?,
+                    reasoning_tokens: row.get(6)?,
+                    total_tokens: row.get(7)?,
+                    ..Default::default()
+                },
+            ))
+        })
+        .map_err(|e| e.to_string())?;
+
+    let mut groups: BTreeMap<Option<String>, (CostLine, BTreeSet<String>)> = BTreeMap::new();
+    let mut all_unpriced = BTreeSet::new();
+    for row in rows {
+        let (model, mut usage) = row.map_err(|e| e.to_string())?;
+        let model = model.unwrap_or_else(|| "unknown".to_string());
+        let (line, unpriced) = groups.entry(usage.key.clone()).or_insert_with(|| {
+            (
+                CostLine {
+                    key: usage.key.clone(),
+                    ..Default::default()
+                },
+                BTreeSet::new(),
+            )
+        });
+        match find_price(pricing, &model) {
+            Some(price) => usage.cost = usage_cost(price, &usage),
+            None => {
+                unpriced.insert(model.clone());
+                all_unpriced.insert(model);
+            }
+        }
+        line.add(&usage);
+    }
+
+    let mut total = CostLine::default();
+    let mut lines: Vec<CostLine> = groups
+        .into_values()
+        .map(|(mut line, unpriced)| {
+            line.unpriced_models = unpriced.into_iter().collect();
+            total.add(&line);
+            line
+        })
+        .collect();
+    lines.sort_by(|a, b| b.cost.total_cmp(&a.cost));
+    total.unpriced_models = all_unpriced.into_iter().collect();
+
+    Ok(CostReport {
+        group_by: plan.group_by,
+        currency: pricing.currency.clone(),
+        from: plan.from,
+        to: plan.to,
+        rows: lines,
+        total,
+    })
+}
+
+/// 基于 `token_stats.db` 生成成本报表
+pub fn cost_report(plan: &CostPlan, pricing: &PricingConfig) -> Result<CostReport, String> {
+    let conn = crate::modules::token_stats::connect_db()?;
+    build_report(&conn, plan, pricing)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::token_stats::{insert_usage, UsageRecord};
+    use chrono::TimeZone;
+
+    fn price(input: f64, output: f64) -> ModelPrice {
+        ModelPrice {
+            input,
+            output,
+            ..Default::default()
+        }
+    }
+
+    fn seeded_usage() -> Connection {
+        let mut conn = Connection::open_in_memory().unwrap();
+        crate::modules::token_stats::migrate(&mut conn).unwrap();
+        let september = chrono::Local
+            .with_ymd_and_hms(2025, 9, 10, 12, 0, 0)
+            .unwrap();
+        let october = chrono::Local
+            .with_ymd_and_hms(2025, 10, 10, 12, 0, 0)
+            .unwrap();
+        for (user, account, mapped, input, output, cached, reasoning, at) in [
+            (
+                Some("team-a"),
+                "a@example.com",
+                "gemini-2.5-pro",
+                1_000_000,
+                200_000,
+                400_000,
+                100_000,
+                september,
+            ),
+            (
+                Some("team-a"),
+                "b@example.com",
+                "gemini-2.5-flash",
+                2_000_000,
+                0,
+                0,
+                0,
+                september,
+            ),
+            (
+                Some("team-b"),
+                "a@example.com",
+                "claude-sonnet-4-5",
+                500_000,
+                100_000,
+                0,
+                0,
+                september,
+            ),
+            (
+                None,
+                "a@example.com",
+                "gemini-2.5-pro",
+                1_000_000,
+                0,
+                0,
+                0,
+                october,
+            ),
+        ] {
+            let record = UsageRecord {
+                account_email: account.to_string(),
+                model: "gpt-4o".to_string(),
+                mapped_model: Some(mapped.to_string()),
+                username: user.map(str::to_string),
+                input_tokens: input,
+                output_tokens: output,
+                cached_tokens: cached,
+                reasoning_tokens: reasoning,
+                input_includes_cached: true,
+            };
+            insert_usage(&conn, &record, at).unwrap();
+        }
+        conn
+    }
+
+    fn pricing() -> PricingConfig {
+        let mut pricing = PricingConfig::default();
+        pricing.models.insert(
+            "gemini-2.5-pro".to_string(),
+            ModelPrice {
+                input: 1.25,
+                output: 10.0,
+                cached_input: Some(0.25),
+                reasoning: Some(20.0),
+            },
+        );
+        pricing
+            .models
+            .insert("gemini-*".to_string(), price(0.3, 2.5));
+        pricing
+    }
+
+    #[test]
+    fn test_find_price_prefers_exact_then_most_specific_wildcard() {
+        let mut pricing = pricing();
+        pricing
+            .models
+            .insert("gemini-2.5-*".to_string(), price(0.5, 3.0));
+        assert_eq!(find_price(&pricing, "gemini-2.5-pro").unwrap().input, 1.25);
+        assert_eq!(find_price(&pricing, "gemini-2.5-flash").unwrap().input, 0.5);
+        assert_eq!(find_price(&pricing, "gemini-3-flash").unwrap().input, 0.3);
+        assert!(find_price(&pricing, "claude-sonnet-4-5").is_none());
+    }
+
+    #[test]
+    fn test_cost_by_user_for_month() {
+        let conn = seeded_usage();
+        let query = CostQuery {
+            month: Some("2025-09".to_string()),
+            ..Default::default()
+        };
+        let report = build_report(&conn, &plan(&query).unwrap(), &pricing()).unwrap();
+        assert_eq!(report.currency, "USD");
+        assert_eq!(report.rows.len(), 2);
+
+        // 600k 未命中缓存 * 1.25 + 400k 缓存 * 0.25 + 100k 输出 * 10 + 100k 推理 * 20 = 3.85
+        // 2M flash 输入 * 0.3 = 0.6
+        let team_a = &report.rows[0];
+        assert_eq!(team_a.key.as_deref(), Some("team-a"));
+        assert_eq!(team_a.request_count, 2);
+        assert_eq!(team_a.uncached_input_tokens, 2_600_000);
+        assert!((team_a.cost - 4.45).abs() < 1e-9);
+        assert!(team_a.unpriced_models.is_empty());
+
+        let team_b = &report.rows[1];
+        assert_eq!(team_b.cost, 0.0);
+        assert_eq!(
+            team_b.unpriced_models,
+            vec!["claude-sonnet-4-5".to_string()]
+        );
+        assert_eq!(report.total.request_count, 3);
+        assert_eq!(report.total.unpriced_models, team_b.unpriced_models);
+    }
+
+    #[test]
+    fn test_cost_by_model_and_account_filter() {
+        let conn = seeded_usage();
+        let query = CostQuery {
+            group_by: CostGroupBy::Model,
+            account: Some("A@example.com".to_string()),
+            ..Default::default()
+        };
+        let report = build_report(&conn, &plan(&query).unwrap(), &pricing()).unwrap();
+        let keys: Vec<_> = report.rows.iter().map(|r| r.key.as_deref()).collect();
+        assert_eq!(
+            keys,
+            vec![Some("gemini-2.5-pro"), Some("claude-sonnet-4-5")]
+        );
+        assert_eq!(report.rows[0].request_count, 2);
+        assert!((report.rows[0].cost - 5.1).abs() < 1e-9);
+
+        assert!(plan(&CostQuery {
+            month: Some("2025-09".to_string()),
+            to: Some("2025-10-01".to_string()),
+            ..Default::default()
+        })
+        .is_err());
+    }
+}
@@ -0,0 +1,1010 @@
+//! 无界面命令行 (服务器 / 脚本场景)
+//!
+//! 用法: `antigravity_tools <command> [args] [--json]`。直接复用 `AccountService`、
+//! `user_token_db`、`token_stats` 等模块，不依赖 Tauri 运行时；`proxy start` 以 headless
+//! 模式前台运行反代服务，其余命令执行完毕即退出。`--json` 输出机器可读结果。
+//!
+//! 修改账号的命令写完文件后，通过 pid 文件中记录的管理地址调用运行中实例的
+//! `/api/system/reload`；用户令牌由反代在每次请求时从数据库读取，无需通知。
+
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use tokio::sync::Notify;
+
+use crate::modules::{self, account, account_service::AccountService, token_stats, user_token_db};
+use crate::proxy::config::ProxyConfig;
+
+/// 可识别的顶层子命令 (首个参数命中时进入 CLI 模式)
+const COMMANDS: &[&str] = &[
+    "accounts", "tokens", "stats", "logs", "proxy", "config", "help",
+];
+
+/// 不带值的开关参数
+const BOOL_FLAGS: &[&str] = &["--json", "--redact"];
+
+const PID_FILE: &str = "headless.pid";
+
+/// 管理接口发起的停止请求 (`POST /api/system/shutdown`)
+static SHUTDOWN_REQUESTED: Lazy<Notify> = Lazy::new(Notify::new);
+/// 仅 headless 模式会等待停止请求，GUI 模式下拒绝该接口
+static SHUTDOWN_LISTENING: AtomicBool = AtomicBool::new(false);
+
+/// 敏感参数的环境变量 (不经由 argv 传递，避免出现在 ps / shell 历史中)
+const REFRESH_TOKEN_ENV: &str = "ABV_REFRESH_TOKEN";
+const IMPORT_PASSPHRASE_ENV: &str = "ABV_IMPORT_PASSPHRASE";
+
+const USAGE: &str = "\
+Usage: antigravity_tools <command> [args] [--json]
+
+Accounts:
+  accounts list
+  accounts add [--refresh-token-file <file|->]
+  accounts remove <id|email>
+  accounts enable <id|email>
+  accounts disable <id|email> [--reason <text>]
+  accounts refresh [<id|email>]
+  accounts import <file> [--passphrase-file <file|->]
+
+User tokens:
+  tokens list
+  tokens create <username> [--expires day|week|month|never] [--description <text>] [--max-ips <n>]
+
+Statistics:
+  stats [--hours <n>]
+
+Audit export:
+  logs export [--dataset request_logs|token_usage|user_token_usage] [--format jsonl|csv]
+              [--month YYYY-MM | --from <time> --to <time>] [--username <name>]
+              [--account <email>] [--model <model>] [--protocol <protocol>]
+              [--status <code|4xx|success|error>] [--redact] [--output <file>]
+
+Proxy service:
+  proxy start [--port <port>] [--bind <address>]
+  proxy stop
+
+Configuration:
+  config validate [<gui_config.json|config.toml|config.yaml>]
+
+Backup:
+  --backup <file> / --restore <file> [--passphrase-file <file|->]
+
+Secrets are never taken from the command line: pass them via a file (`-` reads
+stdin) or the ABV_REFRESH_TOKEN / ABV_IMPORT_PASSPHRASE / ABV_BACKUP_PASSPHRASE
+environment variables. `accounts add` also reads the token from stdin.
+
+`accounts enable/disable` toggle whether the proxy may use an account (the
+same switch as the UI's proxy toggle); accounts disabled automatically after
+an invalid refresh_token stay disabled until re-added. Account changes are
+pushed to a running `proxy start` instance; user tokens apply immediately.";
+
+/// CLI 执行结果
+pub enum CliOutcome {
+    /// 命令已执行完毕，按退出码退出
+    Exit(i32),
+    /// 以 headless 模式启动反代服务
+    Serve(ServeOverrides),
+}
+
+/// `proxy start` 的一次性覆盖项 (不写回配置文件)
+#[derive(Debug, Default, Clone)]
+pub struct ServeOverrides {
+    pub port: Option<u16>,
+    pub bind: Option<String>,
+}
+
+impl ServeOverrides {
+    pub fn is_empty(&self) -> bool {
+        self.port.is_none() && self.bind.is_none()
+    }
+
+    pub fn apply(&self, config: &mut ProxyConfig) {
+        if let Some(port) = self.port {
+            config.port = port;
+        }
+        if let Some(bind) = &self.bind {
+            // 非回环地址视为局域网访问，保持 auto 鉴权模式的判定一致
+            config.bind_address = Some(bind.clone());
+            config.allow_lan_access = config.is_lan_exposed();
+        }
+    }
+}
+
+/// 读取敏感参数 (口令 / refresh_token)
+///
+/// 优先级: `file` (`-` 表示 stdin) > 环境变量 `env_var` > stdin (`stdin_fallback`)。
+/// 读取结果去除首尾空白，空值视为未提供。
+pub(crate) fn read_secret(
+    file: Option<&str>,
+    env_var: &str,
+    stdin_fallback: bool,
+) -> Result<Option<String>, String> {
+    let read_stdin = || {
+        let mut buf = String::new();
+        std::io::Read::read_to_string(&mut std::io::stdin(), &mut buf)
+            .map(|_| buf)
+            .map_err(|e| format!("Failed to read stdin: {}", e))
+    };
+    let raw = match file {
+        Some("-") => Some(read_stdin()?),
+        Some(path) => Some(
+            std::fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path, e))?,
+        ),
+        None => match std::env::var(env_var) {
+            Ok(value) => Some(value),
+            Err(_) if stdin_fallback => Some(read_stdin()?),
+            Err(_) => None,
+        },
+    };
+    Ok(raw.map(|v| v.trim().to_string()).filter(|v| !v.is_empty()))
+}
+
+/// 命令行参数是否为 CLI 子命令调用
+pub fn is_cli_invocation(args: &[String]) -> bool {
+    args.get(1)
+        .is_some_and(|cmd| COMMANDS.contains(&cmd.as_str()))
+}
+
+#[derive(Debug, Default)]
+struct CliArgs {
+    positional: Vec<String>,
+    options: HashMap<String, String>,
+    json: bool,
+}
+
+impl CliArgs {
+    fn option(&self, name: &str) -> Option<&str> {
+        self.options.get(name).map(String::as_str)
+    }
+
+    fn parsed_option<T: std::str::FromStr>(&self, name: &str) -> Result<Option<T>, String> {
+        self.option(name)
+            .map(|v| {
+                v.parse::<T>()
+                    .map_err(|_| format!("Invalid value for --{}: {}", name, v))
+            })
+            .transpose()
+    }
+
+    fn arg(&self, index: usize, what: &str) -> Result<&str, String> {
+        self.positional
+            .get(index)
+            .map(String::as_str)
+            .ok_or_else(|| format!("Missing argument: <{}>", what))
+    }
+}
+
+fn parse_args(args: &[String]) -> Result<CliArgs, String> {
+    let mut parsed = CliArgs::default();
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        if arg == "--json" {
+            parsed.json = true;
+        } else if BOOL_FLAGS.contains(&arg.as_str()) {
+            parsed
+                .options
+                .insert(arg[2..].to_string(), "true".to_string());
+        } else if let Some(name) = arg.strip_prefix("--") {
+            let value = iter
+                .next()
+                .ok_or_else(|| format!("Missing value for --{}", name))?;
+            parsed.options.insert(name.to_string(), value.clone());
+        } else {
+            parsed.positional.push(arg.clone());
+        }
+    }
+    Ok(parsed)
+}
+
+/// 解析并执行 CLI 子命令；非 CLI 调用返回 None
+pub fn run_cli(args: &[String]) -> Option<CliOutcome> {
+    if !is_cli_invocation(args) {
+        return None;
+    }
+    let cli = match parse_args(&args[1..]) {
+        Ok(cli) => cli,
+        Err(e) => {
+            eprintln!("Error: {}\n\n{}", e, USAGE);
+            return Some(CliOutcome::Exit(2));
+        }
+    };
+
+    let command = cli.positional.first().map(String::as_str).unwrap_or("help");
+    let sub = cli.positional.get(1).map(String::as_str).unwrap_or("");
+    let result = match (command, sub) {
+        ("proxy", "start") => {
+            return Some(match serve_overrides(&cli) {
+                Ok(overrides) => CliOutcome::Serve(overrides),
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    CliOutcome::Exit(2)
+                }
+            });
+        }
+        ("proxy", "stop") => stop_headless(&cli),
+        ("accounts", "list") => list_accounts(&cli),
+        ("accounts", "add") => block_on(add_account(&cli)),
+        ("accounts", "remove") => block_on(remove_account(&cli)),
+        ("accounts", "enable") => block_on(set_account_enabled(&cli, true)),
+        ("accounts", "disable") => block_on(set_account_enabled(&cli, false)),
+        ("accounts", "refresh") => block_on(refresh_quotas(&cli)),
+        ("accounts", "import") => block_on(import_accounts(&cli)),
+        ("tokens", "list") => list_tokens(&cli),
+        ("tokens", "create") => create_token(&cli),
+        ("stats", _) => print_stats(&cli),
+        ("logs", "export") => export_logs(&cli),
+        ("config", "validate") => validate_config(&cli),
+        ("help", _) => {
+            println!("{}", USAGE);
+            return Some(CliOutcome::Exit(0));
+        }
+        _ => {
+            eprintln!("Unknown command: {} {}\n\n{}", command, sub, USAGE);
+            return Some(CliOutcome::Exit(2));
+        }
+    };
+
+    Some(CliOutcome::Exit(match result {
+        Ok(()) => 0,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            1
+        }
+    }))
+}
+
+fn block_on<F: std::future::Future<Output = Result<(), String>>>(future: F) -> Result<(), String> {
+    tokio::runtime::Runtime::new()
+        .map_err(|e| format!("Failed to create Tokio runtime: {}", e))?
+        .block_on(future)
+}
+
+/// `--json` 时输出 JSON，否则输出文本
+fn emit<T: Serialize>(cli: &CliArgs, value: &T, text: impl FnOnce() -> String) {
+    if cli.json {
+        match serde_json::to_string_pretty(value) {
+            Ok(json) => println!("{}", json),
+            Err(e) => eprintln!("Error: failed to serialize output: {}", e),
+        }
+    } else {
+        println!("{}", text());
+    }
+}
+
+/// 按账号 ID 或邮箱查找账号 ID
+fn resolve_account_id(key: &str) -> Result<String, String> {
+    account::load_account_index()?
+        .accounts
+        .into_iter()
+        .find(|s| s.id == key || s.email.eq_ignore_ascii_case(key))
+        .map(|s| s.id)
+        .ok_or_else(|| format!("Account not found: {}", key))
+}
+
+// --- accounts ---
+
+#[derive(Serialize)]
+struct AccountRow {
+    id: String,
+    email: String,
+    status: &'static str,
+    label: Option<String>,
+    tier: Option<String>,
+    min_quota_percentage: Option<i32>,
+}
+
+fn list_accounts(cli: &CliArgs) -> Result<(), String> {
+    let rows: Vec<AccountRow> = account::list_accounts()?
+        .into_iter()
+        .map(|acc| AccountRow {
+            status: if acc.disabled {
+                "disabled"
+            } else if acc.proxy_disabled {
+                "proxy_disabled"
+            } else {
+                "active"
+            },
+            tier: acc.quota.as_ref().and_then(|q| q.subscription_tier.clone()),
+            min_quota_percentage: acc
+                .quota
+                .as_ref()
+                .and_then(|q| q.models.iter().map(|m| m.percentage).min()),
+            label: acc.custom_label,
+            id: acc.id,
+            email: acc.email,
+        })
+        .collect();
+
+    emit(cli, &rows, || {
+        let mut out = format!("{} accounts", rows.len());
+        for row in &rows {
+            out.push_str(&format!(
+                "\n{}  {:<40} {:<15} quota={:<5} {}{}",
+                row.id,
+                row.email,
+                row.status,
+                row.min_quota_percentage
+                    .map(|p| format!("{}%", p))
+                    .unwrap_or_else(|| "-".to_string()),
+                row.tier.as_deref().unwrap_or(""),
+                row.label
+                    .as_deref()
+                    .map(|l| format!(" [{}]", l))
+                    .unwrap_or_default()
+            ));
+        }
+        out
+    });
+    Ok(())
+}
+
+async fn add_account(cli: &CliArgs) -> Result<(), String> {
+    if cli.option("refresh-token").is_some() || cli.positional.len() > 2 {
+        return Err(format!(
+            "refresh_token is not accepted on the command line (visible in ps and shell history); \
+             use --refresh-token-file <file|->, {} or stdin",
+            REFRESH_TOKEN_ENV
+        ));
+    }
+    let refresh_token = read_secret(cli.option("refresh-token-file"), REFRESH_TOKEN_ENV, true)?
+        .ok_or_else(|| "Missing refresh_token".to_string())?;
+    let service = AccountService::new(modules::integration::SystemManager::Headless);
+    let acc = service.add_account(refresh_token.trim()).await?;
+    notify_running_proxy().await;
+    emit(cli, &acc.id, || {
+        format!("Added account {} ({})", acc.email, acc.id)
+    });
+    Ok(())
+}
+
+async fn remove_account(cli: &CliArgs) -> Result<(), String> {
+    let id = resolve_account_id(cli.arg(2, "id|email")?)?;
+    AccountService::new(modules::integration::SystemManager::Headless).delete_account(&id)?;
+    notify_running_proxy().await;
+    emit(cli, &id, || format!("Removed account {}", id));
+    Ok(())
+}
+
+/// 切换账号的反代开关 (`proxy_disabled`)；`disabled` 由 refresh_token 失效时自动设置，不在此修改
+async fn set_account_enabled(cli: &CliArgs, enable: bool) -> Result<(), String> {
+    let id = resolve_account_id(cli.arg(2, "id|email")?)?;
+    let reason = cli.option("reason").unwrap_or("Disabled via CLI");
+    account::toggle_proxy_status(&id, enable, (!enable).then_some(reason))?;
+    notify_running_proxy().await;
+    emit(cli, &id, || {
+        format!(
+            "{} account {}",
+            if enable { "Enabled" } else { "Disabled" },
+            id
+        )
+    });
+    Ok(())
+}
+
+async fn refresh_quotas(cli: &CliArgs) -> Result<(), String> {
+    match cli.positional.get(2) {
+        Some(key) => {
+            let id = resolve_account_id(key)?;
+            let mut acc = account::load_account(&id)?;
+            let quota = account::fetch_quota_with_retry(&mut acc)
+                .await
+                .map_err(|e| e.to_string())?;
+            account::update_account_quota(&id, quota.clone())?;
+            notify_running_proxy().await;
+            emit(cli, &quota, || {
+                let min = quota.models.iter().map(|m| m.percentage).min();
+                format!(
+                    "Refreshed quota for {} ({} models, lowest {})",
+                    acc.email,
+                    quota.models.len(),
+                    min.map(|p| format!("{}%", p))
+                        .unwrap_or_else(|| "-".to_string())
+                )
+            });
+        }
+        None => {
+            let stats = account::refresh_all_quotas_logic().await?;
+            notify_running_proxy().await;
+            emit(cli, &stats, || {
+                format!(
+                    "Refreshed {} accounts: {} succeeded, {} failed",
+                    stats.total, stats.success, stats.failed
+                )
+            });
+        }
+    }
+    Ok(())
+}
+
+/// 从导出文件中提取 refresh_token：
+/// 支持 `/api/accounts/export` 的 JSON、refresh_token 字符串/对象数组、以及逐行纯文本
+fn parse_refresh_tokens(content: &str) -> Vec<String> {
+    fn token_of(value: &serde_json::Value) -> Option<String> {
+        value
+            .as_str()
+            .or_else(|| value.get("refresh_token").and_then(|v| v.as_str()))
+            .map(|s| s.trim().to_string())
+            .filter(|s| !s.is_empty())
+    }
+
+    match serde_json::from_str::<serde_json::Value>(content) {
+        Ok(serde_json::Value::Array(items)) => items.iter().filter_map(token_of).collect(),
+        Ok(value) => value
+            .get("accounts")
+            .and_then(|a| a.as_array())
+            .map(|items| items.iter().filter_map(token_of).collect())
+            .unwrap_or_default(),
+        Err(_) => content
+            .lines()
+            .map(str::trim)
+            .filter(|l| !l.is_empty() && !l.starts_with('#'))
+            .map(str::to_string)
+            .collect(),
+    }
+}
+
+async fn import_accounts(cli: &CliArgs) -> Result<(), String> {
+    let path = cli.arg(2, "file")?;
+    let content =
+        std::fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+
+    // 口令加密的便携导出
+    if cli.option("passphrase").is_some() {
+        return Err(format!(
+            "--passphrase is not accepted on the command line (visible in ps and shell history); \
+             use --passphrase-file <file|-> or {}",
+            IMPORT_PASSPHRASE_ENV
+        ));
+    }
+    if modules::account_transfer::is_sealed_bundle(&content) {
+        let passphrase = read_secret(cli.option("passphrase-file"), IMPORT_PASSPHRASE_ENV, false)?
+            .ok_or_else(|| {
+                format!(
+                    "{} is passphrase-encrypted; provide --passphrase-file <file|-> or {}",
+                    path, IMPORT_PASSPHRASE_ENV
+                )
+            })?;
+        let result = modules::account_transfer::import_accounts(&content, &passphrase)?;
+        notify_running_proxy().await;
+        emit(cli, &result, || {
+            format!(
+                "Imported {} accounts ({} proxies added, {} bindings restored)",
+                result.account_ids.len(),
+                result.proxies_added,
+                result.bindings_restored
+            )
+        });
+        return Ok(());
+    }
+
+    let tokens = parse_refresh_tokens(&content);
+    if tokens.is_empty() {
+        return Err(format!("No refresh tokens found in {}", path));
+    }
+
+    let service = AccountService::new(modules::integration::SystemManager::Headless);
+    let mut imported = Vec::new();
+    let mut failed = Vec::new();
+    for token in &tokens {
+        match service.add_account(token).await {
+            Ok(acc) => imported.push(acc.email),
+            Err(e) => failed.push(e),
+        }
+    }
+
+    if !imported.is_empty() {
+        notify_running_proxy().await;
+    }
+
+    let summary = serde_json::json!({ "imported": imported, "failed": failed });
+    emit(cli, &summary, || {
+        let mut out = format!("Imported {} of {} accounts", imported.len(), tokens.len());
+        for e in &failed {
+            out.push_str(&format!("\n  failed: {}", e));
+        }
+        out
+    });
+    if imported.is_empty() {
+        return Err("No accounts were imported".to_string());
+    }
+    Ok(())
+}
+
+// --- tokens ---
+
+fn list_tokens(cli: &CliArgs) -> Result<(), String> {
+    let tokens = user_token_db::list_tokens()?;
+    emit(cli, &tokens, || {
+        let mut out = format!("{} tokens", tokens.len());
+        for t in &tokens {
+            out.push_str(&format!(
+                "\n{}  {:<20} {:<8} {:<8} requests={} tokens={}",
+                t.id,
+                t.username,
+                if t.enabled { "enabled" } else { "disabled" },
+                t.expires_type,
+                t.total_requests,
+                t.total_tokens_used
+            ));
+        }
+        out
+    });
+    Ok(())
+}
+
+fn create_token(cli: &CliArgs) -> Result<(), String> {
+    let username = cli.arg(2, "username")?.to_string();
+    let expires = cli.option("expires").unwrap_or("never").to_string();
+    if !matches!(expires.as_str(), "day" | "week" | "month" | "never") {
+        return Err(format!(
+            "Invalid value for --expires: {} (expected day|week|month|never)",
+            expires
+        ));
+    }
+    let max_ips = cli.parsed_option::<i32>("max-ips")?.unwrap_or(0);
+    let token = user_token_db::create_token(
+        username,
+        expires,
+        cli.option("description").map(str::to_string),
+        max_ips,
+        None,
+        None,
+        None,
+    )?;
+    emit(cli, &token, || {
+        format!(
+            "Created token for {} (id {})\n{}",
+            token.username, token.id, token.token
+        )
+    });
+    Ok(())
+}
+
+// --- stats ---
+
+fn print_stats(cli: &CliArgs) -> Result<(), String> {
+    let hours = cli.parsed_option::<i64>("hours")?.unwrap_or(24);
+    let summary = token_stats::get_summary_stats(hours)?;
+    let accounts = token_stats::get_account_stats(hours)?;
+    let models = token_stats::get_model_stats(hours)?;
+
+    let report = serde_json::json!({
+        "hours": hours,
+        "summary": summary,
+        "accounts": accounts,
+        "models": models,
+    });
+    emit(cli, &report, || {
+        let mut out = format!(
+            "Last {}h: {} requests, {} tokens (input {}, output {}, cached {}), {} accounts",
+            hours,
+            summary.total_requests,
+            summary.total_tokens,
+            summary.total_input_tokens,
+            summary.total_output_tokens,
+            summary.total_cached_tokens,
+            summary.unique_accounts
+        );
+        out.push_str("\n\nBy account:");
+        for a in &accounts {
+            out.push_str(&format!(
+                "\n  {:<40} requests={:<8} tokens={}",
+                a.account_email, a.request_count, a.total_tokens
+            ));
+        }
+        out.push_str("\n\nBy model:");
+        for m in &models {
+            out.push_str(&format!(
+                "\n  {:<40} requests={:<8} tokens={}",
+                m.model, m.request_count, m.total_tokens
+            ));
+        }
+        out
+    });
+    Ok(())
+}
+
+// --- logs ---
+
+fn export_logs(cli: &CliArgs) -> Result<(), String> {
+    use crate::modules::audit_export::{self, ExportQuery};
+    use std::io::Write;
+
+    // dataset / format 与 HTTP 查询串使用同一套取值
+    let mut enums = serde_json::Map::new();
+    for name in ["dataset", "format"] {
+        if let Some(value) = cli.option(name) {
+            enums.insert(name.to_string(), value.into());
+        }
+    }
+    let mut query: ExportQuery = serde_json::from_value(serde_json::Value::Object(enums))
+        .map_err(|e| format!("Invalid --dataset or --format: {}", e))?;
+    query.from = cli.option("from").map(String::from);
+    query.to = cli.option("to").map(String::from);
+    query.month = cli.option("month").map(String::from);
+    query.username = cli.option("username").map(String::from);
+    query.account = cli.option("account").map(String::from);
+    query.model = cli.option("model").map(String::from);
+    query.protocol = cli.option("protocol").map(String::from);
+    query.status = cli.option("status").map(String::from);
+    query.redact = cli.option("redact").is_some();
+
+    let plan = audit_export::plan(&query)?;
+    let count = match cli.option("output") {
+        Some(path) => {
+            let file = std::fs::File::create(path)
+                .map_err(|e| format!("Failed to create {}: {}", path, e))?;
+            audit_export::write_export(&plan, &mut std::io::BufWriter::new(file))?
+        }
+        None => {
+            let stdout = std::io::stdout();
+            let mut out = std::io::BufWriter::new(stdout.lock());
+            let count = audit_export::write_export(&plan, &mut out)?;
+            out.flush().map_err(|e| e.to_string())?;
+            count
+        }
+    };
+
+    let report = serde_json::json!({ "rows": count, "output": cli.option("output") });
+    match cli.option("output") {
+        Some(path) => emit(cli, &report, || {
+            format!("Exported {} rows to {}", count, path)
+        }),
+        // 数据已写入 stdout，摘要输出到 stderr
+        None => eprintln!("Exported {} rows", count),
+    }
+    Ok(())
+}
+
+// --- config ---
+
+fn validate_config(cli: &CliArgs) -> Result<(), String> {
+    use crate::modules::managed_config::ManagedOverlay;
+
+    let (path, config) = match cli.positional.get(2).map(PathBuf::from) {
+        // gui_config.json 结构的完整配置
+        Some(path) if path.extension().is_some_and(|e| e == "json") => {
+            let content = std::fs::read_to_string(&path)
+                .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+            let config: crate::models::AppConfig = serde_json::from_str(&content)
+                .map_err(|e| format!("{} is not a valid config: {}", path.display(), e))?;
+            (path.display().to_string(), config)
+        }
+        // 声明式配置文件 (TOML / YAML)，叠加在默认配置之上校验
+        Some(path) => {
+            let overlay = ManagedOverlay::build(Some(&path), Vec::new())?;
+            let config = overlay.apply(crate::models::AppConfig::new())?;
+            (path.display().to_string(), config)
+        }
+        // 当前生效的配置 (含环境变量 / ABV_CONFIG_FILE 托管字段)
+        None => (
+            account::get_data_dir()?
+                .join("gui_config.json")
+                .display()
+                .to_string(),
+            modules::config::load_app_config()?,
+        ),
+    };
+
+    let problems = modules::config::validate_app_config(&config);
+    let report = serde_json::json!({
+        "file": path,
+        "managed_fields": config.managed_fields,
+        "valid": problems.is_empty(),
+        "problems": problems,
+    });
+    emit(cli, &report, || {
+        if problems.is_empty() {
+            format!("{}: OK", path)
+        } else {
+            format!("{}:\n  {}", path, problems.join("\n  "))
+        }
+    });
+    if problems.is_empty() {
+        Ok(())
+    } else {
+        Err(format!("{} problem(s) found", problems.len()))
+    }
+}
+
+// --- proxy ---
+
+fn serve_overrides(cli: &CliArgs) -> Result<ServeOverrides, String> {
+    let overrides = ServeOverrides {
+        port: cli.parsed_option::<u16>("port")?,
+        bind: cli.option("bind").map(str::to_string),
+    };
+    if overrides.port == Some(0) {
+        return Err("--port must be between 1 and 65535".to_string());
+    }
+    if let Some(bind) = &overrides.bind {
+        bind.parse::<std::net::IpAddr>()
+            .map_err(|_| format!("--bind '{}' is not an IP address", bind))?;
+    }
+    Ok(overrides)
+}
+
+fn pid_file_path() -> Result<PathBuf, String> {
+    Ok(account::get_data_dir()?.join(PID_FILE))
+}
+
+/// 本机访问管理接口的地址 (监听全部网卡时使用回环地址)
+pub fn control_address(proxy: &ProxyConfig) -> String {
+    let ip = match proxy.get_bind_address().parse::<std::net::IpAddr>() {
+        Ok(std::net::IpAddr::V4(ip)) if ip.is_unspecified() => {
+            std::net::IpAddr::V4(std::net::Ipv4Addr::LOCALHOST)
+        }
+        Ok(std::net::IpAddr::V6(ip)) if ip.is_unspecified() => {
+            std::net::IpAddr::V6(std::net::Ipv6Addr::LOCALHOST)
+        }
+        Ok(ip) => ip,
+        Err(_) => std::net::IpAddr::V4(std::net::Ipv4Addr::LOCALHOST),
+    };
+    std::net::SocketAddr::new(ip, proxy.port).to_string()
+}
+
+/// headless 模式启动后记录进程 ID 与管理地址，供 `proxy stop` 与账号命令通知使用
+pub fn write_pid_file(control_addr: &str) {
+    if let Ok(path) = pid_file_path() {
+        let content = format!("{}\n{}", std::process::id(), control_addr);
+        if let Err(e) = std::fs::write(&path, content) {
+            tracing::warn!("Failed to write pid file {}: {}", path.display(), e);
+        }
+    }
+}
+
+/// 删除 pid 文件 (滚动升级时新进程可能已写入自己的 pid，此时保留)
+pub fn remove_pid_file() {
+    if let Ok(path) = pid_file_path() {
+        if read_pid(&path).is_ok_and(|pid| pid == std::process::id()) {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
+
+fn read_pid_file(path: &Path) -> Result<(u32, Option<String>), String> {
+    let content = std::fs::read_to_string(path)
+        .map_err(|_| "No running headless proxy found (pid file missing)".to_string())?;
+    let mut lines = content.lines().map(str::trim);
+    let pid = lines
+        .next()
+        .unwrap_or_default()
+        .parse::<u32>()
+        .map_err(|e| format!("Invalid pid file {}: {}", path.display(), e))?;
+    let control_addr = lines.next().filter(|l| !l.is_empty()).map(str::to_string);
+    Ok((pid, control_addr))
+}
+
+fn read_pid(path: &Path) -> Result<u32, String> {
+    read_pid_file(path).map(|(pid, _)| pid)
+}
+
+/// 调用运行中 headless 实例的管理接口；没有运行中的实例时返回 Ok(None)
+async fn call_running_proxy(endpoint: &str) -> Result<Option<serde_json::Value>, String> {
+    let path = pid_file_path()?;
+    if !path.exists() {
+        return Ok(None);
+    }
+    let (pid, control_addr) = read_pid_file(&path)?;
+    let control_addr = control_addr.ok_or_else(|| {
+        format!(
+            "pid file {} has no admin address (process {} started by an older version)",
+            path.display(),
+            pid
+        )
+    })?;
+
+    let config = modules::config::load_app_config()?;
+    let admin_key = config
+        .proxy
+        .admin_password
+        .filter(|p| !p.is_empty())
+        .unwrap_or(config.proxy.api_key);
+    let response = reqwest::Client::builder()
+        .no_proxy()
+        .timeout(std::time::Duration::from_secs(30))
+        .build()
+        .map_err(|e| format!("Failed to create HTTP client: {}", e))?
+        .post(format!("http://{}/api/{}", control_addr, endpoint))
+        .bearer_auth(admin_key)
+        .send()
+        .await
+        .map_err(|e| format!("Failed to reach headless proxy at {}: {}", control_addr, e))?;
+
+    let status = response.status();
+    let body = response.text().await.unwrap_or_default();
+    if !status.is_success() {
+        return Err(format!(
+            "Headless proxy returned {} for /api/{}: {}",
+            status, endpoint, body
+        ));
+    }
+    Ok(Some(
+        serde_json::from_str(&body).unwrap_or(serde_json::Value::Null),
+    ))
+}
+
+/// 账号文件已修改：通知运行中的实例重新加载 (失败不影响命令本身的结果)
+async fn notify_running_proxy() {
+    if let Err(e) = call_running_proxy("system/reload").await {
+        eprintln!(
+            "Warning: changes were saved but the running proxy was not reloaded: {}",
+            e
+        );
+    }
+}
+
+fn stop_headless(cli: &CliArgs) -> Result<(), String> {
+    let path = pid_file_path()?;
+    let pid = read_pid(&path)?;
+
+    #[cfg(unix)]
+    {
+        // SAFETY: kill 仅向指定进程发送信号
+        let rc = unsafe { libc::kill(pid as libc::pid_t, libc::SIGTERM) };
+        if rc != 0 {
+            let err = std::io::Error::last_os_error();
+            if err.raw_os_error() == Some(libc::ESRCH) {
+                let _ = std::fs::remove_file(&path);
+                return Err(format!(
+                    "Process {} is not running (stale pid file removed)",
+                    pid
+                ));
+            }
+            return Err(format!("Failed to signal process {}: {}", pid, err));
+        }
+    }
+
+    // Windows 没有 SIGTERM，经由管理接口请求优雅停止 (排空进行中的请求)
+    #[cfg(windows)]
+    block_on(async {
+        call_running_proxy("system/shutdown")
+            .await
+            .map(|_| ())
+            .map_err(|e| format!("Failed to stop process {}: {}", pid, e))
+    })?;
+
+    emit(cli, &pid, || {
+        format!("Sent stop signal to headless proxy (pid {})", pid)
+    });
+    Ok(())
+}
+
+/// 管理接口请求停止；当前进程不是 headless 实例时返回 false
+pub fn request_shutdown() -> bool {
+    if !SHUTDOWN_LISTENING.load(Ordering::SeqCst) {
+        return false;
+    }
+    SHUTDOWN_REQUESTED.notify_one();
+    true
+}
+
+/// 等待 Ctrl-C、SIGTERM 或管理接口的停止请求 (`proxy stop`)
+pub async fn wait_for_shutdown_signal() {
+    SHUTDOWN_LISTENING.store(true, Ordering::SeqCst);
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        if let Ok(mut term) = signal(SignalKind::terminate()) {
+            tokio::select! {
+                _ = tokio::signal::ctrl_c() => {}
+                _ = term.recv() => {}
+                _ = SHUTDOWN_REQUESTED.notified() => {}
+            }
+            return;
+        }
+    }
+    tokio::select! {
+        _ = tokio::signal::ctrl_c() => {}
+        _ = SHUTDOWN_REQUESTED.notified() => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(list: &[&str]) -> Vec<String> {
+        std::iter::once("antigravity_tools")
+            .chain(list.iter().copied())
+            .map(str::to_string)
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_args_and_invocation() {
+        assert!(!is_cli_invocation(&args(&[])));
+        assert!(!is_cli_invocation(&args(&["--headless"])));
+        assert!(is_cli_invocation(&args(&["accounts", "list"])));
+
+        let cli =
+            parse_args(&args(&["tokens", "create", "bob", "--expires", "week", "--json"])[1..])
+                .unwrap();
+        assert_eq!(cli.positional, vec!["tokens", "create", "bob"]);
+        assert_eq!(cli.option("expires"), Some("week"));
+        assert!(cli.json);
+        assert!(parse_args(&args(&["stats", "--hours"])[1..]).is_err());
+
+        let cli =
+            parse_args(&args(&["logs", "export", "--redact", "--month", "2025-09"])[1..]).unwrap();
+        assert_eq!(cli.option("redact"), Some("true"));
+        assert_eq!(cli.option("month"), Some("2025-09"));
+        assert!(!cli.json);
+
+        let cli =
+            parse_args(&args(&["proxy", "start", "--port", "9000", "--bind", "10.0.0.5"])[1..])
+                .unwrap();
+        let overrides = serve_overrides(&cli).unwrap();
+        let mut config = ProxyConfig::default();
+        overrides.apply(&mut config);
+        assert_eq!(config.port, 9000);
+        assert_eq!(config.get_bind_address(), "10.0.0.5");
+        assert!(config.allow_lan_access);
+
+        let cli = parse_args(&args(&["proxy", "start", "--bind", "localhost"])[1..]).unwrap();
+        assert!(serve_overrides(&cli).is_err());
+    }
+
+    #[test]
+    fn test_read_secret_sources() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("passphrase.txt");
+        std::fs::write(&file, "  s3cret\n").unwrap();
+
+        assert_eq!(
+            read_secret(file.to_str(), "ABV_TEST_SECRET_UNSET", false).unwrap(),
+            Some("s3cret".to_string())
+        );
+        assert_eq!(
+            read_secret(None, "ABV_TEST_SECRET_UNSET", false).unwrap(),
+            None
+        );
+        assert!(read_secret(Some("/nonexistent/secret"), "ABV_TEST_SECRET_UNSET", false).is_err());
+    }
+
+    #[test]
+    fn test_parse_refresh_tokens_formats() {
+        let export = r#"{"accounts":[{"email":"a@x.com","refresh_token":"1//a"},{"email":"b@x.com","refresh_token":" "}]}"#;
+        assert_eq!(parse_refresh_tokens(export), vec!["1//a"]);
+        assert_eq!(
+            parse_refresh_tokens(r#"["1//a", {"refresh_token": "1//b"}]"#),
+            vec!["1//a", "1//b"]
+        );
+        assert_eq!(
+            parse_refresh_tokens("# tokens\n1//a\n\n  1//b  \n"),
+            vec!["1//a", "1//b"]
+        );
+    }
+
+    #[test]
+    fn test_pid_file_records_control_address() {
+        let mut proxy = ProxyConfig {
+            port: 8045,
+            ..Default::default()
+        };
+        proxy.bind_address = Some("0.0.0.0".to_string());
+        assert_eq!(control_address(&proxy), "127.0.0.1:8045");
+        proxy.bind_address = Some("::".to_string());
+        assert_eq!(control_address(&proxy), "[::1]:8045");
+        proxy.bind_address = Some("192.168.1.5".to_string());
+        assert_eq!(control_address(&proxy), "192.168.1.5:8045");
+
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join(PID_FILE);
+        std::fs::write(&file, "4242\n127.0.0.1:8045\n").unwrap();
+        assert_eq!(
+            read_pid_file(&file).unwrap(),
+            (4242, Some("127.0.0.1:8045".to_string()))
+        );
+        // 旧版本只写入 pid
+        std::fs::write(&file, "4242").unwrap();
+        assert_eq!(read_pid_file(&file).unwrap(), (4242, None));
+    }
+
+    #[test]
+    fn test_shutdown_request_requires_headless_waiter() {
+        assert!(!request_shutdown());
+    }
+}
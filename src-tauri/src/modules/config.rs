@@ -7,6 +7,11 @@ use tracing::warn;
 
 const CONFIG_FILE: &str = "gui_config.json";
 
+/// 配置文件路径 (`gui_config.json`)
+pub fn get_config_path() -> Result<std::path::PathBuf, String> {
+    Ok(get_data_dir()?.join(CONFIG_FILE))
+}
+
 /// Load application configuration
 pub fn load_app_config() -> Result<AppConfig, String> {
     let data_dir = get_data_dir()?;
@@ -16,7 +21,7 @@ pub fn load_app_config() -> Result<AppConfig, String> {
         let config = AppConfig::new();
         // [FIX #1460] Persist initial config to prevent new API Key on every refresh
         let _ = save_app_config(&config);
-        return Ok(config);
+        return super::managed_config::apply(config);
     }
 
     let content = fs::read_to_string(&config_path)
@@ -95,7 +100,8 @@ pub fn load_app_config() -> Result<AppConfig, String> {
         let _ = save_app_config(&config);
     }
 
-    Ok(config)
+    // 叠加环境变量 / 声明式配置文件中的托管字段
+    super::managed_config::apply(config)
 }
 
 /// Save application configuration
@@ -103,8 +109,85 @@ pub fn save_app_config(config: &AppConfig) -> Result<(), String> {
     let data_dir = get_data_dir()?;
     let config_path = data_dir.join(CONFIG_FILE);
 
-    let content = serde_json::to_string_pretty(config)
+    let mut value =
+        serde_json::to_value(config).map_err(|e| format!("failed_to_serialize_config: {}", e))?;
+    if let Some(obj) = value.as_object_mut() {
+        obj.remove("managed_fields");
+    }
+
+    // 托管字段不写回磁盘，保留文件中的原值
+    let managed = super::managed_config::current()?;
+    if !managed.is_empty() {
+        let on_disk = fs::read_to_string(&config_path)
+            .ok()
+            .and_then(|c| serde_json::from_str::<serde_json::Value>(&c).ok());
+        managed.restore_unmanaged(&mut value, on_disk.as_ref());
+    }
+
+    let content = serde_json::to_string_pretty(&value)
         .map_err(|e| format!("failed_to_serialize_config: {}", e))?;
 
     fs::write(&config_path, content).map_err(|e| format!("failed_to_save_config: {}", e))
 }
+
+/// 校验配置的语义合法性，返回发现的问题列表 (空表示通过)
+pub fn validate_app_config(config: &AppConfig) -> Vec<String> {
+    let mut problems = Vec::new();
+    let proxy = &config.proxy;
+
+    if proxy.port == 0 {
+        problems.push("proxy.port must be between 1 and 65535".to_string());
+    }
+    if let Some(addr) = proxy.bind_address.as_deref().filter(|a| !a.is_empty()) {
+        if addr.parse::<std::net::IpAddr>().is_err() {
+            problems.push(format!(
+                "proxy.bind_address '{}' is not an IP address",
+                addr
+            ));
+        }
+    }
+    if matches!(
+        proxy.auth_mode,
+        crate::proxy::ProxyAuthMode::Strict | crate::proxy::ProxyAuthMode::AllExceptHealth
+    ) && proxy.api_key.trim().is_empty()
+    {
+        problems.push("proxy.api_key must not be empty when auth is enabled".to_string());
+    }
+    if proxy.upstream_proxy.enabled {
+        if let Err(e) = validate_proxy_url(&proxy.upstream_proxy.url) {
+            problems.push(format!("proxy.upstream_proxy.url: {}", e));
+        }
+    }
+
+    let pool = &proxy.proxy_pool;
+    let mut seen = std::collections::HashSet::new();
+    for entry in &pool.proxies {
+        if !seen.insert(entry.id.as_str()) {
+            problems.push(format!(
+                "proxy.proxy_pool: duplicate proxy id '{}'",
+                entry.id
+            ));
+        }
+        if let Err(e) = validate_proxy_url(&entry.url) {
+            problems.push(format!("proxy.proxy_pool '{}': {}", entry.name, e));
+        }
+    }
+    for (account_id, proxy_id) in &pool.account_bindings {
+        if !seen.contains(proxy_id.as_str()) {
+            problems.push(format!(
+                "proxy.proxy_pool: account {} is bound to unknown proxy '{}'",
+                account_id, proxy_id
+            ));
+        }
+    }
+
+    problems
+}
+
+fn validate_proxy_url(raw: &str) -> Result<(), String> {
+    let url = url::Url::parse(raw.trim()).map_err(|e| format!("invalid URL '{}': {}", raw, e))?;
+    match url.scheme() {
+        "http" | "https" | "socks5" | "socks5h" => Ok(()),
+        other => Err(format!("unsupported proxy scheme '{}'", other)),
+    }
+}
@@ -0,0 +1,150 @@
+//! SQLite 结构版本迁移
+//!
+//! 各数据库模块声明一组按版本号递增的 `Migration`，由 `run_migrations` 依次在
+//! 独立事务中执行并写入 `PRAGMA user_version`。若数据库的 `user_version` 高于当前
+//! 程序已知的最新版本 (由更新版本的应用写入)，则拒绝打开，避免旧版本误写导致数据损坏。
+
+use rusqlite::Connection;
+
+/// 单个结构迁移步骤
+pub struct Migration {
+    /// 迁移后的结构版本 (从 1 开始严格递增)
+    pub version: u32,
+    pub description: &'static str,
+    pub up: fn(&Connection) -> rusqlite::Result<()>,
+}
+
+fn latest_version(migrations: &[Migration]) -> u32 {
+    migrations.last().map_or(0, |m| m.version)
+}
+
+/// 读取数据库当前的结构版本
+pub fn schema_version(conn: &Connection) -> rusqlite::Result<u32> {
+    conn.pragma_query_value(None, "user_version", |row| row.get(0))
+}
+
+/// 降级保护：数据库结构版本高于当前程序支持的版本时返回错误
+pub fn ensure_supported(
+    conn: &Connection,
+    db_name: &str,
+    migrations: &[Migration],
+) -> Result<(), String> {
+    let current = schema_version(conn).map_err(|e| e.to_string())?;
+    let latest = latest_version(migrations);
+    if current > latest {
+        return Err(format!(
+            "{} schema version {} is newer than supported version {} (written by a newer app version); refusing to open",
+            db_name, current, latest
+        ));
+    }
+    Ok(())
+}
+
+/// 执行所有未应用的迁移，返回迁移后的结构版本
+pub fn run_migrations(
+    conn: &mut Connection,
+    db_name: &str,
+    migrations: &[Migration],
+) -> Result<u32, String> {
+    debug_assert!(
+        migrations.windows(2).all(|w| w[0].version < w[1].version),
+        "migrations must be sorted by strictly increasing version"
+    );
+    ensure_supported(conn, db_name, migrations)?;
+
+    let applied = schema_version(conn).map_err(|e| e.to_string())?;
+    let mut current = applied;
+    for migration in migrations.iter().filter(|m| m.version > applied) {
+        let tx = conn.transaction().map_err(|e| e.to_string())?;
+        (migration.up)(&tx).map_err(|e| {
+            format!(
+                "{} migration v{} ({}) failed: {}",
+                db_name, migration.version, migration.description, e
+            )
+        })?;
+        tx.pragma_update(None, "user_version", migration.version)
+            .map_err(|e| e.to_string())?;
+        tx.commit().map_err(|e| e.to_string())?;
+
+        tracing::debug!(
+            "[DB] {} migrated v{} -> v{}: {}",
+            db_name,
+            current,
+            migration.version,
+            migration.description
+        );
+        current = migration.version;
+    }
+    Ok(current)
+}
+
+/// 旧库兼容：列已存在时忽略 (用于基线迁移补齐历史版本新增的列)
+pub fn add_column_if_missing(
+    conn: &Connection,
+    table: &str,
+    column_def: &str,
+) -> rusqlite::Result<()> {
+    let sql = format!("ALTER TABLE {} ADD COLUMN {}", table, column_def);
+    match conn.execute(&sql, []) {
+        Ok(_) => Ok(()),
+        Err(e) if e.to_string().contains("duplicate column name") => Ok(()),
+        Err(e) => Err(e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MIGRATIONS: &[Migration] = &[
+        Migration {
+            version: 1,
+            description: "create items",
+            up: |conn| {
+                conn.execute_batch("CREATE TABLE IF NOT EXISTS items (id INTEGER PRIMARY KEY)")
+            },
+        },
+        Migration {
+            version: 2,
+            description: "add name column",
+            up: |conn| add_column_if_missing(conn, "items", "name TEXT"),
+        },
+    ];
+
+    #[test]
+    fn test_runs_pending_migrations_once() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        assert_eq!(
+            run_migrations(&mut conn, "test.db", &MIGRATIONS[..1]).unwrap(),
+            1
+        );
+        assert_eq!(run_migrations(&mut conn, "test.db", MIGRATIONS).unwrap(), 2);
+        assert_eq!(run_migrations(&mut conn, "test.db", MIGRATIONS).unwrap(), 2);
+        conn.execute("INSERT INTO items (name) VALUES ('a')", [])
+            .unwrap();
+    }
+
+    #[test]
+    fn test_failed_migration_rolls_back() {
+        const BROKEN: &[Migration] = &[Migration {
+            version: 1,
+            description: "broken",
+            up: |conn| conn.execute_batch("CREATE TABLE t (id INTEGER); SELECT * FROM missing"),
+        }];
+        let mut conn = Connection::open_in_memory().unwrap();
+        let err = run_migrations(&mut conn, "test.db", BROKEN).unwrap_err();
+        assert!(err.contains("v1"), "{}", err);
+        assert_eq!(schema_version(&conn).unwrap(), 0);
+        assert!(conn.execute("INSERT INTO t (id) VALUES (1)", []).is_err());
+    }
+
+    #[test]
+    fn test_refuses_newer_schema() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        conn.pragma_update(None, "user_version", 9).unwrap();
+        let err = run_migrations(&mut conn, "test.db", MIGRATIONS).unwrap_err();
+        assert!(err.contains("newer"), "{}", err);
+        assert!(ensure_supported(&conn, "test.db", MIGRATIONS).is_err());
+        assert_eq!(schema_version(&conn).unwrap(), 9);
+    }
+}
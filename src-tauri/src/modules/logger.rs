@@ -45,7 +45,15 @@ pub fn init_logger() {
     let (non_blocking, _guard) = tracing_appender::non_blocking(file_appender);
 
     // 2. Console output layer (using local timezone)
+    // CLI 子命令模式下日志输出到 stderr，保持 stdout 仅包含命令结果
+    let args: Vec<String> = std::env::args().collect();
+    let console_writer = if crate::modules::cli::is_cli_invocation(&args) {
+        fmt::writer::BoxMakeWriter::new(std::io::stderr)
+    } else {
+        fmt::writer::BoxMakeWriter::new(std::io::stdout)
+    };
     let console_layer = fmt::Layer::new()
+        .with_writer(console_writer)
         .with_target(false)
         .with_thread_ids(false)
         .with_level(true)
@@ -65,8 +73,12 @@ pub fn init_logger() {
     // 6. Log bridge layer
     let bridge_layer = crate::modules::log_bridge::TauriLogBridgeLayer::new();
 
+    // 7. OpenTelemetry layer (empty until enabled via proxy telemetry config)
+    let otel_layer = crate::proxy::telemetry::layer();
+
     // 5. Initialize global subscriber (use try_init to avoid crash on repeated initialization)
     let _ = tracing_subscriber::registry()
+        .with(otel_layer)
         .with(filter_layer)
         .with(console_layer)
         .with(file_layer)
@@ -0,0 +1,357 @@
+//! 托管配置 (容器 / Kubernetes 部署)
+//!
+//! 在 `gui_config.json` 之上叠加两层只读配置，优先级从低到高：
+//! 1. 持久化的 `gui_config.json` (GUI / `save_config` 写入)
+//! 2. `ABV_CONFIG_FILE` 指向的声明式配置文件 (TOML / YAML / JSON，结构与 `AppConfig` 相同，可只写部分字段)
+//! 3. `ABV_PROXY__<FIELD>[__<SUBFIELD>...]` 环境变量，映射到 `proxy` 下的对应字段，
+//!    例如 `ABV_PROXY__PORT=8045`、`ABV_PROXY__ZAI__ENABLED=true`、
+//!    `ABV_PROXY__CUSTOM_MAPPING={"gpt-4o":"gemini-2.5-pro"}`
+//!
+//! 被托管的字段通过 `AppConfig::managed_fields` 报告给 `/api/config`，保存配置时保留磁盘上的原值，
+//! 不会把托管值写回 `gui_config.json`。
+
+use serde_json::{Map, Value};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+
+use crate::models::AppConfig;
+
+/// 声明式配置文件路径的环境变量
+pub const CONFIG_FILE_ENV: &str = "ABV_CONFIG_FILE";
+/// `proxy` 字段覆盖的环境变量前缀
+pub const ENV_PREFIX: &str = "ABV_PROXY__";
+
+/// 已解析的托管配置层
+#[derive(Debug, Default, Clone)]
+pub struct ManagedOverlay {
+    /// 声明式配置文件路径 (如有)
+    pub source: Option<PathBuf>,
+    overlay: Value,
+    paths: Vec<Vec<String>>,
+}
+
+impl ManagedOverlay {
+    /// 由配置文件与环境变量构建托管层
+    pub fn build(
+        file: Option<&Path>,
+        vars: impl IntoIterator<Item = (String, String)>,
+    ) -> Result<Self, String> {
+        let mut overlay = match file {
+            Some(path) => parse_config_file(path)?,
+            None => Value::Object(Map::new()),
+        };
+
+        // 以默认配置的结构判断字段类型：字符串字段保持原样，其余按 JSON 解析
+        let shape = serde_json::to_value(AppConfig::new()).map_err(|e| e.to_string())?;
+        let mut env_vars: Vec<(String, String)> = vars
+            .into_iter()
+            .filter(|(k, _)| k.starts_with(ENV_PREFIX) && k.len() > ENV_PREFIX.len())
+            .collect();
+        env_vars.sort();
+        for (key, raw) in env_vars {
+            let mut path = vec!["proxy".to_string()];
+            path.extend(
+                key[ENV_PREFIX.len()..]
+                    .split("__")
+                    .map(|s| s.to_ascii_lowercase()),
+            );
+            let value = match lookup(&shape, &path) {
+                Some(Value::String(_)) => Value::String(raw),
+                _ => serde_json::from_str(&raw).unwrap_or(Value::String(raw)),
+            };
+            set_path(&mut overlay, &path, value);
+        }
+
+        let mut paths = Vec::new();
+        collect_leaf_paths(&overlay, &mut Vec::new(), &mut paths);
+        Ok(Self {
+            source: file.map(Path::to_path_buf),
+            overlay,
+            paths,
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.paths.is_empty()
+    }
+
+    /// 托管字段的点分路径 (如 `proxy.port`)
+    pub fn managed_fields(&self) -> Vec<String> {
+        self.paths.iter().map(|p| p.join(".")).collect()
+    }
+
+    /// 将托管值合并到配置 JSON 上
+    pub fn merge_into(&self, config: &mut Value) {
+        merge(config, &self.overlay);
+    }
+
+    /// 保存前还原托管字段：使用磁盘上的原值，原先不存在则移除
+    pub fn restore_unmanaged(&self, config: &mut Value, on_disk: Option<&Value>) {
+        for path in &self.paths {
+            match on_disk.and_then(|v| lookup(v, path)) {
+                Some(original) => set_path(config, path, original.clone()),
+                None => remove_path(config, path),
+            }
+        }
+    }
+
+    /// 合并托管值并重新解析为 `AppConfig`
+    pub fn apply(&self, config: AppConfig) -> Result<AppConfig, String> {
+        if self.is_empty() {
+            return Ok(config);
+        }
+        let mut value = serde_json::to_value(&config).map_err(|e| e.to_string())?;
+        self.merge_into(&mut value);
+        let mut managed: AppConfig = serde_json::from_value(value)
+            .map_err(|e| format!("Invalid managed configuration: {}", e))?;
+        managed.managed_fields = self.managed_fields();
+        Ok(managed)
+    }
+}
+
+static CURRENT: RwLock<Option<Arc<ManagedOverlay>>> = RwLock::new(None);
+
+/// 声明式配置文件路径 (`ABV_CONFIG_FILE`)
+pub fn config_file_path() -> Option<PathBuf> {
+    std::env::var(CONFIG_FILE_ENV)
+        .ok()
+        .filter(|p| !p.trim().is_empty())
+        .map(PathBuf::from)
+}
+
+fn build_from_env() -> Result<ManagedOverlay, String> {
+    let file = config_file_path();
+    let overlay = ManagedOverlay::build(file.as_deref(), std::env::vars())?;
+    if !overlay.is_empty() {
+        tracing::info!(
+            "[Config] {} managed fields (config file: {})",
+            overlay.paths.len(),
+            overlay
+                .source
+                .as_ref()
+                .map(|p| p.display().to_string())
+                .unwrap_or_else(|| "none".to_string())
+        );
+    }
+    Ok(overlay)
+}
+
+/// 当前进程的托管配置 (首次调用时从环境读取)
+pub fn current() -> Result<Arc<ManagedOverlay>, String> {
+    if let Some(overlay) = CURRENT.read().unwrap_or_else(|e| e.into_inner()).as_ref() {
+        return Ok(overlay.clone());
+    }
+    let mut guard = CURRENT.write().unwrap_or_else(|e| e.into_inner());
+    if let Some(overlay) = guard.as_ref() {
+        return Ok(overlay.clone());
+    }
+    let overlay = Arc::new(build_from_env()?);
+    *guard = Some(overlay.clone());
+    Ok(overlay)
+}
+
+/// 重新读取声明式配置文件 (热更新)；解析失败时保留原托管配置
+pub fn reload() -> Result<Arc<ManagedOverlay>, String> {
+    let overlay = Arc::new(build_from_env()?);
+    *CURRENT.write().unwrap_or_else(|e| e.into_inner()) = Some(overlay.clone());
+    Ok(overlay)
+}
+
+/// 将当前进程的托管配置应用到 `AppConfig`
+pub fn apply(config: AppConfig) -> Result<AppConfig, String> {
+    current()?.apply(config)
+}
+
+/// 按扩展名解析声明式配置文件
+pub fn parse_config_file(path: &Path) -> Result<Value, String> {
+    let content = std::fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read config file {}: {}", path.display(), e))?;
+    let ext = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or("")
+        .to_ascii_lowercase();
+    let value: Value = match ext.as_str() {
+        "toml" => toml::from_str(&content).map_err(|e| e.to_string()),
+        "yaml" | "yml" => serde_yaml::from_str(&content).map_err(|e| e.to_string()),
+        "json" => serde_json::from_str(&content).map_err(|e| e.to_string()),
+        other => Err(format!(
+            "unsupported extension '{}' (expected .toml, .yaml, .yml or .json)",
+            other
+        )),
+    }
+    .map_err(|e| format!("Invalid config file {}: {}", path.display(), e))?;
+
+    if !value.is_object() {
+        return Err(format!(
+            "Invalid config file {}: top level must be a table/mapping",
+            path.display()
+        ));
+    }
+    Ok(value)
+}
+
+fn merge(base: &mut Value, overlay: &Value) {
+    match (base, overlay) {
+        (Value::Object(base), Value::Object(overlay)) => {
+            for (key, value) in overlay {
+                merge(base.entry(key.clone()).or_insert(Value::Null), value);
+            }
+        }
+        (base, overlay) => *base = overlay.clone(),
+    }
+}
+
+fn lookup<'a>(value: &'a Value, path: &[String]) -> Option<&'a Value> {
+    path.iter().try_fold(value, |v, key| v.get(key))
+}
+
+fn set_path(value: &mut Value, path: &[String], new: Value) {
+    let mut current = value;
+    for key in path {
+        if !current.is_object() {
+            *current = Value::Object(Map::new());
+        }
+        current = current
+            .as_object_mut()
+            .expect("object ensured above")
+            .entry(key.clone())
+            .or_insert(Value::Null);
+    }
+    *current = new;
+}
+
+fn remove_path(value: &mut Value, path: &[String]) {
+    let Some((last, parents)) = path.split_last() else {
+        return;
+    };
+    let mut current = value;
+    for key in parents {
+        match current.get_mut(key) {
+            Some(next) => current = next,
+            None => return,
+        }
+    }
+    if let Some(obj) = current.as_object_mut() {
+        obj.remove(last);
+    }
+}
+
+fn collect_leaf_paths(value: &Value, prefix: &mut Vec<String>, out: &mut Vec<Vec<String>>) {
+    match value {
+        Value::Object(map) if !map.is_empty() => {
+            for (key, child) in map {
+                prefix.push(key.clone());
+                collect_leaf_paths(child, prefix, out);
+                prefix.pop();
+            }
+        }
+        _ if !prefix.is_empty() => out.push(prefix.clone()),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vars(list: &[(&str, &str)]) -> Vec<(String, String)> {
+        list.iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_env_overrides_are_typed_by_field() {
+        let overlay = ManagedOverlay::build(
+            None,
+            vars(&[
+                ("ABV_PROXY__PORT", "9000"),
+                ("ABV_PROXY__API_KEY", "12345"),
+                ("ABV_PROXY__AUTH_MODE", "strict"),
+                ("ABV_PROXY__ZAI__ENABLED", "true"),
+                (
+                    "ABV_PROXY__CUSTOM_MAPPING",
+                    r#"{"gpt-4.1":"gemini-2.5-pro"}"#,
+                ),
+                (
+                    "ABV_PROXY__EXPERIMENTAL__CONTEXT_COMPRESSION_THRESHOLD_L1",
+                    "0.5",
+                ),
+                ("UNRELATED", "x"),
+            ]),
+        )
+        .unwrap();
+
+        let config = overlay.apply(AppConfig::new()).unwrap();
+        assert_eq!(config.proxy.port, 9000);
+        assert_eq!(config.proxy.api_key, "12345");
+        assert!(matches!(
+            config.proxy.auth_mode,
+            crate::proxy::ProxyAuthMode::Strict
+        ));
+        assert!(config.proxy.zai.enabled);
+        assert_eq!(
+            config
+                .proxy
+                .custom_mapping
+                .get("gpt-4.1")
+                .map(String::as_str),
+            Some("gemini-2.5-pro")
+        );
+        assert_eq!(
+            config.proxy.experimental.context_compression_threshold_l1,
+            0.5
+        );
+        assert!(config.managed_fields.contains(&"proxy.port".to_string()));
+        assert!(config
+            .managed_fields
+            .contains(&"proxy.zai.enabled".to_string()));
+    }
+
+    #[test]
+    fn test_file_layer_and_env_precedence() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        std::fs::write(
+            &path,
+            "[proxy]\nport = 8100\nrequest_timeout = 300\n\n[proxy.zai]\nenabled = true\n",
+        )
+        .unwrap();
+
+        let overlay =
+            ManagedOverlay::build(Some(&path), vars(&[("ABV_PROXY__PORT", "8200")])).unwrap();
+        let config = overlay.apply(AppConfig::new()).unwrap();
+        assert_eq!(config.proxy.port, 8200);
+        assert_eq!(config.proxy.request_timeout, 300);
+        assert!(config.proxy.zai.enabled);
+
+        let yaml = dir.path().join("config.yaml");
+        std::fs::write(&yaml, "proxy:\n  port: 8300\n").unwrap();
+        let overlay = ManagedOverlay::build(Some(&yaml), Vec::new()).unwrap();
+        assert_eq!(overlay.apply(AppConfig::new()).unwrap().proxy.port, 8300);
+
+        let bad = dir.path().join("config.ini");
+        std::fs::write(&bad, "port=1").unwrap();
+        assert!(ManagedOverlay::build(Some(&bad), Vec::new()).is_err());
+    }
+
+    #[test]
+    fn test_restore_unmanaged_keeps_disk_values() {
+        let overlay = ManagedOverlay::build(
+            None,
+            vars(&[
+                ("ABV_PROXY__PORT", "9000"),
+                ("ABV_PROXY__BIND_ADDRESS", "0.0.0.0"),
+            ]),
+        )
+        .unwrap();
+        let mut saved = serde_json::json!({"proxy": {"port": 9000, "bind_address": "0.0.0.0", "api_key": "new"}});
+        let disk = serde_json::json!({"proxy": {"port": 8045, "api_key": "old"}});
+        overlay.restore_unmanaged(&mut saved, Some(&disk));
+        assert_eq!(
+            saved,
+            serde_json::json!({"proxy": {"port": 8045, "api_key": "new"}})
+        );
+    }
+}
@@ -1,9 +1,15 @@
 pub mod account;
 pub mod account_service;
+pub mod account_transfer;
+pub mod audit_export;
+pub mod backup;
 pub mod cache;
+pub mod chargeback;
+pub mod cli;
 pub mod cloudflared;
 pub mod config;
 pub mod db;
+pub mod db_migrations;
 pub mod device;
 #[allow(dead_code)]
 pub mod http_api;
@@ -11,12 +17,14 @@ pub mod i18n;
 pub mod integration;
 pub mod log_bridge;
 pub mod logger;
+pub mod managed_config;
 pub mod migration;
 pub mod oauth;
 pub mod oauth_server;
 pub mod process;
 pub mod proxy_db;
 pub mod quota;
+pub mod quota_history;
 pub mod scheduler;
 pub mod security_db;
 pub mod token_stats;
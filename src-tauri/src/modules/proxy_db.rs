@@ -1,3 +1,4 @@
+use crate::modules::db_migrations::{self, Migration};
 use crate::proxy::monitor::ProxyRequestLog;
 use rusqlite::{params, Connection};
 use std::path::PathBuf;
@@ -7,7 +8,24 @@ pub fn get_proxy_db_path() -> Result<PathBuf, String> {
     Ok(data_dir.join("proxy_logs.db"))
 }
 
-fn connect_db() -> Result<Connection, String> {
+const DB_NAME: &str = "proxy_logs.db";
+
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "baseline request_logs schema",
+        up: migrate_v1_baseline,
+    },
+    Migration {
+        version: 2,
+        description: "add reasoning_tokens column",
+        up: |conn| {
+            db_migrations::add_column_if_missing(conn, "request_logs", "reasoning_tokens INTEGER")
+        },
+    },
+];
+
+pub(crate) fn connect_db() -> Result<Connection, String> {
     let db_path = get_proxy_db_path()?;
     let conn = Connection::open(db_path).map_err(|e| e.to_string())?;
 
@@ -23,13 +41,13 @@ fn connect_db() -> Result<Connection, String> {
     conn.pragma_update(None, "synchronous", "NORMAL")
         .map_err(|e| e.to_string())?;
 
+    db_migrations::ensure_supported(&conn, DB_NAME, MIGRATIONS)?;
+
     Ok(conn)
 }
 
-pub fn init_db() -> Result<(), String> {
-    // connect_db will initialize WAL mode and other pragmas
-    let conn = connect_db()?;
-
+/// v1: 基线结构 (兼容未记录版本号的旧库，补齐历史新增列)
+fn migrate_v1_baseline(conn: &Connection) -> rusqlite::Result<()> {
     conn.execute(
         "CREATE TABLE IF NOT EXISTS request_logs (
             id TEXT PRIMARY KEY,
@@ -42,43 +60,46 @@ pub fn init_db() -> Result<(), String> {
             error TEXT
         )",
         [],
-    )
-    .map_err(|e| e.to_string())?;
-
-    // Try to add new columns (ignore errors if they exist)
-    let _ = conn.execute("ALTER TABLE request_logs ADD COLUMN request_body TEXT", []);
-    let _ = conn.execute("ALTER TABLE request_logs ADD COLUMN response_body TEXT", []);
-    let _ = conn.execute(
-        "ALTER TABLE request_logs ADD COLUMN input_tokens INTEGER",
-        [],
-    );
-    let _ = conn.execute(
-        "ALTER TABLE request_logs ADD COLUMN output_tokens INTEGER",
-        [],
-    );
-    let _ = conn.execute(
-        "ALTER TABLE request_logs ADD COLUMN cached_tokens INTEGER",
-        [],
-    );
-    let _ = conn.execute("ALTER TABLE request_logs ADD COLUMN account_email TEXT", []);
-    let _ = conn.execute("ALTER TABLE request_logs ADD COLUMN mapped_model TEXT", []);
-    let _ = conn.execute("ALTER TABLE request_logs ADD COLUMN protocol TEXT", []);
-    let _ = conn.execute("ALTER TABLE request_logs ADD COLUMN client_ip TEXT", []);
-    let _ = conn.execute("ALTER TABLE request_logs ADD COLUMN username TEXT", []);
+    )?;
+
+    for column in [
+        "request_body TEXT",
+        "response_body TEXT",
+        "input_tokens INTEGER",
+        "output_tokens INTEGER",
+        "cached_tokens INTEGER",
+        "account_email TEXT",
+        "mapped_model TEXT",
+        "protocol TEXT",
+        "client_ip TEXT",
+        "username TEXT",
+    ] {
+        db_migrations::add_column_if_missing(conn, "request_logs", column)?;
+    }
 
     conn.execute(
         "CREATE INDEX IF NOT EXISTS idx_timestamp ON request_logs (timestamp DESC)",
         [],
-    )
-    .map_err(|e| e.to_string())?;
+    )?;
 
     // Add status index for faster stats queries
     conn.execute(
         "CREATE INDEX IF NOT EXISTS idx_status ON request_logs (status)",
         [],
-    )
-    .map_err(|e| e.to_string())?;
+    )?;
+
+    Ok(())
+}
+
+/// 对指定连接执行结构迁移 (备份恢复时用于校验并升级暂存副本)
+pub(crate) fn migrate(conn: &mut Connection) -> Result<u32, String> {
+    db_migrations::run_migrations(conn, DB_NAME, MIGRATIONS)
+}
 
+pub fn init_db() -> Result<(), String> {
+    // connect_db will initialize WAL mode and other pragmas
+    let mut conn = connect_db()?;
+    migrate(&mut conn)?;
     Ok(())
 }
 
@@ -86,8 +107,8 @@ pub fn save_log(log: &ProxyRequestLog) -> Result<(), String> {
     let conn = connect_db()?;
 
     conn.execute(
-        "INSERT INTO request_logs (id, timestamp, method, url, status, duration, model, error, request_body, response_body, input_tokens, output_tokens, cached_tokens, account_email, mapped_model, protocol, client_ip, username)
-         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18)",
+        "INSERT INTO request_logs (id, timestamp, method, url, status, duration, model, error, request_body, response_body, input_tokens, output_tokens, cached_tokens, account_email, mapped_model, protocol, client_ip, username, reasoning_tokens)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19)",
         params![
             log.id,
             log.timestamp,
@@ -107,6 +128,7 @@ pub fn save_log(log: &ProxyRequestLog) -> Result<(), String> {
             log.protocol,
             log.client_ip,
             log.username,
+            log.reasoning_tokens,
         ],
     ).map_err(|e| e.to_string())?;
 
@@ -121,7 +143,7 @@ pub fn get_logs_summary(limit: usize, offset: usize) -> Result<Vec<ProxyRequestL
         .prepare(
             "SELECT id, timestamp, method, url, status, duration, model, error,
                 NULL as request_body, NULL as response_body,
-                input_tokens, output_tokens, cached_tokens, account_email, mapped_model, protocol, client_ip, username
+                input_tokens, output_tokens, cached_tokens, account_email, mapped_model, protocol, client_ip, username, reasoning_tokens
          FROM request_logs 
          ORDER BY timestamp DESC 
          LIMIT ?1 OFFSET ?2",
@@ -149,6 +171,7 @@ pub fn get_logs_summary(limit: usize, offset: usize) -> Result<Vec<ProxyRequestL
                 protocol: row.get(15).unwrap_or(None),
                 client_ip: row.get(16).unwrap_or(None),
                 username: row.get(17).unwrap_or(None),
+                reasoning_tokens: row.get(18).unwrap_or(None),
             })
         })
         .map_err(|e| e.to_string())?;
@@ -197,7 +220,7 @@ pub fn get_log_detail(log_id: &str) -> Result<ProxyRequestLog, String> {
         .prepare(
             "SELECT id, timestamp, method, url, status, duration, model, error,
                 request_body, response_body, input_tokens, output_tokens,
-                cached_tokens, account_email, mapped_model, protocol, client_ip, username
+                cached_tokens, account_email, mapped_model, protocol, client_ip, username, reasoning_tokens
          FROM request_logs
          WHERE id = ?1",
         )
@@ -223,6 +246,7 @@ pub fn get_log_detail(log_id: &str) -> Result<ProxyRequestLog, String> {
             protocol: row.get(15).unwrap_or(None),
             client_ip: row.get(16).unwrap_or(None),
             username: row.get(17).unwrap_or(None),
+            reasoning_tokens: row.get(18).unwrap_or(None),
         })
     })
     .map_err(|e| e.to_string())
@@ -329,7 +353,7 @@ pub fn get_logs_filtered(
     let sql = if errors_only {
         "SELECT id, timestamp, method, url, status, duration, model, error,
                 NULL as request_body, NULL as response_body,
-                input_tokens, output_tokens, cached_tokens, account_email, mapped_model, protocol, client_ip, username
+                input_tokens, output_tokens, cached_tokens, account_email, mapped_model, protocol, client_ip, username, reasoning_tokens
          FROM request_logs
          WHERE (status < 200 OR status >= 400)
          ORDER BY timestamp DESC
@@ -337,14 +361,14 @@ pub fn get_logs_filtered(
     } else if filter.is_empty() {
         "SELECT id, timestamp, method, url, status, duration, model, error,
                 NULL as request_body, NULL as response_body,
-                input_tokens, output_tokens, cached_tokens, account_email, mapped_model, protocol, client_ip, username
+                input_tokens, output_tokens, cached_tokens, account_email, mapped_model, protocol, client_ip, username, reasoning_tokens
          FROM request_logs
          ORDER BY timestamp DESC
          LIMIT ?1 OFFSET ?2"
     } else {
         "SELECT id, timestamp, method, url, status, duration, model, error,
                 NULL as request_body, NULL as response_body,
-                input_tokens, output_tokens, cached_tokens, account_email, mapped_model, protocol, client_ip, username
+                input_tokens, output_tokens, cached_tokens, account_email, mapped_model, protocol, client_ip, username, reasoning_tokens
          FROM request_logs
          WHERE (url LIKE ?3 OR method LIKE ?3 OR model LIKE ?3 OR CAST(status AS TEXT) LIKE ?3 OR account_email LIKE ?3 OR client_ip LIKE ?3)
          ORDER BY timestamp DESC
@@ -374,6 +398,7 @@ pub fn get_logs_filtered(
                     protocol: row.get(15).unwrap_or(None),
                     client_ip: row.get(16).unwrap_or(None),
                     username: row.get(17).unwrap_or(None),
+                    reasoning_tokens: row.get(18).unwrap_or(None),
                 })
             })
             .map_err(|e| e.to_string())?;
@@ -401,6 +426,7 @@ pub fn get_logs_filtered(
                     protocol: row.get(15).unwrap_or(None),
                     client_ip: row.get(16).unwrap_or(None),
                     username: row.get(17).unwrap_or(None),
+                    reasoning_tokens: row.get(18).unwrap_or(None),
                 })
             })
             .map_err(|e| e.to_string())?;
@@ -428,6 +454,7 @@ pub fn get_logs_filtered(
                     protocol: row.get(15).unwrap_or(None),
                     client_ip: row.get(16).unwrap_or(None),
                     username: row.get(17).unwrap_or(None),
+                    reasoning_tokens: row.get(18).unwrap_or(None),
                 })
             })
             .map_err(|e| e.to_string())?;
@@ -445,7 +472,7 @@ pub fn get_all_logs_for_export() -> Result<Vec<ProxyRequestLog>, String> {
         .prepare(
             "SELECT id, timestamp, method, url, status, duration, model, error,
                 request_body, response_body, input_tokens, output_tokens,
-                cached_tokens, account_email, mapped_model, protocol, client_ip, username
+                cached_tokens, account_email, mapped_model, protocol, client_ip, username, reasoning_tokens
          FROM request_logs
          ORDER BY timestamp DESC",
         )
@@ -472,6 +499,7 @@ pub fn get_all_logs_for_export() -> Result<Vec<ProxyRequestLog>, String> {
                 protocol: row.get(15).unwrap_or(None),
                 client_ip: row.get(16).unwrap_or(None),
                 username: row.get(17).unwrap_or(None),
+                reasoning_tokens: row.get(18).unwrap_or(None),
             })
         })
         .map_err(|e| e.to_string())?;
@@ -0,0 +1,486 @@
+//! 配额历史时间序列与耗尽预测
+//!
+//! 每次刷新配额时将各 `QuotaBucket` (weekly / 5h 窗口) 的剩余比例以及
+//! `ModelQuota::percentage` 快照写入 `quota_history.db`，并基于当前重置周期内的
+//! 样本做线性回归估算消耗速率，预测配额耗尽时间以及是否会早于重置时间耗尽。
+
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use crate::models::QuotaData;
+use crate::modules::db_migrations::{self, Migration};
+
+/// 历史样本保留天数
+const RETENTION_DAYS: i64 = 30;
+/// 剩余比例回升超过该阈值视为已重置 (开始新的周期)
+const RESET_TOLERANCE: f64 = 0.01;
+/// 计算消耗速率所需的最短样本跨度 (秒)
+const MIN_SPAN_SECS: i64 = 60;
+
+const DB_NAME: &str = "quota_history.db";
+
+const MIGRATIONS: &[Migration] = &[Migration {
+    version: 1,
+    description: "create quota_snapshots",
+    up: migrate_v1_create_snapshots,
+}];
+
+pub const KIND_BUCKET: &str = "bucket";
+pub const KIND_MODEL: &str = "model";
+
+/// 单条配额快照
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QuotaSnapshotPoint {
+    pub timestamp: i64,
+    pub account_id: String,
+    pub account_email: String,
+    /// "bucket" (配额桶) 或 "model" (模型百分比)
+    pub kind: String,
+    /// 桶 ID (如 "3p-weekly") 或模型名
+    pub key: String,
+    /// 窗口类型: "weekly" / "5h"，模型快照为空
+    pub window: Option<String>,
+    /// 剩余比例 0.0-1.0
+    pub remaining: f64,
+    pub reset_time: Option<String>,
+}
+
+/// 单个配额序列的耗尽预测
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QuotaForecast {
+    pub account_id: String,
+    pub account_email: String,
+    pub kind: String,
+    pub key: String,
+    pub window: Option<String>,
+    /// 最新剩余比例 0.0-1.0
+    pub remaining: f64,
+    /// 每小时消耗的比例 (未在消耗时为 0)
+    pub burn_rate_per_hour: f64,
+    /// 预计耗尽所需小时数 (未在消耗时为空)
+    pub hours_to_exhaustion: Option<f64>,
+    /// 预计耗尽时间 (Unix 秒)
+    pub exhausted_at: Option<i64>,
+    /// 下次重置时间 (Unix 秒)
+    pub reset_at: Option<i64>,
+    /// 是否会在重置前耗尽
+    pub exhausts_before_reset: bool,
+    /// 参与计算的样本数 (当前周期)
+    pub samples: usize,
+}
+
+pub(crate) fn get_db_path() -> Result<PathBuf, String> {
+    let data_dir = crate::modules::account::get_data_dir()?;
+    Ok(data_dir.join("quota_history.db"))
+}
+
+fn connect_db() -> Result<Connection, String> {
+    let db_path = get_db_path()?;
+    let conn = Connection::open(db_path).map_err(|e| e.to_string())?;
+
+    conn.pragma_update(None, "journal_mode", "WAL")
+        .map_err(|e| e.to_string())?;
+    conn.pragma_update(None, "busy_timeout", 5000)
+        .map_err(|e| e.to_string())?;
+    conn.pragma_update(None, "synchronous", "NORMAL")
+        .map_err(|e| e.to_string())?;
+
+    db_migrations::ensure_supported(&conn, DB_NAME, MIGRATIONS)?;
+
+    Ok(conn)
+}
+
+fn migrate_v1_create_snapshots(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS quota_snapshots (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            timestamp INTEGER NOT NULL,
+            account_id TEXT NOT NULL,
+            account_email TEXT NOT NULL,
+            kind TEXT NOT NULL,
+            key TEXT NOT NULL,
+            window_type TEXT,
+            remaining REAL NOT NULL,
+            reset_time TEXT
+        )",
+        [],
+    )?;
+
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_quota_series ON quota_snapshots (account_id, kind, key, timestamp)",
+        [],
+    )?;
+
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_quota_timestamp ON quota_snapshots (timestamp DESC)",
+        [],
+    )?;
+
+    Ok(())
+}
+
+/// 对指定连接执行结构迁移 (备份恢复时用于校验并升级暂存副本)
+pub(crate) fn migrate(conn: &mut Connection) -> Result<u32, String> {
+    db_migrations::run_migrations(conn, DB_NAME, MIGRATIONS)
+}
+
+/// Initialize the quota history database
+pub fn init_db() -> Result<(), String> {
+    let mut conn = connect_db()?;
+    migrate(&mut conn)?;
+    prune_expired(&conn, chrono::Utc::now().timestamp())?;
+    Ok(())
+}
+
+/// 清理超过保留期的样本 (启动时及每次写入快照后执行，长时间运行也不会无限增长)
+fn prune_expired(conn: &Connection, now: i64) -> Result<usize, String> {
+    let cutoff = now - RETENTION_DAYS * 86400;
+    conn.execute("DELETE FROM quota_snapshots WHERE timestamp < ?1", [cutoff])
+        .map_err(|e| e.to_string())
+}
+
+fn insert_snapshot(
+    conn: &Connection,
+    account_id: &str,
+    account_email: &str,
+    quota: &QuotaData,
+    timestamp: i64,
+) -> Result<usize, String> {
+    let mut stmt = conn
+        .prepare(
+            "INSERT INTO quota_snapshots (timestamp, account_id, account_email, kind, key, window_type, remaining, reset_time)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+        )
+        .map_err(|e| e.to_string())?;
+
+    let mut inserted = 0;
+    for bucket in quota.quota_groups.iter().flatten().flat_map(|g| &g.buckets) {
+        stmt.execute(params![
+            timestamp,
+            account_id,
+            account_email,
+            KIND_BUCKET,
+            bucket.bucket_id,
+            bucket.window,
+            bucket.remaining_fraction.clamp(0.0, 1.0),
+            bucket.reset_time,
+        ])
+        .map_err(|e| e.to_string())?;
+        inserted += 1;
+    }
+    for model in &quota.models {
+        stmt.execute(params![
+            timestamp,
+            account_id,
+            account_email,
+            KIND_MODEL,
+            model.name,
+            Option::<String>::None,
+            (model.percentage.clamp(0, 100) as f64) / 100.0,
+            model.reset_time,
+        ])
+        .map_err(|e| e.to_string())?;
+        inserted += 1;
+    }
+    Ok(inserted)
+}
+
+/// 记录一次配额刷新结果 (被禁止访问的账号不记录)
+pub fn record_snapshot(
+    account_id: &str,
+    account_email: &str,
+    quota: &QuotaData,
+) -> Result<(), String> {
+    if quota.is_forbidden {
+        return Ok(());
+    }
+    let mut conn = connect_db()?;
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    let now = chrono::Utc::now().timestamp();
+    insert_snapshot(&tx, account_id, account_email, quota, now)?;
+    prune_expired(&tx, now)?;
+    tx.commit().map_err(|e| e.to_string())
+}
+
+fn query_history(
+    conn: &Connection,
+    account_id: Option<&str>,
+    since: i64,
+) -> Result<Vec<QuotaSnapshotPoint>, String> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT timestamp, account_id, account_email, kind, key, window_type, remaining, reset_time
+             FROM quota_snapshots
+             WHERE timestamp >= ?1 AND (?2 IS NULL OR account_id = ?2)
+             ORDER BY account_id, kind, key, timestamp ASC",
+        )
+        .map_err(|e| e.to_string())?;
+
+    let rows = stmt
+        .query_map(params![since, account_id], |row| {
+            Ok(QuotaSnapshotPoint {
+                timestamp: row.get(0)?,
+                account_id: row.get(1)?,
+                account_email: row.get(2)?,
+                kind: row.get(3)?,
+                key: row.get(4)?,
+                window: row.get(5)?,
+                remaining: row.get(6)?,
+                reset_time: row.get(7)?,
+            })
+        })
+        .map_err(|e| e.to_string())?;
+
+    let mut result = Vec::new();
+    for row in rows {
+        result.push(row.map_err(|e| e.to_string())?);
+    }
+    Ok(result)
+}
+
+/// 获取最近 `hours` 小时内的配额快照 (可按账号过滤)
+pub fn get_history(
+    account_id: Option<&str>,
+    hours: i64,
+) -> Result<Vec<QuotaSnapshotPoint>, String> {
+    let conn = connect_db()?;
+    let since = chrono::Utc::now().timestamp() - hours * 3600;
+    query_history(&conn, account_id, since)
+}
+
+fn parse_reset_time(reset_time: Option<&str>) -> Option<i64> {
+    chrono::DateTime::parse_from_rfc3339(reset_time?)
+        .ok()
+        .map(|t| t.timestamp())
+}
+
+/// 取当前重置周期内的样本：从最后一次剩余比例明显回升之后开始
+fn current_cycle(points: &[QuotaSnapshotPoint]) -> &[QuotaSnapshotPoint] {
+    let start = points
+        .windows(2)
+        .rposition(|w| w[1].remaining > w[0].remaining + RESET_TOLERANCE)
+        .map_or(0, |i| i + 1);
+    &points[start..]
+}
+
+/// 最小二乘估算消耗速率 (每小时消耗的比例，未在消耗时为 0)
+fn burn_rate_per_hour(samples: &[(i64, f64)]) -> Option<f64> {
+    let (first, last) = (samples.first()?, samples.last()?);
+    if samples.len() < 2 || last.0 - first.0 < MIN_SPAN_SECS {
+        return None;
+    }
+    let n = samples.len() as f64;
+    let t0 = first.0;
+    let mean_t = samples.iter().map(|(t, _)| (t - t0) as f64).sum::<f64>() / n;
+    let mean_r = samples.iter().map(|(_, r)| *r).sum::<f64>() / n;
+    let (mut cov, mut var) = (0.0, 0.0);
+    for (t, r) in samples {
+        let dt = (t - t0) as f64 - mean_t;
+        cov += dt * (r - mean_r);
+        var += dt * dt;
+    }
+    if var == 0.0 {
+        return None;
+    }
+    let slope_per_sec = cov / var;
+    Some((-slope_per_sec * 3600.0).max(0.0))
+}
+
+/// 基于单个序列 (按时间升序) 计算耗尽预测
+fn forecast_series(points: &[QuotaSnapshotPoint]) -> Option<QuotaForecast> {
+    let cycle = current_cycle(points);
+    let latest = cycle.last()?;
+    let samples: Vec<(i64, f64)> = cycle.iter().map(|p| (p.timestamp, p.remaining)).collect();
+    let rate = burn_rate_per_hour(&samples).unwrap_or(0.0);
+    let reset_at = parse_reset_time(latest.reset_time.as_deref());
+
+    let hours_to_exhaustion = if latest.remaining <= 0.0 {
+        Some(0.0)
+    } else if rate > 0.0 {
+        Some(latest.remaining / rate)
+    } else {
+        None
+    };
+    let exhausted_at = hours_to_exhaustion.map(|h| latest.timestamp + (h * 3600.0).round() as i64);
+    let exhausts_before_reset = match (exhausted_at, reset_at) {
+        (Some(exhausted), Some(reset)) => exhausted < reset,
+        (Some(_), None) => true,
+        _ => false,
+    };
+
+    Some(QuotaForecast {
+        account_id: latest.account_id.clone(),
+        account_email: latest.account_email.clone(),
+        kind: latest.kind.clone(),
+        key: latest.key.clone(),
+        window: latest.window.clone(),
+        remaining: latest.remaining,
+        burn_rate_per_hour: rate,
+        hours_to_exhaustion,
+        exhausted_at,
+        reset_at,
+        exhausts_before_reset,
+        samples: cycle.len(),
+    })
+}
+
+/// 将快照按 (账号, 类型, 键) 分组并逐序列预测，先耗尽的排在前面
+fn build_forecasts(points: Vec<QuotaSnapshotPoint>) -> Vec<QuotaForecast> {
+    let mut series: BTreeMap<(String, String, String), Vec<QuotaSnapshotPoint>> = BTreeMap::new();
+    for point in points {
+        series
+            .entry((
+                point.account_id.clone(),
+                point.kind.clone(),
+                point.key.clone(),
+            ))
+            .or_default()
+            .push(point);
+    }
+
+    let mut forecasts: Vec<QuotaForecast> = series
+        .values_mut()
+        .filter_map(|points| {
+            points.sort_by_key(|p| p.timestamp);
+            forecast_series(points)
+        })
+        .collect();
+    forecasts.sort_by(|a, b| {
+        b.exhausts_before_reset
+            .cmp(&a.exhausts_before_reset)
+            .then_with(|| {
+                let ha = a.hours_to_exhaustion.unwrap_or(f64::INFINITY);
+                let hb = b.hours_to_exhaustion.unwrap_or(f64::INFINITY);
+                ha.total_cmp(&hb)
+            })
+    });
+    forecasts
+}
+
+/// 基于最近 `hours` 小时的快照预测各配额序列的耗尽时间
+pub fn get_forecasts(account_id: Option<&str>, hours: i64) -> Result<Vec<QuotaForecast>, String> {
+    Ok(build_forecasts(get_history(account_id, hours)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::quota::{ModelQuota, QuotaBucket, QuotaGroup};
+
+    fn point(timestamp: i64, remaining: f64, reset_time: &str) -> QuotaSnapshotPoint {
+        QuotaSnapshotPoint {
+            timestamp,
+            account_id: "acc".to_string(),
+            account_email: "a@test.com".to_string(),
+            kind: KIND_BUCKET.to_string(),
+            key: "3p-weekly".to_string(),
+            window: Some("weekly".to_string()),
+            remaining,
+            reset_time: Some(reset_time.to_string()),
+        }
+    }
+
+    #[test]
+    fn test_forecast_linear_burn() {
+        // 每小时消耗 5%，剩余 70% -> 14 小时后耗尽，早于 2 天后的重置
+        let base = 1_700_000_000;
+        let reset = chrono::DateTime::from_timestamp(base + 2 * 86400, 0)
+            .unwrap()
+            .to_rfc3339();
+        let points: Vec<_> = (0..=6)
+            .map(|h| point(base + h * 3600, 1.0 - 0.05 * h as f64, &reset))
+            .collect();
+
+        let forecast = forecast_series(&points).unwrap();
+        assert!((forecast.burn_rate_per_hour - 0.05).abs() < 1e-9);
+        assert!((forecast.hours_to_exhaustion.unwrap() - 14.0).abs() < 1e-6);
+        assert_eq!(forecast.exhausted_at, Some(base + 20 * 3600));
+        assert!(forecast.exhausts_before_reset);
+    }
+
+    #[test]
+    fn test_forecast_uses_current_cycle_only() {
+        let base = 1_700_000_000;
+        let reset = chrono::DateTime::from_timestamp(base + 3600, 0)
+            .unwrap()
+            .to_rfc3339();
+        let points = vec![
+            point(base, 0.4, &reset),
+            point(base + 3600, 0.1, &reset),
+            // 重置后回升，之前的快速消耗不应计入
+            point(base + 7200, 1.0, &reset),
+            point(base + 10800, 1.0, &reset),
+        ];
+
+        let forecast = forecast_series(&points).unwrap();
+        assert_eq!(forecast.samples, 2);
+        assert_eq!(forecast.burn_rate_per_hour, 0.0);
+        assert_eq!(forecast.hours_to_exhaustion, None);
+        assert!(!forecast.exhausts_before_reset);
+    }
+
+    #[test]
+    fn test_snapshot_round_trip_and_forecast_order() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        db_migrations::run_migrations(&mut conn, DB_NAME, MIGRATIONS).unwrap();
+
+        let quota = |fraction: f64, percentage: i32| {
+            let mut quota = QuotaData::new();
+            quota.quota_groups = Some(vec![QuotaGroup {
+                display_name: "Claude and GPT models".to_string(),
+                description: None,
+                buckets: vec![QuotaBucket {
+                    bucket_id: "3p-5h".to_string(),
+                    window: "5h".to_string(),
+                    remaining_fraction: fraction,
+                    reset_time: "2099-01-01T00:00:00Z".to_string(),
+                    display_name: None,
+                    description: None,
+                }],
+            }]);
+            quota.add_model(ModelQuota {
+                name: "gemini-3-flash".to_string(),
+                percentage,
+                reset_time: String::new(),
+                display_name: None,
+                supports_images: None,
+                supports_thinking: None,
+                thinking_budget: None,
+                recommended: None,
+                max_tokens: None,
+                max_output_tokens: None,
+                supported_mime_types: None,
+            });
+            quota
+        };
+
+        let base = 1_700_000_000;
+        assert_eq!(
+            insert_snapshot(&conn, "acc", "a@test.com", &quota(0.9, 100), base).unwrap(),
+            2
+        );
+        insert_snapshot(&conn, "acc", "a@test.com", &quota(0.5, 100), base + 3600).unwrap();
+
+        let history = query_history(&conn, Some("acc"), base).unwrap();
+        assert_eq!(history.len(), 4);
+        assert!(query_history(&conn, Some("other"), base)
+            .unwrap()
+            .is_empty());
+
+        let forecasts = build_forecasts(history);
+        assert_eq!(forecasts.len(), 2);
+        assert_eq!(forecasts[0].key, "3p-5h");
+        assert!(forecasts[0].exhausts_before_reset);
+        assert!((forecasts[0].hours_to_exhaustion.unwrap() - 1.25).abs() < 1e-6);
+        assert_eq!(forecasts[1].key, "gemini-3-flash");
+        assert_eq!(forecasts[1].hours_to_exhaustion, None);
+
+        // 超过保留期的样本在下一次写入后被清理
+        let now = base + RETENTION_DAYS * 86400 + 1800;
+        insert_snapshot(&conn, "acc", "a@test.com", &quota(0.4, 90), now).unwrap();
+        assert_eq!(prune_expired(&conn, now).unwrap(), 2);
+        assert_eq!(query_history(&conn, Some("acc"), 0).unwrap().len(), 4);
+    }
+}
@@ -1,6 +1,7 @@
 //! Security Database Module
 //! 安全监控相关的数据库操作
 
+use crate::modules::db_migrations::{self, Migration};
 use rusqlite::{params, Connection};
 use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
@@ -64,6 +65,14 @@ pub struct IpRanking {
     pub is_blocked: bool,
 }
 
+const DB_NAME: &str = "security.db";
+
+const MIGRATIONS: &[Migration] = &[Migration {
+    version: 1,
+    description: "baseline ip access log / blacklist / whitelist schema",
+    up: migrate_v1_baseline,
+}];
+
 /// 获取安全数据库路径
 pub fn get_security_db_path() -> Result<PathBuf, String> {
     let data_dir = crate::modules::account::get_data_dir()?;
@@ -86,13 +95,13 @@ fn connect_db() -> Result<Connection, String> {
     conn.pragma_update(None, "synchronous", "NORMAL")
         .map_err(|e| e.to_string())?;
 
+    db_migrations::ensure_supported(&conn, DB_NAME, MIGRATIONS)?;
+
     Ok(conn)
 }
 
-/// 初始化安全数据库
-pub fn init_db() -> Result<(), String> {
-    let conn = connect_db()?;
-
+/// v1: 基线结构 (兼容未记录版本号的旧库)
+fn migrate_v1_baseline(conn: &Connection) -> rusqlite::Result<()> {
     // IP 访问日志表
     conn.execute(
         "CREATE TABLE IF NOT EXISTS ip_access_logs (
@@ -109,8 +118,7 @@ pub fn init_db() -> Result<(), String> {
             block_reason TEXT
         )",
         [],
-    )
-    .map_err(|e| e.to_string())?;
+    )?;
 
     // IP 黑名单表
     conn.execute(
@@ -124,8 +132,7 @@ pub fn init_db() -> Result<(), String> {
             hit_count INTEGER DEFAULT 0
         )",
         [],
-    )
-    .map_err(|e| e.to_string())?;
+    )?;
 
     // IP 白名单表
     conn.execute(
@@ -136,40 +143,47 @@ pub fn init_db() -> Result<(), String> {
             created_at INTEGER NOT NULL
         )",
         [],
-    )
-    .map_err(|e| e.to_string())?;
+    )?;
 
     // 创建索引
     conn.execute(
         "CREATE INDEX IF NOT EXISTS idx_ip_access_ip ON ip_access_logs (client_ip)",
         [],
-    )
-    .map_err(|e| e.to_string())?;
+    )?;
 
     conn.execute(
         "CREATE INDEX IF NOT EXISTS idx_ip_access_timestamp ON ip_access_logs (timestamp DESC)",
         [],
-    )
-    .map_err(|e| e.to_string())?;
+    )?;
 
     conn.execute(
         "CREATE INDEX IF NOT EXISTS idx_ip_access_blocked ON ip_access_logs (blocked)",
         [],
-    )
-    .map_err(|e| e.to_string())?;
+    )?;
 
     conn.execute(
         "CREATE INDEX IF NOT EXISTS idx_blacklist_pattern ON ip_blacklist (ip_pattern)",
         [],
-    )
-    .map_err(|e| e.to_string())?;
+    )?;
 
-    // Migration: Add username column to ip_access_logs
-    let _ = conn.execute("ALTER TABLE ip_access_logs ADD COLUMN username TEXT", []);
+    // 旧库补齐 username 列
+    db_migrations::add_column_if_missing(conn, "ip_access_logs", "username TEXT")?;
 
     Ok(())
 }
 
+/// 对指定连接执行结构迁移 (备份恢复时用于校验并升级暂存副本)
+pub(crate) fn migrate(conn: &mut Connection) -> Result<u32, String> {
+    db_migrations::run_migrations(conn, DB_NAME, MIGRATIONS)
+}
+
+/// 初始化安全数据库
+pub fn init_db() -> Result<(), String> {
+    let mut conn = connect_db()?;
+    migrate(&mut conn)?;
+    Ok(())
+}
+
 // ============================================================================
 // IP 访问日志操作
 // ============================================================================
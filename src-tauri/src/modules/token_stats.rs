@@ -1,3 +1,4 @@
+use crate::modules::db_migrations::{self, Migration};
 use rusqlite::{params, Connection};
 use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
@@ -59,12 +60,27 @@ pub struct AccountTrendPoint {
     pub account_data: std::collections::HashMap<String, u64>,
 }
 
+const DB_NAME: &str = "token_stats.db";
+
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "baseline token_usage / token_stats_hourly schema",
+        up: migrate_v1_baseline,
+    },
+    Migration {
+        version: 2,
+        description: "chargeback columns on token_usage",
+        up: migrate_v2_chargeback,
+    },
+];
+
 pub(crate) fn get_db_path() -> Result<PathBuf, String> {
     let data_dir = crate::modules::account::get_data_dir()?;
     Ok(data_dir.join("token_stats.db"))
 }
 
-fn connect_db() -> Result<Connection, String> {
+pub(crate) fn connect_db() -> Result<Connection, String> {
     let db_path = get_db_path()?;
     let conn = Connection::open(db_path).map_err(|e| e.to_string())?;
 
@@ -76,22 +92,13 @@ fn connect_db() -> Result<Connection, String> {
     conn.pragma_update(None, "synchronous", "NORMAL")
         .map_err(|e| e.to_string())?;
 
-    Ok(conn)
-}
+    db_migrations::ensure_supported(&conn, DB_NAME, MIGRATIONS)?;
 
-fn add_column_if_missing(conn: &Connection, table: &str, column_def: &str) -> Result<(), String> {
-    let sql = format!("ALTER TABLE {} ADD COLUMN {}", table, column_def);
-    match conn.execute(&sql, []) {
-        Ok(_) => Ok(()),
-        Err(e) if e.to_string().contains("duplicate column name") => Ok(()),
-        Err(e) => Err(e.to_string()),
-    }
+    Ok(conn)
 }
 
-/// Initialize the token stats database
-pub fn init_db() -> Result<(), String> {
-    let conn = connect_db()?;
-
+/// v1: baseline schema (also upgrades unversioned databases from older releases)
+fn migrate_v1_baseline(conn: &Connection) -> rusqlite::Result<()> {
     // Create main usage table
     conn.execute(
         "CREATE TABLE IF NOT EXISTS token_usage (
@@ -105,21 +112,18 @@ pub fn init_db() -> Result<(), String> {
             total_tokens INTEGER NOT NULL DEFAULT 0
         )",
         [],
-    )
-    .map_err(|e| e.to_string())?;
+    )?;
 
     // Create indexes for efficient queries
     conn.execute(
         "CREATE INDEX IF NOT EXISTS idx_token_timestamp ON token_usage (timestamp DESC)",
         [],
-    )
-    .map_err(|e| e.to_string())?;
+    )?;
 
     conn.execute(
         "CREATE INDEX IF NOT EXISTS idx_token_account ON token_usage (account_email)",
         [],
-    )
-    .map_err(|e| e.to_string())?;
+    )?;
 
     // Create hourly aggregation table for fast queries
     conn.execute(
@@ -134,16 +138,15 @@ pub fn init_db() -> Result<(), String> {
             PRIMARY KEY (hour_bucket, account_email)
         )",
         [],
-    )
-    .map_err(|e| e.to_string())?;
+    )?;
 
-    add_column_if_missing(
-        &conn,
+    db_migrations::add_column_if_missing(
+        conn,
         "token_usage",
         "cached_tokens INTEGER NOT NULL DEFAULT 0",
     )?;
-    add_column_if_missing(
-        &conn,
+    db_migrations::add_column_if_missing(
+        conn,
         "token_stats_hourly",
         "total_cached_tokens INTEGER NOT NULL DEFAULT 0",
     )?;
@@ -151,6 +154,73 @@ pub fn init_db() -> Result<(), String> {
     Ok(())
 }
 
+/// v2: per-request attribution for cost reports (user token, routed model, reasoning tokens)
+fn migrate_v2_chargeback(conn: &Connection) -> rusqlite::Result<()> {
+    for column in [
+        "username TEXT",
+        "mapped_model TEXT",
+        "reasoning_tokens INTEGER NOT NULL DEFAULT 0",
+        "uncached_input_tokens INTEGER NOT NULL DEFAULT 0",
+    ] {
+        db_migrations::add_column_if_missing(conn, "token_usage", column)?;
+    }
+
+    // Older rows did not record the protocol; assume the OpenAI/Gemini convention
+    // where cached tokens are part of input_tokens.
+    conn.execute(
+        "UPDATE token_usage SET uncached_input_tokens = MAX(input_tokens - cached_tokens, 0)",
+        [],
+    )?;
+
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_token_username ON token_usage (username)",
+        [],
+    )?;
+
+    Ok(())
+}
+
+/// 对指定连接执行结构迁移 (备份恢复时用于校验并升级暂存副本)
+pub(crate) fn migrate(conn: &mut Connection) -> Result<u32, String> {
+    db_migrations::run_migrations(conn, DB_NAME, MIGRATIONS)
+}
+
+/// Initialize the token stats database
+pub fn init_db() -> Result<(), String> {
+    let mut conn = connect_db()?;
+    migrate(&mut conn)?;
+    Ok(())
+}
+
+/// Token usage of a single request
+#[derive(Debug, Clone, Default)]
+pub struct UsageRecord {
+    pub account_email: String,
+    pub model: String,
+    pub mapped_model: Option<String>,
+    /// User token username (None for requests authenticated with the global API key)
+    pub username: Option<String>,
+    pub input_tokens: u32,
+    pub output_tokens: u32,
+    pub cached_tokens: u32,
+    /// Reasoning tokens, already counted in `output_tokens`
+    pub reasoning_tokens: u32,
+    /// Whether `input_tokens` already includes `cached_tokens`
+    /// (OpenAI / Gemini convention; Anthropic reports cache reads separately)
+    pub input_includes_cached: bool,
+}
+
+impl UsageRecord {
+    /// Prompt tokens billed at the full input price
+    pub fn uncached_input_tokens(&self) -> u32 {
+        if self.input_includes_cached {
+            self.input_tokens.saturating_sub(self.cached_tokens)
+        } else {
+            self.input_tokens
+        }
+    }
+}
+
 /// Record token usage from a request
 pub fn record_usage(
     account_email: &str,
@@ -159,18 +229,52 @@ pub fn record_usage(
     output_tokens: u32,
     cached_tokens: u32,
 ) -> Result<(), String> {
+    record_usage_detail(&UsageRecord {
+        account_email: account_email.to_string(),
+        model: model.to_string(),
+        input_tokens,
+        output_tokens,
+        cached_tokens,
+        input_includes_cached: true,
+        ..Default::default()
+    })
+}
+
+/// Record token usage with user token / routing attribution
+pub fn record_usage_detail(record: &UsageRecord) -> Result<(), String> {
     let conn = connect_db()?;
-    let timestamp = chrono::Local::now().timestamp();
-    let total_tokens = input_tokens + output_tokens;
+    insert_usage(&conn, record, chrono::Local::now())
+}
+
+pub(crate) fn insert_usage(
+    conn: &Connection,
+    record: &UsageRecord,
+    now: chrono::DateTime<chrono::Local>,
+) -> Result<(), String> {
+    let timestamp = now.timestamp();
+    let total_tokens = record.input_tokens + record.output_tokens;
 
     // Insert into raw usage table
     conn.execute(
-        "INSERT INTO token_usage (timestamp, account_email, model, input_tokens, output_tokens, cached_tokens, total_tokens)
-         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
-        params![timestamp, account_email, model, input_tokens, output_tokens, cached_tokens, total_tokens],
+        "INSERT INTO token_usage (timestamp, account_email, model, input_tokens, output_tokens, cached_tokens, total_tokens,
+            username, mapped_model, reasoning_tokens, uncached_input_tokens)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+        params![
+            timestamp,
+            record.account_email,
+            record.model,
+            record.input_tokens,
+            record.output_tokens,
+            record.cached_tokens,
+            total_tokens,
+            record.username,
+            record.mapped_model,
+            record.reasoning_tokens,
+            record.uncached_input_tokens(),
+        ],
     ).map_err(|e| e.to_string())?;
 
-    let hour_bucket = chrono::Local::now().format("%Y-%m-%d %H:00").to_string();
+    let hour_bucket = now.format("%Y-%m-%d %H:00").to_string();
     conn.execute(
         "INSERT INTO token_stats_hourly (hour_bucket, account_email, total_input_tokens, total_output_tokens, total_cached_tokens, total_tokens, request_count)
          VALUES (?1, ?2, ?3, ?4, ?5, ?6, 1)
@@ -180,7 +284,14 @@ pub fn record_usage(
             total_cached_tokens = total_cached_tokens + ?5,
             total_tokens = total_tokens + ?6,
             request_count = request_count + 1",
-        params![hour_bucket, account_email, input_tokens, output_tokens, cached_tokens, total_tokens],
+        params![
+            hour_bucket,
+            record.account_email,
+            record.input_tokens,
+            record.output_tokens,
+            record.cached_tokens,
+            total_tokens
+        ],
     ).map_err(|e| e.to_string())?;
 
     Ok(())
@@ -97,7 +97,8 @@ pub fn create_tray(app: &tauri::AppHandle) -> tauri::Result<()> {
                         {
                             let mut lock = admin_server.write().await;
                             if let Some(admin) = lock.take() {
-                                admin.axum_server.stop();
+                                // 排空进行中的请求 (SSE / WebSocket) 后再退出，与 headless 模式一致
+                                admin.axum_server.drain_and_stop().await;
                             }
                         }
                         {
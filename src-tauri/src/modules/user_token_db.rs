@@ -4,12 +4,21 @@
 #![allow(dead_code)]
 // 用户令牌存储，部分接口留作后续扩展
 
-use chrono::{FixedOffset, Local, Timelike, Utc};
+use crate::modules::db_migrations::{self, Migration};
+use chrono::{Datelike, FixedOffset, Local, TimeZone, Timelike, Utc};
+use once_cell::sync::Lazy;
 use rusqlite::{params, Connection, OptionalExtension};
 use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
 use std::path::PathBuf;
+use std::sync::Mutex;
 use uuid::Uuid;
 
+/// RPM 滑动窗口: token_id -> 最近 60 秒内已放行请求的时间戳
+/// 在准入时即占位，不依赖请求完成后才写入的 token_usage_logs，避免突发并发绕过限制
+static RPM_WINDOWS: Lazy<Mutex<HashMap<String, VecDeque<i64>>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
 /// 用户令牌结构体
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct UserToken {
@@ -28,6 +37,84 @@ pub struct UserToken {
     pub last_used_at: Option<i64>,
     pub total_requests: i64,
     pub total_tokens_used: i64,
+    #[serde(default)]
+    pub rpm_limit: i32, // 每分钟请求数上限, 0 = unlimited
+    #[serde(default)]
+    pub daily_token_limit: i64, // 每日 token 预算, 0 = unlimited
+    #[serde(default)]
+    pub monthly_token_limit: i64, // 每月 token 预算, 0 = unlimited
+    #[serde(default)]
+    pub allowed_models: Vec<String>, // 模型白名单 (支持 "gemini-*" 前缀通配), 空 = 不限
+}
+
+/// 令牌当前消耗 (从 token_usage_logs 聚合)
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TokenConsumption {
+    pub requests_last_minute: i64,
+    pub tokens_today: i64,
+    pub tokens_this_month: i64,
+    /// 滑动 60 秒窗口内最早一次请求的时间戳 (用于计算 Retry-After)
+    #[serde(default)]
+    pub oldest_request_in_window: Option<i64>,
+}
+
+/// 超出令牌限额的原因
+#[derive(Debug, Clone, PartialEq)]
+pub enum TokenLimitViolation {
+    ModelNotAllowed(String),
+    RateLimited {
+        limit: i32,
+        retry_after_secs: i64,
+    },
+    DailyBudgetExceeded {
+        used: i64,
+        limit: i64,
+        retry_after_secs: i64,
+    },
+    MonthlyBudgetExceeded {
+        used: i64,
+        limit: i64,
+        retry_after_secs: i64,
+    },
+}
+
+impl TokenLimitViolation {
+    pub fn message(&self) -> String {
+        match self {
+            TokenLimitViolation::ModelNotAllowed(model) => format!(
+                "Model '{}' is not allowed for this token. Please contact the administrator.",
+                model
+            ),
+            TokenLimitViolation::RateLimited { limit, .. } => format!(
+                "Rate limit reached ({} requests per minute). Please retry later.",
+                limit
+            ),
+            TokenLimitViolation::DailyBudgetExceeded { used, limit, .. } => format!(
+                "Daily token budget exhausted ({}/{}). Resets at local midnight.",
+                used, limit
+            ),
+            TokenLimitViolation::MonthlyBudgetExceeded { used, limit, .. } => format!(
+                "Monthly token budget exhausted ({}/{}). Resets on the 1st of next month.",
+                used, limit
+            ),
+        }
+    }
+
+    /// 建议的 Retry-After 秒数 (模型白名单拒绝时为 None)
+    pub fn retry_after_secs(&self) -> Option<i64> {
+        match self {
+            TokenLimitViolation::ModelNotAllowed(_) => None,
+            TokenLimitViolation::RateLimited {
+                retry_after_secs, ..
+            }
+            | TokenLimitViolation::DailyBudgetExceeded {
+                retry_after_secs, ..
+            }
+            | TokenLimitViolation::MonthlyBudgetExceeded {
+                retry_after_secs, ..
+            } => Some(*retry_after_secs),
+        }
+    }
 }
 
 /// 令牌 IP 绑定结构体
@@ -62,17 +149,24 @@ pub fn get_db_path() -> Result<PathBuf, String> {
     Ok(path)
 }
 
+const DB_NAME: &str = "user_tokens.db";
+
+const MIGRATIONS: &[Migration] = &[Migration {
+    version: 1,
+    description: "baseline user token / ip binding / usage log schema",
+    up: migrate_v1_baseline,
+}];
+
 /// 连接数据库
 pub fn connect_db() -> Result<Connection, String> {
     let path = get_db_path()?;
     let conn = Connection::open(&path).map_err(|e| format!("Failed to open database: {}", e))?;
+    db_migrations::ensure_supported(&conn, DB_NAME, MIGRATIONS)?;
     Ok(conn)
 }
 
-/// 初始化数据库
-pub fn init_db() -> Result<(), String> {
-    let conn = connect_db()?;
-
+/// v1: 基线结构 (兼容未记录版本号的旧库，补齐历史新增列并清洗 NULL 字段)
+fn migrate_v1_baseline(conn: &Connection) -> rusqlite::Result<()> {
     // 创建 user_tokens 表
     conn.execute(
         "CREATE TABLE IF NOT EXISTS user_tokens (
@@ -90,33 +184,32 @@ pub fn init_db() -> Result<(), String> {
             total_requests INTEGER NOT NULL DEFAULT 0,
             total_tokens_used INTEGER NOT NULL DEFAULT 0,
             curfew_start TEXT,
-            curfew_end TEXT
+            curfew_end TEXT,
+            rpm_limit INTEGER NOT NULL DEFAULT 0,
+            daily_token_limit INTEGER NOT NULL DEFAULT 0,
+            monthly_token_limit INTEGER NOT NULL DEFAULT 0,
+            allowed_models TEXT
         )",
         [],
-    )
-    .map_err(|e| format!("Failed to create user_tokens table: {}", e))?;
-
-    // 尝试添加新列 (用于旧数据库迁移，忽略已存在的错误)
-    let _ = conn.execute("ALTER TABLE user_tokens ADD COLUMN expires_type TEXT", []);
-    let _ = conn.execute("ALTER TABLE user_tokens ADD COLUMN expires_at INTEGER", []);
-    let _ = conn.execute(
-        "ALTER TABLE user_tokens ADD COLUMN max_ips INTEGER DEFAULT 0",
-        [],
-    );
-    let _ = conn.execute(
-        "ALTER TABLE user_tokens ADD COLUMN total_requests INTEGER DEFAULT 0",
-        [],
-    );
-    let _ = conn.execute(
-        "ALTER TABLE user_tokens ADD COLUMN total_tokens_used INTEGER DEFAULT 0",
-        [],
-    );
-    let _ = conn.execute(
-        "ALTER TABLE user_tokens ADD COLUMN last_used_at INTEGER",
-        [],
-    );
-    let _ = conn.execute("ALTER TABLE user_tokens ADD COLUMN curfew_start TEXT", []);
-    let _ = conn.execute("ALTER TABLE user_tokens ADD COLUMN curfew_end TEXT", []);
+    )?;
+
+    // 旧库补齐新增列
+    for column in [
+        "expires_type TEXT",
+        "expires_at INTEGER",
+        "max_ips INTEGER DEFAULT 0",
+        "total_requests INTEGER DEFAULT 0",
+        "total_tokens_used INTEGER DEFAULT 0",
+        "last_used_at INTEGER",
+        "curfew_start TEXT",
+        "curfew_end TEXT",
+        "rpm_limit INTEGER NOT NULL DEFAULT 0",
+        "daily_token_limit INTEGER NOT NULL DEFAULT 0",
+        "monthly_token_limit INTEGER NOT NULL DEFAULT 0",
+        "allowed_models TEXT",
+    ] {
+        db_migrations::add_column_if_missing(conn, "user_tokens", column)?;
+    }
 
     // 创建 token_ip_bindings 表
     conn.execute(
@@ -132,8 +225,7 @@ pub fn init_db() -> Result<(), String> {
             UNIQUE(token_id, ip_address)
         )",
         [],
-    )
-    .map_err(|e| format!("Failed to create token_ip_bindings table: {}", e))?;
+    )?;
 
     // 创建 token_usage_logs 表
     conn.execute(
@@ -149,39 +241,40 @@ pub fn init_db() -> Result<(), String> {
             FOREIGN KEY(token_id) REFERENCES user_tokens(id) ON DELETE CASCADE
         )",
         [],
-    )
-    .map_err(|e| format!("Failed to create token_usage_logs table: {}", e))?;
+    )?;
 
     // 创建索引
-    let _ = conn.execute(
-        "CREATE INDEX IF NOT EXISTS idx_token_usage_logs_token_id ON token_usage_logs(token_id)",
-        [],
-    );
-    let _ = conn.execute("CREATE INDEX IF NOT EXISTS idx_token_usage_logs_request_time ON token_usage_logs(request_time)", []);
+    conn.execute_batch(
+        "CREATE INDEX IF NOT EXISTS idx_token_usage_logs_token_id ON token_usage_logs(token_id);
+         CREATE INDEX IF NOT EXISTS idx_token_usage_logs_request_time ON token_usage_logs(request_time);
+         CREATE INDEX IF NOT EXISTS idx_token_usage_logs_token_time ON token_usage_logs(token_id, request_time);",
+    )?;
 
     // [FIX Issue #1719] 数据清洗：修复旧版本升级导致的 NULL 字段
     // 这些字段在旧版本中可能不存在，ALTER TABLE 添加后默认为 NULL，导致反序列化失败
-    let _ = conn.execute("UPDATE user_tokens SET expires_type = 'never' WHERE expires_type IS NULL OR expires_type = ''", []);
-    let _ = conn.execute(
-        "UPDATE user_tokens SET max_ips = 0 WHERE max_ips IS NULL",
-        [],
-    );
-    let _ = conn.execute(
-        "UPDATE user_tokens SET total_requests = 0 WHERE total_requests IS NULL",
-        [],
-    );
-    let _ = conn.execute(
-        "UPDATE user_tokens SET total_tokens_used = 0 WHERE total_tokens_used IS NULL",
-        [],
-    );
-    let _ = conn.execute(
-        "UPDATE user_tokens SET enabled = 1 WHERE enabled IS NULL",
-        [],
-    );
+    conn.execute_batch(
+        "UPDATE user_tokens SET expires_type = 'never' WHERE expires_type IS NULL OR expires_type = '';
+         UPDATE user_tokens SET max_ips = 0 WHERE max_ips IS NULL;
+         UPDATE user_tokens SET total_requests = 0 WHERE total_requests IS NULL;
+         UPDATE user_tokens SET total_tokens_used = 0 WHERE total_tokens_used IS NULL;
+         UPDATE user_tokens SET enabled = 1 WHERE enabled IS NULL;",
+    )?;
 
     Ok(())
 }
 
+/// 对指定连接执行结构迁移 (备份恢复时用于校验并升级暂存副本)
+pub(crate) fn migrate(conn: &mut Connection) -> Result<u32, String> {
+    db_migrations::run_migrations(conn, DB_NAME, MIGRATIONS)
+}
+
+/// 初始化数据库
+pub fn init_db() -> Result<(), String> {
+    let mut conn = connect_db()?;
+    migrate(&mut conn)?;
+    Ok(())
+}
+
 /// 创建新令牌
 pub fn create_token(
     username: String,
@@ -236,6 +329,10 @@ pub fn create_token(
         last_used_at: None,
         total_requests: 0,
         total_tokens_used: 0,
+        rpm_limit: 0,
+        daily_token_limit: 0,
+        monthly_token_limit: 0,
+        allowed_models: Vec::new(),
     };
 
     conn.execute(
@@ -291,6 +388,10 @@ pub fn list_tokens() -> Result<Vec<UserToken>, String> {
                 last_used_at: row.get("last_used_at").unwrap_or(None),
                 total_requests: row.get("total_requests").unwrap_or(0),
                 total_tokens_used: row.get("total_tokens_used").unwrap_or(0),
+                rpm_limit: row.get("rpm_limit").unwrap_or(0),
+                daily_token_limit: row.get("daily_token_limit").unwrap_or(0),
+                monthly_token_limit: row.get("monthly_token_limit").unwrap_or(0),
+                allowed_models: parse_allowed_models(row.get("allowed_models").unwrap_or(None)),
             })
         })
         .map_err(|e| format!("Failed to query tokens: {}", e))?;
@@ -328,6 +429,10 @@ pub fn get_token_by_id(id: &str) -> Result<Option<UserToken>, String> {
                 last_used_at: row.get("last_used_at")?,
                 total_requests: row.get("total_requests")?,
                 total_tokens_used: row.get("total_tokens_used")?,
+                rpm_limit: row.get("rpm_limit").unwrap_or(0),
+                daily_token_limit: row.get("daily_token_limit").unwrap_or(0),
+                monthly_token_limit: row.get("monthly_token_limit").unwrap_or(0),
+                allowed_models: parse_allowed_models(row.get("allowed_models").unwrap_or(None)),
             })
         })
         .optional()
@@ -361,6 +466,10 @@ pub fn get_token_by_value(token: &str) -> Result<Option<UserToken>, String> {
                 last_used_at: row.get("last_used_at")?,
                 total_requests: row.get("total_requests")?,
                 total_tokens_used: row.get("total_tokens_used")?,
+                rpm_limit: row.get("rpm_limit").unwrap_or(0),
+                daily_token_limit: row.get("daily_token_limit").unwrap_or(0),
+                monthly_token_limit: row.get("monthly_token_limit").unwrap_or(0),
+                allowed_models: parse_allowed_models(row.get("allowed_models").unwrap_or(None)),
             })
         })
         .optional()
@@ -434,6 +543,70 @@ pub fn update_token(
     Ok(())
 }
 
+/// 更新令牌限额
+///
+/// 外层 None 表示保持不变；Some(None) 表示清除 (恢复为不限)
+pub fn update_token_limits(
+    id: &str,
+    rpm_limit: Option<Option<i32>>,
+    daily_token_limit: Option<Option<i64>>,
+    monthly_token_limit: Option<Option<i64>>,
+    allowed_models: Option<Option<Vec<String>>>,
+) -> Result<(), String> {
+    let conn = connect_db()?;
+    let now = Utc::now().timestamp();
+
+    let mut query = "UPDATE user_tokens SET updated_at = ?1".to_string();
+    let mut params_vec: Vec<Box<dyn rusqlite::ToSql>> = vec![Box::new(now)];
+    let mut param_idx = 2;
+
+    if let Some(rpm) = rpm_limit {
+        query.push_str(&format!(", rpm_limit = ?{}", param_idx));
+        params_vec.push(Box::new(rpm.unwrap_or(0).max(0)));
+        param_idx += 1;
+    }
+
+    if let Some(daily) = daily_token_limit {
+        query.push_str(&format!(", daily_token_limit = ?{}", param_idx));
+        params_vec.push(Box::new(daily.unwrap_or(0).max(0)));
+        param_idx += 1;
+    }
+
+    if let Some(monthly) = monthly_token_limit {
+        query.push_str(&format!(", monthly_token_limit = ?{}", param_idx));
+        params_vec.push(Box::new(monthly.unwrap_or(0).max(0)));
+        param_idx += 1;
+    }
+
+    if let Some(models) = allowed_models {
+        // 清除或清洗后为空都存为 NULL (不限)
+        let models: Vec<String> = models
+            .unwrap_or_default()
+            .into_iter()
+            .map(|m| m.trim().to_string())
+            .filter(|m| !m.is_empty())
+            .collect();
+        let stored = if models.is_empty() {
+            None
+        } else {
+            Some(serde_json::to_string(&models).unwrap_or_else(|_| "[]".to_string()))
+        };
+        query.push_str(&format!(", allowed_models = ?{}", param_idx));
+        params_vec.push(Box::new(stored));
+        param_idx += 1;
+    }
+
+    query.push_str(&format!(" WHERE id = ?{}", param_idx));
+    params_vec.push(Box::new(id.to_string()));
+
+    let params_refs: Vec<&dyn rusqlite::ToSql> = params_vec.iter().map(|p| p.as_ref()).collect();
+
+    conn.execute(&query, params_refs.as_slice())
+        .map_err(|e| format!("Failed to update token limits: {}", e))?;
+
+    Ok(())
+}
+
 /// 续期令牌
 pub fn renew_token(id: &str, expires_type: &str) -> Result<(), String> {
     let conn = connect_db()?;
@@ -474,6 +647,10 @@ pub fn delete_token(id: &str) -> Result<(), String> {
     let conn = connect_db()?;
     conn.execute("DELETE FROM user_tokens WHERE id = ?1", params![id])
         .map_err(|e| format!("Failed to delete token: {}", e))?;
+    RPM_WINDOWS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .remove(id);
     Ok(())
 }
 
@@ -669,6 +846,183 @@ pub fn validate_token(token_str: &str, ip: &str) -> Result<(bool, Option<String>
     }
 }
 
+/// 从 token_usage_logs 聚合令牌的当前消耗 (按本地时区切分日/月)
+pub fn get_token_consumption(token_id: &str) -> Result<TokenConsumption, String> {
+    let conn = connect_db()?;
+    let now = Local::now();
+    let (day_start, month_start) = period_starts(now);
+
+    conn.query_row(
+        "SELECT
+            COALESCE(SUM(CASE WHEN request_time >= ?2 THEN 1 ELSE 0 END), 0),
+            COALESCE(SUM(CASE WHEN request_time >= ?3
+                THEN COALESCE(input_tokens, 0) + COALESCE(output_tokens, 0) ELSE 0 END), 0),
+            COALESCE(SUM(CASE WHEN request_time >= ?4
+                THEN COALESCE(input_tokens, 0) + COALESCE(output_tokens, 0) ELSE 0 END), 0),
+            MIN(CASE WHEN request_time >= ?2 THEN request_time END)
+         FROM token_usage_logs
+         WHERE token_id = ?1 AND request_time >= MIN(?2, ?4)",
+        params![token_id, now.timestamp() - 60, day_start, month_start],
+        |row| {
+            Ok(TokenConsumption {
+                requests_last_minute: row.get(0)?,
+                tokens_today: row.get(1)?,
+                tokens_this_month: row.get(2)?,
+                oldest_request_in_window: row.get(3)?,
+            })
+        },
+    )
+    .map_err(|e| format!("Failed to query token consumption: {}", e))
+}
+
+/// 检查令牌的模型白名单、RPM 与 token 预算
+/// 返回 Some(违规原因) 表示应拒绝请求
+pub fn check_token_limits(
+    token: &UserToken,
+    model: Option<&str>,
+) -> Result<Option<TokenLimitViolation>, String> {
+    let has_quota_limits =
+        token.rpm_limit > 0 || token.daily_token_limit > 0 || token.monthly_token_limit > 0;
+    let consumption = if has_quota_limits {
+        get_token_consumption(&token.id)?
+    } else {
+        TokenConsumption::default()
+    };
+    let now = Local::now();
+    if let Some(violation) = evaluate_token_limits(token, &consumption, model, now) {
+        return Ok(Some(violation));
+    }
+    Ok(reserve_rpm_slot(token, now.timestamp()))
+}
+
+/// 在内存滑动窗口中为本次请求占用一个 RPM 名额
+/// 名额已满时返回 RateLimited，不占位
+fn reserve_rpm_slot(token: &UserToken, now: i64) -> Option<TokenLimitViolation> {
+    if token.rpm_limit <= 0 {
+        return None;
+    }
+    let mut windows = RPM_WINDOWS.lock().unwrap_or_else(|e| e.into_inner());
+    windows.retain(|_, window| {
+        while window.front().is_some_and(|&t| t <= now - 60) {
+            window.pop_front();
+        }
+        !window.is_empty()
+    });
+
+    let window = windows.entry(token.id.clone()).or_default();
+    if window.len() >= token.rpm_limit as usize {
+        return Some(TokenLimitViolation::RateLimited {
+            limit: token.rpm_limit,
+            retry_after_secs: window
+                .front()
+                .map(|&oldest| (oldest + 60 - now).clamp(1, 60))
+                .unwrap_or(60),
+        });
+    }
+    window.push_back(now);
+    None
+}
+
+fn evaluate_token_limits(
+    token: &UserToken,
+    consumption: &TokenConsumption,
+    model: Option<&str>,
+    now: chrono::DateTime<Local>,
+) -> Option<TokenLimitViolation> {
+    if let Some(model) = model {
+        if !is_model_allowed(&token.allowed_models, model) {
+            return Some(TokenLimitViolation::ModelNotAllowed(model.to_string()));
+        }
+    }
+
+    if token.rpm_limit > 0 && consumption.requests_last_minute >= token.rpm_limit as i64 {
+        return Some(TokenLimitViolation::RateLimited {
+            limit: token.rpm_limit,
+            // 窗口为滑动 60 秒：最早一次请求移出窗口后即可重试
+            retry_after_secs: consumption
+                .oldest_request_in_window
+                .map(|oldest| (oldest + 60 - now.timestamp()).clamp(1, 60))
+                .unwrap_or(60),
+        });
+    }
+
+    let (next_day, next_month) = next_period_starts(now);
+    if token.daily_token_limit > 0 && consumption.tokens_today >= token.daily_token_limit {
+        return Some(TokenLimitViolation::DailyBudgetExceeded {
+            used: consumption.tokens_today,
+            limit: token.daily_token_limit,
+            retry_after_secs: (next_day - now.timestamp()).max(1),
+        });
+    }
+
+    if token.monthly_token_limit > 0 && consumption.tokens_this_month >= token.monthly_token_limit {
+        return Some(TokenLimitViolation::MonthlyBudgetExceeded {
+            used: consumption.tokens_this_month,
+            limit: token.monthly_token_limit,
+            retry_after_secs: (next_month - now.timestamp()).max(1),
+        });
+    }
+
+    None
+}
+
+/// 模型白名单匹配: 空列表放行全部；支持尾部 "*" 前缀通配；大小写不敏感
+fn is_model_allowed(allowed: &[String], model: &str) -> bool {
+    if allowed.is_empty() {
+        return true;
+    }
+    let model = model.to_lowercase();
+    allowed.iter().any(|pattern| {
+        let pattern = pattern.to_lowercase();
+        match pattern.strip_suffix('*') {
+            Some(prefix) => model.starts_with(prefix),
+            None => model == pattern,
+        }
+    })
+}
+
+fn parse_allowed_models(raw: Option<String>) -> Vec<String> {
+    raw.and_then(|s| serde_json::from_str::<Vec<String>>(&s).ok())
+        .unwrap_or_default()
+}
+
+/// 本地时区当日 0 点与当月 1 日 0 点的时间戳
+fn period_starts(now: chrono::DateTime<Local>) -> (i64, i64) {
+    let day_start = Local
+        .with_ymd_and_hms(now.year(), now.month(), now.day(), 0, 0, 0)
+        .earliest()
+        .map(|t| t.timestamp())
+        .unwrap_or(now.timestamp() - 86400);
+    let month_start = Local
+        .with_ymd_and_hms(now.year(), now.month(), 1, 0, 0, 0)
+        .earliest()
+        .map(|t| t.timestamp())
+        .unwrap_or(day_start);
+    (day_start, month_start)
+}
+
+/// 下一个日/月周期的起始时间戳
+fn next_period_starts(now: chrono::DateTime<Local>) -> (i64, i64) {
+    let next_day = now
+        .date_naive()
+        .succ_opt()
+        .and_then(|d| d.and_hms_opt(0, 0, 0))
+        .and_then(|d| Local.from_local_datetime(&d).earliest())
+        .map(|t| t.timestamp())
+        .unwrap_or(now.timestamp() + 86400);
+    let (year, month) = if now.month() == 12 {
+        (now.year() + 1, 1)
+    } else {
+        (now.year(), now.month() + 1)
+    };
+    let next_month = Local
+        .with_ymd_and_hms(year, month, 1, 0, 0, 0)
+        .earliest()
+        .map(|t| t.timestamp())
+        .unwrap_or(next_day);
+    (next_day, next_month)
+}
+
 /// 获取 IP 关联的用户名 (用于 IP 管理页面)
 /// 返回最近一次使用该 IP 的 Token 所属的用户名
 pub fn get_username_for_ip(ip: &str) -> Result<Option<String>, String> {
@@ -720,6 +1074,157 @@ mod tests {
         assert_eq!(fetched.unwrap().unwrap().username, username);
     }
 
+    fn limited_token(rpm: i32, daily: i64, monthly: i64, models: &[&str]) -> UserToken {
+        UserToken {
+            id: "t".to_string(),
+            token: "sk-test".to_string(),
+            username: "u".to_string(),
+            description: None,
+            enabled: true,
+            expires_type: "never".to_string(),
+            expires_at: None,
+            max_ips: 0,
+            curfew_start: None,
+            curfew_end: None,
+            created_at: 0,
+            updated_at: 0,
+            last_used_at: None,
+            total_requests: 0,
+            total_tokens_used: 0,
+            rpm_limit: rpm,
+            daily_token_limit: daily,
+            monthly_token_limit: monthly,
+            allowed_models: models.iter().map(|m| m.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn test_evaluate_token_limits() {
+        let now = Local::now();
+        let usage = TokenConsumption {
+            requests_last_minute: 5,
+            tokens_today: 1_000,
+            tokens_this_month: 50_000,
+            oldest_request_in_window: Some(now.timestamp() - 45),
+        };
+
+        let unlimited = limited_token(0, 0, 0, &[]);
+        assert_eq!(
+            evaluate_token_limits(&unlimited, &usage, Some("any"), now),
+            None
+        );
+
+        let models = limited_token(0, 0, 0, &["gemini-*", "claude-sonnet-4-5"]);
+        assert_eq!(
+            evaluate_token_limits(&models, &usage, Some("Gemini-3-Flash"), now),
+            None
+        );
+        assert_eq!(
+            evaluate_token_limits(&models, &usage, Some("claude-opus-4-6"), now),
+            Some(TokenLimitViolation::ModelNotAllowed(
+                "claude-opus-4-6".to_string()
+            ))
+        );
+        assert_eq!(evaluate_token_limits(&models, &usage, None, now), None);
+
+        let rpm = limited_token(5, 0, 0, &[]);
+        assert!(matches!(
+            evaluate_token_limits(&rpm, &usage, None, now),
+            Some(TokenLimitViolation::RateLimited {
+                limit: 5,
+                retry_after_secs: 15
+            })
+        ));
+
+        let daily = limited_token(10, 1_000, 0, &[]);
+        let violation = evaluate_token_limits(&daily, &usage, None, now).unwrap();
+        assert!(matches!(
+            violation,
+            TokenLimitViolation::DailyBudgetExceeded { used: 1_000, .. }
+        ));
+        assert!(violation.retry_after_secs().unwrap() <= 86_400 + 3_600);
+
+        let monthly = limited_token(0, 2_000, 40_000, &[]);
+        assert!(matches!(
+            evaluate_token_limits(&monthly, &usage, None, now),
+            Some(TokenLimitViolation::MonthlyBudgetExceeded { limit: 40_000, .. })
+        ));
+    }
+
+    #[test]
+    fn test_rpm_slots_are_reserved_at_admission() {
+        let mut token = limited_token(2, 0, 0, &[]);
+        token.id = format!("rpm-{}", Uuid::new_v4());
+        let now = Utc::now().timestamp();
+
+        // 尚无任何使用日志时，并发突发也只放行 rpm_limit 个请求
+        assert_eq!(reserve_rpm_slot(&token, now), None);
+        assert_eq!(reserve_rpm_slot(&token, now + 10), None);
+        assert_eq!(
+            reserve_rpm_slot(&token, now + 20),
+            Some(TokenLimitViolation::RateLimited {
+                limit: 2,
+                retry_after_secs: 40
+            })
+        );
+
+        // 最早的请求移出窗口后释放名额
+        assert_eq!(reserve_rpm_slot(&token, now + 60), None);
+        assert!(reserve_rpm_slot(&token, now + 61).is_some());
+    }
+
+    #[test]
+    fn test_token_limits_roundtrip_and_consumption() {
+        let _ = init_db();
+        let token = create_token(
+            format!("LimitUser_{}", Uuid::new_v4()),
+            "never".to_string(),
+            None,
+            0,
+            None,
+            None,
+            None,
+        )
+        .unwrap();
+        update_token_limits(
+            &token.id,
+            Some(Some(2)),
+            Some(Some(100)),
+            None,
+            Some(Some(vec![" gemini-* ".to_string(), "".to_string()])),
+        )
+        .unwrap();
+
+        let fetched = get_token_by_id(&token.id).unwrap().unwrap();
+        assert_eq!(fetched.rpm_limit, 2);
+        assert_eq!(fetched.daily_token_limit, 100);
+        assert_eq!(fetched.monthly_token_limit, 0);
+        assert_eq!(fetched.allowed_models, vec!["gemini-*".to_string()]);
+
+        record_token_usage_and_ip(&token.id, "127.0.0.1", "gemini-3-flash", 40, 20, 200, None)
+            .unwrap();
+        let usage = get_token_consumption(&token.id).unwrap();
+        assert_eq!(usage.requests_last_minute, 1);
+        assert_eq!(usage.tokens_today, 60);
+        assert_eq!(usage.tokens_this_month, 60);
+
+        record_token_usage_and_ip(&token.id, "127.0.0.1", "gemini-3-flash", 30, 10, 200, None)
+            .unwrap();
+        assert!(matches!(
+            check_token_limits(&fetched, Some("gemini-3-flash")).unwrap(),
+            Some(TokenLimitViolation::RateLimited { .. })
+        ));
+
+        // 显式清除: 恢复为不限
+        update_token_limits(&token.id, Some(None), None, None, Some(None)).unwrap();
+        let cleared = get_token_by_id(&token.id).unwrap().unwrap();
+        assert_eq!(cleared.rpm_limit, 0);
+        assert_eq!(cleared.daily_token_limit, 100);
+        assert!(cleared.allowed_models.is_empty());
+
+        let _ = delete_token(&token.id);
+    }
+
     #[test]
     fn test_never_expire_token_validation() {
         let _ = init_db();
@@ -5,14 +5,23 @@
 //! evidence summary plus an artifact id.
 
 use std::collections::HashMap;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::{Mutex, OnceLock};
 use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 use rusqlite::{params, Connection, OptionalExtension};
 
+use crate::modules::db_migrations::{self, Migration};
+
+/// `tool_artifacts_meta` 中的结构版本标记 (保留兼容，结构版本以 `PRAGMA user_version` 为准)
 const SCHEMA_VERSION: i64 = 1;
+const DB_NAME: &str = "tool_artifacts.db";
+const MIGRATIONS: &[Migration] = &[Migration {
+    version: 1,
+    description: "create tool_artifacts",
+    up: create_schema,
+}];
 const DEFAULT_PERSISTED_TTL: Duration = Duration::from_secs(30 * 24 * 3600);
 const DEFAULT_L1_SIZE: usize = 64;
 const DEFAULT_L1_TTL: Duration = Duration::from_secs(3600);
@@ -339,7 +348,20 @@ impl ToolArtifactRecord {
     }
 }
 
-fn init_db(db_path: &Path) -> rusqlite::Result<Connection> {
+/// 对指定连接执行结构迁移 (备份恢复时用于校验并升级暂存副本)
+pub(crate) fn migrate(conn: &mut Connection) -> Result<u32, String> {
+    db_migrations::run_migrations(conn, DB_NAME, MIGRATIONS)
+}
+
+/// 共享 `tool_artifacts.db` 的默认位置 (测试中不落盘)
+pub(crate) fn default_db_path() -> Option<PathBuf> {
+    if cfg!(test) {
+        return None;
+    }
+    dirs::home_dir().map(|p| p.join(".codex-app-transfer").join(DB_NAME))
+}
+
+fn init_db(db_path: &Path) -> Result<Connection, String> {
     if let Some(parent) = db_path.parent() {
         if let Err(e) = std::fs::create_dir_all(parent) {
             log_artifact_warning(
@@ -348,7 +370,7 @@ fn init_db(db_path: &Path) -> rusqlite::Result<Connection> {
             );
         }
     }
-    let conn = Connection::open(db_path)?;
+    let mut conn = Connection::open(db_path).map_err(|e| e.to_string())?;
     if let Err(e) = conn.pragma_update(None, "journal_mode", "WAL") {
         log_artifact_warning(
             "TOOL_ARTIFACT_DB_PRAGMA_FAILED",
@@ -371,7 +393,7 @@ fn init_db(db_path: &Path) -> rusqlite::Result<Connection> {
             format!("busy_timeout failed: {e}"),
         );
     }
-    create_schema(&conn)?;
+    migrate(&mut conn)?;
     Ok(conn)
 }
 
@@ -437,9 +459,7 @@ pub fn global_tool_artifact_store() -> &'static ToolArtifactStore {
         }
         #[cfg(not(test))]
         {
-            let db_path =
-                dirs::home_dir().map(|p| p.join(".codex-app-transfer").join("tool_artifacts.db"));
-            match db_path {
+            match default_db_path() {
                 Some(path) => {
                     let (store, warn) = ToolArtifactStore::with_db_path(
                         DEFAULT_L1_SIZE,
@@ -0,0 +1,609 @@
+//! OpenAI Batch API 执行器
+//!
+//! 后台调度任务从 `batches.db` 逐行领取请求，以 `batch.max_concurrency` 为上限并发地交给
+//! 现有的 `/v1/chat/completions` / `/v1/completions` 处理器执行 (强制非流式)。每行执行前按
+//! 提交作业的 user token 检查有效期与限额，执行后与中间件一致地记录请求日志与用量。目标模型
+//! 暂无可用账号时整体暂停到最近的限流解除时间；429 / 5xx 按退避重新排队。作业的全部请求结束后
+//! 写出 OpenAI 格式的 output / error 文件。反代停止或排空期间暂停领取新请求。
+
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::extract::{Json, OriginalUri, State};
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::IntoResponse;
+use serde_json::{json, Value};
+use tokio::sync::Notify;
+use tokio_util::sync::CancellationToken;
+
+use crate::modules::user_token_db::{self, TokenLimitViolation};
+use crate::proxy::batch_store::{
+    BatchStore, ClaimedRequest, FileDeletion, FileObject, RequestResult, BATCH_DB_FILE,
+    BATCH_FILES_DIR,
+};
+use crate::proxy::middleware::auth::UserTokenIdentity;
+use crate::proxy::monitor::ProxyRequestLog;
+use crate::proxy::server::AppState;
+
+/// 无事可做时的轮询间隔 (兜底，正常由 `wake` 唤醒)
+const IDLE_POLL: Duration = Duration::from_secs(5);
+
+/// 可重试错误的退避基数与上限 (秒)
+const RETRY_BASE_SECS: i64 = 5;
+const RETRY_MAX_SECS: i64 = 300;
+
+/// 单个响应体的读取上限
+const MAX_RESPONSE_BYTES: usize = 64 * 1024 * 1024;
+
+/// 批处理作业存储与调度状态
+pub struct BatchRunner {
+    store: BatchStore,
+    files_dir: PathBuf,
+    wake: Notify,
+    running: AtomicUsize,
+    shutdown: CancellationToken,
+}
+
+impl BatchRunner {
+    pub fn open(data_dir: &Path) -> Result<Self, String> {
+        let files_dir = data_dir.join(BATCH_FILES_DIR);
+        std::fs::create_dir_all(&files_dir)
+            .map_err(|e| format!("Failed to create batch files dir: {}", e))?;
+        Ok(Self {
+            store: BatchStore::open(&data_dir.join(BATCH_DB_FILE))?,
+            files_dir,
+            wake: Notify::new(),
+            running: AtomicUsize::new(0),
+            shutdown: CancellationToken::new(),
+        })
+    }
+
+    pub fn store(&self) -> &BatchStore {
+        &self.store
+    }
+
+    /// 唤醒调度任务 (新建 / 取消作业后调用)
+    pub fn wake(&self) {
+        self.wake.notify_one();
+    }
+
+    /// 停止调度任务，执行中的请求下次启动时重新排队
+    pub fn stop(&self) {
+        self.shutdown.cancel();
+    }
+
+    fn file_path(&self, id: &str) -> PathBuf {
+        self.files_dir.join(id)
+    }
+
+    /// 保存文件内容并登记
+    pub fn save_file(&self, file: &FileObject, content: &[u8]) -> Result<(), String> {
+        std::fs::write(self.file_path(&file.id), content)
+            .map_err(|e| format!("Failed to write file {}: {}", file.id, e))?;
+        if let Err(e) = self.store.insert_file(file) {
+            let _ = std::fs::remove_file(self.file_path(&file.id));
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    pub fn read_file(&self, id: &str) -> Result<Vec<u8>, String> {
+        std::fs::read(self.file_path(id)).map_err(|e| format!("Failed to read file {}: {}", id, e))
+    }
+
+    /// 删除 `owner` 的文件 (仍被未结束的作业引用时不删除)
+    pub fn delete_file(&self, id: &str, owner: Option<&str>) -> Result<FileDeletion, String> {
+        let deletion = self.store.delete_file(id, owner)?;
+        if deletion != FileDeletion::Deleted {
+            return Ok(deletion);
+        }
+        match std::fs::remove_file(self.file_path(id)) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                tracing::warn!("[Batch] 删除文件 {} 内容失败: {}", id, e);
+            }
+            _ => {}
+        }
+        Ok(deletion)
+    }
+
+    /// 为已全部结束的作业写出结果文件 (归属作业提交者) 并设置最终状态
+    fn finalize_ready(&self) -> Result<(), String> {
+        for (batch_id, owner) in self.store.batches_ready_to_finalize()? {
+            let output = self.store.batch_output(&batch_id)?;
+            let now = chrono::Utc::now().timestamp();
+            let output_file =
+                self.write_result_file(&batch_id, &owner, "output", &output.output, now)?;
+            let error_file =
+                self.write_result_file(&batch_id, &owner, "error", &output.errors, now)?;
+            self.store
+                .finalize_batch(&batch_id, output_file.as_ref(), error_file.as_ref(), now)?;
+            tracing::info!(
+                "[Batch] 作业 {} 已结束: {} 成功, {} 失败",
+                batch_id,
+                output.output.len(),
+                output.errors.len()
+            );
+        }
+        Ok(())
+    }
+
+    fn write_result_file(
+        &self,
+        batch_id: &str,
+        owner: &Option<String>,
+        kind: &str,
+        lines: &[String],
+        now: i64,
+    ) -> Result<Option<FileObject>, String> {
+        if lines.is_empty() {
+            return Ok(None);
+        }
+        let mut content = lines.join("\n");
+        content.push('\n');
+        let file = FileObject::new(
+            format!("{}_{}.jsonl", batch_id, kind),
+            "batch_output".to_string(),
+            content.len() as u64,
+            now,
+            owner.clone(),
+        );
+        std::fs::write(self.file_path(&file.id), content)
+            .map_err(|e| format!("Failed to write file {}: {}", file.id, e))?;
+        Ok(Some(file))
+    }
+
+    async fn idle(&self, duration: Duration) {
+        tokio::select! {
+            _ = self.wake.notified() => {}
+            _ = tokio::time::sleep(duration) => {}
+            _ = self.shutdown.cancelled() => {}
+        }
+    }
+}
+
+/// 启动后台调度任务：先把上次中断的请求重新排队，再持续领取执行
+pub fn spawn(state: AppState) {
+    tokio::spawn(run(state));
+}
+
+async fn run(state: AppState) {
+    let runner = state.batches.clone();
+    match blocking(&runner, |runner| runner.store.requeue_running()).await {
+        Ok(0) => {}
+        Ok(n) => tracing::info!("[Batch] 恢复 {} 个中断的批处理请求", n),
+        Err(e) => tracing::warn!("[Batch] 恢复中断请求失败: {}", e),
+    }
+
+    while !runner.shutdown.is_cancelled() {
+        finalize(&runner).await;
+
+        if state.drain.is_draining() || !*state.is_running.read().await {
+            runner.idle(IDLE_POLL).await;
+            continue;
+        }
+
+        let now = chrono::Utc::now().timestamp();
+        let limit = state.config.load().proxy.batch.max_concurrency.max(1);
+        if runner.running.load(Ordering::SeqCst) >= limit {
+            runner.idle(IDLE_POLL).await;
+            continue;
+        }
+
+        match blocking(&runner, move |runner| runner.store.claim_next(now)).await {
+            Ok(Some(request)) => {
+                runner.running.fetch_add(1, Ordering::SeqCst);
+                let state = state.clone();
+                tokio::spawn(async move {
+                    let _guard = state.drain.track();
+                    process(&state, request).await;
+                    state.batches.running.fetch_sub(1, Ordering::SeqCst);
+                    state.batches.wake();
+                });
+            }
+            Ok(None) => {
+                if let Err(e) = blocking(&runner, move |runner| runner.store.expire_due(now)).await
+                {
+                    tracing::warn!("[Batch] 处理过期作业失败: {}", e);
+                }
+                let wait = match blocking(&runner, |runner| runner.store.next_retry_at()).await {
+                    Ok(Some(retry_at)) if retry_at > now => {
+                        Duration::from_secs((retry_at - now) as u64).min(IDLE_POLL)
+                    }
+                    _ => IDLE_POLL,
+                };
+                runner.idle(wait).await;
+            }
+            Err(e) => {
+                tracing::warn!("[Batch] 领取批处理请求失败: {}", e);
+                runner.idle(IDLE_POLL).await;
+            }
+        }
+    }
+}
+
+/// 在阻塞线程池中执行存储 / 文件操作，避免 SQLite 与磁盘 I/O 阻塞调度任务
+async fn blocking<T, F>(runner: &Arc<BatchRunner>, f: F) -> Result<T, String>
+where
+    T: Send + 'static,
+    F: FnOnce(&BatchRunner) -> Result<T, String> + Send + 'static,
+{
+    let runner = runner.clone();
+    tokio::task::spawn_blocking(move || f(&runner))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+async fn finalize(runner: &Arc<BatchRunner>) {
+    if let Err(e) = blocking(runner, |runner| runner.finalize_ready()).await {
+        tracing::warn!("[Batch] 写出批处理结果失败: {}", e);
+    }
+}
+
+/// 请求放回队列 (`attempted` 为 false 时不计入重试次数)
+async fn requeue(state: &AppState, request: &ClaimedRequest, retry_at: i64, attempted: bool) {
+    let (batch_id, line_index) = (request.batch_id.clone(), request.line_index);
+    let requeued = blocking(&state.batches, move |runner| {
+        runner
+            .store
+            .requeue(&batch_id, line_index, retry_at, attempted)
+    })
+    .await;
+    if let Err(e) = requeued {
+        tracing::warn!("[Batch] 请求重新排队失败: {}", e);
+    }
+}
+
+/// 提交者令牌对单行请求的准入结果
+#[derive(Debug)]
+enum Admission {
+    /// 放行 (主 API Key 提交的作业没有令牌身份)
+    Allowed(Option<UserTokenIdentity>),
+    /// 令牌限额暂时用尽，指定秒数后重试
+    Deferred(i64),
+    /// 令牌已失效或模型不在白名单内，该行直接失败
+    Rejected(RequestResult),
+}
+
+/// 与鉴权中间件一致地检查提交者令牌的有效期与限额 (阻塞调用)
+fn admit(owner: Option<&str>, model: Option<&str>) -> Result<Admission, String> {
+    let Some(token_id) = owner else {
+        return Ok(Admission::Allowed(None));
+    };
+    let now = chrono::Utc::now().timestamp();
+    let token = match user_token_db::get_token_by_id(token_id)? {
+        Some(token)
+            if token.enabled
+                && (token.expires_type == "never"
+                    || token.expires_at.is_none_or(|expires_at| expires_at >= now)) =>
+        {
+            token
+        }
+        _ => {
+            return Ok(Admission::Rejected(RequestResult::Error {
+                code: "token_rejected".to_string(),
+                message: "The token that created this batch is disabled, expired or deleted."
+                    .to_string(),
+            }))
+        }
+    };
+
+    Ok(match user_token_db::check_token_limits(&token, model)? {
+        None => Admission::Allowed(Some(UserTokenIdentity {
+            token_id: token.id,
+            token: token.token,
+            username: token.username,
+        })),
+        Some(violation @ TokenLimitViolation::ModelNotAllowed(_)) => {
+            Admission::Rejected(RequestResult::Failed {
+                status_code: StatusCode::FORBIDDEN.as_u16(),
+                body: json!({
+                    "error": {
+                        "message": violation.message(),
+                        "type": "permission_denied",
+                        "code": "model_not_allowed"
+                    }
+                }),
+            })
+        }
+        Some(violation) => Admission::Deferred(
+            violation
+                .retry_after_secs()
+                .unwrap_or(RETRY_BASE_SECS)
+                .max(1),
+        ),
+    })
+}
+
+/// 执行一行请求并记录结果
+async fn process(state: &AppState, request: ClaimedRequest) {
+    let owner = request.owner.clone();
+    let model = request
+        .body
+        .get("model")
+        .and_then(|m| m.as_str())
+        .map(|m| m.to_string());
+    let admission = tokio::task::spawn_blocking(move || admit(owner.as_deref(), model.as_deref()))
+        .await
+        .map_err(|e| e.to_string())
+        .and_then(|admission| admission);
+    let identity = match admission {
+        Ok(Admission::Allowed(identity)) => identity,
+        Ok(Admission::Deferred(secs)) => {
+            tracing::debug!(
+                "[Batch] {}#{} 令牌限额已用尽，{} 秒后重试",
+                request.batch_id,
+                request.line_index,
+                secs
+            );
+            requeue(
+                state,
+                &request,
+                chrono::Utc::now().timestamp() + secs,
+                false,
+            )
+            .await;
+            return;
+        }
+        Ok(Admission::Rejected(result)) => {
+            finish(state, &request, result).await;
+            return;
+        }
+        Err(e) => {
+            // 令牌数据库异常时稍后重试，不以未鉴权身份执行
+            tracing::warn!("[Batch] 检查令牌限额失败: {}", e);
+            requeue(
+                state,
+                &request,
+                chrono::Utc::now().timestamp() + RETRY_BASE_SECS,
+                false,
+            )
+            .await;
+            return;
+        }
+    };
+
+    // 仅推迟当前行，其他模型的请求不受影响
+    if let Some(wait) = capacity_wait(state, &request.body).await {
+        tracing::debug!(
+            "[Batch] {}#{} 目标模型暂无可用账号，{:?} 后重试",
+            request.batch_id,
+            request.line_index,
+            wait
+        );
+        let retry_at = chrono::Utc::now().timestamp() + wait.as_secs() as i64;
+        requeue(state, &request, retry_at, false).await;
+        return;
+    }
+
+    let outcome = execute(state, &request.endpoint, request.body.clone(), identity).await;
+    let max_retries = state.config.load().proxy.batch.max_retries;
+    let result = match outcome {
+        Ok((status, retry_after, _)) if is_retryable(status) && request.attempts < max_retries => {
+            let delay = retry_after.unwrap_or_else(|| retry_delay(request.attempts));
+            let retry_at = chrono::Utc::now().timestamp() + delay;
+            tracing::debug!(
+                "[Batch] {}#{} 返回 {}，{} 秒后重试",
+                request.batch_id,
+                request.line_index,
+                status,
+                delay
+            );
+            requeue(state, &request, retry_at, true).await;
+            return;
+        }
+        Ok((status, _, body)) if status.is_success() => RequestResult::Completed {
+            status_code: status.as_u16(),
+            body,
+        },
+        Ok((status, _, body)) => RequestResult::Failed {
+            status_code: status.as_u16(),
+            body,
+        },
+        Err(message) => RequestResult::Error {
+            code: "batch_execution_error".to_string(),
+            message,
+        },
+    };
+
+    finish(state, &request, result).await;
+}
+
+async fn finish(state: &AppState, request: &ClaimedRequest, result: RequestResult) {
+    let (batch_id, line_index) = (request.batch_id.clone(), request.line_index);
+    let finished = blocking(&state.batches, move |runner| {
+        runner.store.finish_request(&batch_id, line_index, &result)
+    })
+    .await;
+    if let Err(e) = finished {
+        tracing::warn!("[Batch] 记录批处理结果失败: {}", e);
+    }
+}
+
+/// 目标模型当前没有可用账号 (全部限流) 时，返回建议的等待时间
+async fn capacity_wait(state: &AppState, body: &Value) -> Option<Duration> {
+    if state.token_manager.len() == 0 {
+        return None;
+    }
+    let model = body.get("model").and_then(|m| m.as_str())?;
+    let mapped = crate::proxy::common::model_mapping::resolve_model_route(
+        model,
+        &state.config.load().proxy.custom_mapping,
+    );
+    // 与限流记录使用同一个 Key，Claude 系列统一记在 "claude" 下
+    let limit_key = crate::proxy::common::model_mapping::rate_limit_model_key(&mapped);
+    let quota_group = if limit_key == "claude" {
+        "claude"
+    } else {
+        "gemini"
+    };
+    if state
+        .token_manager
+        .has_available_account(quota_group, &limit_key)
+        .await
+    {
+        return None;
+    }
+
+    // 只参考账号级锁与该模型的锁，其他模型的限流不影响等待时间
+    let wait = state
+        .token_manager
+        .get_rate_limit_snapshot()
+        .iter()
+        .filter(|limit| limit.model.as_deref().is_none_or(|m| m == limit_key))
+        .map(|limit| limit.remaining_sec)
+        .min()
+        .unwrap_or(RETRY_BASE_SECS as u64);
+    Some(Duration::from_secs(wait.clamp(1, RETRY_MAX_SECS as u64)))
+}
+
+fn is_retryable(status: StatusCode) -> bool {
+    matches!(status.as_u16(), 429 | 500 | 502 | 503 | 504 | 529)
+}
+
+fn retry_delay(attempts: u32) -> i64 {
+    (RETRY_BASE_SECS << attempts.min(16)).min(RETRY_MAX_SECS)
+}
+
+/// 通过现有处理器执行请求并记录请求日志与用量，返回 (状态码, Retry-After 秒数, 响应体)
+async fn execute(
+    state: &AppState,
+    endpoint: &str,
+    mut body: Value,
+    identity: Option<UserTokenIdentity>,
+) -> Result<(StatusCode, Option<i64>, Value), String> {
+    if let Some(obj) = body.as_object_mut() {
+        obj.insert("stream".to_string(), Value::Bool(false));
+        obj.remove("stream_options");
+    }
+    let start = std::time::Instant::now();
+    let model = body
+        .get("model")
+        .and_then(|m| m.as_str())
+        .map(|m| m.to_string());
+    let request_body = body.to_string();
+
+    let response = if endpoint == "/v1/chat/completions" {
+        crate::proxy::handlers::openai::handle_chat_completions(
+            State(state.clone()),
+            HeaderMap::new(),
+            Json(body),
+        )
+        .await
+        .into_response()
+    } else {
+        let uri = endpoint
+            .parse()
+            .map_err(|e| format!("Invalid endpoint {}: {}", endpoint, e))?;
+        crate::proxy::handlers::openai::handle_completions(
+            OriginalUri(uri),
+            State(state.clone()),
+            HeaderMap::new(),
+            Json(body),
+        )
+        .await
+    };
+
+    let status = response.status();
+    let account_email = header_value(response.headers(), "X-Account-Email");
+    let mapped_model = header_value(response.headers(), "X-Mapped-Model");
+    let retry_after = response
+        .headers()
+        .get(axum::http::header::RETRY_AFTER)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.trim().parse::<i64>().ok())
+        .map(|secs| secs.clamp(1, RETRY_MAX_SECS));
+    let bytes = axum::body::to_bytes(response.into_body(), MAX_RESPONSE_BYTES)
+        .await
+        .map_err(|e| format!("Failed to read response body: {}", e))?;
+    let body = serde_json::from_slice(&bytes)
+        .unwrap_or_else(|_| Value::String(String::from_utf8_lossy(&bytes).into_owned()));
+
+    let log = ProxyRequestLog {
+        id: uuid::Uuid::new_v4().to_string(),
+        timestamp: chrono::Utc::now().timestamp_millis(),
+        method: "POST".to_string(),
+        url: endpoint.to_string(),
+        status: status.as_u16(),
+        duration: start.elapsed().as_millis() as u64,
+        model,
+        mapped_model,
+        account_email,
+        client_ip: None,
+        error: None,
+        request_body: Some(request_body),
+        response_body: Some(String::from_utf8_lossy(&bytes).into_owned()),
+        input_tokens: None,
+        output_tokens: None,
+        cached_tokens: None,
+        protocol: Some("openai".to_string()),
+        username: None,
+        reasoning_tokens: None,
+    };
+    crate::proxy::middleware::monitor::log_internal_request(state, identity, log, &body).await;
+    Ok((status, retry_after, body))
+}
+
+fn header_value(headers: &HeaderMap, name: &str) -> Option<String> {
+    headers
+        .get(name)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_retry_policy() {
+        assert!(is_retryable(StatusCode::TOO_MANY_REQUESTS));
+        assert!(is_retryable(StatusCode::SERVICE_UNAVAILABLE));
+        assert!(!is_retryable(StatusCode::BAD_REQUEST));
+        assert_eq!(retry_delay(0), 5);
+        assert_eq!(retry_delay(2), 20);
+        assert_eq!(retry_delay(10), RETRY_MAX_SECS);
+    }
+
+    #[test]
+    fn test_batches_without_token_are_admitted() {
+        // 主 API Key 提交的作业不经过令牌限额
+        assert!(matches!(
+            admit(None, Some("gemini-2.5-flash")),
+            Ok(Admission::Allowed(None))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_capacity_wait_only_defers_limited_model() {
+        use crate::proxy::tests::mock_upstream::MockUpstream;
+        use crate::proxy::tests::replay_tests::build_state;
+
+        let mock = MockUpstream::new().start().await.unwrap();
+        let state = build_state(&mock).await;
+        let claude_line = json!({ "model": "claude-sonnet-4-5" });
+        let gemini_line = json!({ "model": "gemini-3-flash" });
+        assert!(capacity_wait(&state, &claude_line).await.is_none());
+
+        // 以映射后的模型名记录限流，与 Claude handler 一致
+        let mapped = crate::proxy::common::model_mapping::resolve_model_route(
+            "claude-sonnet-4-5",
+            &state.config.load().proxy.custom_mapping,
+        );
+        state
+            .token_manager
+            .mark_rate_limited_async(
+                "replay@test.com",
+                429,
+                Some("60"),
+                r#"{"error":{"details":[{"reason":"QUOTA_EXHAUSTED"}]}}"#,
+                Some(&mapped),
+            )
+            .await;
+
+        let wait = capacity_wait(&state, &claude_line).await.unwrap();
+        assert!(wait.as_secs() >= 1 && wait.as_secs() <= 60);
+        assert!(capacity_wait(&state, &gemini_line).await.is_none());
+    }
+}
@@ -0,0 +1,1173 @@
+//! OpenAI Batch API 作业队列 (batches.db)
+//!
+//! `/v1/files` 上传的 JSONL 内容保存在数据目录的 `batch_files/` 下，元数据与批处理作业
+//! 记录在 `batches.db`。创建批处理时输入文件被拆分为逐行的 `batch_requests`，执行状态
+//! 逐行落库，反代重启后由 `batch_runner` 从中断处继续。
+
+use std::collections::HashSet;
+use std::path::Path;
+use std::sync::Mutex;
+
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::Serialize;
+use serde_json::{json, Value};
+
+use crate::modules::db_migrations::{self, Migration};
+
+pub const BATCH_DB_FILE: &str = "batches.db";
+
+/// 文件内容的存放目录 (位于数据目录下)
+pub const BATCH_FILES_DIR: &str = "batch_files";
+
+/// 批处理支持的端点
+pub const SUPPORTED_ENDPOINTS: &[&str] =
+    &["/v1/chat/completions", "/v1/completions", "/v1/responses"];
+
+/// 目前仅支持的完成窗口
+pub const COMPLETION_WINDOW: &str = "24h";
+const COMPLETION_WINDOW_SECS: i64 = 24 * 3600;
+
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "create files / batches / batch_requests",
+        up: migrate_v1_create_tables,
+    },
+    Migration {
+        version: 2,
+        description: "owner columns on files / batches",
+        up: migrate_v2_owner,
+    },
+];
+
+/// 对指定连接执行结构迁移
+pub(crate) fn migrate(conn: &mut Connection) -> Result<u32, String> {
+    db_migrations::run_migrations(conn, BATCH_DB_FILE, MIGRATIONS)
+}
+
+fn migrate_v1_create_tables(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS files (
+            id TEXT PRIMARY KEY,
+            filename TEXT NOT NULL,
+            purpose TEXT NOT NULL,
+            bytes INTEGER NOT NULL,
+            created_at INTEGER NOT NULL
+        );
+        CREATE TABLE IF NOT EXISTS batches (
+            id TEXT PRIMARY KEY,
+            endpoint TEXT NOT NULL,
+            input_file_id TEXT NOT NULL,
+            completion_window TEXT NOT NULL,
+            status TEXT NOT NULL,
+            output_file_id TEXT,
+            error_file_id TEXT,
+            metadata TEXT,
+            created_at INTEGER NOT NULL,
+            in_progress_at INTEGER,
+            expires_at INTEGER NOT NULL,
+            finalizing_at INTEGER,
+            completed_at INTEGER,
+            expired_at INTEGER,
+            cancelling_at INTEGER,
+            cancelled_at INTEGER
+        );
+        CREATE INDEX IF NOT EXISTS idx_batches_status ON batches (status);
+        CREATE TABLE IF NOT EXISTS batch_requests (
+            batch_id TEXT NOT NULL,
+            line_index INTEGER NOT NULL,
+            request_id TEXT NOT NULL,
+            custom_id TEXT NOT NULL,
+            body TEXT NOT NULL,
+            status TEXT NOT NULL,
+            attempts INTEGER NOT NULL DEFAULT 0,
+            retry_at INTEGER NOT NULL DEFAULT 0,
+            response_status INTEGER,
+            response_body TEXT,
+            error_code TEXT,
+            error_message TEXT,
+            PRIMARY KEY (batch_id, line_index)
+        );
+        CREATE INDEX IF NOT EXISTS idx_batch_requests_status ON batch_requests (status, batch_id);",
+    )
+}
+
+/// v2: 文件与作业归属提交者 (user token id；使用主 API Key 时为 NULL)
+fn migrate_v2_owner(conn: &Connection) -> rusqlite::Result<()> {
+    for table in ["files", "batches"] {
+        db_migrations::add_column_if_missing(conn, table, "owner TEXT")?;
+    }
+    Ok(())
+}
+
+/// OpenAI File 对象
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct FileObject {
+    pub id: String,
+    pub object: &'static str,
+    pub bytes: u64,
+    pub created_at: i64,
+    pub filename: String,
+    pub purpose: String,
+    /// 上传者的 user token id (主 API Key 为 None)，不对外输出
+    #[serde(skip)]
+    pub owner: Option<String>,
+}
+
+impl FileObject {
+    pub fn new(
+        filename: String,
+        purpose: String,
+        bytes: u64,
+        created_at: i64,
+        owner: Option<String>,
+    ) -> Self {
+        Self {
+            id: format!("file-{}", uuid::Uuid::new_v4().simple()),
+            object: "file",
+            bytes,
+            created_at,
+            filename,
+            purpose,
+            owner,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, PartialEq)]
+pub struct RequestCounts {
+    pub total: u64,
+    pub completed: u64,
+    pub failed: u64,
+}
+
+/// OpenAI Batch 对象
+#[derive(Debug, Clone, Serialize)]
+pub struct BatchObject {
+    pub id: String,
+    pub object: &'static str,
+    pub endpoint: String,
+    pub errors: Option<Value>,
+    pub input_file_id: String,
+    pub completion_window: String,
+    pub status: String,
+    pub output_file_id: Option<String>,
+    pub error_file_id: Option<String>,
+    pub created_at: i64,
+    pub in_progress_at: Option<i64>,
+    pub expires_at: i64,
+    pub finalizing_at: Option<i64>,
+    pub completed_at: Option<i64>,
+    pub failed_at: Option<i64>,
+    pub expired_at: Option<i64>,
+    pub cancelling_at: Option<i64>,
+    pub cancelled_at: Option<i64>,
+    pub request_counts: RequestCounts,
+    pub metadata: Option<Value>,
+    /// 提交作业的 user token id (主 API Key 为 None)，执行时据此检查限额并记录用量
+    #[serde(skip)]
+    pub owner: Option<String>,
+}
+
+impl BatchObject {
+    /// 作业是否已结束 (不再执行任何请求)
+    pub fn is_terminal(&self) -> bool {
+        matches!(
+            self.status.as_str(),
+            "completed" | "failed" | "expired" | "cancelled"
+        )
+    }
+}
+
+/// 输入文件中的一行
+#[derive(Debug, Clone, PartialEq)]
+pub struct BatchLine {
+    pub custom_id: String,
+    pub body: Value,
+}
+
+/// 解析并校验批处理输入 JSONL：每行须为 `{custom_id, method: "POST", url, body}`，
+/// `url` 与批处理端点一致且 `custom_id` 不重复
+pub fn parse_batch_input(content: &str, endpoint: &str) -> Result<Vec<BatchLine>, String> {
+    let mut lines = Vec::new();
+    let mut seen = HashSet::new();
+
+    for (idx, raw) in content.lines().enumerate() {
+        let line_no = idx + 1;
+        let raw = raw.trim();
+        if raw.is_empty() {
+            continue;
+        }
+        let value: Value = serde_json::from_str(raw)
+            .map_err(|e| format!("Line {}: invalid JSON: {}", line_no, e))?;
+
+        let custom_id = value
+            .get("custom_id")
+            .and_then(|v| v.as_str())
+            .filter(|s| !s.is_empty())
+            .ok_or_else(|| format!("Line {}: missing custom_id", line_no))?;
+        if !seen.insert(custom_id.to_string()) {
+            return Err(format!(
+                "Line {}: duplicate custom_id '{}'",
+                line_no, custom_id
+            ));
+        }
+
+        let method = value.get("method").and_then(|v| v.as_str()).unwrap_or("");
+        if !method.eq_ignore_ascii_case("POST") {
+            return Err(format!("Line {}: method must be POST", line_no));
+        }
+        let url = value.get("url").and_then(|v| v.as_str()).unwrap_or("");
+        if url != endpoint {
+            return Err(format!(
+                "Line {}: url '{}' does not match batch endpoint '{}'",
+                line_no, url, endpoint
+            ));
+        }
+        let body = match value.get("body") {
+            Some(body) if body.is_object() => body.clone(),
+            _ => return Err(format!("Line {}: body must be a JSON object", line_no)),
+        };
+
+        lines.push(BatchLine {
+            custom_id: custom_id.to_string(),
+            body,
+        });
+    }
+
+    if lines.is_empty() {
+        return Err("Input file contains no requests".to_string());
+    }
+    Ok(lines)
+}
+
+/// 已领取、待执行的一行请求
+#[derive(Debug, Clone)]
+pub struct ClaimedRequest {
+    pub batch_id: String,
+    pub line_index: i64,
+    pub endpoint: String,
+    pub body: Value,
+    /// 此前因可重试错误已执行的次数
+    pub attempts: u32,
+    /// 提交作业的 user token id
+    pub owner: Option<String>,
+}
+
+/// 删除文件的结果
+#[derive(Debug, Clone, PartialEq)]
+pub enum FileDeletion {
+    Deleted,
+    NotFound,
+    /// 仍被未结束的作业引用 (附作业 ID)
+    InUse(String),
+}
+
+/// 单行请求的最终结果
+#[derive(Debug, Clone, PartialEq)]
+pub enum RequestResult {
+    /// 上游返回 2xx
+    Completed { status_code: u16, body: Value },
+    /// 上游返回错误响应
+    Failed { status_code: u16, body: Value },
+    /// 请求未能执行
+    Error { code: String, message: String },
+}
+
+/// 待写入 output / error 文件的 JSONL 内容
+#[derive(Debug, Default)]
+pub struct BatchOutput {
+    pub output: Vec<String>,
+    pub errors: Vec<String>,
+}
+
+#[derive(Debug)]
+pub struct BatchStore {
+    conn: Mutex<Connection>,
+}
+
+const BATCH_COLUMNS: &str = "id, endpoint, input_file_id, completion_window, status, output_file_id, error_file_id, metadata, created_at, in_progress_at, expires_at, finalizing_at, completed_at, expired_at, cancelling_at, cancelled_at, owner";
+
+impl BatchStore {
+    pub fn open(db_path: &Path) -> Result<Self, String> {
+        if let Some(parent) = db_path.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+        }
+        let mut conn = Connection::open(db_path).map_err(|e| e.to_string())?;
+
+        conn.pragma_update(None, "journal_mode", "WAL")
+            .map_err(|e| e.to_string())?;
+        conn.pragma_update(None, "busy_timeout", 5000)
+            .map_err(|e| e.to_string())?;
+        conn.pragma_update(None, "synchronous", "NORMAL")
+            .map_err(|e| e.to_string())?;
+
+        migrate(&mut conn)?;
+
+        Ok(Self {
+            conn: Mutex::new(conn),
+        })
+    }
+
+    pub fn insert_file(&self, file: &FileObject) -> Result<(), String> {
+        let conn = self.conn.lock().map_err(|e| e.to_string())?;
+        insert_file_row(&conn, file)
+    }
+
+    /// 按 ID 查询文件；`owner` 不匹配时视为不存在
+    pub fn get_file(&self, id: &str, owner: Option<&str>) -> Result<Option<FileObject>, String> {
+        let conn = self.conn.lock().map_err(|e| e.to_string())?;
+        conn.query_row(
+            "SELECT id, filename, purpose, bytes, created_at, owner FROM files
+             WHERE id = ?1 AND owner IS ?2",
+            params![id, owner],
+            file_from_row,
+        )
+        .optional()
+        .map_err(|e| e.to_string())
+    }
+
+    /// 按创建时间倒序列出 `owner` 的文件，可按 purpose 过滤
+    pub fn list_files(
+        &self,
+        owner: Option<&str>,
+        purpose: Option<&str>,
+    ) -> Result<Vec<FileObject>, String> {
+        let conn = self.conn.lock().map_err(|e| e.to_string())?;
+        let mut stmt = conn
+            .prepare(
+                "SELECT id, filename, purpose, bytes, created_at, owner FROM files
+                 WHERE owner IS ?1 AND (?2 IS NULL OR purpose = ?2)
+                 ORDER BY created_at DESC, id DESC",
+            )
+            .map_err(|e| e.to_string())?;
+        let rows = stmt
+            .query_map(params![owner, purpose], file_from_row)
+            .map_err(|e| e.to_string())?;
+
+        let mut files = Vec::new();
+        for row in rows {
+            files.push(row.map_err(|e| e.to_string())?);
+        }
+        Ok(files)
+    }
+
+    /// 删除文件记录；仍被未结束的作业引用时拒绝删除
+    pub fn delete_file(&self, id: &str, owner: Option<&str>) -> Result<FileDeletion, String> {
+        let mut conn = self.conn.lock().map_err(|e| e.to_string())?;
+        let tx = conn.transaction().map_err(|e| e.to_string())?;
+        let exists: bool = tx
+            .query_row(
+                "SELECT EXISTS(SELECT 1 FROM files WHERE id = ?1 AND owner IS ?2)",
+                params![id, owner],
+                |row| row.get(0),
+            )
+            .map_err(|e| e.to_string())?;
+        if !exists {
+            return Ok(FileDeletion::NotFound);
+        }
+        let in_use: Option<String> = tx
+            .query_row(
+                "SELECT id FROM batches
+                 WHERE input_file_id = ?1
+                   AND status NOT IN ('completed', 'failed', 'expired', 'cancelled')
+                 LIMIT 1",
+                [id],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(|e| e.to_string())?;
+        if let Some(batch_id) = in_use {
+            return Ok(FileDeletion::InUse(batch_id));
+        }
+        tx.execute("DELETE FROM files WHERE id = ?1", [id])
+            .map_err(|e| e.to_string())?;
+        tx.commit().map_err(|e| e.to_string())?;
+        Ok(FileDeletion::Deleted)
+    }
+
+    /// 创建批处理作业并写入全部请求行 (直接进入 in_progress)
+    pub fn create_batch(
+        &self,
+        owner: Option<&str>,
+        endpoint: &str,
+        input_file_id: &str,
+        metadata: Option<&Value>,
+        lines: &[BatchLine],
+        now: i64,
+    ) -> Result<BatchObject, String> {
+        let id = format!("batch_{}", uuid::Uuid::new_v4().simple());
+        {
+            let mut conn = self.conn.lock().map_err(|e| e.to_string())?;
+            let tx = conn.transaction().map_err(|e| e.to_string())?;
+            tx.execute(
+                "INSERT INTO batches (id, endpoint, input_file_id, completion_window, status, metadata, created_at, in_progress_at, expires_at, owner)
+                 VALUES (?1, ?2, ?3, ?4, 'in_progress', ?5, ?6, ?6, ?7, ?8)",
+                params![
+                    id,
+                    endpoint,
+                    input_file_id,
+                    COMPLETION_WINDOW,
+                    metadata.map(|m| m.to_string()),
+                    now,
+                    now + COMPLETION_WINDOW_SECS,
+                    owner,
+                ],
+            )
+            .map_err(|e| e.to_string())?;
+            {
+                let mut stmt = tx
+                    .prepare(
+                        "INSERT INTO batch_requests (batch_id, line_index, request_id, custom_id, body, status)
+                         VALUES (?1, ?2, ?3, ?4, ?5, 'pending')",
+                    )
+                    .map_err(|e| e.to_string())?;
+                for (idx, line) in lines.iter().enumerate() {
+                    stmt.execute(params![
+                        id,
+                        idx as i64,
+                        format!("batch_req_{}", uuid::Uuid::new_v4().simple()),
+                        line.custom_id,
+                        line.body.to_string(),
+                    ])
+                    .map_err(|e| e.to_string())?;
+                }
+            }
+            tx.commit().map_err(|e| e.to_string())?;
+        }
+
+        self.get_batch(&id, owner)?
+            .ok_or_else(|| format!("Batch {} disappeared after insert", id))
+    }
+
+    /// 按 ID 查询作业；`owner` 不匹配时视为不存在
+    pub fn get_batch(&self, id: &str, owner: Option<&str>) -> Result<Option<BatchObject>, String> {
+        let conn = self.conn.lock().map_err(|e| e.to_string())?;
+        let batch = conn
+            .query_row(
+                &format!(
+                    "SELECT {} FROM batches WHERE id = ?1 AND owner IS ?2",
+                    BATCH_COLUMNS
+                ),
+                params![id, owner],
+                batch_from_row,
+            )
+            .optional()
+            .map_err(|e| e.to_string())?;
+        match batch {
+            Some(mut batch) => {
+                batch.request_counts = request_counts(&conn, &batch.id)?;
+                Ok(Some(batch))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// 按创建时间倒序分页列出 `owner` 的作业，`after` 为上一页最后一个批处理 ID。
+    /// 返回 (当前页, 是否还有更多)
+    pub fn list_batches(
+        &self,
+        owner: Option<&str>,
+        after: Option<&str>,
+        limit: usize,
+    ) -> Result<(Vec<BatchObject>, bool), String> {
+        let conn = self.conn.lock().map_err(|e| e.to_string())?;
+        let cursor: Option<(i64, String)> = match after {
+            Some(after) => conn
+                .query_row(
+                    "SELECT created_at, id FROM batches WHERE id = ?1 AND owner IS ?2",
+                    params![after, owner],
+                    |row| Ok((row.get(0)?, row.get(1)?)),
+                )
+                .optional()
+                .map_err(|e| e.to_string())?,
+            None => None,
+        };
+        let (cursor_at, cursor_id) = match cursor {
+            Some((at, id)) => (Some(at), Some(id)),
+            None => (None, None),
+        };
+
+        let mut stmt = conn
+            .prepare(&format!(
+                "SELECT {} FROM batches
+                 WHERE owner IS ?4
+                   AND (?1 IS NULL OR created_at < ?1 OR (created_at = ?1 AND id < ?2))
+                 ORDER BY created_at DESC, id DESC
+                 LIMIT ?3",
+                BATCH_COLUMNS
+            ))
+            .map_err(|e| e.to_string())?;
+        let rows = stmt
+            .query_map(
+                params![cursor_at, cursor_id, limit as i64 + 1, owner],
+                batch_from_row,
+            )
+            .map_err(|e| e.to_string())?;
+
+        let mut batches = Vec::new();
+        for row in rows {
+            batches.push(row.map_err(|e| e.to_string())?);
+        }
+        let has_more = batches.len() > limit;
+        batches.truncate(limit);
+        for batch in batches.iter_mut() {
+            batch.request_counts = request_counts(&conn, &batch.id)?;
+        }
+        Ok((batches, has_more))
+    }
+
+    /// 取消 `owner` 的作业：未执行的请求标记为 cancelled，执行中的请求完成后再收尾
+    pub fn cancel_batch(&self, id: &str, owner: Option<&str>, now: i64) -> Result<(), String> {
+        let mut conn = self.conn.lock().map_err(|e| e.to_string())?;
+        let tx = conn.transaction().map_err(|e| e.to_string())?;
+        let cancelled = tx
+            .execute(
+                "UPDATE batches SET status = 'cancelling', cancelling_at = ?2
+                 WHERE id = ?1 AND owner IS ?3 AND status IN ('validating', 'in_progress')",
+                params![id, now, owner],
+            )
+            .map_err(|e| e.to_string())?;
+        if cancelled == 0 {
+            return Ok(());
+        }
+        tx.execute(
+            "UPDATE batch_requests SET status = 'cancelled' WHERE batch_id = ?1 AND status = 'pending'",
+            [id],
+        )
+        .map_err(|e| e.to_string())?;
+        tx.commit().map_err(|e| e.to_string())
+    }
+
+    /// 重启恢复：上次进程退出时执行中的请求重新排队
+    pub fn requeue_running(&self) -> Result<usize, String> {
+        let conn = self.conn.lock().map_err(|e| e.to_string())?;
+        conn.execute(
+            "UPDATE batch_requests SET status = 'pending' WHERE status = 'running'",
+            [],
+        )
+        .map_err(|e| e.to_string())
+    }
+
+    /// 领取下一条可执行的请求 (按作业创建顺序与行号)
+    pub fn claim_next(&self, now: i64) -> Result<Option<ClaimedRequest>, String> {
+        let conn = self.conn.lock().map_err(|e| e.to_string())?;
+        let claimed = conn
+            .query_row(
+                "SELECT r.batch_id, r.line_index, b.endpoint, r.body, r.attempts, b.owner
+                 FROM batch_requests r JOIN batches b ON b.id = r.batch_id
+                 WHERE r.status = 'pending' AND r.retry_at <= ?1
+                   AND b.status = 'in_progress' AND b.expires_at > ?1
+                 ORDER BY b.created_at, b.id, r.line_index
+                 LIMIT 1",
+                [now],
+                |row| {
+                    let body: String = row.get(3)?;
+                    Ok(ClaimedRequest {
+                        batch_id: row.get(0)?,
+                        line_index: row.get(1)?,
+                        endpoint: row.get(2)?,
+                        body: serde_json::from_str(&body).unwrap_or(Value::Null),
+                        attempts: row.get(4)?,
+                        owner: row.get(5)?,
+                    })
+                },
+            )
+            .optional()
+            .map_err(|e| e.to_string())?;
+
+        if let Some(req) = &claimed {
+            conn.execute(
+                "UPDATE batch_requests SET status = 'running'
+                 WHERE batch_id = ?1 AND line_index = ?2",
+                params![req.batch_id, req.line_index],
+            )
+            .map_err(|e| e.to_string())?;
+        }
+        Ok(claimed)
+    }
+
+    /// 最早的待重试时间 (用于调度器休眠)
+    pub fn next_retry_at(&self) -> Result<Option<i64>, String> {
+        let conn = self.conn.lock().map_err(|e| e.to_string())?;
+        conn.query_row(
+            "SELECT MIN(r.retry_at) FROM batch_requests r JOIN batches b ON b.id = r.batch_id
+             WHERE r.status = 'pending' AND b.status = 'in_progress'",
+            [],
+            |row| row.get(0),
+        )
+        .map_err(|e| e.to_string())
+    }
+
+    /// 请求放回队列，在 `retry_at` 之后重新执行；`attempted` 表示本次已实际发往上游 (计入重试次数)
+    pub fn requeue(
+        &self,
+        batch_id: &str,
+        line_index: i64,
+        retry_at: i64,
+        attempted: bool,
+    ) -> Result<(), String> {
+        let conn = self.conn.lock().map_err(|e| e.to_string())?;
+        // 作业在执行期间被取消时，不再放回队列
+        conn.execute(
+            "UPDATE batch_requests SET
+                status = CASE WHEN (SELECT status FROM batches WHERE id = ?1) = 'in_progress'
+                         THEN 'pending' ELSE 'cancelled' END,
+                retry_at = ?3,
+                attempts = attempts + ?4
+             WHERE batch_id = ?1 AND line_index = ?2",
+            params![batch_id, line_index, retry_at, attempted as i64],
+        )
+        .map_err(|e| e.to_string())?;
+        Ok(())
+    }
+
+    /// 记录请求的最终结果
+    pub fn finish_request(
+        &self,
+        batch_id: &str,
+        line_index: i64,
+        result: &RequestResult,
+    ) -> Result<(), String> {
+        let (status, response_status, response_body, error_code, error_message) = match result {
+            RequestResult::Completed { status_code, body } => (
+                "completed",
+                Some(*status_code),
+                Some(body.to_string()),
+                None,
+                None,
+            ),
+            RequestResult::Failed { status_code, body } => (
+                "failed",
+                Some(*status_code),
+                Some(body.to_string()),
+                None,
+                None,
+            ),
+            RequestResult::Error { code, message } => (
+                "failed",
+                None,
+                None,
+                Some(code.as_str()),
+                Some(message.as_str()),
+            ),
+        };
+        let conn = self.conn.lock().map_err(|e| e.to_string())?;
+        conn.execute(
+            "UPDATE batch_requests SET status = ?3, response_status = ?4, response_body = ?5,
+                error_code = ?6, error_message = ?7
+             WHERE batch_id = ?1 AND line_index = ?2",
+            params![
+                batch_id,
+                line_index,
+                status,
+                response_status,
+                response_body,
+                error_code,
+                error_message
+            ],
+        )
+        .map_err(|e| e.to_string())?;
+        Ok(())
+    }
+
+    /// 超过完成窗口的作业：剩余未执行的请求标记为 expired，返回受影响的作业数
+    pub fn expire_due(&self, now: i64) -> Result<usize, String> {
+        let mut conn = self.conn.lock().map_err(|e| e.to_string())?;
+        let tx = conn.transaction().map_err(|e| e.to_string())?;
+        tx.execute(
+            "UPDATE batch_requests SET status = 'expired'
+             WHERE status = 'pending' AND batch_id IN
+                (SELECT id FROM batches WHERE status = 'in_progress' AND expires_at <= ?1)",
+            [now],
+        )
+        .map_err(|e| e.to_string())?;
+        let expired = tx
+            .execute(
+                "UPDATE batches SET expired_at = ?1
+                 WHERE status = 'in_progress' AND expires_at <= ?1 AND expired_at IS NULL",
+                [now],
+            )
+            .map_err(|e| e.to_string())?;
+        tx.commit().map_err(|e| e.to_string())?;
+        Ok(expired)
+    }
+
+    /// 所有请求均已结束、等待写出结果文件的作业 (ID 与提交者)
+    pub fn batches_ready_to_finalize(&self) -> Result<Vec<(String, Option<String>)>, String> {
+        let conn = self.conn.lock().map_err(|e| e.to_string())?;
+        let mut stmt = conn
+            .prepare(
+                "SELECT id, owner FROM batches b
+                 WHERE status IN ('in_progress', 'cancelling')
+                   AND NOT EXISTS (
+                       SELECT 1 FROM batch_requests r
+                       WHERE r.batch_id = b.id AND r.status IN ('pending', 'running')
+                   )
+                 ORDER BY created_at",
+            )
+            .map_err(|e| e.to_string())?;
+        let rows = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .map_err(|e| e.to_string())?;
+
+        let mut ids = Vec::new();
+        for row in rows {
+            ids.push(row.map_err(|e| e.to_string())?);
+        }
+        Ok(ids)
+    }
+
+    /// 生成 OpenAI 格式的 output / error JSONL 行 (按输入行序)
+    pub fn batch_output(&self, batch_id: &str) -> Result<BatchOutput, String> {
+        let conn = self.conn.lock().map_err(|e| e.to_string())?;
+        let mut stmt = conn
+            .prepare(
+                "SELECT request_id, custom_id, status, response_status, response_body, error_code, error_message
+                 FROM batch_requests WHERE batch_id = ?1 ORDER BY line_index",
+            )
+            .map_err(|e| e.to_string())?;
+        let rows = stmt
+            .query_map([batch_id], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, String>(2)?,
+                    row.get::<_, Option<u16>>(3)?,
+                    row.get::<_, Option<String>>(4)?,
+                    row.get::<_, Option<String>>(5)?,
+                    row.get::<_, Option<String>>(6)?,
+                ))
+            })
+            .map_err(|e| e.to_string())?;
+
+        let mut out = BatchOutput::default();
+        for row in rows {
+            let (request_id, custom_id, status, response_status, response_body, code, message) =
+                row.map_err(|e| e.to_string())?;
+            let response = response_status.map(|status_code| {
+                let body = response_body
+                    .as_deref()
+                    .map(|b| serde_json::from_str(b).unwrap_or_else(|_| json!(b)))
+                    .unwrap_or(Value::Null);
+                json!({ "status_code": status_code, "request_id": request_id, "body": body })
+            });
+            let error = match status.as_str() {
+                "completed" | "failed" if response.is_some() => Value::Null,
+                "expired" => json!({
+                    "code": "batch_expired",
+                    "message": "This request could not be executed before the completion window expired."
+                }),
+                "cancelled" => json!({
+                    "code": "batch_cancelled",
+                    "message": "This request was not executed because the batch was cancelled."
+                }),
+                _ => json!({ "code": code, "message": message }),
+            };
+
+            let line = json!({
+                "id": request_id,
+                "custom_id": custom_id,
+                "response": response,
+                "error": error,
+            })
+            .to_string();
+            if status == "completed" {
+                out.output.push(line);
+            } else {
+                out.errors.push(line);
+            }
+        }
+        Ok(out)
+    }
+
+    /// 写出结果文件后结束作业：登记文件并设置最终状态
+    pub fn finalize_batch(
+        &self,
+        batch_id: &str,
+        output_file: Option<&FileObject>,
+        error_file: Option<&FileObject>,
+        now: i64,
+    ) -> Result<(), String> {
+        let mut conn = self.conn.lock().map_err(|e| e.to_string())?;
+        let tx = conn.transaction().map_err(|e| e.to_string())?;
+        for file in [output_file, error_file].into_iter().flatten() {
+            insert_file_row(&tx, file)?;
+        }
+        tx.execute(
+            "UPDATE batches SET
+                status = CASE
+                    WHEN status = 'cancelling' THEN 'cancelled'
+                    WHEN expired_at IS NOT NULL THEN 'expired'
+                    ELSE 'completed' END,
+                completed_at = CASE WHEN status = 'cancelling' OR expired_at IS NOT NULL
+                    THEN completed_at ELSE ?4 END,
+                cancelled_at = CASE WHEN status = 'cancelling' THEN ?4 ELSE cancelled_at END,
+                finalizing_at = COALESCE(finalizing_at, ?4),
+                output_file_id = ?2,
+                error_file_id = ?3
+             WHERE id = ?1",
+            params![
+                batch_id,
+                output_file.map(|f| f.id.as_str()),
+                error_file.map(|f| f.id.as_str()),
+                now
+            ],
+        )
+        .map_err(|e| e.to_string())?;
+        tx.commit().map_err(|e| e.to_string())
+    }
+}
+
+fn insert_file_row(conn: &Connection, file: &FileObject) -> Result<(), String> {
+    conn.execute(
+        "INSERT INTO files (id, filename, purpose, bytes, created_at, owner) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        params![
+            file.id,
+            file.filename,
+            file.purpose,
+            file.bytes as i64,
+            file.created_at,
+            file.owner
+        ],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+fn file_from_row(row: &rusqlite::Row) -> rusqlite::Result<FileObject> {
+    Ok(FileObject {
+        id: row.get(0)?,
+        object: "file",
+        filename: row.get(1)?,
+        purpose: row.get(2)?,
+        bytes: row.get::<_, i64>(3)?.max(0) as u64,
+        created_at: row.get(4)?,
+        owner: row.get(5)?,
+    })
+}
+
+fn batch_from_row(row: &rusqlite::Row) -> rusqlite::Result<BatchObject> {
+    let metadata: Option<String> = row.get(7)?;
+    Ok(BatchObject {
+        id: row.get(0)?,
+        object: "batch",
+        endpoint: row.get(1)?,
+        errors: None,
+        input_file_id: row.get(2)?,
+        completion_window: row.get(3)?,
+        status: row.get(4)?,
+        output_file_id: row.get(5)?,
+        error_file_id: row.get(6)?,
+        metadata: metadata.and_then(|m| serde_json::from_str(&m).ok()),
+        created_at: row.get(8)?,
+        in_progress_at: row.get(9)?,
+        expires_at: row.get(10)?,
+        finalizing_at: row.get(11)?,
+        completed_at: row.get(12)?,
+        failed_at: None,
+        expired_at: row.get(13)?,
+        cancelling_at: row.get(14)?,
+        cancelled_at: row.get(15)?,
+        request_counts: RequestCounts::default(),
+        owner: row.get(16)?,
+    })
+}
+
+fn request_counts(conn: &Connection, batch_id: &str) -> Result<RequestCounts, String> {
+    conn.query_row(
+        "SELECT COUNT(*),
+                COALESCE(SUM(status = 'completed'), 0),
+                COALESCE(SUM(status IN ('failed', 'expired')), 0)
+         FROM batch_requests WHERE batch_id = ?1",
+        [batch_id],
+        |row| {
+            Ok(RequestCounts {
+                total: row.get::<_, i64>(0)? as u64,
+                completed: row.get::<_, i64>(1)? as u64,
+                failed: row.get::<_, i64>(2)? as u64,
+            })
+        },
+    )
+    .map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn open_store() -> (tempfile::TempDir, BatchStore) {
+        let dir = tempfile::tempdir().unwrap();
+        let store = BatchStore::open(&dir.path().join(BATCH_DB_FILE)).unwrap();
+        (dir, store)
+    }
+
+    fn input_line(custom_id: &str) -> String {
+        json!({
+            "custom_id": custom_id,
+            "method": "POST",
+            "url": "/v1/chat/completions",
+            "body": { "model": "gemini-2.5-flash", "messages": [{ "role": "user", "content": "hi" }] }
+        })
+        .to_string()
+    }
+
+    #[test]
+    fn test_parse_batch_input_validates_lines() {
+        let content = format!("{}\n\n{}\n", input_line("a"), input_line("b"));
+        let lines = parse_batch_input(&content, "/v1/chat/completions").unwrap();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[1].custom_id, "b");
+
+        let dup = format!("{}\n{}", input_line("a"), input_line("a"));
+        let err = parse_batch_input(&dup, "/v1/chat/completions").unwrap_err();
+        assert!(
+            err.contains("Line 2") && err.contains("duplicate"),
+            "{}",
+            err
+        );
+
+        let err = parse_batch_input(&input_line("a"), "/v1/completions").unwrap_err();
+        assert!(err.contains("does not match"), "{}", err);
+
+        assert!(parse_batch_input("not json", "/v1/chat/completions").is_err());
+        assert!(parse_batch_input("\n", "/v1/chat/completions").is_err());
+    }
+
+    #[test]
+    fn test_batch_lifecycle_survives_reopen() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join(BATCH_DB_FILE);
+        let content = format!(
+            "{}\n{}\n{}",
+            input_line("a"),
+            input_line("b"),
+            input_line("c")
+        );
+        let lines = parse_batch_input(&content, "/v1/chat/completions").unwrap();
+
+        let batch_id = {
+            let store = BatchStore::open(&db_path).unwrap();
+            let batch = store
+                .create_batch(None, "/v1/chat/completions", "file-in", None, &lines, 100)
+                .unwrap();
+            assert_eq!(batch.status, "in_progress");
+            assert_eq!(batch.request_counts.total, 3);
+
+            let first = store.claim_next(100).unwrap().unwrap();
+            assert_eq!(first.line_index, 0);
+            store
+                .finish_request(
+                    &batch.id,
+                    0,
+                    &RequestResult::Completed {
+                        status_code: 200,
+                        body: json!({ "id": "chatcmpl-1" }),
+                    },
+                )
+                .unwrap();
+            // 第二行执行中时进程退出
+            assert_eq!(store.claim_next(100).unwrap().unwrap().line_index, 1);
+            batch.id
+        };
+
+        let store = BatchStore::open(&db_path).unwrap();
+        assert_eq!(store.requeue_running().unwrap(), 1);
+        let resumed = store.claim_next(100).unwrap().unwrap();
+        assert_eq!(resumed.line_index, 1);
+        assert_eq!(resumed.attempts, 0);
+
+        store.requeue(&batch_id, 1, 200, true).unwrap();
+        assert_eq!(store.claim_next(100).unwrap().unwrap().line_index, 2);
+        assert!(store.claim_next(100).unwrap().is_none());
+        assert_eq!(store.next_retry_at().unwrap(), Some(200));
+
+        store
+            .finish_request(
+                &batch_id,
+                2,
+                &RequestResult::Failed {
+                    status_code: 400,
+                    body: json!({ "error": { "message": "bad" } }),
+                },
+            )
+            .unwrap();
+        let retried = store.claim_next(200).unwrap().unwrap();
+        assert_eq!(retried.attempts, 1);
+        store
+            .finish_request(
+                &batch_id,
+                retried.line_index,
+                &RequestResult::Error {
+                    code: "upstream_error".to_string(),
+                    message: "boom".to_string(),
+                },
+            )
+            .unwrap();
+
+        assert_eq!(
+            store.batches_ready_to_finalize().unwrap(),
+            vec![(batch_id.clone(), None)]
+        );
+        let output = store.batch_output(&batch_id).unwrap();
+        assert_eq!(output.output.len(), 1);
+        assert_eq!(output.errors.len(), 2);
+        let ok: Value = serde_json::from_str(&output.output[0]).unwrap();
+        assert_eq!(ok["custom_id"], "a");
+        assert_eq!(ok["response"]["status_code"], 200);
+        assert_eq!(ok["response"]["body"]["id"], "chatcmpl-1");
+        let failed: Value = serde_json::from_str(&output.errors[1]).unwrap();
+        assert_eq!(failed["custom_id"], "c");
+        assert_eq!(failed["response"]["status_code"], 400);
+        assert!(failed["error"].is_null());
+        let errored: Value = serde_json::from_str(&output.errors[0]).unwrap();
+        assert!(errored["response"].is_null());
+        assert_eq!(errored["error"]["code"], "upstream_error");
+
+        let out_file = FileObject::new("out.jsonl".into(), "batch_output".into(), 10, 300, None);
+        store
+            .finalize_batch(&batch_id, Some(&out_file), None, 300)
+            .unwrap();
+        let batch = store.get_batch(&batch_id, None).unwrap().unwrap();
+        assert_eq!(batch.status, "completed");
+        assert_eq!(batch.completed_at, Some(300));
+        assert_eq!(batch.output_file_id.as_deref(), Some(out_file.id.as_str()));
+        assert_eq!(
+            batch.request_counts,
+            RequestCounts {
+                total: 3,
+                completed: 1,
+                failed: 2
+            }
+        );
+        assert!(store.batches_ready_to_finalize().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_cancel_and_expire() {
+        let (_dir, store) = open_store();
+        let lines = parse_batch_input(
+            &format!("{}\n{}", input_line("a"), input_line("b")),
+            "/v1/chat/completions",
+        )
+        .unwrap();
+
+        let cancelled = store
+            .create_batch(None, "/v1/chat/completions", "file-in", None, &lines, 100)
+            .unwrap();
+        let running = store.claim_next(100).unwrap().unwrap();
+        store.cancel_batch(&cancelled.id, None, 110).unwrap();
+        // 执行中的请求尚未结束，暂不收尾
+        assert!(store.batches_ready_to_finalize().unwrap().is_empty());
+        store
+            .requeue(&cancelled.id, running.line_index, 0, false)
+            .unwrap();
+        assert_eq!(
+            store.batches_ready_to_finalize().unwrap(),
+            vec![(cancelled.id.clone(), None)]
+        );
+        store
+            .finalize_batch(&cancelled.id, None, None, 120)
+            .unwrap();
+        let batch = store.get_batch(&cancelled.id, None).unwrap().unwrap();
+        assert_eq!(batch.status, "cancelled");
+        assert_eq!(batch.cancelled_at, Some(120));
+        assert!(batch.completed_at.is_none());
+
+        let expiring = store
+            .create_batch(
+                None,
+                "/v1/chat/completions",
+                "file-in",
+                Some(&json!({ "job": "eval" })),
+                &lines,
+                200,
+            )
+            .unwrap();
+        let deadline = expiring.expires_at;
+        assert!(store.claim_next(deadline).unwrap().is_none());
+        assert_eq!(store.expire_due(deadline).unwrap(), 1);
+        let output = store.batch_output(&expiring.id).unwrap();
+        assert_eq!(output.errors.len(), 2);
+        assert!(output.errors[0].contains("batch_expired"));
+        store
+            .finalize_batch(&expiring.id, None, None, deadline)
+            .unwrap();
+        let batch = store.get_batch(&expiring.id, None).unwrap().unwrap();
+        assert_eq!(batch.status, "expired");
+        assert_eq!(batch.metadata, Some(json!({ "job": "eval" })));
+        assert_eq!(batch.request_counts.failed, 2);
+
+        let (page, has_more) = store.list_batches(None, None, 1).unwrap();
+        assert_eq!(page[0].id, expiring.id);
+        assert!(has_more);
+        let (page, has_more) = store.list_batches(None, Some(&expiring.id), 10).unwrap();
+        assert_eq!(page.len(), 1);
+        assert_eq!(page[0].id, cancelled.id);
+        assert!(!has_more);
+    }
+
+    #[test]
+    fn test_files_and_batches_are_scoped_to_owner() {
+        let (_dir, store) = open_store();
+        let lines = parse_batch_input(&input_line("a"), "/v1/chat/completions").unwrap();
+        let file = FileObject::new(
+            "in.jsonl".into(),
+            "batch".into(),
+            10,
+            100,
+            Some("tok-a".into()),
+        );
+        store.insert_file(&file).unwrap();
+
+        assert!(store.get_file(&file.id, Some("tok-a")).unwrap().is_some());
+        assert!(store.get_file(&file.id, Some("tok-b")).unwrap().is_none());
+        assert!(store.get_file(&file.id, None).unwrap().is_none());
+        assert_eq!(store.list_files(Some("tok-a"), None).unwrap().len(), 1);
+        assert!(store.list_files(None, None).unwrap().is_empty());
+
+        let batch = store
+            .create_batch(
+                Some("tok-a"),
+                "/v1/chat/completions",
+                &file.id,
+                None,
+                &lines,
+                100,
+            )
+            .unwrap();
+        assert_eq!(batch.owner.as_deref(), Some("tok-a"));
+        assert!(store.get_batch(&batch.id, Some("tok-b")).unwrap().is_none());
+        assert!(store
+            .list_batches(Some("tok-b"), None, 10)
+            .unwrap()
+            .0
+            .is_empty());
+        assert_eq!(
+            store.claim_next(100).unwrap().unwrap().owner.as_deref(),
+            Some("tok-a")
+        );
+
+        // 其他令牌既不能取消也不能删除
+        store.cancel_batch(&batch.id, Some("tok-b"), 110).unwrap();
+        let current = store.get_batch(&batch.id, Some("tok-a")).unwrap().unwrap();
+        assert_eq!(current.status, "in_progress");
+        assert_eq!(
+            store.delete_file(&file.id, Some("tok-b")).unwrap(),
+            FileDeletion::NotFound
+        );
+
+        // 未结束的作业仍引用输入文件时拒绝删除
+        assert_eq!(
+            store.delete_file(&file.id, Some("tok-a")).unwrap(),
+            FileDeletion::InUse(batch.id.clone())
+        );
+        store.cancel_batch(&batch.id, Some("tok-a"), 110).unwrap();
+        store.requeue(&batch.id, 0, 0, false).unwrap();
+        store.finalize_batch(&batch.id, None, None, 120).unwrap();
+        assert_eq!(
+            store.delete_file(&file.id, Some("tok-a")).unwrap(),
+            FileDeletion::Deleted
+        );
+    }
+}
@@ -0,0 +1,250 @@
+// 后台任务识别规则引擎
+//
+// 根据 ProxyConfig.background_tasks 中配置的规则表识别客户端发起的后台请求
+// (标题生成、摘要、提示建议等)，并决定路由到指定模型或直接返回本地固定回复。
+// 三种协议 (Claude / OpenAI / Gemini) 的 Handler 共用此模块。
+
+use crate::proxy::config::{BackgroundTaskAction, BackgroundTaskConfig, BackgroundTaskRule};
+
+/// 规则匹配所需的请求特征
+#[derive(Debug, Clone, Default)]
+pub struct TaskSignals<'a> {
+    /// 协议名称: "claude" / "openai" / "gemini"
+    pub protocol: &'a str,
+    /// 命中的客户端适配器名称
+    pub client_adapter: Option<&'a str>,
+    pub user_agent: &'a str,
+    /// 最后一条 (有效的) 用户消息文本
+    pub last_user_message: Option<&'a str>,
+    pub system_prompt: &'a str,
+    pub has_tools: bool,
+}
+
+/// 命中的规则
+#[derive(Debug, Clone, PartialEq)]
+pub struct MatchedRule {
+    pub name: String,
+    pub action: BackgroundTaskAction,
+}
+
+/// 按顺序评估规则，返回第一条命中的规则
+///
+/// 功能关闭或处于 dry_run 模式时返回 None；dry_run 仅记录将会命中的规则
+pub fn resolve(
+    config: &BackgroundTaskConfig,
+    signals: &TaskSignals,
+    trace_id: &str,
+) -> Option<MatchedRule> {
+    if !config.enabled {
+        return None;
+    }
+
+    let rule = config
+        .rules
+        .iter()
+        .find(|rule| rule.enabled && rule_matches(rule, signals))?;
+
+    if config.dry_run {
+        tracing::info!(
+            "[{}][BackgroundTask] dry-run: rule '{}' would fire ({}, action: {:?})",
+            trace_id,
+            rule.name,
+            signals.protocol,
+            rule.action
+        );
+        return None;
+    }
+
+    Some(MatchedRule {
+        name: rule.name.clone(),
+        action: rule.action.clone(),
+    })
+}
+
+/// 判断单条规则是否命中 (所有已设置的条件必须同时满足)
+pub fn rule_matches(rule: &BackgroundTaskRule, signals: &TaskSignals) -> bool {
+    if !rule.protocols.is_empty()
+        && !rule
+            .protocols
+            .iter()
+            .any(|p| p.eq_ignore_ascii_case(signals.protocol))
+    {
+        return false;
+    }
+
+    if let Some(adapter) = &rule.client_adapter {
+        match signals.client_adapter {
+            Some(name) if name.eq_ignore_ascii_case(adapter) => {}
+            _ => return false,
+        }
+    }
+
+    if let Some(expected) = rule.has_tools {
+        if expected != signals.has_tools {
+            return false;
+        }
+    }
+
+    if let Some(pattern) = &rule.user_agent {
+        if !pattern.is_match(signals.user_agent) {
+            return false;
+        }
+    }
+
+    if let Some(pattern) = &rule.system_prompt {
+        if !pattern.is_match(signals.system_prompt) {
+            return false;
+        }
+    }
+
+    let needs_message = rule.min_message_chars.is_some()
+        || rule.max_message_chars.is_some()
+        || rule.last_user_message.is_some();
+    if needs_message {
+        // 消息类条件要求存在有效的用户消息
+        let Some(message) = signals.last_user_message else {
+            return false;
+        };
+        let chars = message.chars().count();
+        if rule.min_message_chars.is_some_and(|min| chars < min) {
+            return false;
+        }
+        if rule.max_message_chars.is_some_and(|max| chars > max) {
+            return false;
+        }
+        if let Some(pattern) = &rule.last_user_message {
+            if !pattern.is_match(message) {
+                return false;
+            }
+        }
+    }
+
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::proxy::config::RulePattern;
+
+    fn claude_signals(message: &str) -> TaskSignals<'_> {
+        TaskSignals {
+            protocol: "claude",
+            user_agent: "claude-cli/2.0.0",
+            last_user_message: Some(message),
+            ..Default::default()
+        }
+    }
+
+    fn reply_rule(name: &str) -> BackgroundTaskRule {
+        BackgroundTaskRule {
+            name: name.to_string(),
+            enabled: true,
+            protocols: Vec::new(),
+            client_adapter: None,
+            user_agent: None,
+            min_message_chars: None,
+            max_message_chars: None,
+            last_user_message: None,
+            system_prompt: None,
+            has_tools: None,
+            action: BackgroundTaskAction::Reply {
+                text: "OK".to_string(),
+            },
+        }
+    }
+
+    #[test]
+    fn test_default_rules_match_title_prompt() {
+        let config = BackgroundTaskConfig::default();
+        let signals = claude_signals("Please write a 5-10 word title for the following");
+        let matched = resolve(&config, &signals, "t").expect("title rule should fire");
+        assert_eq!(matched.name, "claude-code-title");
+        assert_eq!(
+            matched.action,
+            BackgroundTaskAction::Route {
+                model: "internal-background-task".to_string()
+            }
+        );
+
+        // 其他协议与超长消息不命中默认规则
+        let openai = TaskSignals {
+            protocol: "openai",
+            ..signals.clone()
+        };
+        assert!(resolve(&config, &openai, "t").is_none());
+        let long = format!("Generate a title for {}", "x".repeat(900));
+        assert!(resolve(&config, &claude_signals(&long), "t").is_none());
+    }
+
+    #[test]
+    fn test_dry_run_and_disabled_do_not_fire() {
+        let mut config = BackgroundTaskConfig {
+            dry_run: true,
+            ..Default::default()
+        };
+        let signals = claude_signals("Summarize the conversation");
+        assert!(resolve(&config, &signals, "t").is_none());
+
+        config.dry_run = false;
+        config.enabled = false;
+        assert!(resolve(&config, &signals, "t").is_none());
+    }
+
+    #[test]
+    fn test_rule_conditions() {
+        let mut rule = reply_rule("cline-title");
+        rule.protocols = vec!["openai".to_string()];
+        rule.user_agent = RulePattern::new("(?i)cline").ok();
+        rule.system_prompt = RulePattern::new("title").ok();
+        rule.has_tools = Some(false);
+        rule.min_message_chars = Some(3);
+
+        let mut signals = TaskSignals {
+            protocol: "openai",
+            user_agent: "Cline/3.1",
+            last_user_message: Some("hello"),
+            system_prompt: "Generate a short title",
+            ..Default::default()
+        };
+        assert!(rule_matches(&rule, &signals));
+
+        signals.has_tools = true;
+        assert!(!rule_matches(&rule, &signals));
+        signals.has_tools = false;
+
+        signals.last_user_message = Some("hi");
+        assert!(!rule_matches(&rule, &signals));
+        signals.last_user_message = None;
+        assert!(!rule_matches(&rule, &signals));
+        signals.last_user_message = Some("hello");
+
+        signals.user_agent = "curl/8.0";
+        assert!(!rule_matches(&rule, &signals));
+    }
+
+    #[test]
+    fn test_client_adapter() {
+        let mut rule = reply_rule("opencode-only");
+        rule.client_adapter = Some("opencode".to_string());
+        let mut signals = claude_signals("anything");
+        assert!(!rule_matches(&rule, &signals));
+        signals.client_adapter = Some("opencode");
+        assert!(rule_matches(&rule, &signals));
+    }
+
+    #[test]
+    fn test_invalid_regex_rejected_when_config_loads() {
+        let rule = r#"{"name":"bad","last_user_message":"([unclosed","action":{"type":"reply","text":"OK"}}"#;
+        let err = serde_json::from_str::<BackgroundTaskRule>(rule).unwrap_err();
+        assert!(err.to_string().contains("invalid regex"));
+
+        // 合法正则编译后随配置保存，序列化时还原为原始字符串
+        let rule = rule.replace("([unclosed", "(?i)title");
+        let parsed: BackgroundTaskRule = serde_json::from_str(&rule).unwrap();
+        let pattern = parsed.last_user_message.as_ref().unwrap();
+        assert!(pattern.is_match("Generate a TITLE"));
+        let value = serde_json::to_value(&parsed).unwrap();
+        assert_eq!(value["last_user_message"], "(?i)title");
+    }
+}
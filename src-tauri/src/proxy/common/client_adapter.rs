@@ -13,6 +13,9 @@ use std::sync::Arc; // [NEW] Import Arc
 /// 2. **向后兼容**：未匹配到适配器的请求完全按照现有流程处理
 /// 3. **单文件修改**：客户端特定逻辑封装在各自的适配器文件中
 pub trait ClientAdapter: Send + Sync {
+    /// 适配器名称 (用于配置中引用，如后台任务规则的 client_adapter 条件)
+    fn name(&self) -> &'static str;
+
     /// 判断该适配器是否匹配给定的请求
     ///
     /// # Arguments
@@ -109,6 +112,10 @@ mod tests {
     struct TestAdapter;
 
     impl ClientAdapter for TestAdapter {
+        fn name(&self) -> &'static str {
+            "test"
+        }
+
         fn matches(&self, headers: &HeaderMap) -> bool {
             get_user_agent(headers)
                 .map(|ua| ua.contains("test-client"))
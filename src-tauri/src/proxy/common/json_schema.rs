@@ -829,6 +829,203 @@ fn fix_single_arg_recursive(value: &mut Value, schema: &Value) {
     }
 }
 
+/// [NEW] 将结构化输出 (OpenAI json_schema / Claude 强制工具) 的 Schema 转换为 Gemini responseSchema
+///
+/// 复用 clean_json_schema 的 $ref/allOf 展开逻辑，原始 Schema 保持不变以便后续校验
+pub fn build_response_schema(schema: &Value) -> Value {
+    let mut cleaned = schema.clone();
+    clean_json_schema(&mut cleaned);
+    cleaned
+}
+
+/// [NEW] 按原始 JSON Schema 校验输出值
+///
+/// 覆盖结构化输出常用的关键字: type, properties, required, additionalProperties,
+/// items, enum, const, anyOf/oneOf/allOf, 长度与数值范围, 以及 #/$defs 与 #/definitions 引用。
+/// 返回第一个不匹配位置的描述 (例如 `$.items[0].name: expected string`)
+pub fn validate_json_schema(instance: &Value, schema: &Value) -> Result<(), String> {
+    validate_node(instance, schema, schema, "$", 0)
+}
+
+fn resolve_schema_ref<'a>(root: &'a Value, ref_path: &str) -> Option<&'a Value> {
+    let pointer = ref_path.strip_prefix('#')?;
+    if pointer.is_empty() {
+        return Some(root);
+    }
+    root.pointer(pointer)
+}
+
+fn json_type_matches(instance: &Value, type_name: &str) -> bool {
+    match type_name {
+        "object" => instance.is_object(),
+        "array" => instance.is_array(),
+        "string" => instance.is_string(),
+        "boolean" => instance.is_boolean(),
+        "null" => instance.is_null(),
+        "number" => instance.is_number(),
+        "integer" => {
+            instance.is_i64()
+                || instance.is_u64()
+                || instance.as_f64().is_some_and(|f| f.fract() == 0.0)
+        }
+        _ => true,
+    }
+}
+
+fn validate_node(
+    instance: &Value,
+    schema: &Value,
+    root: &Value,
+    path: &str,
+    depth: usize,
+) -> Result<(), String> {
+    // 深度限制与 clean_json_schema 不同: 校验需要覆盖较深的输出结构，同时防止循环引用
+    if depth > MAX_RECURSION_DEPTH * 4 {
+        return Err(format!("{}: schema nesting too deep", path));
+    }
+
+    let map = match schema {
+        Value::Bool(true) => return Ok(()),
+        Value::Bool(false) => return Err(format!("{}: no value is allowed here", path)),
+        Value::Object(map) => map,
+        _ => return Ok(()),
+    };
+
+    if let Some(ref_path) = map.get("$ref").and_then(|r| r.as_str()) {
+        let target = resolve_schema_ref(root, ref_path)
+            .ok_or_else(|| format!("{}: unresolved $ref '{}'", path, ref_path))?;
+        validate_node(instance, target, root, path, depth + 1)?;
+    }
+
+    if let Some(type_val) = map.get("type") {
+        let allowed: Vec<&str> = match type_val {
+            Value::String(s) => vec![s.as_str()],
+            Value::Array(arr) => arr.iter().filter_map(|t| t.as_str()).collect(),
+            _ => Vec::new(),
+        };
+        if !allowed.is_empty() && !allowed.iter().any(|t| json_type_matches(instance, t)) {
+            return Err(format!("{}: expected {}", path, allowed.join(" | ")));
+        }
+    }
+
+    if let Some(Value::Array(options)) = map.get("enum") {
+        if !options.contains(instance) {
+            return Err(format!(
+                "{}: value is not one of the allowed enum values",
+                path
+            ));
+        }
+    }
+    if let Some(expected) = map.get("const") {
+        if expected != instance {
+            return Err(format!("{}: expected constant {}", path, expected));
+        }
+    }
+
+    if let Some(Value::Array(all_of)) = map.get("allOf") {
+        for sub in all_of {
+            validate_node(instance, sub, root, path, depth + 1)?;
+        }
+    }
+    if let Some(Value::Array(any_of)) = map.get("anyOf") {
+        if !any_of
+            .iter()
+            .any(|sub| validate_node(instance, sub, root, path, depth + 1).is_ok())
+        {
+            return Err(format!("{}: value does not match any allowed schema", path));
+        }
+    }
+    if let Some(Value::Array(one_of)) = map.get("oneOf") {
+        let matched = one_of
+            .iter()
+            .filter(|sub| validate_node(instance, sub, root, path, depth + 1).is_ok())
+            .count();
+        if matched != 1 {
+            return Err(format!(
+                "{}: value must match exactly one schema (matched {})",
+                path, matched
+            ));
+        }
+    }
+
+    match instance {
+        Value::Object(obj) => {
+            if let Some(Value::Array(required)) = map.get("required") {
+                for key in required.iter().filter_map(|k| k.as_str()) {
+                    if !obj.contains_key(key) {
+                        return Err(format!("{}: missing required property '{}'", path, key));
+                    }
+                }
+            }
+            let properties = map.get("properties").and_then(|p| p.as_object());
+            for (key, value) in obj {
+                let child_path = format!("{}.{}", path, key);
+                match properties.and_then(|p| p.get(key)) {
+                    Some(prop_schema) => {
+                        validate_node(value, prop_schema, root, &child_path, depth + 1)?
+                    }
+                    None => match map.get("additionalProperties") {
+                        Some(Value::Bool(false)) => {
+                            return Err(format!("{}: unexpected property '{}'", path, key))
+                        }
+                        Some(extra @ Value::Object(_)) => {
+                            validate_node(value, extra, root, &child_path, depth + 1)?
+                        }
+                        _ => {}
+                    },
+                }
+            }
+        }
+        Value::Array(items) => {
+            if let Some(min) = map.get("minItems").and_then(|v| v.as_u64()) {
+                if (items.len() as u64) < min {
+                    return Err(format!("{}: expected at least {} items", path, min));
+                }
+            }
+            if let Some(max) = map.get("maxItems").and_then(|v| v.as_u64()) {
+                if items.len() as u64 > max {
+                    return Err(format!("{}: expected at most {} items", path, max));
+                }
+            }
+            if let Some(item_schema) = map.get("items") {
+                for (i, item) in items.iter().enumerate() {
+                    let child_path = format!("{}[{}]", path, i);
+                    validate_node(item, item_schema, root, &child_path, depth + 1)?;
+                }
+            }
+        }
+        Value::String(s) => {
+            let len = s.chars().count() as u64;
+            if let Some(min) = map.get("minLength").and_then(|v| v.as_u64()) {
+                if len < min {
+                    return Err(format!("{}: expected at least {} characters", path, min));
+                }
+            }
+            if let Some(max) = map.get("maxLength").and_then(|v| v.as_u64()) {
+                if len > max {
+                    return Err(format!("{}: expected at most {} characters", path, max));
+                }
+            }
+        }
+        Value::Number(n) => {
+            let n = n.as_f64().unwrap_or(0.0);
+            if let Some(min) = map.get("minimum").and_then(|v| v.as_f64()) {
+                if n < min {
+                    return Err(format!("{}: expected a value >= {}", path, min));
+                }
+            }
+            if let Some(max) = map.get("maximum").and_then(|v| v.as_f64()) {
+                if n > max {
+                    return Err(format!("{}: expected a value <= {}", path, max));
+                }
+            }
+        }
+        _ => {}
+    }
+
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1665,4 +1862,47 @@ mod tests {
             .unwrap()
             .contains("Accepts: string | object"));
     }
+
+    #[test]
+    fn test_validate_json_schema() {
+        let schema = json!({
+            "type": "object",
+            "properties": {
+                "name": { "type": "string", "minLength": 1 },
+                "tags": { "type": "array", "items": { "$ref": "#/$defs/Tag" } },
+                "score": { "anyOf": [{ "type": "integer" }, { "type": "null" }] }
+            },
+            "required": ["name", "tags", "score"],
+            "additionalProperties": false,
+            "$defs": {
+                "Tag": { "type": "string", "enum": ["a", "b"] }
+            }
+        });
+
+        let valid = json!({ "name": "x", "tags": ["a", "b"], "score": null });
+        assert!(validate_json_schema(&valid, &schema).is_ok());
+
+        let bad_enum = json!({ "name": "x", "tags": ["c"], "score": 1 });
+        let err = validate_json_schema(&bad_enum, &schema).unwrap_err();
+        assert!(err.starts_with("$.tags[0]"), "{}", err);
+
+        let missing = json!({ "name": "x", "tags": [] });
+        assert!(validate_json_schema(&missing, &schema)
+            .unwrap_err()
+            .contains("missing required property 'score'"));
+
+        let extra = json!({ "name": "x", "tags": [], "score": 2, "other": true });
+        assert!(validate_json_schema(&extra, &schema)
+            .unwrap_err()
+            .contains("unexpected property 'other'"));
+
+        // 原始 Schema 不受 responseSchema 转换影响
+        let response_schema = build_response_schema(&schema);
+        assert!(response_schema.get("$defs").is_none());
+        assert_eq!(
+            response_schema["properties"]["tags"]["items"]["type"],
+            "string"
+        );
+        assert!(schema.get("$defs").is_some());
+    }
 }
@@ -2,10 +2,12 @@
 
 // pub mod error;
 // pub mod rate_limiter;
+pub mod background_task; // 后台任务识别与路由规则
 pub mod client_adapter;
 pub mod client_adapters;
 pub mod json_schema;
 pub mod model_mapping;
+pub mod output_validation; // 结构化输出 / 强制工具校验失败后的重试策略
 pub mod schema_cache;
 pub mod session;
 pub mod tool_adapter;
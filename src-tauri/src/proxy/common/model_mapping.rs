@@ -143,7 +143,7 @@ pub fn get_supported_models() -> Vec<String> {
 
 /// 动态获取所有可用模型列表 (包含内置与用户自定义与官方端点动态下发)
 pub async fn get_all_dynamic_models(
-    custom_mapping: &tokio::sync::RwLock<std::collections::HashMap<String, String>>,
+    custom_mapping: &std::collections::HashMap<String, String>,
     token_manager: Option<&crate::proxy::token_manager::TokenManager>,
     only_raw_quota_models: bool,
 ) -> Vec<String> {
@@ -160,11 +160,8 @@ pub async fn get_all_dynamic_models(
     // 如果未开启 only_raw_quota_models，则追加 custom_mapping、内置映射别名与硬编码画画/变体模型
     if !only_raw_quota_models {
         // 2. 获取所有自定义映射模型 (Custom)
-        {
-            let mapping = custom_mapping.read().await;
-            for key in mapping.keys() {
-                model_ids.insert(key.clone());
-            }
+        for key in custom_mapping.keys() {
+            model_ids.insert(key.clone());
         }
 
         // 3. 获取所有内置映射模型
@@ -210,7 +207,7 @@ pub async fn get_all_dynamic_models(
 /// - `claude-*-sonnet-*` matches `claude-3-5-sonnet-20241022` ✓
 /// - `*-thinking` matches `claude-opus-4-5-thinking` ✓
 /// - `a*b*c` matches `a123b456c` ✓
-fn wildcard_match(pattern: &str, text: &str) -> bool {
+pub(crate) fn wildcard_match(pattern: &str, text: &str) -> bool {
     let parts: Vec<&str> = pattern.split('*').collect();
 
     // No wildcard - exact match
@@ -314,6 +311,95 @@ pub fn resolve_model_route(
     result
 }
 
+/// 查找请求模型对应的降级链
+/// 优先级：精确匹配 > 通配符匹配 (非通配字符最多者胜出)
+pub fn find_fallback_chain<'a>(
+    original_model: &str,
+    chains: &'a HashMap<String, Vec<String>>,
+) -> Option<&'a [String]> {
+    if let Some(chain) = chains.get(original_model) {
+        return Some(chain.as_slice());
+    }
+
+    chains
+        .iter()
+        .filter(|(pattern, _)| pattern.contains('*') && wildcard_match(pattern, original_model))
+        .max_by_key(|(pattern, _)| pattern.chars().count() - pattern.matches('*').count())
+        .map(|(_, chain)| chain.as_slice())
+}
+
+/// 展开降级候选列表：[主路由目标, 备选 1, 备选 2, ...]
+/// 备选项同样经过 `resolve_model_route` 解析 (支持别名与自定义映射)，重复项会被去除
+pub fn fallback_candidates(
+    original_model: &str,
+    custom_mapping: &HashMap<String, String>,
+    chains: &HashMap<String, Vec<String>>,
+) -> Vec<String> {
+    let mut candidates = vec![resolve_model_route(original_model, custom_mapping)];
+    if let Some(chain) = find_fallback_chain(original_model, chains) {
+        for fallback in chain {
+            let resolved = resolve_model_route(fallback, custom_mapping);
+            if !candidates.contains(&resolved) {
+                candidates.push(resolved);
+            }
+        }
+    }
+    candidates
+}
+
+/// 带降级链的模型路由解析
+///
+/// 先按 `resolve_model_route` 得到主目标；若该请求模型配置了降级链，
+/// 依次通过 `TokenManager::has_available_account` 检查候选模型，返回第一个仍有可用账号的模型。
+/// 全部不可用时返回主目标，由后续的账号选择给出原始错误。
+#[tracing::instrument(name = "model.route", skip_all, fields(model = original_model))]
+pub async fn resolve_model_route_with_fallback(
+    original_model: &str,
+    custom_mapping: &HashMap<String, String>,
+    chains: &HashMap<String, Vec<String>>,
+    token_manager: &crate::proxy::TokenManager,
+) -> String {
+    if find_fallback_chain(original_model, chains).is_none() {
+        return resolve_model_route(original_model, custom_mapping);
+    }
+
+    let candidates = fallback_candidates(original_model, custom_mapping, chains);
+
+    for (idx, candidate) in candidates.iter().enumerate() {
+        // 与限流记录使用同一个 Key，否则锁定后的模型仍会被判定为可用
+        let limit_key = rate_limit_model_key(candidate);
+        let quota_group = if limit_key == "claude" {
+            "claude"
+        } else {
+            "gemini"
+        };
+        if token_manager
+            .has_available_account(quota_group, &limit_key)
+            .await
+        {
+            if idx > 0 {
+                crate::modules::logger::log_info(&format!(
+                    "[Router] 降级链生效: {} -> {} (主目标 {} 无可用账号)",
+                    original_model, candidate, candidates[0]
+                ));
+            }
+            return candidate.clone();
+        }
+    }
+
+    tracing::warn!(
+        "[Router] 降级链已耗尽: {} (候选: {:?})，回退到主目标",
+        original_model,
+        candidates
+    );
+    candidates[0].clone()
+}
+
+/// 模型级限流与配额保护使用的 Key：可归一化的模型使用标准 ID，其余保留原名
+pub fn rate_limit_model_key(model_name: &str) -> String {
+    normalize_to_standard_id(model_name).unwrap_or_else(|| model_name.to_string())
+}
+
 /// Normalize any physical model name to one of the 3 standard protection IDs.
 /// This ensures quota protection works consistently regardless of API versioning or request variations.
 ///
@@ -402,11 +488,10 @@ mod tests {
 
     #[tokio::test]
     async fn test_get_all_dynamic_models_only_raw_quota_models() {
-        let custom_mapping = tokio::sync::RwLock::new(
+        let custom_mapping: std::collections::HashMap<String, String> =
             [("gpt-4o".to_string(), "gemini-3.1-pro-high".to_string())]
                 .into_iter()
-                .collect(),
-        );
+                .collect();
 
         // When only_raw_quota_models is TRUE, custom_mapping & built-in aliases (like gpt-4o) should be filtered out
         let models_raw = get_all_dynamic_models(&custom_mapping, None, true).await;
@@ -553,4 +638,51 @@ mod tests {
         // Multi-wildcard: "a*b*c" (3)
         assert_eq!(resolve_model_route("a-test-b-foo-c", &custom), "multi-wild");
     }
+
+    #[test]
+    fn test_fallback_chain_lookup_and_candidates() {
+        let mut custom = HashMap::new();
+        custom.insert(
+            "opus-alias".to_string(),
+            "claude-opus-4-6-thinking".to_string(),
+        );
+
+        let mut chains = HashMap::new();
+        chains.insert(
+            "claude-opus-4-6-thinking".to_string(),
+            vec![
+                "claude-sonnet-4-6-thinking".to_string(),
+                "gemini-3-pro-high".to_string(),
+                "gemini-3-pro-high".to_string(),
+            ],
+        );
+        chains.insert("gemini-3-*".to_string(), vec!["generic".to_string()]);
+        chains.insert("gemini-3-pro-*".to_string(), vec!["specific".to_string()]);
+
+        // 精确匹配优先，通配符按具体程度选择
+        assert_eq!(
+            find_fallback_chain("claude-opus-4-6-thinking", &chains).map(|c| c.len()),
+            Some(3)
+        );
+        assert_eq!(
+            find_fallback_chain("gemini-3-pro-low", &chains),
+            Some(&["specific".to_string()][..])
+        );
+        assert!(find_fallback_chain("gpt-4o", &chains).is_none());
+
+        // 主目标在首位，重复的备选被去除
+        assert_eq!(
+            fallback_candidates("claude-opus-4-6-thinking", &custom, &chains),
+            vec![
+                "claude-opus-4-6-thinking".to_string(),
+                "claude-sonnet-4-6-thinking".to_string(),
+                resolve_model_route("gemini-3-pro-high", &custom),
+            ]
+        );
+        // 降级链按请求模型名匹配，而非映射后的名称
+        assert_eq!(
+            fallback_candidates("opus-alias", &custom, &chains),
+            vec!["claude-opus-4-6-thinking"]
+        );
+    }
 }
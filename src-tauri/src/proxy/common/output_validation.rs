@@ -0,0 +1,112 @@
+// 结构化输出校验的重试策略
+//
+// OpenAI strict json_schema 与 Claude tool_choice 强制工具的非流式输出在返回前按原始 Schema 校验 (流式输出原样透传)。
+// 校验失败时在同一账号上重试一次，仍不通过由 Handler 返回 502。两种协议的 Handler 共用此模块。
+
+/// get_token 返回的账号信息: (access_token, project_id, email, account_id, wait_ms)
+pub type AccountToken = (String, String, String, String, u64);
+
+/// 校验失败后的处理方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValidationDecision {
+    /// 已固定当前账号，继续下一次尝试
+    Retry,
+    /// 不再重试，返回 502
+    Fail,
+}
+
+/// 单个请求内的输出校验重试状态
+#[derive(Debug)]
+pub struct OutputValidation {
+    /// 日志中的输出类型，例如 "Structured output"
+    kind: &'static str,
+    pinned: Option<AccountToken>,
+    retried: bool,
+}
+
+impl OutputValidation {
+    pub fn new(kind: &'static str) -> Self {
+        Self {
+            kind,
+            pinned: None,
+            retried: false,
+        }
+    }
+
+    /// 取出上一次校验失败时固定的账号 (重试必须使用同一账号)
+    pub fn take_pinned(&mut self) -> Option<AccountToken> {
+        self.pinned.take()
+    }
+
+    /// 输出未通过校验：首次失败且还有剩余尝试次数时固定当前账号并返回 Retry，否则返回 Fail
+    pub fn on_invalid(
+        &mut self,
+        trace_id: &str,
+        error: &str,
+        attempt: usize,
+        max_attempts: usize,
+        account: (&str, &str, &str, &str),
+        last_error: &mut String,
+    ) -> ValidationDecision {
+        if self.retried || attempt + 1 >= max_attempts {
+            return ValidationDecision::Fail;
+        }
+        tracing::warn!(
+            "[{}] {} failed schema validation ({}), retrying on the same account",
+            trace_id,
+            self.kind,
+            error
+        );
+        *last_error = format!("{} validation failed: {}", self.kind, error);
+        self.retried = true;
+        let (access_token, project_id, email, account_id) = account;
+        self.pinned = Some((
+            access_token.to_string(),
+            project_id.to_string(),
+            email.to_string(),
+            account_id.to_string(),
+            0,
+        ));
+        ValidationDecision::Retry
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ACCOUNT: (&str, &str, &str, &str) = ("at", "proj", "a@example.com", "acc");
+
+    #[test]
+    fn test_retries_once_on_the_same_account() {
+        let mut check = OutputValidation::new("Structured output");
+        let mut last_error = String::new();
+        assert!(check.take_pinned().is_none());
+
+        assert_eq!(
+            check.on_invalid("t", "bad", 0, 3, ACCOUNT, &mut last_error),
+            ValidationDecision::Retry
+        );
+        assert!(last_error.contains("bad"));
+        let pinned = check.take_pinned().unwrap();
+        assert_eq!(pinned.2, "a@example.com");
+        assert!(check.take_pinned().is_none());
+
+        // 第二次失败不再重试
+        assert_eq!(
+            check.on_invalid("t", "bad", 1, 3, ACCOUNT, &mut last_error),
+            ValidationDecision::Fail
+        );
+    }
+
+    #[test]
+    fn test_fails_on_last_attempt() {
+        let mut check = OutputValidation::new("Forced tool output");
+        let mut last_error = String::new();
+        assert_eq!(
+            check.on_invalid("t", "bad", 2, 3, ACCOUNT, &mut last_error),
+            ValidationDecision::Fail
+        );
+        assert!(check.take_pinned().is_none());
+    }
+}
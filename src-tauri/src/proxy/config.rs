@@ -256,16 +256,17 @@ impl Default for ProxyAuthMode {
     }
 }
 
+/// Dispatch mode for extra (non-Google) upstreams: z.ai and custom providers.
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 #[serde(rename_all = "snake_case")]
 pub enum ZaiDispatchMode {
-    /// Never use z.ai.
+    /// Never use this upstream.
     Off,
-    /// Use z.ai for all Anthropic protocol requests.
+    /// Use this upstream for all matching protocol requests.
     Exclusive,
-    /// Treat z.ai as one additional slot in the shared pool.
+    /// Treat this upstream as one additional slot in the shared pool.
     Pooled,
-    /// Use z.ai only when the Google pool is unavailable.
+    /// Use this upstream only when the Google pool is unavailable.
     Fallback,
 }
 
@@ -275,6 +276,45 @@ impl Default for ZaiDispatchMode {
     }
 }
 
+/// 通用 Provider 的调度模式 (语义与 z.ai 一致)
+pub type ProviderDispatchMode = ZaiDispatchMode;
+
+/// 通用 Provider 的协议类型 (决定由哪个入口 handler 透传以及鉴权方式)
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ProviderProtocol {
+    /// OpenAI 兼容 (`/v1/chat/completions`，Bearer 鉴权)
+    #[default]
+    Openai,
+    /// Anthropic 兼容 (`/v1/messages`，x-api-key 鉴权)
+    Anthropic,
+    /// Gemini 兼容 (`/v1beta/models/{model}:{method}`，x-goog-api-key 鉴权)
+    Gemini,
+}
+
+/// 通用上游 Provider (vLLM / llama.cpp / 企业网关等)，与 Google 账号池一起参与调度
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UpstreamProviderConfig {
+    /// 唯一名称，同时作为显式路由前缀 (`<name>:<model>`)
+    pub name: String,
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    /// 上游根地址，请求路径直接拼接其后 (末尾的 `/v1` 会与请求路径去重)
+    pub base_url: String,
+    #[serde(default)]
+    pub api_key: String,
+    #[serde(default)]
+    pub protocol: ProviderProtocol,
+    #[serde(default)]
+    pub dispatch_mode: ProviderDispatchMode,
+    /// 模型映射: 入站模型名 -> 上游模型名
+    #[serde(default)]
+    pub model_mapping: HashMap<String, String>,
+    /// 可服务的入站模型 (支持 `*` 通配)，为空表示全部
+    #[serde(default)]
+    pub models: Vec<String>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ZaiModelDefaults {
     /// Default model for "opus" family (when the incoming model is a Claude id).
@@ -362,6 +402,11 @@ pub struct ExperimentalConfig {
     #[serde(default = "default_true")]
     pub enable_signature_cache: bool,
 
+    /// 将签名缓存持久化到 signature_cache.db (重启后恢复会话签名)
+    /// 默认关闭: 缓存中包含会话推理文本,属于敏感数据
+    #[serde(default = "default_false")]
+    pub enable_persistent_signature_cache: bool,
+
     /// 启用工具循环自动恢复 (Tool Loop Recovery)
     #[serde(default = "default_true")]
     pub enable_tool_loop_recovery: bool,
@@ -398,6 +443,7 @@ impl Default for ExperimentalConfig {
     fn default() -> Self {
         Self {
             enable_signature_cache: true,
+            enable_persistent_signature_cache: false,
             enable_tool_loop_recovery: true,
             enable_cross_model_checks: true,
             enable_usage_scaling: false,
@@ -485,6 +531,14 @@ pub struct DebugLoggingConfig {
     pub enabled: bool,
     #[serde(default)]
     pub output_dir: Option<String>,
+    /// 录制模式: 将每次 v1internal 上游交互 (请求体 + 原始响应/SSE) 保存为回放夹具
+    /// 输出到 `<output_dir>/upstream_fixtures/`，可直接交给 `MockUpstream` 回放
+    #[serde(default)]
+    pub record_fixtures: bool,
+    /// 覆盖 v1internal 上游地址 (如 `http://127.0.0.1:8787/v1internal`)
+    /// 用于指向本地 mock / 回放服务器；为空时使用 Sandbox → Daily → Prod 降级链
+    #[serde(default)]
+    pub upstream_base_url: Option<String>,
 }
 
 impl Default for DebugLoggingConfig {
@@ -492,10 +546,346 @@ impl Default for DebugLoggingConfig {
         Self {
             enabled: false,
             output_dir: None,
+            record_fixtures: false,
+            upstream_base_url: None,
+        }
+    }
+}
+
+/// OpenTelemetry 链路追踪配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TelemetryConfig {
+    /// 是否启用 OTLP 导出
+    #[serde(default)]
+    pub enabled: bool,
+    /// OTLP/HTTP traces 端点 (如本地 collector `http://127.0.0.1:4318/v1/traces`)
+    #[serde(default = "default_otlp_endpoint")]
+    pub otlp_endpoint: String,
+    /// 上报的 service.name
+    #[serde(default = "default_telemetry_service_name")]
+    pub service_name: String,
+    /// 采样率 (0.0 - 1.0)，对上游传入的 traceparent 遵循父级采样决定
+    #[serde(default = "default_telemetry_sample_ratio")]
+    pub sample_ratio: f64,
+}
+
+fn default_otlp_endpoint() -> String {
+    "http://127.0.0.1:4318/v1/traces".to_string()
+}
+
+fn default_telemetry_service_name() -> String {
+    "antigravity-tools".to_string()
+}
+
+fn default_telemetry_sample_ratio() -> f64 {
+    1.0
+}
+
+impl Default for TelemetryConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            otlp_endpoint: default_otlp_endpoint(),
+            service_name: default_telemetry_service_name(),
+            sample_ratio: default_telemetry_sample_ratio(),
+        }
+    }
+}
+
+/// 优雅停机 / 滚动升级配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ShutdownConfig {
+    /// 停止时等待进行中请求 (含流式响应 / WebSocket 会话) 完成的最长时间 (秒)
+    #[serde(default = "default_drain_timeout_secs")]
+    pub drain_timeout_secs: u64,
+    /// 排空期间 503 响应的 Retry-After (秒)
+    #[serde(default = "default_retry_after_secs")]
+    pub retry_after_secs: u64,
+    /// 以 SO_REUSEPORT 绑定监听端口，允许新进程在旧进程排空期间接管端口 (仅 Unix)
+    #[serde(default)]
+    pub reuse_port: bool,
+}
+
+fn default_drain_timeout_secs() -> u64 {
+    30
+}
+
+fn default_retry_after_secs() -> u64 {
+    5
+}
+
+impl Default for ShutdownConfig {
+    fn default() -> Self {
+        Self {
+            drain_timeout_secs: default_drain_timeout_secs(),
+            retry_after_secs: default_retry_after_secs(),
+            reuse_port: false,
+        }
+    }
+}
+
+/// OpenAI Batch API 执行配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchConfig {
+    /// 同时执行的批处理请求数上限
+    #[serde(default = "default_batch_max_concurrency")]
+    pub max_concurrency: usize,
+    /// 单行请求遇到 429 / 5xx 时的最大重试次数
+    #[serde(default = "default_batch_max_retries")]
+    pub max_retries: u32,
+}
+
+fn default_batch_max_concurrency() -> usize {
+    4
+}
+
+fn default_batch_max_retries() -> u32 {
+    5
+}
+
+impl Default for BatchConfig {
+    fn default() -> Self {
+        Self {
+            max_concurrency: default_batch_max_concurrency(),
+            max_retries: default_batch_max_retries(),
+        }
+    }
+}
+
+/// 后台任务识别与路由规则
+///
+/// 规则按顺序匹配，第一条命中的规则生效；dry_run 时只记录日志不改变请求
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackgroundTaskConfig {
+    #[serde(default = "default_background_task_enabled")]
+    pub enabled: bool,
+    /// 仅记录将会命中的规则，不执行路由或本地回复
+    #[serde(default)]
+    pub dry_run: bool,
+    #[serde(default = "default_background_task_rules")]
+    pub rules: Vec<BackgroundTaskRule>,
+}
+
+/// 单条后台任务规则，所有已设置的条件同时满足时命中
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct BackgroundTaskRule {
+    pub name: String,
+    #[serde(default = "default_background_task_enabled")]
+    pub enabled: bool,
+    /// 适用协议 ("claude" / "openai" / "gemini")，为空表示全部
+    #[serde(default)]
+    pub protocols: Vec<String>,
+    /// 匹配的客户端适配器名称 (如 "opencode")
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_adapter: Option<String>,
+    /// User-Agent 正则
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user_agent: Option<RulePattern>,
+    /// 最后一条用户消息的字符数下限
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_message_chars: Option<usize>,
+    /// 最后一条用户消息的字符数上限
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_message_chars: Option<usize>,
+    /// 最后一条用户消息正则
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_user_message: Option<RulePattern>,
+    /// 系统提示词正则
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub system_prompt: Option<RulePattern>,
+    /// 是否要求请求携带 (true) 或不携带 (false) 工具定义
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub has_tools: Option<bool>,
+    pub action: BackgroundTaskAction,
+}
+
+/// 后台任务规则中的正则条件
+///
+/// 反序列化时即编译，加载或保存配置时无效正则直接报错；编译结果随配置快照保存，
+/// 序列化时还原为原始字符串
+#[derive(Debug, Clone)]
+pub struct RulePattern(regex::Regex);
+
+impl RulePattern {
+    pub fn new(pattern: &str) -> Result<Self, String> {
+        regex::Regex::new(pattern)
+            .map(Self)
+            .map_err(|e| format!("invalid regex '{}': {}", pattern, e))
+    }
+
+    pub fn is_match(&self, text: &str) -> bool {
+        self.0.is_match(text)
+    }
+
+    pub fn as_str(&self) -> &str {
+        self.0.as_str()
+    }
+}
+
+impl PartialEq for RulePattern {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_str() == other.as_str()
+    }
+}
+
+impl Serialize for RulePattern {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for RulePattern {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let pattern = String::deserialize(deserializer)?;
+        Self::new(&pattern).map_err(serde::de::Error::custom)
+    }
+}
+
+/// 规则命中后的动作
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum BackgroundTaskAction {
+    /// 路由到指定模型 (仍经过自定义映射)，并移除工具与 Thinking 配置
+    Route { model: String },
+    /// 不请求上游，直接返回固定文本
+    Reply { text: String },
+}
+
+fn default_background_task_enabled() -> bool {
+    true
+}
+
+/// 默认规则: 沿用 Claude Code 后台任务 (标题/摘要/建议/系统消息/环境探测) 的关键词识别
+fn default_background_task_rules() -> Vec<BackgroundTaskRule> {
+    const GROUPS: &[(&str, &[&str])] = &[
+        (
+            "claude-code-system-message",
+            &["Warmup", "<system-reminder>", "This is a system message"],
+        ),
+        (
+            "claude-code-title",
+            &[
+                "write a 5-10 word title",
+                "Respond with the title",
+                "Generate a title for",
+                "Create a brief title",
+                "title for the conversation",
+                "conversation title",
+                "生成标题",
+                "为对话起个标题",
+            ],
+        ),
+        (
+            "claude-code-summary",
+            &[
+                "Summarize this coding conversation",
+                "Summarize the conversation",
+                "Concise summary",
+                "in under 50 characters",
+                "compress the context",
+                "Provide a concise summary",
+                "condense the previous messages",
+                "shorten the conversation history",
+                "extract key points from",
+            ],
+        ),
+        (
+            "claude-code-suggestion",
+            &[
+                "prompt suggestion generator",
+                "suggest next prompts",
+                "what should I ask next",
+                "generate follow-up questions",
+                "recommend next steps",
+                "possible next actions",
+            ],
+        ),
+        (
+            "claude-code-probe",
+            &[
+                "check current directory",
+                "list available tools",
+                "verify environment",
+                "test connection",
+            ],
+        ),
+    ];
+
+    GROUPS
+        .iter()
+        .map(|(name, keywords)| BackgroundTaskRule {
+            name: name.to_string(),
+            enabled: true,
+            protocols: vec!["claude".to_string()],
+            client_adapter: None,
+            user_agent: None,
+            min_message_chars: None,
+            max_message_chars: Some(800),
+            // 关键词均已转义，拼接结果必为合法正则
+            last_user_message: RulePattern::new(
+                &keywords
+                    .iter()
+                    .map(|kw| regex::escape(kw))
+                    .collect::<Vec<_>>()
+                    .join("|"),
+            )
+            .ok(),
+            system_prompt: None,
+            has_tools: None,
+            action: BackgroundTaskAction::Route {
+                model: "internal-background-task".to_string(),
+            },
+        })
+        .collect()
+}
+
+impl Default for BackgroundTaskConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            dry_run: false,
+            rules: default_background_task_rules(),
         }
     }
 }
 
+/// 成本分摊标价表 (单价均为每百万 Token)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PricingConfig {
+    /// 报表中显示的货币单位
+    #[serde(default = "default_pricing_currency")]
+    pub currency: String,
+    /// 模型名 (支持 `*` 通配符，精确匹配优先) -> 单价
+    #[serde(default)]
+    pub models: HashMap<String, ModelPrice>,
+}
+
+fn default_pricing_currency() -> String {
+    "USD".to_string()
+}
+
+impl Default for PricingConfig {
+    fn default() -> Self {
+        Self {
+            currency: default_pricing_currency(),
+            models: HashMap::new(),
+        }
+    }
+}
+
+/// 单个模型的标价
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ModelPrice {
+    pub input: f64,
+    pub output: f64,
+    /// 缓存命中的输入单价，未设置时按 input 计价
+    #[serde(default)]
+    pub cached_input: Option<f64>,
+    /// 推理 Token 单价，未设置时按 output 计价
+    #[serde(default)]
+    pub reasoning: Option<f64>,
+}
+
 /// IP 黑名单配置
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct IpBlacklistConfig {
@@ -575,6 +965,10 @@ pub struct ProxyConfig {
     #[serde(default)]
     pub allow_lan_access: bool,
 
+    /// 显式监听地址 (可选，设置后覆盖 allow_lan_access 推导的地址)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bind_address: Option<String>,
+
     /// Authorization policy for the proxy.
     /// - off: no auth required
     /// - strict: auth required for all routes
@@ -599,6 +993,11 @@ pub struct ProxyConfig {
     #[serde(default)]
     pub custom_mapping: std::collections::HashMap<String, String>,
 
+    /// 跨协议降级链 (key: 请求模型名或通配符, value: 按顺序尝试的备选模型)
+    /// 主映射目标没有可用账号时，依次选择第一个仍有可用账号的备选模型
+    #[serde(default)]
+    pub model_fallback_chains: std::collections::HashMap<String, Vec<String>>,
+
     /// API 请求超时时间(秒)
     #[serde(default = "default_request_timeout")]
     pub request_timeout: u64,
@@ -611,6 +1010,26 @@ pub struct ProxyConfig {
     #[serde(default)]
     pub debug_logging: DebugLoggingConfig,
 
+    /// OpenTelemetry 链路追踪导出配置
+    #[serde(default)]
+    pub telemetry: TelemetryConfig,
+
+    /// 优雅停机 / 滚动升级配置
+    #[serde(default)]
+    pub shutdown: ShutdownConfig,
+
+    /// 成本分摊标价表
+    #[serde(default)]
+    pub pricing: PricingConfig,
+
+    /// Batch API 执行配置
+    #[serde(default)]
+    pub batch: BatchConfig,
+
+    /// 后台任务识别与路由规则
+    #[serde(default)]
+    pub background_tasks: BackgroundTaskConfig,
+
     /// 上游代理配置
     #[serde(default)]
     pub upstream_proxy: UpstreamProxyConfig,
@@ -623,6 +1042,10 @@ pub struct ProxyConfig {
     #[serde(default)]
     pub zai: ZaiConfig,
 
+    /// 通用上游 Provider 列表 (OpenAI / Anthropic / Gemini 兼容端点)
+    #[serde(default)]
+    pub providers: Vec<UpstreamProviderConfig>,
+
     /// 自定义 User-Agent 请求头 (可选覆盖)
     #[serde(default)]
     pub user_agent_override: Option<String>,
@@ -684,18 +1107,26 @@ impl Default for ProxyConfig {
         Self {
             enabled: false,
             allow_lan_access: false, // 默认仅本机访问，隐私优先
+            bind_address: None,
             auth_mode: ProxyAuthMode::default(),
             port: 8045,
             api_key: format!("sk-{}", uuid::Uuid::new_v4().simple()),
             admin_password: None,
             auto_start: false,
             custom_mapping: std::collections::HashMap::new(),
+            model_fallback_chains: std::collections::HashMap::new(),
             request_timeout: default_request_timeout(),
             enable_logging: true, // 默认开启，支持 token 统计功能
             debug_logging: DebugLoggingConfig::default(),
+            telemetry: TelemetryConfig::default(),
+            shutdown: ShutdownConfig::default(),
+            pricing: PricingConfig::default(),
+            batch: BatchConfig::default(),
+            background_tasks: BackgroundTaskConfig::default(),
             upstream_proxy: UpstreamProxyConfig::default(),
             only_raw_quota_models: false,
             zai: ZaiConfig::default(),
+            providers: Vec::new(),
             scheduling: crate::proxy::sticky_config::StickySessionConfig::default(),
             experimental: ExperimentalConfig::default(),
             security_monitor: SecurityMonitorConfig::default(),
@@ -734,13 +1165,31 @@ impl ProxyConfig {
     /// 获取实际的监听地址
     /// - allow_lan_access = false: 返回 "127.0.0.1"（默认，隐私优先）
     /// - allow_lan_access = true: 返回 "0.0.0.0"（允许局域网访问）
+    /// - bind_address 已设置时优先使用
     pub fn get_bind_address(&self) -> &str {
-        if self.allow_lan_access {
+        if let Some(addr) = self.bind_address.as_deref().filter(|a| !a.is_empty()) {
+            addr
+        } else if self.allow_lan_access {
             "0.0.0.0"
         } else {
             "127.0.0.1"
         }
     }
+
+    /// 实际监听地址是否对本机以外开放 (非回环地址即视为暴露到局域网)
+    pub fn is_lan_exposed(&self) -> bool {
+        let addr = self.get_bind_address().trim();
+        let host = addr
+            .strip_prefix('[')
+            .and_then(|a| a.strip_suffix(']'))
+            .unwrap_or(addr);
+        if host.eq_ignore_ascii_case("localhost") {
+            return false;
+        }
+        host.parse::<std::net::IpAddr>()
+            .map(|ip| !ip.is_loopback())
+            .unwrap_or(true)
+    }
 }
 
 /// 代理认证信息
@@ -1,10 +1,34 @@
 use futures::StreamExt;
+use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use std::path::PathBuf;
 use tokio::fs;
 
 use crate::proxy::config::DebugLoggingConfig;
 
+/// 上游交互回放夹具 (录制模式输出，`MockUpstream` 回放输入)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UpstreamFixture {
+    /// v1internal 方法名 (如 `streamGenerateContent`)
+    pub method: String,
+    #[serde(default)]
+    pub query: Option<String>,
+    /// 请求体中的模型名，回放时用于匹配 (为空则匹配任意模型)
+    #[serde(default)]
+    pub model: Option<String>,
+    #[serde(default)]
+    pub request: Value,
+    pub status: u16,
+    #[serde(default = "default_fixture_content_type")]
+    pub content_type: String,
+    /// 原始响应体 (流式响应为完整的 SSE 文本)
+    pub body: String,
+}
+
+fn default_fixture_content_type() -> String {
+    "application/json".to_string()
+}
+
 fn build_filename(prefix: &str, trace_id: Option<&str>) -> String {
     let ts = chrono::Utc::now().format("%Y%m%d_%H%M%S%.3f");
     let tid = trace_id.unwrap_or("unknown");
@@ -25,6 +49,88 @@ fn resolve_exchange_output_dir(cfg: &DebugLoggingConfig) -> Option<PathBuf> {
     resolve_output_dir(cfg).map(|dir| dir.join("debug_exchanges"))
 }
 
+fn resolve_fixture_output_dir(cfg: &DebugLoggingConfig) -> Option<PathBuf> {
+    resolve_output_dir(cfg).map(|dir| dir.join("upstream_fixtures"))
+}
+
+async fn write_fixture(cfg: &DebugLoggingConfig, fixture: &UpstreamFixture) {
+    let output_dir = match resolve_fixture_output_dir(cfg) {
+        Some(dir) => dir,
+        None => {
+            tracing::warn!("[Debug-Fixture] Enabled but output_dir is not available.");
+            return;
+        }
+    };
+
+    if let Err(e) = fs::create_dir_all(&output_dir).await {
+        tracing::warn!("[Debug-Fixture] Failed to create output dir: {}", e);
+        return;
+    }
+
+    let filename = build_filename(&fixture.method, fixture.model.as_deref());
+    match serde_json::to_vec_pretty(fixture) {
+        Ok(bytes) => {
+            if let Err(e) = fs::write(output_dir.join(filename), bytes).await {
+                tracing::warn!("[Debug-Fixture] Failed to write file: {}", e);
+            }
+        }
+        Err(e) => {
+            tracing::warn!("[Debug-Fixture] Failed to serialize fixture: {}", e);
+        }
+    }
+}
+
+/// 录制上游交互: 透传响应字节流，流结束后将请求与完整响应写入夹具文件
+///
+/// 响应通过 `rquest::Response::from(http::Response)` 重建，状态码与响应头保持不变
+pub fn record_upstream_fixture(
+    cfg: DebugLoggingConfig,
+    method: &str,
+    query: Option<&str>,
+    request: &Value,
+    response: rquest::Response,
+) -> rquest::Response {
+    let status = response.status();
+    let mut headers = response.headers().clone();
+    // 响应体改为流式透传，原 Content-Length 交由 Body 重新计算
+    headers.remove(rquest::header::CONTENT_LENGTH);
+
+    let mut fixture = UpstreamFixture {
+        method: method.to_string(),
+        query: query.map(|q| q.to_string()),
+        model: request
+            .get("model")
+            .and_then(|m| m.as_str())
+            .map(|m| m.to_string()),
+        request: request.clone(),
+        status: status.as_u16(),
+        content_type: headers
+            .get(rquest::header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.to_string())
+            .unwrap_or_else(default_fixture_content_type),
+        body: String::new(),
+    };
+
+    let mut inner = response.bytes_stream();
+    let teed = async_stream::stream! {
+        let mut collected: Vec<u8> = Vec::new();
+        while let Some(item) = inner.next().await {
+            if let Ok(bytes) = &item {
+                collected.extend_from_slice(bytes);
+            }
+            yield item;
+        }
+        fixture.body = String::from_utf8_lossy(&collected).to_string();
+        write_fixture(&cfg, &fixture).await;
+    };
+
+    let mut rebuilt = axum::http::Response::new(rquest::Body::wrap_stream(teed));
+    *rebuilt.status_mut() = status;
+    *rebuilt.headers_mut() = headers;
+    rquest::Response::from(rebuilt)
+}
+
 pub async fn write_debug_payload(
     cfg: &DebugLoggingConfig,
     trace_id: Option<&str>,
@@ -0,0 +1,262 @@
+//! 优雅排空 (停止反代 / 进程退出 / 滚动升级)
+//!
+//! 停止后新请求由 `service_status_middleware` 返回 503 + Retry-After；进行中的请求
+//! (含 SSE 流与 Codex `/v1/responses` WebSocket 会话) 继续完成，超过截止时间仍未结束的流被截断。
+//! 进程退出时还会关闭监听器，配合 `shutdown.reuse_port` 可由新进程在排空期间接管端口。
+
+use axum::body::Body;
+use axum::response::Response;
+use futures::StreamExt;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::{watch, Notify};
+use tokio_util::sync::CancellationToken;
+
+use crate::proxy::server::AppState;
+
+/// 后台任务清理的超时时间
+const BACKGROUND_TASK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// 进行中请求的计数与排空状态
+pub struct DrainController {
+    draining: watch::Sender<bool>,
+    /// 每次恢复服务递增，避免上一轮排空的超时截断新一轮的请求
+    generation: AtomicU64,
+    in_flight: AtomicUsize,
+    idle: Notify,
+    cancel: Mutex<CancellationToken>,
+}
+
+impl Default for DrainController {
+    fn default() -> Self {
+        Self {
+            draining: watch::channel(false).0,
+            generation: AtomicU64::new(0),
+            in_flight: AtomicUsize::new(0),
+            idle: Notify::new(),
+            cancel: Mutex::new(CancellationToken::new()),
+        }
+    }
+}
+
+/// 进行中请求的计数守卫，释放时计数减一
+pub struct InFlightGuard {
+    controller: Arc<DrainController>,
+    cancel: CancellationToken,
+}
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        if self.controller.in_flight.fetch_sub(1, Ordering::SeqCst) == 1 {
+            self.controller.idle.notify_waiters();
+        }
+    }
+}
+
+impl DrainController {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_draining(&self) -> bool {
+        *self.draining.borrow()
+    }
+
+    /// 进行中的请求数
+    pub fn in_flight(&self) -> usize {
+        self.in_flight.load(Ordering::SeqCst)
+    }
+
+    /// 登记一个进行中的请求
+    pub fn track(self: &Arc<Self>) -> InFlightGuard {
+        self.in_flight.fetch_add(1, Ordering::SeqCst);
+        InFlightGuard {
+            controller: self.clone(),
+            cancel: self.cancel_token(),
+        }
+    }
+
+    fn cancel_token(&self) -> CancellationToken {
+        self.cancel
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    /// 进入排空状态，返回本轮排空的代次
+    pub fn begin(&self) -> u64 {
+        self.draining.send_replace(true);
+        self.generation.load(Ordering::SeqCst)
+    }
+
+    /// 恢复服务
+    pub fn resume(&self) {
+        self.generation.fetch_add(1, Ordering::SeqCst);
+        *self.cancel.lock().unwrap_or_else(|e| e.into_inner()) = CancellationToken::new();
+        self.draining.send_replace(false);
+    }
+
+    /// 等待进入排空状态 (用于长连接在当前轮次结束后主动断开)
+    pub async fn wait_draining(&self) {
+        let mut rx = self.draining.subscribe();
+        let _ = rx.wait_for(|draining| *draining).await;
+    }
+
+    /// 等待进行中的请求全部完成；超时后截断剩余的流。返回是否在截止时间前排空
+    pub async fn wait_idle(&self, timeout: Duration, generation: u64) -> bool {
+        let finished = tokio::time::timeout(timeout, async {
+            loop {
+                let notified = self.idle.notified();
+                if self.in_flight() == 0 {
+                    break;
+                }
+                notified.await;
+            }
+        })
+        .await
+        .is_ok();
+
+        if !finished && self.is_draining() && self.generation.load(Ordering::SeqCst) == generation {
+            tracing::warn!(
+                "[Drain] {} 个请求在 {:?} 内未完成，强制结束剩余流",
+                self.in_flight(),
+                timeout
+            );
+            self.cancel_token().cancel();
+        }
+        finished
+    }
+}
+
+/// 将计数守卫绑定到响应：流式响应在响应体结束 (或被截断) 后才释放
+pub fn attach(response: Response, guard: InFlightGuard) -> Response {
+    let is_stream = response
+        .headers()
+        .get(axum::http::header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|ct| ct.starts_with("text/event-stream"));
+    if !is_stream {
+        return response;
+    }
+
+    let cancelled = guard.cancel.clone().cancelled_owned();
+    response.map(|body| {
+        let stream = body
+            .into_data_stream()
+            .take_until(cancelled)
+            .map(move |chunk| {
+                let _ = &guard;
+                chunk
+            });
+        Body::from_stream(stream)
+    })
+}
+
+fn drain_timeout(state: &AppState) -> Duration {
+    Duration::from_secs(state.config.load().proxy.shutdown.drain_timeout_secs)
+}
+
+/// 切换反代运行状态；停止时在后台排空进行中的请求
+pub async fn set_running(state: &AppState, running: bool) {
+    *state.is_running.write().await = running;
+    if running {
+        state.drain.resume();
+        return;
+    }
+
+    let generation = state.drain.begin();
+    let in_flight = state.drain.in_flight();
+    if in_flight == 0 {
+        return;
+    }
+    let timeout = drain_timeout(state);
+    tracing::info!(
+        "[Drain] 反代已停止接收新请求，等待 {} 个进行中请求完成 (最长 {:?})",
+        in_flight,
+        timeout
+    );
+    let drain = state.drain.clone();
+    tokio::spawn(async move {
+        if drain.wait_idle(timeout, generation).await {
+            tracing::info!("[Drain] 进行中请求已全部完成");
+        }
+    });
+}
+
+/// 进程退出前的完整排空：停止接收请求与连接，等待进行中请求，清理后台任务
+pub async fn shutdown(state: &AppState) {
+    *state.is_running.write().await = false;
+    let generation = state.drain.begin();
+    // 关闭监听器后，使用 SO_REUSEPORT 的新进程接收全部新连接
+    state.listener.stop().await;
+
+    let timeout = drain_timeout(state);
+    tracing::info!(
+        "[Drain] 正在排空 {} 个进行中请求 (最长 {:?})",
+        state.drain.in_flight(),
+        timeout
+    );
+    if state.drain.wait_idle(timeout, generation).await {
+        tracing::info!("[Drain] 进行中请求已全部完成");
+    }
+
+    state
+        .token_manager
+        .graceful_shutdown(BACKGROUND_TASK_TIMEOUT)
+        .await;
+    let _ = tokio::task::spawn_blocking(crate::proxy::telemetry::force_flush).await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::response::IntoResponse;
+
+    fn sse_response() -> Response {
+        let stream = futures::stream::pending::<Result<bytes::Bytes, std::io::Error>>();
+        (
+            [(axum::http::header::CONTENT_TYPE, "text/event-stream")],
+            Body::from_stream(stream),
+        )
+            .into_response()
+    }
+
+    #[tokio::test]
+    async fn test_stream_holds_guard_until_body_finishes() {
+        let drain = Arc::new(DrainController::new());
+
+        let plain = attach("ok".into_response(), drain.track());
+        assert_eq!(drain.in_flight(), 0);
+        drop(plain);
+
+        let response = attach(sse_response(), drain.track());
+        assert_eq!(drain.in_flight(), 1);
+
+        let generation = drain.begin();
+        assert!(!drain.wait_idle(Duration::from_millis(50), generation).await);
+
+        // 超时后流被截断，守卫随之释放
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert!(body.is_empty());
+        assert_eq!(drain.in_flight(), 0);
+        assert!(drain.wait_idle(Duration::from_millis(50), generation).await);
+    }
+
+    #[tokio::test]
+    async fn test_resume_ignores_stale_drain_deadline() {
+        let drain = Arc::new(DrainController::new());
+        let generation = drain.begin();
+        drain.resume();
+
+        let response = attach(sse_response(), drain.track());
+        assert!(!drain.wait_idle(Duration::from_millis(20), generation).await);
+        // 新一轮的流不会被上一轮排空截断
+        assert!(!drain.cancel_token().is_cancelled());
+        assert_eq!(drain.in_flight(), 1);
+        drop(response);
+        assert_eq!(drain.in_flight(), 0);
+    }
+}
@@ -0,0 +1,398 @@
+// Batch Handler
+// OpenAI /v1/files 与 /v1/batches 兼容端点 (作业由 batch_runner 在本地执行)
+use axum::{
+    extract::{Extension, Json, Multipart, Path, Query, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use tracing::info;
+
+use crate::proxy::batch_store::{
+    parse_batch_input, FileDeletion, FileObject, COMPLETION_WINDOW, SUPPORTED_ENDPOINTS,
+};
+use crate::proxy::middleware::auth::UserTokenIdentity;
+use crate::proxy::server::AppState;
+
+/// Batch 输入文件的 purpose
+const BATCH_PURPOSE: &str = "batch";
+
+const DEFAULT_LIST_LIMIT: usize = 20;
+const MAX_LIST_LIMIT: usize = 100;
+
+fn error_response(status: StatusCode, error_type: &str, message: &str) -> Response {
+    (
+        status,
+        Json(json!({
+            "error": { "message": message, "type": error_type, "param": null, "code": null }
+        })),
+    )
+        .into_response()
+}
+
+fn invalid_request(message: &str) -> Response {
+    error_response(StatusCode::BAD_REQUEST, "invalid_request_error", message)
+}
+
+fn not_found(kind: &str, id: &str) -> Response {
+    error_response(
+        StatusCode::NOT_FOUND,
+        "invalid_request_error",
+        &format!("No such {}: '{}'", kind, id),
+    )
+}
+
+/// 文件与作业归属调用方的 user token (主 API Key 为 None)，其他调用方一律视为不存在
+type Caller = Option<Extension<UserTokenIdentity>>;
+
+fn owner_of(caller: Caller) -> Option<String> {
+    caller.map(|Extension(identity)| identity.token_id)
+}
+
+/// 在阻塞线程池中执行存储操作，失败时返回 500
+async fn run_blocking<T, F>(f: F) -> Result<T, Response>
+where
+    T: Send + 'static,
+    F: FnOnce() -> Result<T, String> + Send + 'static,
+{
+    match tokio::task::spawn_blocking(f).await {
+        Ok(Ok(value)) => Ok(value),
+        Ok(Err(e)) => Err(error_response(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "server_error",
+            &e,
+        )),
+        Err(e) => Err(error_response(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "server_error",
+            &e.to_string(),
+        )),
+    }
+}
+
+/// POST /v1/files (multipart: file + purpose)
+pub async fn handle_upload_file(
+    State(state): State<AppState>,
+    caller: Caller,
+    mut multipart: Multipart,
+) -> Response {
+    let mut content: Option<(String, Vec<u8>)> = None;
+    let mut purpose: Option<String> = None;
+
+    loop {
+        let field = match multipart.next_field().await {
+            Ok(Some(field)) => field,
+            Ok(None) => break,
+            Err(e) => return invalid_request(&format!("Invalid multipart body: {}", e)),
+        };
+        match field.name().unwrap_or("") {
+            "file" => {
+                let filename = field.file_name().unwrap_or("upload.jsonl").to_string();
+                match field.bytes().await {
+                    Ok(bytes) => content = Some((filename, bytes.to_vec())),
+                    Err(e) => return invalid_request(&format!("Failed to read file: {}", e)),
+                }
+            }
+            "purpose" => purpose = field.text().await.ok(),
+            _ => {}
+        }
+    }
+
+    let Some((filename, bytes)) = content else {
+        return invalid_request("Missing required parameter: 'file'");
+    };
+    match purpose.as_deref() {
+        Some(BATCH_PURPOSE) => {}
+        Some(other) => {
+            return invalid_request(&format!(
+                "Unsupported purpose '{}', only 'batch' is supported",
+                other
+            ))
+        }
+        None => return invalid_request("Missing required parameter: 'purpose'"),
+    }
+    if std::str::from_utf8(&bytes).is_err() {
+        return invalid_request("File must be UTF-8 encoded JSONL");
+    }
+
+    let file = FileObject::new(
+        filename,
+        BATCH_PURPOSE.to_string(),
+        bytes.len() as u64,
+        chrono::Utc::now().timestamp(),
+        owner_of(caller),
+    );
+    let runner = state.batches.clone();
+    let saved = file.clone();
+    if let Err(resp) = run_blocking(move || runner.save_file(&saved, &bytes)).await {
+        return resp;
+    }
+    info!("[Batch] 已上传文件 {} ({} bytes)", file.id, file.bytes);
+    Json(file).into_response()
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ListFilesQuery {
+    pub purpose: Option<String>,
+}
+
+/// GET /v1/files
+pub async fn handle_list_files(
+    State(state): State<AppState>,
+    caller: Caller,
+    Query(query): Query<ListFilesQuery>,
+) -> Response {
+    let runner = state.batches.clone();
+    let owner = owner_of(caller);
+    match run_blocking(move || {
+        runner
+            .store()
+            .list_files(owner.as_deref(), query.purpose.as_deref())
+    })
+    .await
+    {
+        Ok(files) => {
+            Json(json!({ "object": "list", "data": files, "has_more": false })).into_response()
+        }
+        Err(resp) => resp,
+    }
+}
+
+/// GET /v1/files/:file_id
+pub async fn handle_get_file(
+    State(state): State<AppState>,
+    caller: Caller,
+    Path(file_id): Path<String>,
+) -> Response {
+    let runner = state.batches.clone();
+    let id = file_id.clone();
+    let owner = owner_of(caller);
+    match run_blocking(move || runner.store().get_file(&id, owner.as_deref())).await {
+        Ok(Some(file)) => Json(file).into_response(),
+        Ok(None) => not_found("file", &file_id),
+        Err(resp) => resp,
+    }
+}
+
+/// GET /v1/files/:file_id/content
+pub async fn handle_get_file_content(
+    State(state): State<AppState>,
+    caller: Caller,
+    Path(file_id): Path<String>,
+) -> Response {
+    let runner = state.batches.clone();
+    let id = file_id.clone();
+    let owner = owner_of(caller);
+    let content = run_blocking(
+        move || match runner.store().get_file(&id, owner.as_deref())? {
+            Some(_) => runner.read_file(&id).map(Some),
+            None => Ok(None),
+        },
+    )
+    .await;
+    match content {
+        Ok(Some(bytes)) => {
+            ([(header::CONTENT_TYPE, "application/octet-stream")], bytes).into_response()
+        }
+        Ok(None) => not_found("file", &file_id),
+        Err(resp) => resp,
+    }
+}
+
+/// DELETE /v1/files/:file_id
+pub async fn handle_delete_file(
+    State(state): State<AppState>,
+    caller: Caller,
+    Path(file_id): Path<String>,
+) -> Response {
+    let runner = state.batches.clone();
+    let id = file_id.clone();
+    let owner = owner_of(caller);
+    match run_blocking(move || runner.delete_file(&id, owner.as_deref())).await {
+        Ok(FileDeletion::Deleted) => {
+            Json(json!({ "id": file_id, "object": "file", "deleted": true })).into_response()
+        }
+        Ok(FileDeletion::NotFound) => not_found("file", &file_id),
+        Ok(FileDeletion::InUse(batch_id)) => error_response(
+            StatusCode::CONFLICT,
+            "invalid_request_error",
+            &format!("File '{}' is still in use by batch '{}'", file_id, batch_id),
+        ),
+        Err(resp) => resp,
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateBatchRequest {
+    pub input_file_id: String,
+    pub endpoint: String,
+    #[serde(default)]
+    pub completion_window: Option<String>,
+    #[serde(default)]
+    pub metadata: Option<Value>,
+}
+
+/// POST /v1/batches
+pub async fn handle_create_batch(
+    State(state): State<AppState>,
+    caller: Caller,
+    Json(request): Json<CreateBatchRequest>,
+) -> Response {
+    if !SUPPORTED_ENDPOINTS.contains(&request.endpoint.as_str()) {
+        return invalid_request(&format!(
+            "Unsupported endpoint '{}', expected one of: {}",
+            request.endpoint,
+            SUPPORTED_ENDPOINTS.join(", ")
+        ));
+    }
+    if let Some(window) = request.completion_window.as_deref() {
+        if window != COMPLETION_WINDOW {
+            return invalid_request(&format!(
+                "Unsupported completion_window '{}', only '{}' is supported",
+                window, COMPLETION_WINDOW
+            ));
+        }
+    }
+    if request.metadata.as_ref().is_some_and(|m| !m.is_object()) {
+        return invalid_request("metadata must be an object");
+    }
+
+    let runner = state.batches.clone();
+    let owner = owner_of(caller);
+    let created = run_blocking(move || {
+        let file = match runner
+            .store()
+            .get_file(&request.input_file_id, owner.as_deref())?
+        {
+            Some(file) => file,
+            None => return Ok(Err(not_found("file", &request.input_file_id))),
+        };
+        if file.purpose != BATCH_PURPOSE {
+            return Ok(Err(invalid_request(&format!(
+                "File {} has purpose '{}', expected 'batch'",
+                file.id, file.purpose
+            ))));
+        }
+        let content = runner.read_file(&file.id)?;
+        let lines = match parse_batch_input(&String::from_utf8_lossy(&content), &request.endpoint) {
+            Ok(lines) => lines,
+            Err(e) => return Ok(Err(invalid_request(&e))),
+        };
+        let batch = runner.store().create_batch(
+            owner.as_deref(),
+            &request.endpoint,
+            &file.id,
+            request.metadata.as_ref(),
+            &lines,
+            chrono::Utc::now().timestamp(),
+        )?;
+        Ok(Ok(batch))
+    })
+    .await;
+
+    match created {
+        Ok(Ok(batch)) => {
+            info!(
+                "[Batch] 已创建作业 {} ({} 个请求, {})",
+                batch.id, batch.request_counts.total, batch.endpoint
+            );
+            state.batches.wake();
+            Json(batch).into_response()
+        }
+        Ok(Err(resp)) | Err(resp) => resp,
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ListBatchesQuery {
+    pub after: Option<String>,
+    pub limit: Option<usize>,
+}
+
+/// GET /v1/batches
+pub async fn handle_list_batches(
+    State(state): State<AppState>,
+    caller: Caller,
+    Query(query): Query<ListBatchesQuery>,
+) -> Response {
+    let limit = query
+        .limit
+        .unwrap_or(DEFAULT_LIST_LIMIT)
+        .clamp(1, MAX_LIST_LIMIT);
+    let runner = state.batches.clone();
+    let owner = owner_of(caller);
+    match run_blocking(move || {
+        runner
+            .store()
+            .list_batches(owner.as_deref(), query.after.as_deref(), limit)
+    })
+    .await
+    {
+        Ok((batches, has_more)) => Json(json!({
+            "object": "list",
+            "first_id": batches.first().map(|b| b.id.clone()),
+            "last_id": batches.last().map(|b| b.id.clone()),
+            "has_more": has_more,
+            "data": batches,
+        }))
+        .into_response(),
+        Err(resp) => resp,
+    }
+}
+
+/// GET /v1/batches/:batch_id
+pub async fn handle_get_batch(
+    State(state): State<AppState>,
+    caller: Caller,
+    Path(batch_id): Path<String>,
+) -> Response {
+    let runner = state.batches.clone();
+    let id = batch_id.clone();
+    let owner = owner_of(caller);
+    match run_blocking(move || runner.store().get_batch(&id, owner.as_deref())).await {
+        Ok(Some(batch)) => Json(batch).into_response(),
+        Ok(None) => not_found("batch", &batch_id),
+        Err(resp) => resp,
+    }
+}
+
+/// POST /v1/batches/:batch_id/cancel
+pub async fn handle_cancel_batch(
+    State(state): State<AppState>,
+    caller: Caller,
+    Path(batch_id): Path<String>,
+) -> Response {
+    let runner = state.batches.clone();
+    let id = batch_id.clone();
+    let owner = owner_of(caller);
+    let cancelled = run_blocking(move || {
+        let owner = owner.as_deref();
+        let Some(batch) = runner.store().get_batch(&id, owner)? else {
+            return Ok(None);
+        };
+        if batch.is_terminal() || batch.status == "cancelling" {
+            return Ok(Some(Err(batch.status)));
+        }
+        runner
+            .store()
+            .cancel_batch(&id, owner, chrono::Utc::now().timestamp())?;
+        Ok(runner.store().get_batch(&id, owner)?.map(Ok))
+    })
+    .await;
+
+    match cancelled {
+        Ok(Some(Ok(batch))) => {
+            info!("[Batch] 已取消作业 {}", batch.id);
+            state.batches.wake();
+            Json(batch).into_response()
+        }
+        Ok(Some(Err(status))) => error_response(
+            StatusCode::CONFLICT,
+            "invalid_request_error",
+            &format!("Cannot cancel a batch with status '{}'", status),
+        ),
+        Ok(None) => not_found("batch", &batch_id),
+        Err(resp) => resp,
+    }
+}
@@ -12,12 +12,15 @@ use serde_json::{json, Value};
 use tokio::time::Duration;
 use tracing::{debug, error, info};
 
-use crate::proxy::common::client_adapter::CLIENT_ADAPTERS; // [NEW] Import Adapter Registry
+use crate::proxy::common::background_task::{self, TaskSignals};
+use crate::proxy::common::client_adapter::{get_user_agent, CLIENT_ADAPTERS}; // [NEW] Import Adapter Registry
+use crate::proxy::common::output_validation::{OutputValidation, ValidationDecision};
+use crate::proxy::config::BackgroundTaskAction;
 use crate::proxy::debug_logger;
 use crate::proxy::mappers::claude::{
     clean_cache_control_from_messages, close_tool_loop_for_thinking, create_claude_sse_stream,
     filter_invalid_thinking_blocks_with_family, merge_consecutive_messages,
-    models::{Message, MessageContent},
+    models::{ClaudeResponse, ContentBlock, Message, MessageContent, SystemPrompt, Tool},
     transform_claude_request_in, transform_response, ClaudeRequest,
 };
 use crate::proxy::mappers::context_manager::ContextManager;
@@ -382,6 +385,42 @@ fn apply_variant(
     Some(spec)
 }
 
+/// 校验 tool_choice 强制工具的调用输入是否符合原始 input_schema
+fn validate_forced_tool_output(response: &ClaudeResponse, tool: &Tool) -> Result<(), String> {
+    let name = tool.name.as_deref().unwrap_or_default();
+    let schema = tool.input_schema.as_ref().ok_or("missing input_schema")?;
+    let input = response
+        .content
+        .iter()
+        .find_map(|block| match block {
+            ContentBlock::ToolUse {
+                name: called,
+                input,
+                ..
+            } if called == name => Some(input),
+            _ => None,
+        })
+        .ok_or_else(|| format!("forced tool '{}' was not called", name))?;
+    crate::proxy::common::json_schema::validate_json_schema(input, schema)
+        .map_err(|e| format!("tool '{}' input: {}", name, e))
+}
+
+/// 强制工具的输入重试后仍不匹配时的 502 响应
+fn forced_tool_error(email: &str, mapped_model: &str, error: &str) -> Response {
+    (
+        StatusCode::BAD_GATEWAY,
+        [("X-Account-Email", email), ("X-Mapped-Model", mapped_model)],
+        Json(json!({
+            "type": "error",
+            "error": {
+                "type": "api_error",
+                "message": format!("Forced tool output does not match its input_schema: {}", error)
+            }
+        })),
+    )
+        .into_response()
+}
+
 /// 处理 Claude messages 请求
 ///
 /// 处理 Chat 消息请求流程
@@ -406,7 +445,10 @@ pub async fn handle_messages(
             .map(char::from)
             .collect::<String>()
             .to_lowercase();
-    let debug_cfg = state.debug_logging.read().await.clone();
+    crate::proxy::telemetry::record("trace_id", trace_id.as_str());
+    // 整个请求使用同一份配置快照，避免热更新时读到新旧混合的配置
+    let app_config = state.config.load_full();
+    let debug_cfg = app_config.proxy.debug_logging.clone();
 
     // [NEW] Detect Client Adapter
     // 检查是否有匹配的客户端适配器（如 opencode）
@@ -422,7 +464,7 @@ pub async fn handle_messages(
     }
 
     // Decide whether this request should be handled by z.ai (Anthropic passthrough) or the existing Google flow.
-    let zai = state.zai.read().await.clone();
+    let zai = app_config.proxy.zai.clone();
     let zai_enabled =
         zai.enabled && !matches!(zai.dispatch_mode, crate::proxy::ZaiDispatchMode::Off);
     let google_accounts = state.token_manager.len();
@@ -561,11 +603,11 @@ pub async fn handle_messages(
 
     // [New] Recover from broken tool loops (where signatures were stripped)
     // This prevents "Assistant message must start with thinking" errors by closing the loop with synthetic messages
-    if state.experimental.read().await.enable_tool_loop_recovery {
+    if app_config.proxy.experimental.enable_tool_loop_recovery {
         close_tool_loop_for_thinking(&mut request.messages);
     }
 
-    let experimental_cfg = state.experimental.read().await;
+    let experimental_cfg = &app_config.proxy.experimental;
     let compression_level = if experimental_cfg.compression_level == "disabled" {
         if experimental_cfg.enable_usage_scaling {
             "high".to_string()
@@ -626,7 +668,74 @@ pub async fn handle_messages(
             "[{}] 🔥 拦截 Warmup 请求，返回模拟响应（节省配额）",
             trace_id
         );
-        return create_warmup_response(&request, request.stream);
+        return create_local_response(&request, "OK", request.stream, "X-Warmup-Intercepted");
+    }
+
+    // ===== 后台任务规则匹配 =====
+    // 规则表来自 ProxyConfig.background_tasks：命中 Reply 直接返回本地回复，命中 Route 在下方重定向模型
+    let background_rule = {
+        let last_user_message = extract_last_user_message_for_detection(&request);
+        let system_prompt = match &request.system {
+            Some(SystemPrompt::String(s)) => s.clone(),
+            Some(SystemPrompt::Array(blocks)) => blocks
+                .iter()
+                .map(|b| b.text.as_str())
+                .collect::<Vec<_>>()
+                .join("\n"),
+            None => String::new(),
+        };
+        let user_agent = get_user_agent(&headers).unwrap_or_default();
+        let signals = TaskSignals {
+            protocol: "claude",
+            client_adapter: client_adapter.as_ref().map(|a| a.name()),
+            user_agent: &user_agent,
+            last_user_message: last_user_message.as_deref(),
+            system_prompt: &system_prompt,
+            has_tools: request.tools.as_ref().is_some_and(|t| !t.is_empty()),
+        };
+        background_task::resolve(&app_config.proxy.background_tasks, &signals, &trace_id)
+    };
+    if let Some(rule) = &background_rule {
+        if let BackgroundTaskAction::Reply { text } = &rule.action {
+            info!(
+                "[{}][AUTO] 后台任务规则 '{}' 命中，返回本地回复",
+                trace_id, rule.name
+            );
+            return create_local_response(
+                &request,
+                text,
+                request.stream,
+                "X-Background-Task-Intercepted",
+            );
+        }
+    }
+
+    // 通用 Anthropic 兼容 Provider (命中时直接透传，不再走 z.ai / Google)
+    if let Some(provider) = crate::proxy::providers::custom::select_provider(
+        &state,
+        crate::proxy::config::ProviderProtocol::Anthropic,
+        &canonical_model,
+        &trace_id,
+    )
+    .await
+    {
+        let new_body = match serde_json::to_value(&request) {
+            Ok(v) => v,
+            Err(e) => {
+                tracing::error!("Failed to serialize request for provider: {}", e);
+                return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+            }
+        };
+        return crate::proxy::providers::custom::forward_json(
+            &state,
+            &provider,
+            "/v1/messages",
+            None,
+            &headers,
+            new_body,
+            &canonical_model,
+        )
+        .await;
     }
 
     if use_zai {
@@ -657,7 +766,7 @@ pub async fn handle_messages(
     // (后续代码不需要再次 filter_invalid_thinking_blocks)
 
     // [NEW] 获取上下文控制配置
-    let experimental = state.experimental.read().await;
+    let experimental = &app_config.proxy.experimental;
     let scaling_enabled = experimental.enable_usage_scaling;
     let threshold_l1 = experimental.context_compression_threshold_l1;
     let threshold_l2 = experimental.context_compression_threshold_l2;
@@ -800,13 +909,23 @@ pub async fn handle_messages(
     let mut last_mapped_model: Option<String> = None;
     let mut last_status = StatusCode::SERVICE_UNAVAILABLE; // Default to 503 if no response reached
     let mut force_rotate = false;
+    // [NEW] tool_choice 强制工具: 输入校验失败时在同一账号上重试一次
+    // 客户端要求流式时内容边生成边下发，无法在返回前校验，按原样透传不做校验
+    let forced_tool = request.forced_tool().filter(|_| !request.stream).cloned();
+    let mut output_check = OutputValidation::new("Forced tool output");
 
     for attempt in 0..max_attempts {
+        crate::proxy::telemetry::record_attempt(attempt);
         // 2. 模型路由解析
-        let mut mapped_model = crate::proxy::common::model_mapping::resolve_model_route(
-            &request_for_body.model,
-            &*state.custom_mapping.read().await,
-        );
+        // 每次尝试都重新评估降级链，上一次 429 锁定后可切换到链上的下一个模型
+        let mut mapped_model =
+            crate::proxy::common::model_mapping::resolve_model_route_with_fallback(
+                &request_for_body.model,
+                &app_config.proxy.custom_mapping,
+                &app_config.proxy.model_fallback_chains,
+                &token_manager,
+            )
+            .await;
         last_mapped_model = Some(mapped_model.clone());
 
         // 将 Claude 工具转为 Value 数组以便探测联网
@@ -830,17 +949,24 @@ pub async fn handle_messages(
         // 使用 SessionManager 生成稳定的会话指纹
         let session_id_str =
             crate::proxy::session_manager::SessionManager::extract_session_id(&request_for_body);
+        crate::proxy::telemetry::record("session_id", session_id_str.as_str());
         let session_id = Some(session_id_str.as_str());
 
-        let (access_token, project_id, email, account_id, _wait_ms) = match token_manager
-            .get_token(
-                &config.request_type,
-                force_rotate,
-                session_id,
-                &config.final_model,
-            )
-            .await
-        {
+        // 强制工具校验失败的重试固定使用上一次的账号
+        let token = match output_check.take_pinned() {
+            Some(pinned) => Ok(pinned),
+            None => {
+                token_manager
+                    .get_token(
+                        &config.request_type,
+                        force_rotate,
+                        session_id,
+                        &config.final_model,
+                    )
+                    .await
+            }
+        };
+        let (access_token, project_id, email, account_id, _wait_ms) = match token {
             Ok(t) => t,
             Err(e) => {
                 let safe_message = if e.contains("invalid_grant") {
@@ -867,27 +993,29 @@ pub async fn handle_messages(
         last_email = Some(email.clone());
         info!("✓ Using account: {} (type: {})", email, config.request_type);
 
-        // ===== 【优化】后台任务智能检测与降级 =====
-        // 使用新的检测系统，支持 5 大类关键词和多 Flash 模型策略
-        let background_task_type = detect_background_task_type(&request_for_body);
+        // ===== 【优化】后台任务路由与降级 =====
+        // 命中 Route 规则的后台任务 (标题/摘要/建议等) 重定向到规则指定的模型
+        let background_route = background_rule
+            .as_ref()
+            .and_then(|rule| match &rule.action {
+                BackgroundTaskAction::Route { model } => Some((rule.name.as_str(), model.as_str())),
+                BackgroundTaskAction::Reply { .. } => None,
+            });
 
         // 传递映射后的模型名
         let mut request_with_mapped = request_for_body.clone();
 
-        if let Some(task_type) = background_task_type {
-            // 检测到后台任务,强制降级到 Flash 模型
-            let virtual_model_id = select_background_model(task_type);
-
+        if let Some((rule_name, virtual_model_id)) = background_route {
             // [FIX] 必须根据虚拟 ID Re-resolve 路由，以支持用户自定义映射 (如 internal-task -> gemini-3)
             // 否则会直接使用 generic ID 导致下游无法识别或只能使用静态默认值
             let resolved_model = crate::proxy::common::model_mapping::resolve_model_route(
                 virtual_model_id,
-                &*state.custom_mapping.read().await,
+                &app_config.proxy.custom_mapping,
             );
 
             info!(
-                "[{}][AUTO] 检测到后台任务 (类型: {:?}), 路由重定向: {} -> {} (最终物理模型: {})",
-                trace_id, task_type, mapped_model, virtual_model_id, resolved_model
+                "[{}][AUTO] 后台任务规则 '{}' 命中, 路由重定向: {} -> {} (最终物理模型: {})",
+                trace_id, rule_name, mapped_model, virtual_model_id, resolved_model
             );
 
             // 覆盖用户自定义映射 (同时更新变量和 Request 对象)
@@ -950,6 +1078,7 @@ pub async fn handle_messages(
                         threshold_l1 * 100.0
                     );
                     compression_applied = true;
+                    crate::proxy::telemetry::record("compression.layer", 1);
 
                     // Re-estimate after trimming (with calibration)
                     let new_raw = ContextManager::estimate_token_usage(&request_with_mapped);
@@ -993,6 +1122,7 @@ pub async fn handle_messages(
                 ) {
                     is_purified = true; // Still breaks cache, but preserves signatures
                     compression_applied = true;
+                    crate::proxy::telemetry::record("compression.layer", 2);
 
                     let new_raw = ContextManager::estimate_token_usage(&request_with_mapped);
                     let new_usage = calibrator.calibrate(new_raw);
@@ -1039,6 +1169,7 @@ pub async fn handle_messages(
 
                         request_with_mapped = forked_request;
                         is_purified = false; // Fork doesn't break cache!
+                        crate::proxy::telemetry::record("compression.layer", 3);
 
                         // Re-estimate after fork (with calibration)
                         let new_raw = ContextManager::estimate_token_usage(&request_with_mapped);
@@ -1431,6 +1562,27 @@ pub async fn handle_messages(
                                         "[{}] ✓ Stream collected and converted to JSON",
                                         trace_id
                                     );
+                                    if let Some(Err(e)) = forced_tool.as_ref().map(|tool| {
+                                        validate_forced_tool_output(&full_response, tool)
+                                    }) {
+                                        match output_check.on_invalid(
+                                            &trace_id,
+                                            &e,
+                                            attempt,
+                                            max_attempts,
+                                            (&access_token, &project_id, &email, &account_id),
+                                            &mut last_error,
+                                        ) {
+                                            ValidationDecision::Retry => continue,
+                                            ValidationDecision::Fail => {
+                                                return forced_tool_error(
+                                                    &email,
+                                                    &request_with_mapped.model,
+                                                    &e,
+                                                )
+                                            }
+                                        }
+                                    }
                                     return Response::builder()
                                         .status(StatusCode::OK)
                                         .header(header::CONTENT_TYPE, "application/json")
@@ -1535,6 +1687,25 @@ pub async fn handle_messages(
                     }
                 };
 
+                if let Some(Err(e)) = forced_tool
+                    .as_ref()
+                    .map(|tool| validate_forced_tool_output(&claude_response, tool))
+                {
+                    match output_check.on_invalid(
+                        &trace_id,
+                        &e,
+                        attempt,
+                        max_attempts,
+                        (&access_token, &project_id, &email, &account_id),
+                        &mut last_error,
+                    ) {
+                        ValidationDecision::Retry => continue,
+                        ValidationDecision::Fail => {
+                            return forced_tool_error(&email, &request_with_mapped.model, &e)
+                        }
+                    }
+                }
+
                 // [Optimization] 记录闭环日志：消耗情况
                 let cache_info = if let Some(cached) = claude_response.usage.cache_read_input_tokens
                 {
@@ -1913,9 +2084,13 @@ pub async fn handle_messages(
 pub async fn handle_list_models(State(state): State<AppState>) -> impl IntoResponse {
     use crate::proxy::common::model_mapping::get_all_dynamic_models;
 
-    let only_raw = *state.only_raw_quota_models.read().await;
-    let model_ids =
-        get_all_dynamic_models(&state.custom_mapping, Some(&state.token_manager), only_raw).await;
+    let config = state.config.load_full();
+    let model_ids = get_all_dynamic_models(
+        &config.proxy.custom_mapping,
+        Some(&state.token_manager),
+        config.proxy.only_raw_quota_models,
+    )
+    .await;
 
     let data: Vec<_> = model_ids
         .into_iter()
@@ -1941,7 +2116,8 @@ pub async fn handle_count_tokens(
     headers: HeaderMap,
     Json(body): Json<Value>,
 ) -> Response {
-    let zai = state.zai.read().await.clone();
+    let app_config = state.config.load_full();
+    let zai = app_config.proxy.zai.clone();
     let zai_enabled =
         zai.enabled && !matches!(zai.dispatch_mode, crate::proxy::ZaiDispatchMode::Off);
 
@@ -1957,11 +2133,76 @@ pub async fn handle_count_tokens(
         .await;
     }
 
-    Json(json!({
-        "input_tokens": 0,
-        "output_tokens": 0
-    }))
-    .into_response()
+    let mut request: ClaudeRequest = match serde_json::from_value(body) {
+        Ok(r) => r,
+        Err(e) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(json!({
+                    "type": "error",
+                    "error": {
+                        "type": "invalid_request_error",
+                        "message": format!("Invalid request body: {}", e)
+                    }
+                })),
+            )
+                .into_response();
+        }
+    };
+
+    let mapped_model = crate::proxy::common::model_mapping::resolve_model_route(
+        &request.model,
+        &app_config.proxy.custom_mapping,
+    );
+    let original_model = std::mem::replace(&mut request.model, mapped_model.clone());
+
+    // 优先使用上游 countTokens 的真实计数；无可用账号或上游失败时回退到校准后的本地估算
+    match count_tokens_upstream(&state, &original_model, &mapped_model, &request).await {
+        Ok((input_tokens, email)) => (
+            StatusCode::OK,
+            [
+                ("X-Account-Email", email.as_str()),
+                ("X-Mapped-Model", mapped_model.as_str()),
+            ],
+            Json(json!({ "input_tokens": input_tokens })),
+        )
+            .into_response(),
+        Err(e) => {
+            let raw_estimated = ContextManager::estimate_token_usage(&request);
+            let input_tokens = get_calibrator().calibrate(raw_estimated);
+            debug!(
+                "[count_tokens] Upstream count unavailable ({}), using calibrated estimate: {} (raw: {})",
+                e, input_tokens, raw_estimated
+            );
+            (
+                StatusCode::OK,
+                [("X-Mapped-Model", mapped_model.as_str())],
+                Json(json!({ "input_tokens": input_tokens })),
+            )
+                .into_response()
+        }
+    }
+}
+
+/// 将 Claude 请求转换为 Gemini 格式后调用上游 countTokens
+async fn count_tokens_upstream(
+    state: &AppState,
+    original_model: &str,
+    mapped_model: &str,
+    request: &ClaudeRequest,
+) -> Result<(i64, String), String> {
+    // countTokens 不携带 project，也不需要按账号规格调整生成参数
+    let gemini_body = transform_claude_request_in(request, "", false, None, "", None)?;
+    let session_id = crate::proxy::session_manager::SessionManager::extract_session_id(request);
+    crate::proxy::handlers::gemini::count_tokens_upstream(
+        state,
+        original_model,
+        mapped_model,
+        &session_id,
+        crate::proxy::handlers::gemini::count_tokens_request(&gemini_body),
+    )
+    .await
+    .map_err(|(status, msg)| format!("{}: {}", status, msg))
 }
 
 #[cfg(test)]
@@ -2013,111 +2254,6 @@ mod tests {
 
 // ===== 后台任务检测辅助函数 =====
 
-/// 后台任务类型
-#[derive(Debug, Clone, Copy, PartialEq)]
-enum BackgroundTaskType {
-    TitleGeneration,    // 标题生成
-    SimpleSummary,      // 简单摘要
-    ContextCompression, // 上下文压缩
-    PromptSuggestion,   // 提示建议
-    SystemMessage,      // 系统消息
-    EnvironmentProbe,   // 环境探测
-}
-
-/// 标题生成关键词
-const TITLE_KEYWORDS: &[&str] = &[
-    "write a 5-10 word title",
-    "Please write a 5-10 word title",
-    "Respond with the title",
-    "Generate a title for",
-    "Create a brief title",
-    "title for the conversation",
-    "conversation title",
-    "生成标题",
-    "为对话起个标题",
-];
-
-/// 摘要生成关键词
-const SUMMARY_KEYWORDS: &[&str] = &[
-    "Summarize this coding conversation",
-    "Summarize the conversation",
-    "Concise summary",
-    "in under 50 characters",
-    "compress the context",
-    "Provide a concise summary",
-    "condense the previous messages",
-    "shorten the conversation history",
-    "extract key points from",
-];
-
-/// 建议生成关键词
-const SUGGESTION_KEYWORDS: &[&str] = &[
-    "prompt suggestion generator",
-    "suggest next prompts",
-    "what should I ask next",
-    "generate follow-up questions",
-    "recommend next steps",
-    "possible next actions",
-];
-
-/// 系统消息关键词
-const SYSTEM_KEYWORDS: &[&str] = &[
-    "Warmup",
-    "<system-reminder>",
-    // Removed: "Caveat: The messages below were generated" - this is a normal Claude Desktop system prompt
-    "This is a system message",
-];
-
-/// 环境探测关键词
-const PROBE_KEYWORDS: &[&str] = &[
-    "check current directory",
-    "list available tools",
-    "verify environment",
-    "test connection",
-];
-
-/// 检测后台任务并返回任务类型
-fn detect_background_task_type(request: &ClaudeRequest) -> Option<BackgroundTaskType> {
-    let last_user_msg = extract_last_user_message_for_detection(request)?;
-    let preview = last_user_msg.chars().take(500).collect::<String>();
-
-    // 长度过滤：后台任务通常不超过 800 字符
-    if last_user_msg.len() > 800 {
-        return None;
-    }
-
-    // 按优先级匹配
-    if matches_keywords(&preview, SYSTEM_KEYWORDS) {
-        return Some(BackgroundTaskType::SystemMessage);
-    }
-
-    if matches_keywords(&preview, TITLE_KEYWORDS) {
-        return Some(BackgroundTaskType::TitleGeneration);
-    }
-
-    if matches_keywords(&preview, SUMMARY_KEYWORDS) {
-        if preview.contains("in under 50 characters") {
-            return Some(BackgroundTaskType::SimpleSummary);
-        }
-        return Some(BackgroundTaskType::ContextCompression);
-    }
-
-    if matches_keywords(&preview, SUGGESTION_KEYWORDS) {
-        return Some(BackgroundTaskType::PromptSuggestion);
-    }
-
-    if matches_keywords(&preview, PROBE_KEYWORDS) {
-        return Some(BackgroundTaskType::EnvironmentProbe);
-    }
-
-    None
-}
-
-/// 辅助函数：关键词匹配
-fn matches_keywords(text: &str, keywords: &[&str]) -> bool {
-    keywords.iter().any(|kw| text.contains(kw))
-}
-
 /// 辅助函数：提取最后一条用户消息（用于检测）
 fn extract_last_user_message_for_detection(request: &ClaudeRequest) -> Option<String> {
     request
@@ -2151,18 +2287,6 @@ fn extract_last_user_message_for_detection(request: &ClaudeRequest) -> Option<St
         })
 }
 
-/// 根据后台任务类型选择合适的模型
-fn select_background_model(task_type: BackgroundTaskType) -> &'static str {
-    match task_type {
-        BackgroundTaskType::TitleGeneration => INTERNAL_BACKGROUND_TASK,
-        BackgroundTaskType::SimpleSummary => INTERNAL_BACKGROUND_TASK,
-        BackgroundTaskType::SystemMessage => INTERNAL_BACKGROUND_TASK,
-        BackgroundTaskType::PromptSuggestion => INTERNAL_BACKGROUND_TASK,
-        BackgroundTaskType::EnvironmentProbe => INTERNAL_BACKGROUND_TASK,
-        BackgroundTaskType::ContextCompression => INTERNAL_BACKGROUND_TASK,
-    }
-}
-
 // ===== [Issue #467 Fix] Warmup 请求拦截 =====
 
 /// 检测是否为 Warmup 请求
@@ -2222,10 +2346,15 @@ fn is_warmup_request(request: &ClaudeRequest) -> bool {
     false
 }
 
-/// 创建 Warmup 请求的模拟响应
+/// 创建本地模拟响应 (Warmup 拦截 / 后台任务规则的固定回复)
 ///
-/// 返回一个简单的响应，不消耗上游配额
-fn create_warmup_response(request: &ClaudeRequest, is_stream: bool) -> Response {
+/// 返回一个简单的响应，不消耗上游配额；`intercept_header` 标识拦截来源
+fn create_local_response(
+    request: &ClaudeRequest,
+    text: &str,
+    is_stream: bool,
+    intercept_header: &'static str,
+) -> Response {
     let model = &request.model;
     let message_id = format!("msg_warmup_{}", chrono::Utc::now().timestamp_millis());
 
@@ -2240,7 +2369,10 @@ fn create_warmup_response(request: &ClaudeRequest, is_stream: bool) -> Response
             // content_block_start
             "event: content_block_start\ndata: {\"type\":\"content_block_start\",\"index\":0,\"content_block\":{\"type\":\"text\",\"text\":\"\"}}\n\n".to_string(),
             // content_block_delta
-            "event: content_block_delta\ndata: {\"type\":\"content_block_delta\",\"index\":0,\"delta\":{\"type\":\"text_delta\",\"text\":\"OK\"}}\n\n".to_string(),
+            format!(
+                "event: content_block_delta\ndata: {}\n\n",
+                json!({"type": "content_block_delta", "index": 0, "delta": {"type": "text_delta", "text": text}})
+            ),
             // content_block_stop
             "event: content_block_stop\ndata: {\"type\":\"content_block_stop\",\"index\":0}\n\n".to_string(),
             // message_delta
@@ -2256,7 +2388,7 @@ fn create_warmup_response(request: &ClaudeRequest, is_stream: bool) -> Response
             .header(header::CONTENT_TYPE, "text/event-stream")
             .header(header::CACHE_CONTROL, "no-cache")
             .header(header::CONNECTION, "keep-alive")
-            .header("X-Warmup-Intercepted", "true")
+            .header(intercept_header, "true")
             .body(Body::from(body))
             .unwrap()
     } else {
@@ -2267,7 +2399,7 @@ fn create_warmup_response(request: &ClaudeRequest, is_stream: bool) -> Response
             "role": "assistant",
             "content": [{
                 "type": "text",
-                "text": "OK"
+                "text": text
             }],
             "model": model,
             "stop_reason": "end_turn",
@@ -2278,12 +2410,7 @@ fn create_warmup_response(request: &ClaudeRequest, is_stream: bool) -> Response
             }
         });
 
-        (
-            StatusCode::OK,
-            [("X-Warmup-Intercepted", "true")],
-            Json(response),
-        )
-            .into_response()
+        (StatusCode::OK, [(intercept_header, "true")], Json(response)).into_response()
     }
 }
 
@@ -2375,6 +2502,7 @@ async fn call_gemini_sync(
 /// 5. Returns the forked request
 ///
 /// Returns Ok(forked_request) on success, Err(error_message) on failure
+#[tracing::instrument(name = "context.compress.fork", skip_all)]
 async fn try_compress_with_summary(
     original_request: &ClaudeRequest,
     trace_id: &str,
@@ -2428,6 +2556,7 @@ async fn try_compress_with_summary(
         top_p: None,
         top_k: None,
         output_config: None,
+        tool_choice: None,
         size: None,
         quality: None,
     };
@@ -2506,6 +2635,7 @@ async fn try_compress_with_summary(
         top_p: original_request.top_p,
         top_k: original_request.top_k,
         output_config: original_request.output_config.clone(),
+        tool_choice: original_request.tool_choice.clone(),
         size: original_request.size.clone(),
         quality: original_request.quality.clone(),
     })
@@ -194,7 +194,7 @@ pub async fn handle_detect_model(
     // 1. Resolve mapping
     let mapped_model = crate::proxy::common::model_mapping::resolve_model_route(
         model_name,
-        &*state.custom_mapping.read().await,
+        &state.config.load().proxy.custom_mapping,
     );
 
     // 2. Resolve capabilities
@@ -0,0 +1,489 @@
+// Embeddings Handler
+// OpenAI /v1/embeddings 与 Gemini 原生 :embedContent / :batchEmbedContents
+use axum::{
+    extract::{Json, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use base64::Engine as _;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use tracing::{debug, info, warn};
+
+use crate::proxy::handlers::common::{
+    apply_retry_strategy, determine_retry_strategy, should_rotate_account,
+};
+use crate::proxy::mappers::context_manager::estimate_tokens_from_str;
+use crate::proxy::server::AppState;
+use crate::proxy::upstream::client::mask_email;
+
+const MAX_RETRY_ATTEMPTS: usize = 3;
+
+/// 默认 embedding 模型 (OpenAI 模型名统一映射到此)
+pub const DEFAULT_EMBEDDING_MODEL: &str = "gemini-embedding-001";
+
+/// batchEmbedContents 单次请求上限
+const MAX_BATCH_INPUTS: usize = 100;
+
+/// OpenAI embeddings 请求
+#[derive(Debug, Clone, Deserialize)]
+pub struct EmbeddingRequest {
+    pub model: String,
+    pub input: EmbeddingInput,
+    #[serde(default)]
+    pub dimensions: Option<u32>,
+    #[serde(default)]
+    pub encoding_format: Option<String>,
+}
+
+/// OpenAI 允许 string / string[] / token 数组，Gemini 只接受文本
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum EmbeddingInput {
+    Single(String),
+    Batch(Vec<String>),
+    Tokens(Vec<u32>),
+    TokenBatch(Vec<Vec<u32>>),
+}
+
+impl EmbeddingInput {
+    fn into_texts(self) -> Result<Vec<String>, String> {
+        match self {
+            EmbeddingInput::Single(s) => Ok(vec![s]),
+            EmbeddingInput::Batch(v) => Ok(v),
+            EmbeddingInput::Tokens(t) => Err(format!(
+                "Token array input ({} tokens) is not supported, please send text input",
+                t.len()
+            )),
+            EmbeddingInput::TokenBatch(b) => Err(format!(
+                "Token array input ({} items) is not supported, please send text input",
+                b.len()
+            )),
+        }
+    }
+}
+
+/// 解析 embedding 模型: 自定义映射精确命中优先，其次 Gemini 原生模型直通，
+/// OpenAI 模型名 (text-embedding-3-*, ada-002) 映射到默认模型
+pub fn resolve_embedding_model(
+    model: &str,
+    custom_mapping: &std::collections::HashMap<String, String>,
+) -> String {
+    if let Some(mapped) = custom_mapping.get(model) {
+        return mapped.clone();
+    }
+    let model = model.strip_prefix("models/").unwrap_or(model);
+    if model.starts_with("gemini-embedding")
+        || model.starts_with("text-embedding-0")
+        || model.starts_with("embedding-")
+        || model.starts_with("text-multilingual-embedding")
+    {
+        model.to_string()
+    } else {
+        DEFAULT_EMBEDDING_MODEL.to_string()
+    }
+}
+
+/// 构建 Gemini batchEmbedContents 请求体
+fn build_batch_request(model: &str, texts: &[String], dimensions: Option<u32>) -> Value {
+    let requests: Vec<Value> = texts
+        .iter()
+        .map(|text| {
+            let mut req = json!({
+                "model": format!("models/{}", model),
+                "content": { "parts": [{ "text": text }] },
+            });
+            if let Some(dim) = dimensions {
+                req["outputDimensionality"] = json!(dim);
+            }
+            req
+        })
+        .collect();
+    json!({ "requests": requests })
+}
+
+/// 将 float 向量编码为 OpenAI 格式的 base64 (小端 f32)
+fn encode_base64_embedding(values: &[f64]) -> String {
+    let mut bytes = Vec::with_capacity(values.len() * 4);
+    for v in values {
+        bytes.extend_from_slice(&(*v as f32).to_le_bytes());
+    }
+    base64::engine::general_purpose::STANDARD.encode(bytes)
+}
+
+/// 从 Gemini 响应中提取向量 (兼容 embedContent 与 batchEmbedContents)
+fn extract_embeddings(resp: &Value) -> Vec<Vec<f64>> {
+    let to_vec = |e: &Value| -> Vec<f64> {
+        e.get("values")
+            .and_then(|v| v.as_array())
+            .map(|arr| arr.iter().filter_map(|x| x.as_f64()).collect())
+            .unwrap_or_default()
+    };
+    if let Some(list) = resp.get("embeddings").and_then(|v| v.as_array()) {
+        return list.iter().map(to_vec).collect();
+    }
+    resp.get("embedding").map(to_vec).into_iter().collect()
+}
+
+/// 估算 Gemini 原生请求体中的文本 token 数
+fn estimate_request_tokens(method: &str, body: &Value) -> u32 {
+    let count_content = |content: Option<&Value>| -> u32 {
+        content
+            .and_then(|c| c.get("parts"))
+            .and_then(|p| p.as_array())
+            .map(|parts| {
+                parts
+                    .iter()
+                    .filter_map(|p| p.get("text").and_then(|t| t.as_str()))
+                    .map(estimate_tokens_from_str)
+                    .sum()
+            })
+            .unwrap_or(0)
+    };
+    if method == "batchEmbedContents" {
+        body.get("requests")
+            .and_then(|r| r.as_array())
+            .map(|reqs| reqs.iter().map(|r| count_content(r.get("content"))).sum())
+            .unwrap_or(0)
+    } else {
+        count_content(body.get("content"))
+    }
+}
+
+fn record_embedding_usage(email: &str, model: &str, input_tokens: u32) {
+    let email = email.to_string();
+    let model = model.to_string();
+    tokio::task::spawn_blocking(move || {
+        if let Err(e) =
+            crate::modules::token_stats::record_usage(&email, &model, input_tokens, 0, 0)
+        {
+            debug!("Failed to record embedding token stats: {}", e);
+        }
+    });
+}
+
+/// 带账号轮换的上游调用，返回 (响应 JSON, 账号邮箱)
+async fn call_with_rotation(
+    state: &AppState,
+    model: &str,
+    method: &str,
+    body: &Value,
+) -> Result<(Value, String), Response> {
+    let token_manager = state.token_manager.clone();
+    let max_attempts = MAX_RETRY_ATTEMPTS.min(token_manager.len()).max(1);
+    let trace_id = format!("embed_{}", chrono::Utc::now().timestamp_subsec_millis());
+
+    let mut last_error = String::new();
+    let mut last_status = StatusCode::TOO_MANY_REQUESTS;
+    let mut force_rotate = false;
+
+    for attempt in 0..max_attempts {
+        let (access_token, project_id, email, account_id, _wait_ms) = token_manager
+            .get_token("text", force_rotate, None, model)
+            .await
+            .map_err(|e| {
+                (
+                    StatusCode::SERVICE_UNAVAILABLE,
+                    format!("Token error: {}", e),
+                )
+                    .into_response()
+            })?;
+
+        info!(
+            "[{}] Embedding {} via {} (attempt {}/{})",
+            trace_id,
+            method,
+            mask_email(&email),
+            attempt + 1,
+            max_attempts
+        );
+
+        let response = match state
+            .upstream
+            .call_gemini_embedding(
+                model,
+                method,
+                &access_token,
+                &project_id,
+                body.clone(),
+                Some(account_id.as_str()),
+            )
+            .await
+        {
+            Ok(r) => r,
+            Err(e) => {
+                last_error = e;
+                last_status = StatusCode::BAD_GATEWAY;
+                force_rotate = true;
+                continue;
+            }
+        };
+
+        let status = response.status();
+        if status.is_success() {
+            let json: Value = response.json().await.map_err(|e| {
+                (StatusCode::BAD_GATEWAY, format!("Parse error: {}", e)).into_response()
+            })?;
+            return Ok((json, email));
+        }
+
+        let status_code = status.as_u16();
+        let retry_after = response
+            .headers()
+            .get("Retry-After")
+            .and_then(|h| h.to_str().ok())
+            .map(|s| s.to_string());
+        let error_text = response
+            .text()
+            .await
+            .unwrap_or_else(|_| format!("HTTP {}", status_code));
+        last_error = format!("HTTP {}: {}", status_code, error_text);
+        last_status = StatusCode::from_u16(status_code).unwrap_or(StatusCode::BAD_GATEWAY);
+
+        if status_code == 429 || status_code == 503 || status_code == 500 {
+            token_manager
+                .mark_rate_limited_async(
+                    &email,
+                    status_code,
+                    retry_after.as_deref(),
+                    &error_text,
+                    Some(model),
+                )
+                .await;
+        }
+
+        let strategy = determine_retry_strategy(status_code, &error_text, false);
+        if apply_retry_strategy(
+            strategy.clone(),
+            attempt,
+            max_attempts,
+            status_code,
+            &trace_id,
+        )
+        .await
+        {
+            force_rotate = should_rotate_account(status_code, Some(&strategy));
+            continue;
+        }
+
+        warn!(
+            "[{}] Embedding upstream non-retryable error {}: {}",
+            trace_id, status_code, error_text
+        );
+        break;
+    }
+
+    Err((
+        last_status,
+        Json(json!({
+            "error": {
+                "message": last_error,
+                "type": "upstream_error",
+                "code": last_status.as_u16()
+            }
+        })),
+    )
+        .into_response())
+}
+
+/// 处理 OpenAI /v1/embeddings
+pub async fn handle_embeddings(
+    State(state): State<AppState>,
+    Json(request): Json<EmbeddingRequest>,
+) -> Response {
+    let bad_request = |msg: String| {
+        (
+            StatusCode::BAD_REQUEST,
+            Json(json!({
+                "error": { "message": msg, "type": "invalid_request_error" }
+            })),
+        )
+            .into_response()
+    };
+
+    let use_base64 = match request.encoding_format.as_deref() {
+        None | Some("float") => false,
+        Some("base64") => true,
+        Some(other) => return bad_request(format!("Unsupported encoding_format: {}", other)),
+    };
+
+    let texts = match request.input.clone().into_texts() {
+        Ok(t) => t,
+        Err(e) => return bad_request(e),
+    };
+    if texts.is_empty() {
+        return bad_request("input must not be empty".to_string());
+    }
+    if texts.len() > MAX_BATCH_INPUTS {
+        return bad_request(format!(
+            "Too many inputs: {} (max {})",
+            texts.len(),
+            MAX_BATCH_INPUTS
+        ));
+    }
+
+    let mapped_model =
+        resolve_embedding_model(&request.model, &state.config.load().proxy.custom_mapping);
+    debug!(
+        "Embedding request: model={} -> {}, inputs={}",
+        request.model,
+        mapped_model,
+        texts.len()
+    );
+
+    let body = build_batch_request(&mapped_model, &texts, request.dimensions);
+    let (gemini_resp, email) =
+        match call_with_rotation(&state, &mapped_model, "batchEmbedContents", &body).await {
+            Ok(r) => r,
+            Err(resp) => return resp,
+        };
+
+    let data: Vec<Value> = extract_embeddings(&gemini_resp)
+        .into_iter()
+        .enumerate()
+        .map(|(index, values)| {
+            let embedding = if use_base64 {
+                json!(encode_base64_embedding(&values))
+            } else {
+                json!(values)
+            };
+            json!({ "object": "embedding", "index": index, "embedding": embedding })
+        })
+        .collect();
+
+    let prompt_tokens: u32 = texts.iter().map(|t| estimate_tokens_from_str(t)).sum();
+    record_embedding_usage(&email, &mapped_model, prompt_tokens);
+
+    (
+        StatusCode::OK,
+        [
+            ("X-Account-Email", email.as_str()),
+            ("X-Mapped-Model", mapped_model.as_str()),
+        ],
+        Json(json!({
+            "object": "list",
+            "data": data,
+            "model": request.model,
+            "usage": {
+                "prompt_tokens": prompt_tokens,
+                "total_tokens": prompt_tokens
+            }
+        })),
+    )
+        .into_response()
+}
+
+/// 处理 Gemini 原生 :embedContent / :batchEmbedContents (透传请求体)
+pub async fn execute_gemini_embed(
+    state: AppState,
+    model_name: String,
+    method: &str,
+    mut body: Value,
+) -> Result<Response, (StatusCode, String)> {
+    let mapped_model =
+        resolve_embedding_model(&model_name, &state.config.load().proxy.custom_mapping);
+
+    // batch 子请求中的 model 字段需与路径一致，否则上游返回 400
+    if method == "batchEmbedContents" {
+        if let Some(reqs) = body.get_mut("requests").and_then(|r| r.as_array_mut()) {
+            for req in reqs {
+                req["model"] = json!(format!("models/{}", mapped_model));
+            }
+        }
+    }
+
+    let (gemini_resp, email) = match call_with_rotation(&state, &mapped_model, method, &body).await
+    {
+        Ok(r) => r,
+        Err(resp) => return Ok(resp),
+    };
+
+    record_embedding_usage(
+        &email,
+        &mapped_model,
+        estimate_request_tokens(method, &body),
+    );
+
+    Ok((
+        StatusCode::OK,
+        [
+            ("X-Account-Email", email.as_str()),
+            ("X-Mapped-Model", mapped_model.as_str()),
+        ],
+        Json(gemini_resp),
+    )
+        .into_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_resolve_embedding_model() {
+        let mut mapping = HashMap::new();
+        assert_eq!(
+            resolve_embedding_model("text-embedding-3-small", &mapping),
+            DEFAULT_EMBEDDING_MODEL
+        );
+        assert_eq!(
+            resolve_embedding_model("models/text-embedding-004", &mapping),
+            "text-embedding-004"
+        );
+        mapping.insert(
+            "text-embedding-3-large".to_string(),
+            "gemini-embedding-exp".to_string(),
+        );
+        assert_eq!(
+            resolve_embedding_model("text-embedding-3-large", &mapping),
+            "gemini-embedding-exp"
+        );
+    }
+
+    #[test]
+    fn test_input_variants() {
+        let req: EmbeddingRequest =
+            serde_json::from_value(json!({"model": "m", "input": "hello"})).unwrap();
+        assert_eq!(req.input.into_texts().unwrap(), vec!["hello"]);
+
+        let req: EmbeddingRequest =
+            serde_json::from_value(json!({"model": "m", "input": ["a", "b"]})).unwrap();
+        assert_eq!(req.input.into_texts().unwrap().len(), 2);
+
+        let req: EmbeddingRequest =
+            serde_json::from_value(json!({"model": "m", "input": [1, 2, 3]})).unwrap();
+        assert!(req.input.into_texts().is_err());
+    }
+
+    #[test]
+    fn test_build_batch_request_with_dimensions() {
+        let body = build_batch_request(
+            "gemini-embedding-001",
+            &["a".to_string(), "b".to_string()],
+            Some(256),
+        );
+        let reqs = body["requests"].as_array().unwrap();
+        assert_eq!(reqs.len(), 2);
+        assert_eq!(reqs[0]["model"], "models/gemini-embedding-001");
+        assert_eq!(reqs[1]["content"]["parts"][0]["text"], "b");
+        assert_eq!(reqs[0]["outputDimensionality"], 256);
+    }
+
+    #[test]
+    fn test_extract_and_encode_embeddings() {
+        let batch = json!({"embeddings": [{"values": [1.0, -2.5]}, {"values": [0.5]}]});
+        let vectors = extract_embeddings(&batch);
+        assert_eq!(vectors, vec![vec![1.0, -2.5], vec![0.5]]);
+
+        let single = json!({"embedding": {"values": [0.25]}});
+        assert_eq!(extract_embeddings(&single), vec![vec![0.25]]);
+
+        let encoded = encode_base64_embedding(&[1.0, -2.5]);
+        let bytes = base64::engine::general_purpose::STANDARD
+            .decode(encoded)
+            .unwrap();
+        assert_eq!(&bytes[0..4], &1.0f32.to_le_bytes());
+        assert_eq!(&bytes[4..8], &(-2.5f32).to_le_bytes());
+    }
+}
@@ -8,7 +8,9 @@ use axum::{
 use serde_json::{json, Value};
 use tracing::{debug, error, info};
 
-use crate::proxy::common::client_adapter::CLIENT_ADAPTERS;
+use crate::proxy::common::background_task::{self, TaskSignals};
+use crate::proxy::common::client_adapter::{get_user_agent, CLIENT_ADAPTERS};
+use crate::proxy::config::BackgroundTaskAction;
 use crate::proxy::debug_logger;
 use crate::proxy::handlers::common::{
     apply_retry_strategy, determine_retry_strategy, should_rotate_account,
@@ -21,6 +23,70 @@ use axum::http::HeaderMap;
 
 const MAX_RETRY_ATTEMPTS: usize = 3;
 
+/// 合并 Gemini parts 中的文本
+fn gemini_parts_text(value: &Value) -> String {
+    value
+        .get("parts")
+        .and_then(|p| p.as_array())
+        .map(|parts| {
+            parts
+                .iter()
+                .filter_map(|p| p.get("text").and_then(|t| t.as_str()))
+                .collect::<Vec<_>>()
+                .join(" ")
+        })
+        .unwrap_or_default()
+}
+
+/// 提取后台任务规则所需的文本特征: (最后一条非空用户消息, 系统提示词)
+fn background_task_texts(body: &Value) -> (Option<String>, String) {
+    let last_user_message = body
+        .get("contents")
+        .and_then(|c| c.as_array())
+        .and_then(|contents| {
+            contents
+                .iter()
+                .rev()
+                .filter(|c| c.get("role").and_then(|r| r.as_str()).unwrap_or("user") == "user")
+                .map(gemini_parts_text)
+                .find(|text| !text.trim().is_empty())
+        });
+    let system_prompt = body
+        .get("systemInstruction")
+        .map(gemini_parts_text)
+        .unwrap_or_default();
+    (last_user_message, system_prompt)
+}
+
+/// 后台任务规则命中 Reply 时返回的本地 Gemini 响应 (不消耗上游配额)
+fn create_local_gemini_response(model: &str, text: &str, is_stream: bool) -> Response {
+    let response = json!({
+        "candidates": [{
+            "content": {"role": "model", "parts": [{"text": text}]},
+            "finishReason": "STOP",
+            "index": 0
+        }],
+        "usageMetadata": {"promptTokenCount": 0, "candidatesTokenCount": 0, "totalTokenCount": 0},
+        "modelVersion": model
+    });
+
+    if is_stream {
+        return Response::builder()
+            .header("Content-Type", "text/event-stream")
+            .header("Cache-Control", "no-cache")
+            .header("X-Background-Task-Intercepted", "true")
+            .body(axum::body::Body::from(format!("data: {}\n\n", response)))
+            .unwrap();
+    }
+
+    (
+        StatusCode::OK,
+        [("X-Background-Task-Intercepted", "true")],
+        Json(response),
+    )
+        .into_response()
+}
+
 /// 处理 generateContent 和 streamGenerateContent
 /// 路径参数: model_name, method (e.g. "gemini-pro", "generateContent")
 pub async fn handle_generate(
@@ -30,7 +96,7 @@ pub async fn handle_generate(
     Json(mut body): Json<Value>, // 改为 mut 以支持修复提示词注入
 ) -> Result<impl IntoResponse, (StatusCode, String)> {
     // 解析 model:method
-    let (model_name, method) = if let Some((m, action)) = model_action.rsplit_once(':') {
+    let (mut model_name, method) = if let Some((m, action)) = model_action.rsplit_once(':') {
         (m.to_string(), action.to_string())
     } else {
         (model_action, "generateContent".to_string())
@@ -41,7 +107,10 @@ pub async fn handle_generate(
         model_name, method
     ));
     let trace_id = format!("req_{}", chrono::Utc::now().timestamp_subsec_millis());
-    let debug_cfg = state.debug_logging.read().await.clone();
+    crate::proxy::telemetry::record("trace_id", trace_id.as_str());
+    // 整个请求使用同一份配置快照，避免热更新时读到新旧混合的配置
+    let app_config = state.config.load_full();
+    let debug_cfg = app_config.proxy.debug_logging.clone();
 
     // [NEW] Detect Client Adapter
     let client_adapter = CLIENT_ADAPTERS
@@ -58,12 +127,45 @@ pub async fn handle_generate(
         return execute_count_tokens(state, model_name, body).await;
     }
 
+    // :embedContent / :batchEmbedContents 走 embedding 专用处理器
+    if method == "embedContent" || method == "batchEmbedContents" {
+        return crate::proxy::handlers::embeddings::execute_gemini_embed(
+            state, model_name, &method, body,
+        )
+        .await;
+    }
+
     if method != "generateContent" && method != "streamGenerateContent" {
         return Err((
             StatusCode::BAD_REQUEST,
             format!("Unsupported method: {}", method),
         ));
     }
+
+    // 通用 Gemini 兼容 Provider，命中时直接透传
+    if let Some(provider) = crate::proxy::providers::custom::select_provider(
+        &state,
+        crate::proxy::config::ProviderProtocol::Gemini,
+        &model_name,
+        &trace_id,
+    )
+    .await
+    {
+        let upstream_model =
+            crate::proxy::providers::custom::map_model_for_provider(&provider, &model_name);
+        let path = format!("/v1beta/models/{}:{}", upstream_model, method);
+        let query = (method == "streamGenerateContent").then_some("alt=sse");
+        return Ok(crate::proxy::providers::custom::forward_json(
+            &state,
+            &provider,
+            &path,
+            query,
+            &headers,
+            body,
+            &model_name,
+        )
+        .await);
+    }
     if debug_logger::is_enabled(&debug_cfg) {
         let original_payload = json!({
             "kind": "original_request",
@@ -82,6 +184,59 @@ pub async fn handle_generate(
         .await;
     }
     let client_wants_stream = method == "streamGenerateContent";
+
+    // ===== 后台任务规则匹配 =====
+    // 规则表来自 ProxyConfig.background_tasks：Reply 直接返回本地回复，Route 改写目标模型
+    let background_rule = {
+        let (last_user_message, system_prompt) = background_task_texts(&body);
+        let user_agent = get_user_agent(&headers).unwrap_or_default();
+        let signals = TaskSignals {
+            protocol: "gemini",
+            client_adapter: client_adapter.as_ref().map(|a| a.name()),
+            user_agent: &user_agent,
+            last_user_message: last_user_message.as_deref(),
+            system_prompt: &system_prompt,
+            has_tools: body
+                .get("tools")
+                .and_then(|t| t.as_array())
+                .is_some_and(|t| !t.is_empty()),
+        };
+        background_task::resolve(&app_config.proxy.background_tasks, &signals, &trace_id)
+    };
+    if let Some(rule) = background_rule {
+        match rule.action {
+            BackgroundTaskAction::Reply { text } => {
+                info!(
+                    "[{}][AUTO] 后台任务规则 '{}' 命中，返回本地回复",
+                    trace_id, rule.name
+                );
+                return Ok(create_local_gemini_response(
+                    &model_name,
+                    &text,
+                    client_wants_stream,
+                ));
+            }
+            BackgroundTaskAction::Route { model } => {
+                info!(
+                    "[{}][AUTO] 后台任务规则 '{}' 命中, 路由重定向: {} -> {}",
+                    trace_id, rule.name, model_name, model
+                );
+                // 后台任务不需要工具与 Thinking
+                model_name = model;
+                if let Some(obj) = body.as_object_mut() {
+                    obj.remove("tools");
+                    obj.remove("toolConfig");
+                }
+                if let Some(gen_config) = body
+                    .get_mut("generationConfig")
+                    .and_then(|v| v.as_object_mut())
+                {
+                    gen_config.remove("thinkingConfig");
+                }
+            }
+        }
+    }
+
     // [AUTO-CONVERSION] 强制内部流式化
     let force_stream_internally = !client_wants_stream;
     let is_stream = client_wants_stream || force_stream_internally;
@@ -101,11 +256,15 @@ pub async fn handle_generate(
     let mut force_rotate = false;
 
     for attempt in 0..max_attempts {
+        crate::proxy::telemetry::record_attempt(attempt);
         // 3. 模型路由解析
-        let mapped_model = crate::proxy::common::model_mapping::resolve_model_route(
+        let mapped_model = crate::proxy::common::model_mapping::resolve_model_route_with_fallback(
             &model_name,
-            &*state.custom_mapping.read().await,
-        );
+            &app_config.proxy.custom_mapping,
+            &app_config.proxy.model_fallback_chains,
+            &token_manager,
+        )
+        .await;
         // 提取 tools 列表以进行联网探测 (Gemini 风格可能是嵌套的)
         let tools_val: Option<Vec<Value>> =
             body.get("tools").and_then(|t| t.as_array()).map(|arr| {
@@ -136,6 +295,7 @@ pub async fn handle_generate(
         // 4. 获取 Token (使用准确的 request_type)
         // 提取 SessionId (粘性指纹)
         let session_id = SessionManager::extract_gemini_session_id(&body, &model_name);
+        crate::proxy::telemetry::record("session_id", session_id.as_str());
 
         // 关键：根据 force_rotate 标志决定是否轮换账号（支持 Grace Retry 原地重试）
         let (access_token, project_id, email, account_id, _wait_ms) = match token_manager
@@ -722,9 +882,13 @@ pub async fn handle_list_models(
     use crate::proxy::common::model_mapping::get_all_dynamic_models;
 
     // 获取所有动态模型列表（与 /v1/models 一致）
-    let only_raw = *state.only_raw_quota_models.read().await;
-    let model_ids =
-        get_all_dynamic_models(&state.custom_mapping, Some(&state.token_manager), only_raw).await;
+    let config = state.config.load_full();
+    let model_ids = get_all_dynamic_models(
+        &config.proxy.custom_mapping,
+        Some(&state.token_manager),
+        config.proxy.only_raw_quota_models,
+    )
+    .await;
 
     // 转换为 Gemini API 格式
     let models: Vec<_> = model_ids
@@ -780,28 +944,52 @@ pub async fn execute_count_tokens(
     // 1. 模型路由解析
     let mapped_model = crate::proxy::common::model_mapping::resolve_model_route(
         &model_name,
-        &*state.custom_mapping.read().await,
+        &state.config.load().proxy.custom_mapping,
     );
+    let session_id = SessionManager::extract_gemini_session_id(&body, &model_name);
+
+    let (total_tokens, email) =
+        count_tokens_upstream(&state, &model_name, &mapped_model, &session_id, body).await?;
 
-    // 2. 解析请求配置并获取 Token
+    // 返回标准 Gemini REST 响应
+    Ok((
+        StatusCode::OK,
+        [
+            ("X-Account-Email", email.as_str()),
+            ("X-Mapped-Model", mapped_model.as_str()),
+        ],
+        Json(json!({ "totalTokens": total_tokens })),
+    )
+        .into_response())
+}
+
+/// 调用上游 v1internal:countTokens，返回 (totalTokens, 账号邮箱)
+///
+/// `request` 为标准 Gemini 请求体 (contents 等)，供 Claude / OpenAI 协议的计数接口共用
+pub(crate) async fn count_tokens_upstream(
+    state: &AppState,
+    model_name: &str,
+    mapped_model: &str,
+    session_id: &str,
+    request: Value,
+) -> Result<(i64, String), (StatusCode, String)> {
+    // 1. 解析请求配置并获取 Token
     let config = crate::proxy::mappers::common_utils::resolve_request_config(
-        &model_name,
-        &mapped_model,
+        model_name,
+        mapped_model,
         &None,
         None,
         None,
         None,
-        Some(&body),
+        Some(&request),
     );
 
-    let session_id = SessionManager::extract_gemini_session_id(&body, &model_name);
-
     let (access_token, _project_id, email, account_id, _wait_ms) = state
         .token_manager
         .get_token(
             &config.request_type,
             false,
-            Some(&session_id),
+            Some(session_id),
             &config.final_model,
         )
         .await
@@ -812,11 +1000,11 @@ pub async fn execute_count_tokens(
             )
         })?;
 
-    // 3. 包装为 v1internal 格式
+    // 2. 包装为 v1internal 格式
     // [已验证] countTokens 与 generateContent 不同: 顶层只允许 "request" 键,
     // 携带 model/project 会被上游 400 拒绝 (Unknown name "model"/"project");
     // request 内的 safetySettings 同样不被接受 (对齐 CLIProxyAPI 的处理)
-    let mut inner_body = body;
+    let mut inner_body = request;
     if let Some(obj) = inner_body.as_object_mut() {
         obj.remove("safetySettings");
     }
@@ -824,7 +1012,7 @@ pub async fn execute_count_tokens(
         "request": inner_body,
     });
 
-    // 4. 调用上游 v1internal:countTokens
+    // 3. 调用上游 v1internal:countTokens
     let call_result = state
         .upstream
         .call_v1_internal_with_headers(
@@ -856,7 +1044,7 @@ pub async fn execute_count_tokens(
         .await
         .map_err(|e| (StatusCode::BAD_GATEWAY, format!("Parse error: {}", e)))?;
 
-    // 5. 提取 totalTokens (兼容 wrapped / unwrapped 两种响应格式)
+    // 4. 提取 totalTokens (兼容 wrapped / unwrapped 两种响应格式)
     let total_tokens = gemini_resp
         .get("response")
         .and_then(|r| r.get("totalTokens"))
@@ -864,14 +1052,40 @@ pub async fn execute_count_tokens(
         .and_then(|v| v.as_i64())
         .unwrap_or(0);
 
-    // 6. 返回标准 Gemini REST 响应
-    Ok((
-        StatusCode::OK,
-        [
-            ("X-Account-Email", email.as_str()),
-            ("X-Mapped-Model", mapped_model.as_str()),
-        ],
-        Json(json!({ "totalTokens": total_tokens })),
-    )
-        .into_response())
+    Ok((total_tokens, email))
+}
+
+/// 将转换后的 v1internal 生成请求裁剪为 countTokens 请求体
+///
+/// countTokens 只统计 contents：systemInstruction 与工具声明以文本形式并入 contents，
+/// 使计数覆盖它们 (与 Anthropic count_tokens / Responses input_tokens 的口径一致)
+pub(crate) fn count_tokens_request(transformed: &Value) -> Value {
+    let inner = transformed.get("request").unwrap_or(transformed);
+    let mut contents = Vec::new();
+
+    let system_text = inner
+        .get("systemInstruction")
+        .and_then(|s| s.get("parts"))
+        .and_then(|p| p.as_array())
+        .map(|parts| {
+            parts
+                .iter()
+                .filter_map(|p| p.get("text").and_then(|t| t.as_str()))
+                .collect::<Vec<_>>()
+                .join("\n")
+        })
+        .unwrap_or_default();
+    if !system_text.is_empty() {
+        contents.push(json!({ "role": "user", "parts": [{ "text": system_text }] }));
+    }
+
+    if let Some(items) = inner.get("contents").and_then(|c| c.as_array()) {
+        contents.extend(items.iter().cloned());
+    }
+
+    if let Some(tools) = inner.get("tools").filter(|t| !t.is_null()) {
+        contents.push(json!({ "role": "user", "parts": [{ "text": tools.to_string() }] }));
+    }
+
+    json!({ "contents": contents })
 }
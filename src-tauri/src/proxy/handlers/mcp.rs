@@ -51,7 +51,8 @@ async fn forward_mcp(
     upstream_url: &str,
     body: Body,
 ) -> Response {
-    let zai = state.zai.read().await.clone();
+    let config = state.config.load_full();
+    let zai = config.proxy.zai.clone();
     if !zai.enabled || zai.api_key.trim().is_empty() {
         return (StatusCode::BAD_REQUEST, "z.ai is not configured").into_response();
     }
@@ -60,8 +61,8 @@ async fn forward_mcp(
         return StatusCode::NOT_FOUND.into_response();
     }
 
-    let upstream_proxy = state.upstream_proxy.read().await.clone();
-    let client = match build_client(upstream_proxy, state.request_timeout) {
+    let upstream_proxy = config.proxy.upstream_proxy.clone();
+    let client = match build_client(upstream_proxy, config.proxy.request_timeout) {
         Ok(c) => c,
         Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, e).into_response(),
     };
@@ -124,7 +125,7 @@ pub async fn handle_web_search_prime(
     method: Method,
     body: Body,
 ) -> Response {
-    let zai = state.zai.read().await.clone();
+    let zai = state.config.load().proxy.zai.clone();
     if !zai.mcp.web_search_enabled {
         return StatusCode::NOT_FOUND.into_response();
     }
@@ -146,7 +147,7 @@ pub async fn handle_web_reader(
     method: Method,
     body: Body,
 ) -> Response {
-    let zai = state.zai.read().await.clone();
+    let zai = state.config.load().proxy.zai.clone();
     if !zai.mcp.web_reader_enabled {
         return StatusCode::NOT_FOUND.into_response();
     }
@@ -354,9 +355,10 @@ async fn handle_vision_post(state: AppState, headers: HeaderMap, body: Body) ->
                 .cloned()
                 .unwrap_or(Value::Object(Default::default()));
 
-            let zai = state.zai.read().await.clone();
-            let upstream_proxy = state.upstream_proxy.read().await.clone();
-            let timeout = state.request_timeout;
+            let config = state.config.load_full();
+            let zai = config.proxy.zai.clone();
+            let upstream_proxy = config.proxy.upstream_proxy.clone();
+            let timeout = config.proxy.request_timeout;
 
             match crate::proxy::zai_vision_tools::call_tool(
                 &zai,
@@ -401,7 +403,7 @@ pub async fn handle_zai_mcp_server(
     method: Method,
     body: Body,
 ) -> Response {
-    let zai = state.zai.read().await.clone();
+    let zai = state.config.load().proxy.zai.clone();
     if !zai.enabled || zai.api_key.trim().is_empty() {
         return (StatusCode::BAD_REQUEST, "z.ai is not configured").into_response();
     }
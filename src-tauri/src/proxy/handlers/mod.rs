@@ -2,8 +2,10 @@
 // 核心端点处理器模块
 
 pub mod audio; // 音频转录处理器
+pub mod batch; // OpenAI Files / Batches API
 pub mod claude;
 pub mod common;
+pub mod embeddings; // Embeddings 处理器
 pub mod gemini;
 pub mod mcp;
 pub mod openai;
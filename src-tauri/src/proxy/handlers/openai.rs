@@ -8,8 +8,8 @@ use serde_json::{json, Value};
 use tracing::{debug, error, info}; // Import Engine trait for encode method
 
 use crate::proxy::mappers::openai::{
-    transform_openai_request, transform_openai_response, OpenAIContent, OpenAIContentBlock,
-    OpenAIMessage, OpenAIRequest, OpenAIResponse,
+    chat_logprobs_to_legacy, transform_openai_request, transform_openai_response, OpenAIContent,
+    OpenAIContentBlock, OpenAIMessage, OpenAIRequest, OpenAIResponse,
 };
 // use crate::proxy::upstream::client::UpstreamClient; // 通过 state 获取
 use crate::proxy::debug_logger;
@@ -22,7 +22,10 @@ use super::common::{
     apply_retry_strategy, determine_retry_strategy, should_rotate_account, RetryStrategy,
 };
 use crate::modules::account;
-use crate::proxy::common::client_adapter::CLIENT_ADAPTERS; // [NEW] Adapter Registry
+use crate::proxy::common::background_task::{self, TaskSignals};
+use crate::proxy::common::client_adapter::{get_user_agent, CLIENT_ADAPTERS}; // [NEW] Adapter Registry
+use crate::proxy::common::output_validation::{OutputValidation, ValidationDecision};
+use crate::proxy::config::BackgroundTaskAction;
 use crate::proxy::session_manager::SessionManager;
 use axum::http::HeaderMap;
 use std::collections::VecDeque;
@@ -162,20 +165,22 @@ fn openai_content_text(content: &OpenAIContent) -> String {
     }
 }
 
+fn empty_responses_usage() -> Value {
+    json!({
+        "input_tokens": 0,
+        "input_tokens_details": { "cached_tokens": 0 },
+        "output_tokens": 0,
+        "output_tokens_details": { "reasoning_tokens": 0 },
+        "total_tokens": 0
+    })
+}
+
 fn responses_usage_value(chat_response: &OpenAIResponse) -> Value {
     chat_response
         .usage
         .as_ref()
         .map(|usage| usage.to_responses_usage_value())
-        .unwrap_or_else(|| {
-            json!({
-                "input_tokens": 0,
-                "input_tokens_details": { "cached_tokens": 0 },
-                "output_tokens": 0,
-                "output_tokens_details": { "reasoning_tokens": 0 },
-                "total_tokens": 0
-            })
-        })
+        .unwrap_or_else(empty_responses_usage)
 }
 
 fn convert_chat_response_to_responses(chat_response: &OpenAIResponse) -> Value {
@@ -687,6 +692,381 @@ fn prefix_with_step_marker(_marker: Option<String>, content: String) -> String {
     strip_codex_step_markers(&content)
 }
 
+/// 校验 logprobs / top_logprobs 参数，上游不支持时返回明确错误而不是静默丢弃
+fn validate_logprobs(openai_req: &OpenAIRequest, mapped_model: &str) -> Result<(), String> {
+    if openai_req.requested_logprobs()?.is_some()
+        && !crate::proxy::mappers::openai::request::model_supports_logprobs(mapped_model)
+    {
+        return Err(format!(
+            "logprobs is not supported for model '{}'",
+            openai_req.model
+        ));
+    }
+    Ok(())
+}
+
+/// 按 strict json_schema 校验聚合后的响应内容 (工具调用与拒答不参与校验)
+fn validate_structured_output(response: &OpenAIResponse, schema: &Value) -> Result<(), String> {
+    for choice in &response.choices {
+        if choice.message.refusal.is_some() {
+            continue;
+        }
+        let text = match &choice.message.content {
+            Some(OpenAIContent::String(s)) => s.as_str(),
+            _ => "",
+        };
+        if text.trim().is_empty() && choice.message.tool_calls.is_some() {
+            continue;
+        }
+        let value: Value = serde_json::from_str(text.trim())
+            .map_err(|e| format!("choice {} is not valid JSON: {}", choice.index, e))?;
+        crate::proxy::common::json_schema::validate_json_schema(&value, schema)
+            .map_err(|e| format!("choice {}: {}", choice.index, e))?;
+    }
+    Ok(())
+}
+
+/// 提取后台任务规则所需的文本特征: (最后一条非空用户消息, 合并后的系统提示词)
+fn background_task_texts(openai_req: &OpenAIRequest) -> (Option<String>, String) {
+    let last_user_message = openai_req
+        .messages
+        .iter()
+        .rev()
+        .filter(|m| m.role == "user")
+        .filter_map(|m| m.content.as_ref().map(openai_content_text))
+        .find(|text| !text.trim().is_empty());
+    let system_prompt = openai_req
+        .messages
+        .iter()
+        .filter(|m| m.role == "system" || m.role == "developer")
+        .filter_map(|m| m.content.as_ref().map(openai_content_text))
+        .collect::<Vec<_>>()
+        .join("\n");
+    (last_user_message, system_prompt)
+}
+
+/// 后台任务规则命中 Reply 时返回的本地 Chat Completion 响应 (不消耗上游配额)
+fn create_local_chat_response(model: &str, text: &str, is_stream: bool) -> Response {
+    let id = format!("chatcmpl-local-{}", chrono::Utc::now().timestamp_millis());
+    let created = chrono::Utc::now().timestamp();
+
+    if is_stream {
+        let chunk = |delta: Value, finish_reason: Value| {
+            json!({
+                "id": id,
+                "object": "chat.completion.chunk",
+                "created": created,
+                "model": model,
+                "choices": [{"index": 0, "delta": delta, "finish_reason": finish_reason}]
+            })
+        };
+        let body = [
+            chunk(json!({"role": "assistant", "content": text}), Value::Null),
+            chunk(json!({}), json!("stop")),
+        ]
+        .iter()
+        .map(|c| format!("data: {}\n\n", c))
+        .chain(std::iter::once("data: [DONE]\n\n".to_string()))
+        .collect::<String>();
+
+        return Response::builder()
+            .header("Content-Type", "text/event-stream")
+            .header("Cache-Control", "no-cache")
+            .header("X-Background-Task-Intercepted", "true")
+            .body(axum::body::Body::from(body))
+            .unwrap();
+    }
+
+    (
+        StatusCode::OK,
+        [("X-Background-Task-Intercepted", "true")],
+        Json(json!({
+            "id": id,
+            "object": "chat.completion",
+            "created": created,
+            "model": model,
+            "choices": [{
+                "index": 0,
+                "message": {"role": "assistant", "content": text},
+                "finish_reason": "stop"
+            }],
+            "usage": {"prompt_tokens": 0, "completion_tokens": 0, "total_tokens": 0}
+        })),
+    )
+        .into_response()
+}
+
+/// Chat Completion 回复转为 Legacy Completions 格式 (reasoning 拼接在正文之前)
+fn convert_chat_response_to_legacy(chat_resp: &OpenAIResponse) -> Value {
+    let choices = chat_resp
+        .choices
+        .iter()
+        .map(|c| {
+            let mut text = match &c.message.content {
+                Some(OpenAIContent::String(s)) => s.clone(),
+                _ => "".to_string(),
+            };
+            // text_offset 以字符计 (与 chat_logprobs_to_legacy 一致)，不能用字节长度
+            let content_chars = text.chars().count();
+            if let Some(ref reasoning) = c.message.reasoning_content {
+                if !reasoning.is_empty() {
+                    text = format!("{}\n\n{}", reasoning, text);
+                }
+            }
+            // logprobs 只覆盖正文部分，偏移量从拼接的 reasoning 之后开始
+            let logprobs = c
+                .logprobs
+                .as_ref()
+                .map(|l| chat_logprobs_to_legacy(l, text.chars().count() - content_chars));
+            json!({
+                "text": text,
+                "index": c.index,
+                "logprobs": logprobs,
+                "finish_reason": c.finish_reason
+            })
+        })
+        .collect::<Vec<_>>();
+
+    json!({
+        "id": chat_resp.id,
+        "object": "text_completion",
+        "created": chat_resp.created,
+        "model": chat_resp.model,
+        "choices": choices,
+        "usage": chat_resp.usage
+    })
+}
+
+/// 将完整的 Chat Completion 回复还原为单个 Gemini SSE 分片 (v1internal 包装格式)
+fn chat_response_to_gemini_chunk(chat_resp: &OpenAIResponse) -> Value {
+    let choice = chat_resp.choices.first();
+    let mut parts = Vec::new();
+    if let Some(message) = choice.map(|c| &c.message) {
+        if let Some(reasoning) = message
+            .reasoning_content
+            .as_deref()
+            .filter(|r| !r.is_empty())
+        {
+            parts.push(json!({ "text": reasoning, "thought": true }));
+        }
+        let text = message
+            .content
+            .as_ref()
+            .map(openai_content_text)
+            .unwrap_or_default();
+        if !text.is_empty() {
+            parts.push(json!({ "text": text }));
+        }
+        for function in message
+            .tool_calls
+            .iter()
+            .flatten()
+            .filter_map(|call| call.function.as_ref())
+        {
+            let args: Value = serde_json::from_str(&function.arguments).unwrap_or(json!({}));
+            parts.push(json!({ "functionCall": { "name": function.name, "args": args } }));
+        }
+    }
+    let finish_reason = match choice.and_then(|c| c.finish_reason.as_deref()) {
+        Some("length") => "MAX_TOKENS",
+        Some("content_filter") => "SAFETY",
+        _ => "STOP",
+    };
+
+    let mut response = json!({
+        "candidates": [{
+            "content": { "role": "model", "parts": parts },
+            "finishReason": finish_reason
+        }],
+        "modelVersion": chat_resp.model
+    });
+    if let Some(usage) = &chat_resp.usage {
+        response["usageMetadata"] = json!({
+            "promptTokenCount": usage.prompt_tokens,
+            "candidatesTokenCount": usage.completion_tokens,
+            "totalTokenCount": usage.total_tokens
+        });
+    }
+    json!({ "response": response })
+}
+
+/// 将完整的 Chat Completion 回复转换为 Responses / Legacy Completions 响应
+///
+/// 用于不经过 Gemini 上游的回复 (后台任务本地回复、OpenAI 兼容 Provider)。
+/// 流式回复还原为 Gemini SSE 分片后交给与上游相同的转换器，保证事件序列一致
+fn completions_response_from_chat(
+    chat_resp: &OpenAIResponse,
+    is_stream: bool,
+    include_usage: bool,
+    is_codex_style: bool,
+    is_responses_api: bool,
+) -> Response {
+    if !is_stream {
+        let body = if is_responses_api {
+            convert_chat_response_to_responses(chat_resp)
+        } else {
+            convert_chat_response_to_legacy(chat_resp)
+        };
+        return Json(body).into_response();
+    }
+
+    use crate::proxy::mappers::openai::streaming::{
+        create_codex_sse_stream, create_legacy_sse_stream,
+    };
+    let chunk = chat_response_to_gemini_chunk(chat_resp);
+    let gemini_stream = Box::pin(futures::stream::iter([Ok::<Bytes, String>(Bytes::from(
+        format!("data: {}\n\n", chunk),
+    ))]));
+    let model = chat_resp.model.clone();
+    let session_id = chat_resp.id.clone();
+    let stream = if is_codex_style {
+        create_codex_sse_stream(gemini_stream, model, session_id, 0, 0)
+    } else {
+        create_legacy_sse_stream(gemini_stream, model, session_id, 0, include_usage)
+    };
+
+    Response::builder()
+        .header("Content-Type", "text/event-stream")
+        .header("Cache-Control", "no-cache")
+        .body(axum::body::Body::from_stream(stream))
+        .unwrap()
+}
+
+/// 后台任务规则命中 Reply 时返回的本地 Responses / Legacy Completions 响应 (不消耗上游配额)
+fn create_local_completions_response(
+    model: &str,
+    text: &str,
+    is_stream: bool,
+    is_codex_style: bool,
+    is_responses_api: bool,
+) -> Response {
+    let chat_resp = OpenAIResponse {
+        id: format!("chatcmpl-local-{}", chrono::Utc::now().timestamp_millis()),
+        object: "chat.completion".to_string(),
+        created: chrono::Utc::now().timestamp() as u64,
+        model: model.to_string(),
+        choices: vec![crate::proxy::mappers::openai::models::Choice {
+            index: 0,
+            message: OpenAIMessage {
+                role: "assistant".to_string(),
+                content: Some(OpenAIContent::String(text.to_string())),
+                reasoning_content: None,
+                tool_calls: None,
+                tool_call_id: None,
+                name: None,
+                refusal: None,
+            },
+            logprobs: None,
+            finish_reason: Some("stop".to_string()),
+        }],
+        usage: None,
+    };
+    let mut response = completions_response_from_chat(
+        &chat_resp,
+        is_stream,
+        false,
+        is_codex_style,
+        is_responses_api,
+    );
+    response.headers_mut().insert(
+        "X-Background-Task-Intercepted",
+        axum::http::HeaderValue::from_static("true"),
+    );
+    response
+}
+
+/// Responses / Legacy Completions 请求命中 OpenAI 兼容 Provider 时的转发
+///
+/// Provider 只提供 Chat Completions 接口：以非流式 Chat 请求转发，再把回复转换为客户端请求的格式
+async fn forward_completions_to_provider(
+    state: &AppState,
+    provider: &crate::proxy::config::UpstreamProviderConfig,
+    headers: &HeaderMap,
+    mut body: Value,
+    requested_model: &str,
+    is_codex_style: bool,
+    is_responses_api: bool,
+) -> Response {
+    let is_stream = body.get("stream").and_then(Value::as_bool).unwrap_or(false);
+    let include_usage = body
+        .pointer("/stream_options/include_usage")
+        .and_then(Value::as_bool)
+        .unwrap_or(false);
+    if let Some(obj) = body.as_object_mut() {
+        for key in [
+            "input",
+            "prompt",
+            "previous_response_id",
+            "_interaction_ledger",
+            "stream_options",
+        ] {
+            obj.remove(key);
+        }
+        obj.insert("stream".to_string(), Value::Bool(false));
+    }
+
+    let response = crate::proxy::providers::custom::forward_json(
+        state,
+        provider,
+        "/v1/chat/completions",
+        None,
+        headers,
+        body,
+        requested_model,
+    )
+    .await;
+    if !response.status().is_success() {
+        return response;
+    }
+
+    let (parts, body) = response.into_parts();
+    let chat_resp = match axum::body::to_bytes(body, usize::MAX)
+        .await
+        .map_err(|e| e.to_string())
+        .and_then(|bytes| {
+            serde_json::from_slice::<OpenAIResponse>(&bytes).map_err(|e| e.to_string())
+        }) {
+        Ok(chat_resp) => chat_resp,
+        Err(e) => {
+            return (
+                StatusCode::BAD_GATEWAY,
+                format!(
+                    "Provider '{}' returned an invalid chat completion: {}",
+                    provider.name, e
+                ),
+            )
+                .into_response();
+        }
+    };
+
+    let mut out = completions_response_from_chat(
+        &chat_resp,
+        is_stream,
+        include_usage,
+        is_codex_style,
+        is_responses_api,
+    );
+    for name in ["X-Account-Email", "X-Mapped-Model"] {
+        if let Some(value) = parts.headers.get(name) {
+            out.headers_mut().insert(name, value.clone());
+        }
+    }
+    out
+}
+
+/// strict json_schema 重试后仍不匹配时的 502 响应
+fn structured_output_error(email: &str, mapped_model: &str, error: &str) -> Response {
+    (
+        StatusCode::BAD_GATEWAY,
+        [("X-Account-Email", email), ("X-Mapped-Model", mapped_model)],
+        format!(
+            "Structured output does not match the requested json_schema: {}",
+            error
+        ),
+    )
+        .into_response()
+}
+
 pub async fn handle_chat_completions(
     State(state): State<AppState>,
     headers: HeaderMap, // [CHANGED] Extract headers
@@ -774,6 +1154,41 @@ pub async fn handle_chat_completions(
         }
     }
 
+    let trace_id = format!("req_{}", chrono::Utc::now().timestamp_subsec_millis());
+    crate::proxy::telemetry::record("trace_id", trace_id.as_str());
+
+    // 通用 OpenAI 兼容 Provider (vLLM / llama.cpp / 网关)，命中时直接透传
+    let requested_model = body
+        .get("model")
+        .and_then(|v| v.as_str())
+        .unwrap_or_default()
+        .to_string();
+    if let Some(provider) = crate::proxy::providers::custom::select_provider(
+        &state,
+        crate::proxy::config::ProviderProtocol::Openai,
+        &requested_model,
+        &trace_id,
+    )
+    .await
+    {
+        let mut forward_body = body;
+        if is_responses_format {
+            if let Some(obj) = forward_body.as_object_mut() {
+                obj.remove("input");
+            }
+        }
+        return Ok(crate::proxy::providers::custom::forward_json(
+            &state,
+            &provider,
+            "/v1/chat/completions",
+            None,
+            &headers,
+            forward_body,
+            &requested_model,
+        )
+        .await);
+    }
+
     let normalized_interaction_ledger = body.get("_interaction_ledger").cloned();
     let mut openai_req: OpenAIRequest = serde_json::from_value(body)
         .map_err(|e| (StatusCode::BAD_REQUEST, format!("Invalid request: {}", e)))?;
@@ -796,7 +1211,6 @@ pub async fn handle_chat_completions(
             });
     }
 
-    let trace_id = format!("req_{}", chrono::Utc::now().timestamp_subsec_millis());
     info!(
         "[{}] OpenAI Chat Request: {} | {} messages | stream: {}",
         trace_id,
@@ -804,7 +1218,9 @@ pub async fn handle_chat_completions(
         openai_req.messages.len(),
         openai_req.stream
     );
-    let debug_cfg = state.debug_logging.read().await.clone();
+    // 整个请求使用同一份配置快照，避免热更新时读到新旧混合的配置
+    let app_config = state.config.load_full();
+    let debug_cfg = app_config.proxy.debug_logging.clone();
 
     let mut force_rotate = false;
 
@@ -852,6 +1268,48 @@ pub async fn handle_chat_completions(
         debug!("[{}] Client Adapter detected", trace_id);
     }
 
+    // ===== 后台任务规则匹配 =====
+    // 规则表来自 ProxyConfig.background_tasks：Reply 直接返回本地回复，Route 改写目标模型
+    let background_rule = {
+        let (last_user_message, system_prompt) = background_task_texts(&openai_req);
+        let user_agent = get_user_agent(&headers).unwrap_or_default();
+        let signals = TaskSignals {
+            protocol: "openai",
+            client_adapter: client_adapter.as_ref().map(|a| a.name()),
+            user_agent: &user_agent,
+            last_user_message: last_user_message.as_deref(),
+            system_prompt: &system_prompt,
+            has_tools: openai_req.tools.as_ref().is_some_and(|t| !t.is_empty()),
+        };
+        background_task::resolve(&app_config.proxy.background_tasks, &signals, &trace_id)
+    };
+    if let Some(rule) = background_rule {
+        match rule.action {
+            BackgroundTaskAction::Reply { text } => {
+                info!(
+                    "[{}][AUTO] 后台任务规则 '{}' 命中，返回本地回复",
+                    trace_id, rule.name
+                );
+                return Ok(create_local_chat_response(
+                    &openai_req.model,
+                    &text,
+                    openai_req.stream,
+                ));
+            }
+            BackgroundTaskAction::Route { model } => {
+                info!(
+                    "[{}][AUTO] 后台任务规则 '{}' 命中, 路由重定向: {} -> {}",
+                    trace_id, rule.name, openai_req.model, model
+                );
+                // 后台任务不需要工具与 Thinking
+                openai_req.model = model;
+                openai_req.tools = None;
+                openai_req.tool_choice = None;
+                openai_req.thinking = None;
+            }
+        }
+    }
+
     // [Variant] Resolve canonical model + variant → real model + real params.
     // Replace the client's model/thinking/max_tokens with verified real values so the
     // forwarded request matches the expected upstream format. OpenCode encodes the variant as
@@ -901,12 +1359,39 @@ pub async fn handle_chat_completions(
     let mut last_email: Option<String> = None;
 
     // 2. 模型路由解析 (移到循环外以支持在所有路径返回 X-Mapped-Model)
-    let mapped_model = crate::proxy::common::model_mapping::resolve_model_route(
+    let mut mapped_model = crate::proxy::common::model_mapping::resolve_model_route_with_fallback(
         &openai_req.model,
-        &*state.custom_mapping.read().await,
-    );
+        &app_config.proxy.custom_mapping,
+        &app_config.proxy.model_fallback_chains,
+        &token_manager,
+    )
+    .await;
+
+    validate_logprobs(&openai_req, &mapped_model)
+        .map_err(|e| (StatusCode::BAD_REQUEST, format!("Invalid request: {}", e)))?;
+
+    // [NEW] strict json_schema: 校验失败时在同一账号上重试一次
+    // 客户端要求流式时内容边生成边下发，无法在返回前校验，按原样透传不做校验
+    let strict_schema = openai_req
+        .strict_json_schema()
+        .filter(|_| !openai_req.stream)
+        .cloned();
+    let mut output_check = OutputValidation::new("Structured output");
 
     for attempt in 0..max_attempts {
+        crate::proxy::telemetry::record_attempt(attempt);
+        // 结构化输出校验失败的重试固定使用上一次的账号与模型
+        let pinned = output_check.take_pinned();
+        // 其余重试重新评估降级链，上一次 429 锁定后可切换到链上的下一个模型
+        if attempt > 0 && pinned.is_none() {
+            mapped_model = crate::proxy::common::model_mapping::resolve_model_route_with_fallback(
+                &openai_req.model,
+                &app_config.proxy.custom_mapping,
+                &app_config.proxy.model_fallback_chains,
+                &token_manager,
+            )
+            .await;
+        }
         // 将 OpenAI 工具转为 Value 数组以便探测联网
         let tools_val: Option<Vec<Value>> = openai_req
             .tools
@@ -924,18 +1409,24 @@ pub async fn handle_chat_completions(
 
         // 3. 提取 SessionId (粘性指纹)
         let session_id = SessionManager::extract_openai_session_id(&openai_req);
+        crate::proxy::telemetry::record("session_id", session_id.as_str());
 
         // 4. 获取 Token (使用准确的 request_type)
         // 关键：在重试尝试时根据 force_rotate 决定是否轮换账号
-        let (access_token, project_id, email, account_id, _wait_ms) = match token_manager
-            .get_token(
-                &config.request_type,
-                force_rotate,
-                Some(&session_id),
-                &mapped_model,
-            )
-            .await
-        {
+        let token = match pinned {
+            Some(pinned) => Ok(pinned),
+            None => {
+                token_manager
+                    .get_token(
+                        &config.request_type,
+                        force_rotate,
+                        Some(&session_id),
+                        &mapped_model,
+                    )
+                    .await
+            }
+        };
+        let (access_token, project_id, email, account_id, _wait_ms) = match token {
             Ok(t) => t,
             Err(e) => {
                 // [FIX] Attach headers to error response for logging visibility
@@ -1118,6 +1609,7 @@ pub async fn handle_chat_completions(
                     session_id,
                     message_count,
                     Some(client_tool_names.clone()),
+                    openai_req.include_usage(),
                 );
 
                 let mut first_data_chunk = None;
@@ -1273,6 +1765,28 @@ pub async fn handle_chat_completions(
                     match collect_stream_to_json(combined_stream).await {
                         Ok(full_response) => {
                             info!("[{}] ✓ Stream collected and converted to JSON", trace_id);
+                            if let Some(Err(e)) = strict_schema
+                                .as_ref()
+                                .map(|schema| validate_structured_output(&full_response, schema))
+                            {
+                                match output_check.on_invalid(
+                                    &trace_id,
+                                    &e,
+                                    attempt,
+                                    max_attempts,
+                                    (&access_token, &project_id, &email, &account_id),
+                                    &mut last_error,
+                                ) {
+                                    ValidationDecision::Retry => continue,
+                                    ValidationDecision::Fail => {
+                                        return Ok(structured_output_error(
+                                            &email,
+                                            &mapped_model,
+                                            &e,
+                                        ))
+                                    }
+                                }
+                            }
                             if debug_logger::is_enabled(&debug_cfg) {
                                 let converted_response = serde_json::to_value(&full_response)
                                     .unwrap_or_else(
@@ -1352,6 +1866,24 @@ pub async fn handle_chat_completions(
                 message_count,
                 Some(&client_tool_names),
             );
+            if let Some(Err(e)) = strict_schema
+                .as_ref()
+                .map(|schema| validate_structured_output(&openai_response, schema))
+            {
+                match output_check.on_invalid(
+                    &trace_id,
+                    &e,
+                    attempt,
+                    max_attempts,
+                    (&access_token, &project_id, &email, &account_id),
+                    &mut last_error,
+                ) {
+                    ValidationDecision::Retry => continue,
+                    ValidationDecision::Fail => {
+                        return Ok(structured_output_error(&email, &mapped_model, &e))
+                    }
+                }
+            }
             if debug_logger::is_enabled(&debug_cfg) {
                 let converted_response = serde_json::to_value(&openai_response)
                     .unwrap_or_else(|e| json!({ "serialization_error": e.to_string() }));
@@ -1746,64 +2278,9 @@ fn web_tools_guidance_message() -> Value {
 
 // --- END Codex GUIDANCE PROMPTS ---
 
-/// 处理 Legacy Completions API (/v1/completions)
-/// 将 Prompt 转换为 Chat Message 格式，复用 handle_chat_completions
-pub async fn handle_completions(
-    axum::extract::OriginalUri(uri): axum::extract::OriginalUri,
-    State(state): State<AppState>,
-    Json(mut body): Json<Value>,
-) -> Response {
-    debug!(
-        "Received /v1/completions or /v1/responses payload: {:?}",
-        body
-    );
-    let original_body = body.clone();
-    let debug_cfg = state.debug_logging.read().await.clone();
-
-    // [MULTI-TURN] 支持 previous_response_id 链式历史恢复
-    // 当客户端通过 HTTP POST /v1/responses 传入 previous_response_id 时，
-    // 从服务器端 session store 取出上一轮的历史，合并到本轮的 input 中
-    let previous_response_id = body
-        .get("previous_response_id")
-        .and_then(|v| v.as_str())
-        .map(|s| s.to_string());
-    let response_id_for_save = format!("resp-{}", uuid::Uuid::new_v4());
-    let http_tool_call_cache: std::collections::HashMap<String, serde_json::Value> =
-        std::collections::HashMap::new();
-    if let Some(ref prev_id) = previous_response_id {
-        if let Some(session) = crate::proxy::http_session_store::get_session(prev_id).await {
-            // 把历史 input items 合并进来
-            let existing_input = body
-                .get("input")
-                .and_then(|v| v.as_array())
-                .cloned()
-                .unwrap_or_default();
-            let merged = crate::proxy::http_session_store::merge_history_with_new_input(
-                session.input_items,
-                &[],
-                &existing_input,
-                &http_tool_call_cache,
-            );
-            let merged_len = merged.len();
-            if let Some(obj) = body.as_object_mut() {
-                obj.insert("input".to_string(), json!(merged));
-                // 从历史 session 继承 instructions（如果本轮没带）
-                if !obj.contains_key("instructions") && !session.instructions.is_empty() {
-                    obj.insert("instructions".to_string(), json!(session.instructions));
-                }
-                // 继承 model（如果本轮没带）
-                if !obj.contains_key("model") && !session.model.is_empty() {
-                    obj.insert("model".to_string(), json!(session.model));
-                }
-            }
-            tracing::debug!(
-                "[MultiTurn] Restored session from prev_id={}, {} items in history",
-                prev_id,
-                merged_len
-            );
-        }
-    }
-
+/// 将 Responses API (`instructions` / `input`) 与 Legacy Completions (`prompt`) 请求体
+/// 规范化为 Chat `messages`，返回是否为 Codex 风格请求
+fn normalize_completions_payload(body: &mut Value) -> bool {
     let is_codex_style = body.get("input").is_some() || body.get("instructions").is_some();
 
     // 1. Convert Payload to Messages (Shared Chat Format)
@@ -2272,6 +2749,72 @@ pub async fn handle_completions(
         }
     }
 
+    is_codex_style
+}
+
+/// 处理 Legacy Completions API (/v1/completions)
+/// 将 Prompt 转换为 Chat Message 格式，复用 handle_chat_completions
+pub async fn handle_completions(
+    axum::extract::OriginalUri(uri): axum::extract::OriginalUri,
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(mut body): Json<Value>,
+) -> Response {
+    debug!(
+        "Received /v1/completions or /v1/responses payload: {:?}",
+        body
+    );
+    let original_body = body.clone();
+    // 整个请求使用同一份配置快照，避免热更新时读到新旧混合的配置
+    let app_config = state.config.load_full();
+    let debug_cfg = app_config.proxy.debug_logging.clone();
+
+    // [MULTI-TURN] 支持 previous_response_id 链式历史恢复
+    // 当客户端通过 HTTP POST /v1/responses 传入 previous_response_id 时，
+    // 从服务器端 session store 取出上一轮的历史，合并到本轮的 input 中
+    let previous_response_id = body
+        .get("previous_response_id")
+        .and_then(|v| v.as_str())
+        .map(|s| s.to_string());
+    let response_id_for_save = format!("resp-{}", uuid::Uuid::new_v4());
+    let http_tool_call_cache: std::collections::HashMap<String, serde_json::Value> =
+        std::collections::HashMap::new();
+    if let Some(ref prev_id) = previous_response_id {
+        if let Some(session) = crate::proxy::http_session_store::get_session(prev_id).await {
+            // 把历史 input items 合并进来
+            let existing_input = body
+                .get("input")
+                .and_then(|v| v.as_array())
+                .cloned()
+                .unwrap_or_default();
+            let merged = crate::proxy::http_session_store::merge_history_with_new_input(
+                session.input_items,
+                &[],
+                &existing_input,
+                &http_tool_call_cache,
+            );
+            let merged_len = merged.len();
+            if let Some(obj) = body.as_object_mut() {
+                obj.insert("input".to_string(), json!(merged));
+                // 从历史 session 继承 instructions（如果本轮没带）
+                if !obj.contains_key("instructions") && !session.instructions.is_empty() {
+                    obj.insert("instructions".to_string(), json!(session.instructions));
+                }
+                // 继承 model（如果本轮没带）
+                if !obj.contains_key("model") && !session.model.is_empty() {
+                    obj.insert("model".to_string(), json!(session.model));
+                }
+            }
+            tracing::debug!(
+                "[MultiTurn] Restored session from prev_id={}, {} items in history",
+                prev_id,
+                merged_len
+            );
+        }
+    }
+
+    let is_codex_style = normalize_completions_payload(&mut body);
+
     // [FIX] 在 openai_req 反序列化之前，从 body 中捕获原始 input 和 instructions
     // 用于后续 session 保存时，保留完整的工具调用历史（而非从 openai_req.messages 重建丢失信息）
     let session_save_input: Vec<serde_json::Value> = body
@@ -2290,6 +2833,36 @@ pub async fn handle_completions(
         obj.remove("instructions");
     }
 
+    let trace_id = format!("req_{}", chrono::Utc::now().timestamp_subsec_millis());
+    crate::proxy::telemetry::record("trace_id", trace_id.as_str());
+    let is_responses_api = uri.path() == "/v1/responses";
+
+    // 通用 OpenAI 兼容 Provider：与 Chat 接口相同的调度规则，回复转换为 Responses / Completions 格式
+    let requested_model = body
+        .get("model")
+        .and_then(|v| v.as_str())
+        .unwrap_or_default()
+        .to_string();
+    if let Some(provider) = crate::proxy::providers::custom::select_provider(
+        &state,
+        crate::proxy::config::ProviderProtocol::Openai,
+        &requested_model,
+        &trace_id,
+    )
+    .await
+    {
+        return forward_completions_to_provider(
+            &state,
+            &provider,
+            &headers,
+            body,
+            &requested_model,
+            is_codex_style,
+            is_responses_api,
+        )
+        .await;
+    }
+
     let mut openai_req: OpenAIRequest = match serde_json::from_value(body.clone()) {
         Ok(req) => req,
         Err(e) => {
@@ -2314,8 +2887,54 @@ pub async fn handle_completions(
             });
     }
 
+    // ===== 后台任务规则匹配 =====
+    // 与 Chat 接口共用规则表：Reply 按 Responses / Completions 格式返回本地回复，Route 改写目标模型
+    let background_rule = {
+        let client_adapter = CLIENT_ADAPTERS.iter().find(|a| a.matches(&headers));
+        let (last_user_message, system_prompt) = background_task_texts(&openai_req);
+        let user_agent = get_user_agent(&headers).unwrap_or_default();
+        let signals = TaskSignals {
+            protocol: "openai",
+            client_adapter: client_adapter.map(|a| a.name()),
+            user_agent: &user_agent,
+            last_user_message: last_user_message.as_deref(),
+            system_prompt: &system_prompt,
+            has_tools: openai_req.tools.as_ref().is_some_and(|t| !t.is_empty()),
+        };
+        background_task::resolve(&app_config.proxy.background_tasks, &signals, &trace_id)
+    };
+    if let Some(rule) = background_rule {
+        match rule.action {
+            BackgroundTaskAction::Reply { text } => {
+                info!(
+                    "[{}][AUTO] 后台任务规则 '{}' 命中，返回本地回复",
+                    trace_id, rule.name
+                );
+                return create_local_completions_response(
+                    &openai_req.model,
+                    &text,
+                    openai_req.stream,
+                    is_codex_style,
+                    is_responses_api,
+                );
+            }
+            BackgroundTaskAction::Route { model } => {
+                info!(
+                    "[{}][AUTO] 后台任务规则 '{}' 命中, 路由重定向: {} -> {}",
+                    trace_id, rule.name, openai_req.model, model
+                );
+                // 后台任务不需要工具与 Thinking
+                openai_req.model = model;
+                openai_req.tools = None;
+                openai_req.tool_choice = None;
+                openai_req.thinking = None;
+            }
+        }
+    }
+
     // [NEW v4.2.0] Context Management & Reasoning Replay
     let session_id_str = SessionManager::extract_openai_session_id(&openai_req);
+    crate::proxy::telemetry::record("session_id", session_id_str.as_str());
 
     let client_tool_names =
         crate::proxy::mappers::openai::request::extract_client_tool_names(&openai_req.tools);
@@ -2325,7 +2944,7 @@ pub async fn handle_completions(
         &session_id_str,
     );
 
-    let experimental_cfg = state.experimental.read().await;
+    let experimental_cfg = &app_config.proxy.experimental;
     let compression_level = if experimental_cfg.compression_level == "disabled" {
         if experimental_cfg.enable_usage_scaling {
             "high".to_string()
@@ -2336,11 +2955,16 @@ pub async fn handle_completions(
         experimental_cfg.compression_level.clone()
     };
 
-    let mapped_model = crate::proxy::common::model_mapping::resolve_model_route(
+    let mut mapped_model = crate::proxy::common::model_mapping::resolve_model_route_with_fallback(
         &openai_req.model,
-        &*state.custom_mapping.read().await,
-    );
-    let trace_id = format!("req_{}", chrono::Utc::now().timestamp_subsec_millis());
+        &app_config.proxy.custom_mapping,
+        &app_config.proxy.model_fallback_chains,
+        &state.token_manager,
+    )
+    .await;
+    if let Err(e) = validate_logprobs(&openai_req, &mapped_model) {
+        return (StatusCode::BAD_REQUEST, format!("Invalid request: {}", e)).into_response();
+    }
     if debug_logger::is_enabled(&debug_cfg) {
         if let Some(ledger) = normalized_interaction_ledger {
             let payload = json!({
@@ -2400,6 +3024,7 @@ pub async fn handle_completions(
                     trace_id, usage_ratio * 100.0, threshold_l1 * 100.0
                 );
                 compression_applied = true;
+                crate::proxy::telemetry::record("compression.layer", 1);
 
                 let new_raw = crate::proxy::mappers::context_manager::ContextManager::estimate_openai_token_usage(&openai_req);
                 let new_usage = calibrator.calibrate(new_raw);
@@ -2436,6 +3061,7 @@ pub async fn handle_completions(
             ) {
                 is_purified = true;
                 compression_applied = true;
+                crate::proxy::telemetry::record("compression.layer", 2);
 
                 let new_raw = crate::proxy::mappers::context_manager::ContextManager::estimate_openai_token_usage(&openai_req);
                 let new_usage = calibrator.calibrate(new_raw);
@@ -2477,6 +3103,7 @@ pub async fn handle_completions(
 
                     openai_req = forked_req;
                     is_purified = false;
+                    crate::proxy::telemetry::record("compression.layer", 3);
 
                     let new_raw = crate::proxy::mappers::context_manager::ContextManager::estimate_openai_token_usage(&openai_req);
                     let new_usage = calibrator.calibrate(new_raw);
@@ -2552,6 +3179,17 @@ pub async fn handle_completions(
     let mut force_rotate = false;
 
     for attempt in 0..max_attempts {
+        crate::proxy::telemetry::record_attempt(attempt);
+        // 重试时重新评估降级链，上一次 429 锁定后可切换到链上的下一个模型
+        if attempt > 0 {
+            mapped_model = crate::proxy::common::model_mapping::resolve_model_route_with_fallback(
+                &openai_req.model,
+                &app_config.proxy.custom_mapping,
+                &app_config.proxy.model_fallback_chains,
+                &token_manager,
+            )
+            .await;
+        }
         // 3. 模型配置解析
         // 将 OpenAI 工具转为 Value 数组以便探测联网
         let tools_val: Option<Vec<Value>> = openai_req
@@ -2750,6 +3388,7 @@ pub async fn handle_completions(
                             openai_req.model.clone(),
                             session_id,
                             message_count,
+                            openai_req.include_usage(),
                         )
                     };
 
@@ -2867,6 +3506,7 @@ pub async fn handle_completions(
                         session_id,
                         message_count,
                         Some(client_tool_names.clone()),
+                        openai_req.include_usage(),
                     );
 
                     // Peek Logic (Repeated for safety/correctness on this stream type)
@@ -2946,8 +3586,6 @@ pub async fn handle_completions(
                     use crate::proxy::mappers::openai::collector::collect_stream_to_json;
                     match collect_stream_to_json(combined_stream).await {
                         Ok(chat_resp) => {
-                            let is_responses_api = uri.path() == "/v1/responses";
-
                             if is_responses_api {
                                 let resp = convert_chat_response_to_responses(&chat_resp);
                                 if debug_logger::is_enabled(&debug_cfg) {
@@ -2981,39 +3619,8 @@ pub async fn handle_completions(
                                     .into_response();
                             }
 
-                            // NOW: Convert Chat Response -> Legacy Response (Same logic as below)
-                            let choices = chat_resp
-                                .choices
-                                .iter()
-                                .map(|c| {
-                                    let mut text = match &c.message.content {
-                                        Some(
-                                            crate::proxy::mappers::openai::OpenAIContent::String(s),
-                                        ) => s.clone(),
-                                        _ => "".to_string(),
-                                    };
-                                    if let Some(ref reasoning) = c.message.reasoning_content {
-                                        if !reasoning.is_empty() {
-                                            text = format!("{}\n\n{}", reasoning, text);
-                                        }
-                                    }
-                                    json!({
-                                        "text": text,
-                                        "index": c.index,
-                                        "logprobs": null,
-                                        "finish_reason": c.finish_reason
-                                    })
-                                })
-                                .collect::<Vec<_>>();
-
-                            let legacy_resp = json!({
-                                "id": chat_resp.id,
-                                "object": "text_completion",
-                                "created": chat_resp.created,
-                                "model": chat_resp.model,
-                                "choices": choices,
-                                "usage": chat_resp.usage
-                            });
+                            // NOW: Convert Chat Response -> Legacy Response
+                            let legacy_resp = convert_chat_response_to_legacy(&chat_resp);
                             if debug_logger::is_enabled(&debug_cfg) {
                                 let payload = json!({
                                     "kind": "exchange_summary",
@@ -3074,8 +3681,6 @@ pub async fn handle_completions(
                 Some(&client_tool_names),
             );
 
-            let is_responses_api = uri.path() == "/v1/responses";
-
             if is_responses_api {
                 let resp = convert_chat_response_to_responses(&chat_resp);
                 if debug_logger::is_enabled(&debug_cfg) {
@@ -3117,7 +3722,7 @@ pub async fn handle_completions(
                         _ => "".to_string()
                     },
                     "index": c.index,
-                    "logprobs": null,
+                    "logprobs": c.logprobs.as_ref().map(|l| chat_logprobs_to_legacy(l, 0)),
                     "finish_reason": c.finish_reason
                 })
             }).collect::<Vec<_>>();
@@ -3241,12 +3846,80 @@ pub async fn handle_completions(
     }
 }
 
+/// 处理 Responses API 输入 Token 计数 (/v1/responses/input_tokens)
+///
+/// 与生成请求走相同的规范化与 Gemini 转换后调用上游 countTokens；
+/// 无可用账号或上游失败时回退到校准后的本地估算
+pub async fn handle_responses_input_tokens(
+    State(state): State<AppState>,
+    Json(mut body): Json<Value>,
+) -> Response {
+    normalize_completions_payload(&mut body);
+    if let Some(obj) = body.as_object_mut() {
+        obj.remove("instructions");
+    }
+
+    let openai_req: OpenAIRequest = match serde_json::from_value(body) {
+        Ok(req) => req,
+        Err(e) => {
+            return (StatusCode::BAD_REQUEST, format!("Invalid request: {}", e)).into_response();
+        }
+    };
+
+    let mapped_model = crate::proxy::common::model_mapping::resolve_model_route(
+        &openai_req.model,
+        &state.config.load().proxy.custom_mapping,
+    );
+
+    let (gemini_body, session_id, _, _) =
+        transform_openai_request(&openai_req, "", &mapped_model, None);
+    let counted = crate::proxy::handlers::gemini::count_tokens_upstream(
+        &state,
+        &openai_req.model,
+        &mapped_model,
+        &session_id,
+        crate::proxy::handlers::gemini::count_tokens_request(&gemini_body),
+    )
+    .await;
+
+    let input_tokens = match counted {
+        Ok((tokens, _email)) => tokens,
+        Err((status, msg)) => {
+            let raw_estimated =
+                crate::proxy::mappers::context_manager::ContextManager::estimate_openai_token_usage(
+                    &openai_req,
+                );
+            let calibrated = crate::proxy::mappers::estimation_calibrator::get_calibrator()
+                .calibrate(raw_estimated);
+            debug!(
+                "[input_tokens] Upstream count unavailable ({}: {}), using calibrated estimate: {} (raw: {})",
+                status, msg, calibrated, raw_estimated
+            );
+            calibrated as i64
+        }
+    };
+
+    (
+        StatusCode::OK,
+        [("X-Mapped-Model", mapped_model.as_str())],
+        Json(json!({
+            "object": "response.input_tokens",
+            "input_tokens": input_tokens
+        })),
+    )
+        .into_response()
+}
+
 pub async fn handle_list_models(State(state): State<AppState>) -> impl IntoResponse {
     use crate::proxy::common::model_mapping::get_all_dynamic_models;
 
-    let only_raw = *state.only_raw_quota_models.read().await;
-    let model_ids =
-        get_all_dynamic_models(&state.custom_mapping, Some(&state.token_manager), only_raw).await;
+    let config = state.config.load_full();
+    let model_ids = get_all_dynamic_models(
+        &config.proxy.custom_mapping,
+        Some(&state.token_manager),
+        config.proxy.only_raw_quota_models,
+    )
+    .await;
 
     let data: Vec<_> = model_ids
         .into_iter()
@@ -4265,7 +4938,26 @@ async fn handle_websocket_session(mut socket: WebSocket, headers: HeaderMap, sta
         tool_call_cache: std::collections::HashMap::new(),
     };
 
-    while let Some(msg_result) = socket.recv().await {
+    // 会话期间计入进行中请求；服务排空时在当前轮次结束后通知客户端重连
+    let _in_flight = state.drain.track();
+
+    loop {
+        let msg_result = tokio::select! {
+            msg = socket.recv() => match msg {
+                Some(msg) => msg,
+                None => break,
+            },
+            _ = state.drain.wait_draining() => {
+                tracing::info!("responses websocket: service draining, closing session");
+                let _ = socket
+                    .send(Message::Close(Some(axum::extract::ws::CloseFrame {
+                        code: axum::extract::ws::close_code::RESTART,
+                        reason: "service restarting".into(),
+                    })))
+                    .await;
+                break;
+            }
+        };
         let msg = match msg_result {
             Ok(m) => m,
             Err(e) => {
@@ -4302,7 +4994,7 @@ async fn handle_websocket_session(mut socket: WebSocket, headers: HeaderMap, sta
             }
         };
         let ws_trace_id = format!("ws_{}", chrono::Utc::now().timestamp_subsec_millis());
-        let debug_cfg = state.debug_logging.read().await.clone();
+        let debug_cfg = state.config.load().proxy.debug_logging.clone();
         if debug_logger::is_enabled(&debug_cfg) {
             let payload_log = json!({
                 "kind": "codex_websocket_raw_request",
@@ -4358,6 +5050,7 @@ async fn handle_websocket_session(mut socket: WebSocket, headers: HeaderMap, sta
         };
 
         let openai_body = convert_codex_to_openai_request(normalized);
+        // 后台任务规则 (含 dry-run 日志) 由 Chat Handler 统一评估，Reply 的本地 SSE 同样经下方转换为 WS 事件
         let response_result =
             handle_chat_completions(State(state.clone()), headers.clone(), Json(openai_body)).await;
 
@@ -5599,6 +6292,7 @@ async fn call_openai_gemini_sync(
         .ok_or_else(|| "Failed to extract text from response".to_string())
 }
 
+#[tracing::instrument(name = "context.compress.fork", skip_all)]
 async fn try_compress_openai_with_summary(
     original_request: &OpenAIRequest,
     trace_id: &str,
@@ -108,6 +108,7 @@ pub async fn handle_warmup(
             }),
             thinking: None,
             output_config: None,
+            tool_choice: None,
             size: None,
             quality: None,
         };
@@ -245,6 +246,7 @@ pub async fn handle_warmup(
                 cached_tokens: None,
                 protocol: Some("warmup".to_string()),
                 username: None,
+                reasoning_tokens: None,
             };
             state.monitor.log_request(log).await;
 
@@ -343,6 +345,7 @@ pub async fn handle_warmup(
                 cached_tokens: None,
                 protocol: Some("warmup".to_string()),
                 username: None,
+                reasoning_tokens: None,
             };
             state.monitor.log_request(log).await;
 
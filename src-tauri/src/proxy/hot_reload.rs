@@ -0,0 +1,572 @@
+//! 反代配置热更新
+//!
+//! - `AppState::config` 保存当前生效的 `AppConfig` 快照，通过 `ArcSwap` 整体原子替换，
+//!   读取方拿到的总是某一次完整保存的配置。
+//! - Handler 与中间件只从快照读取配置 (映射、降级链、安全、z.ai、Provider、实验性与调试
+//!   配置、代理池、超时等)，不再保留独立的运行时副本，因此一次请求不会读到新旧混合的配置。
+//! - `apply_config` 是 Web 保存、桌面端保存与文件监听共用的唯一入口：替换快照后
+//!   只同步无法从快照派生的组件 (上游客户端、全局请求变换、代理池绑定、调度等)。
+//! - 只有监听地址或端口变化时才重新绑定：先绑定新地址，再让旧监听器停止接收新连接，
+//!   旧连接在完成当前请求 (含流式响应) 后优雅关闭。
+//! - `spawn_config_watcher` 轮询 `gui_config.json` 与 `ABV_CONFIG_FILE`，外部修改后自动热更新。
+
+use arc_swap::ArcSwap;
+use axum::Router;
+use serde::Serialize;
+use serde_json::Value;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, SystemTime};
+use tokio::sync::{oneshot, watch, Mutex};
+use tracing::{debug, error, info, warn};
+
+use crate::models::AppConfig;
+use crate::proxy::server::AppState;
+
+/// 配置文件轮询间隔
+const WATCH_INTERVAL: Duration = Duration::from_secs(2);
+
+/// 串行化所有配置应用 (保存触发与文件监听触发)，避免两次应用交错写入运行时状态
+fn apply_lock() -> &'static Mutex<()> {
+    static LOCK: OnceLock<Mutex<()>> = OnceLock::new();
+    LOCK.get_or_init(|| Mutex::new(()))
+}
+
+/// 可原子替换的配置快照
+pub type ConfigSnapshot = Arc<ArcSwap<AppConfig>>;
+
+pub fn new_snapshot(config: AppConfig) -> ConfigSnapshot {
+    Arc::new(ArcSwap::from_pointee(config))
+}
+
+/// 一次热更新的结果
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct ReloadReport {
+    /// 发生变化的配置项 (`proxy` 下按字段列出，如 `proxy.custom_mapping`)
+    pub changed: Vec<String>,
+    /// 监听器重新绑定后的地址
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rebound: Option<String>,
+    /// 重新绑定失败的原因 (此时仍在旧地址上监听)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rebind_error: Option<String>,
+}
+
+impl ReloadReport {
+    fn has(&self, key: &str) -> bool {
+        self.changed.iter().any(|c| c == key)
+    }
+}
+
+struct ActiveListener {
+    /// 请求绑定的地址 (`host:port`)
+    requested: String,
+    local_addr: SocketAddr,
+    drain_tx: watch::Sender<bool>,
+    stopped_rx: oneshot::Receiver<()>,
+}
+
+/// 监听器控制：支持在运行中切换监听地址并排空旧连接
+#[derive(Default)]
+pub struct ListenerControl {
+    app: OnceLock<Router>,
+    active: Mutex<Option<ActiveListener>>,
+    reuse_port: AtomicBool,
+}
+
+impl ListenerControl {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 设置要服务的路由 (仅首次生效)
+    pub fn set_app(&self, app: Router) {
+        let _ = self.app.set(app);
+    }
+
+    /// 后续绑定是否使用 SO_REUSEPORT (不触发重新绑定)
+    pub fn set_reuse_port(&self, enabled: bool) {
+        self.reuse_port.store(enabled, Ordering::Relaxed);
+    }
+
+    /// 当前实际监听的地址
+    pub async fn local_addr(&self) -> Option<SocketAddr> {
+        self.active.lock().await.as_ref().map(|a| a.local_addr)
+    }
+
+    /// 当前请求绑定的地址 (`host:port`)
+    pub async fn requested_addr(&self) -> Option<String> {
+        self.active
+            .lock()
+            .await
+            .as_ref()
+            .map(|a| a.requested.clone())
+    }
+
+    /// 绑定到新地址；已有监听器时先绑定新地址，再排空旧监听器
+    pub async fn bind(&self, addr: &str) -> Result<tokio::task::JoinHandle<()>, String> {
+        let app = self
+            .app
+            .get()
+            .cloned()
+            .ok_or_else(|| "监听器尚未初始化".to_string())?;
+        let reuse_port = self.reuse_port.load(Ordering::Relaxed);
+        let mut active = self.active.lock().await;
+
+        let listener = match bind_listener(addr, reuse_port).await {
+            Ok(listener) => listener,
+            // 同端口仅变更监听地址 (如 127.0.0.1 -> 0.0.0.0) 时新旧套接字冲突，需先释放旧监听器
+            Err(e)
+                if e.kind() == std::io::ErrorKind::AddrInUse
+                    && active
+                        .as_ref()
+                        .is_some_and(|a| port_of(addr) == Some(a.local_addr.port())) =>
+            {
+                let old = active.take().expect("checked above");
+                let old_requested = old.requested.clone();
+                drain(old).await;
+                match bind_listener(addr, reuse_port).await {
+                    Ok(listener) => listener,
+                    Err(e) => {
+                        // 回退到原地址，避免服务完全不可达
+                        if let Ok(listener) = bind_listener(&old_requested, reuse_port).await {
+                            *active = Some(spawn_accept_loop(listener, app, old_requested)?.0);
+                        }
+                        return Err(format!("地址 {} 绑定失败: {}", addr, e));
+                    }
+                }
+            }
+            Err(e) => return Err(format!("地址 {} 绑定失败: {}", addr, e)),
+        };
+
+        let (next, handle) = spawn_accept_loop(listener, app, addr.to_string())?;
+        info!("反代服务器启动在 http://{}", next.local_addr);
+        if let Some(old) = active.replace(next) {
+            info!(
+                "旧监听器 {} 已停止接收新连接，现有连接将在完成后关闭",
+                old.local_addr
+            );
+            let _ = old.drain_tx.send(true);
+        }
+        Ok(handle)
+    }
+
+    /// 停止监听并优雅关闭现有连接
+    pub async fn stop(&self) {
+        if let Some(old) = self.active.lock().await.take() {
+            let _ = old.drain_tx.send(true);
+            tracing::info!("Axum server 停止信号已发送");
+        }
+    }
+}
+
+/// 停止旧监听器并等待其释放监听套接字
+async fn drain(old: ActiveListener) {
+    let _ = old.drain_tx.send(true);
+    let _ = old.stopped_rx.await;
+}
+
+/// 绑定监听套接字；启用 reuse_port 时允许多个进程同时监听同一端口 (滚动升级)
+async fn bind_listener(addr: &str, reuse_port: bool) -> std::io::Result<tokio::net::TcpListener> {
+    if !reuse_port {
+        return tokio::net::TcpListener::bind(addr).await;
+    }
+    #[cfg(unix)]
+    {
+        let socket_addr = tokio::net::lookup_host(addr).await?.next().ok_or_else(|| {
+            std::io::Error::new(std::io::ErrorKind::InvalidInput, "无法解析监听地址")
+        })?;
+        let socket = if socket_addr.is_ipv4() {
+            tokio::net::TcpSocket::new_v4()?
+        } else {
+            tokio::net::TcpSocket::new_v6()?
+        };
+        socket.set_reuseaddr(true)?;
+        socket.set_reuseport(true)?;
+        socket.bind(socket_addr)?;
+        socket.listen(1024)
+    }
+    #[cfg(not(unix))]
+    {
+        warn!("当前平台不支持 SO_REUSEPORT，按普通方式绑定");
+        tokio::net::TcpListener::bind(addr).await
+    }
+}
+
+fn port_of(addr: &str) -> Option<u16> {
+    addr.rsplit_once(':').and_then(|(_, p)| p.parse().ok())
+}
+
+fn spawn_accept_loop(
+    listener: tokio::net::TcpListener,
+    app: Router,
+    requested: String,
+) -> Result<(ActiveListener, tokio::task::JoinHandle<()>), String> {
+    let local_addr = listener
+        .local_addr()
+        .map_err(|e| format!("无法获取监听地址: {}", e))?;
+    let (drain_tx, drain_rx) = watch::channel(false);
+    let (stopped_tx, stopped_rx) = oneshot::channel::<()>();
+    let handle = tokio::spawn(accept_loop(listener, app, drain_rx, stopped_tx));
+    Ok((
+        ActiveListener {
+            requested,
+            local_addr,
+            drain_tx,
+            stopped_rx,
+        },
+        handle,
+    ))
+}
+
+/// 等待排空信号 (发送端被丢弃同样视为排空)
+async fn wait_for_drain(drain_rx: &mut watch::Receiver<bool>) {
+    let _ = drain_rx.wait_for(|draining| *draining).await;
+}
+
+async fn accept_loop(
+    listener: tokio::net::TcpListener,
+    app: Router,
+    mut drain_rx: watch::Receiver<bool>,
+    _stopped_tx: oneshot::Sender<()>,
+) {
+    use hyper::body::Incoming;
+    use hyper::server::conn::http1;
+    use hyper_util::rt::TokioIo;
+    use hyper_util::service::TowerToHyperService;
+    use tower::ServiceExt;
+
+    loop {
+        tokio::select! {
+            res = listener.accept() => {
+                match res {
+                    Ok((stream, remote_addr)) => {
+                        let io = TokioIo::new(stream);
+
+                        // 注入 ConnectInfo (用于获取真实 IP)
+                        let app_with_info = app.clone().map_request(move |mut req: axum::http::Request<Incoming>| {
+                            req.extensions_mut().insert(axum::extract::ConnectInfo(remote_addr));
+                            req
+                        });
+                        let service = TowerToHyperService::new(app_with_info);
+                        let mut conn_drain = drain_rx.clone();
+
+                        tokio::task::spawn(async move {
+                            let conn = http1::Builder::new()
+                                .serve_connection(io, service)
+                                .with_upgrades(); // 支持 WebSocket (如果以后需要)
+                            tokio::pin!(conn);
+                            let result = tokio::select! {
+                                res = conn.as_mut() => res,
+                                _ = wait_for_drain(&mut conn_drain) => {
+                                    // 完成当前请求后关闭 keep-alive 连接
+                                    conn.as_mut().graceful_shutdown();
+                                    conn.await
+                                }
+                            };
+                            if let Err(err) = result {
+                                debug!("连接处理结束或出错: {:?}", err);
+                            }
+                        });
+                    }
+                    Err(e) => {
+                        error!("接收连接失败: {:?}", e);
+                    }
+                }
+            }
+            _ = wait_for_drain(&mut drain_rx) => {
+                info!("反代服务器停止监听 {:?}", listener.local_addr().ok());
+                break;
+            }
+        }
+    }
+}
+
+/// 用于比较的配置表示：解密本机加密字段，避免随机 nonce 造成误判
+fn comparable(config: &AppConfig) -> Value {
+    fn normalize(value: &mut Value) {
+        match value {
+            Value::String(s) if crate::utils::crypto::is_machine_encrypted(s) => {
+                if let Ok(plain) = crate::utils::crypto::decrypt_string(s) {
+                    *s = plain;
+                }
+            }
+            Value::Array(items) => items.iter_mut().for_each(normalize),
+            Value::Object(map) => map.values_mut().for_each(normalize),
+            _ => {}
+        }
+    }
+    let mut value = serde_json::to_value(config).unwrap_or(Value::Null);
+    normalize(&mut value);
+    value
+}
+
+/// 列出两份配置之间变化的配置项
+fn changed_sections(old: &AppConfig, new: &AppConfig) -> Vec<String> {
+    let old = comparable(old);
+    let new = comparable(new);
+    let empty = serde_json::Map::new();
+    let old = old.as_object().unwrap_or(&empty);
+    let new = new.as_object().unwrap_or(&empty);
+
+    let mut changed = Vec::new();
+    let mut keys: Vec<&String> = old.keys().chain(new.keys()).collect();
+    keys.sort();
+    keys.dedup();
+    for key in keys {
+        if key == "proxy" {
+            let old_proxy = old.get(key).and_then(Value::as_object).unwrap_or(&empty);
+            let new_proxy = new.get(key).and_then(Value::as_object).unwrap_or(&empty);
+            let mut fields: Vec<&String> = old_proxy.keys().chain(new_proxy.keys()).collect();
+            fields.sort();
+            fields.dedup();
+            changed.extend(
+                fields
+                    .into_iter()
+                    .filter(|f| old_proxy.get(*f) != new_proxy.get(*f))
+                    .map(|f| format!("proxy.{}", f)),
+            );
+        } else if old.get(key) != new.get(key) {
+            changed.push(key.clone());
+        }
+    }
+    changed
+}
+
+/// 应用新配置：原子替换快照，同步运行时状态，必要时重新绑定监听器
+pub async fn apply_config(state: &AppState, config: AppConfig) -> Result<ReloadReport, String> {
+    let _guard = apply_lock().lock().await;
+    apply_config_locked(state, config).await
+}
+
+async fn apply_config_locked(state: &AppState, config: AppConfig) -> Result<ReloadReport, String> {
+    let previous = state.config.load_full();
+    let mut report = ReloadReport {
+        changed: changed_sections(&previous, &config),
+        ..Default::default()
+    };
+    let config = Arc::new(config);
+    state.config.store(config.clone());
+    let proxy = &config.proxy;
+
+    // 上游代理 (仅在变化时重建客户端，避免无谓地丢弃连接池)
+    if report.has("proxy.upstream_proxy") {
+        state
+            .upstream
+            .rebuild_default_client(Some(proxy.upstream_proxy.clone()))
+            .await;
+        state.upstream.clear_client_cache();
+    }
+
+    // 实验性配置中的全局开关
+    crate::proxy::signature_cache::apply_persistence_config(
+        proxy.experimental.enable_persistent_signature_cache,
+    );
+    crate::proxy::config::update_global_compression_level(
+        proxy.experimental.compression_level.clone(),
+        proxy.experimental.enable_usage_scaling,
+    );
+    crate::proxy::config::update_global_thresholds(
+        proxy.experimental.context_compression_threshold_l1,
+        proxy.experimental.context_compression_threshold_l2,
+        proxy.experimental.context_compression_threshold_l3,
+    );
+
+    // 上游客户端的调试日志 (夹具录制 / 上游地址覆盖) 与 User-Agent
+    state
+        .upstream
+        .set_debug_logging(proxy.debug_logging.clone())
+        .await;
+    state
+        .upstream
+        .set_user_agent_override(proxy.user_agent_override.clone())
+        .await;
+
+    // 全局请求变换配置
+    crate::proxy::update_thinking_budget_config(proxy.thinking_budget.clone());
+    crate::proxy::update_global_system_prompt_config(proxy.global_system_prompt.clone());
+    crate::proxy::update_image_thinking_mode(proxy.image_thinking_mode.clone());
+    state.monitor.set_enabled(proxy.enable_logging);
+
+    // OpenTelemetry 导出器重建代价较高，仅在变化时更新
+    if report.has("proxy.telemetry") {
+        crate::proxy::telemetry::apply_config(&proxy.telemetry);
+    }
+
+    // 代理池 (配置从快照读取，这里只重建账号绑定与客户端缓存)
+    if report.has("proxy.proxy_pool") {
+        state.proxy_pool_manager.sync_bindings_from_config().await;
+        state.upstream.clear_client_cache();
+    }
+
+    // 调度与熔断
+    state
+        .token_manager
+        .update_sticky_config(proxy.scheduling.clone())
+        .await;
+    state
+        .token_manager
+        .update_circuit_breaker_config(config.circuit_breaker.clone())
+        .await;
+
+    // 监听地址：仅在地址或端口变化时重新绑定
+    state.listener.set_reuse_port(proxy.shutdown.reuse_port);
+    let desired = format!("{}:{}", proxy.get_bind_address(), proxy.port);
+    if let Some(current) = state.listener.requested_addr().await {
+        if current != desired {
+            match state.listener.bind(&desired).await {
+                Ok(_) => report.rebound = Some(desired),
+                Err(e) => {
+                    warn!(
+                        "[HotReload] 监听器重新绑定失败，继续使用 {}: {}",
+                        current, e
+                    );
+                    report.rebind_error = Some(e);
+                }
+            }
+        }
+    }
+
+    if !report.changed.is_empty() {
+        info!("[HotReload] 配置已热更新: {}", report.changed.join(", "));
+    }
+    Ok(report)
+}
+
+/// 从磁盘重新加载配置 (含声明式配置文件)，有变化时应用
+pub async fn reload_from_disk(state: &AppState) -> Result<Option<ReloadReport>, String> {
+    let config = tokio::task::spawn_blocking(|| {
+        if crate::modules::managed_config::config_file_path().is_some() {
+            crate::modules::managed_config::reload()?;
+        }
+        crate::modules::config::load_app_config()
+    })
+    .await
+    .map_err(|e| e.to_string())??;
+
+    let _guard = apply_lock().lock().await;
+    if comparable(&config) == comparable(&state.config.load()) {
+        return Ok(None);
+    }
+    apply_config_locked(state, config).await.map(Some)
+}
+
+type Fingerprint = Option<(SystemTime, u64)>;
+
+fn watched_paths() -> Vec<PathBuf> {
+    crate::modules::config::get_config_path()
+        .ok()
+        .into_iter()
+        .chain(crate::modules::managed_config::config_file_path())
+        .collect()
+}
+
+fn fingerprint(paths: &[PathBuf]) -> Vec<Fingerprint> {
+    paths
+        .iter()
+        .map(|p| {
+            std::fs::metadata(p)
+                .ok()
+                .and_then(|m| Some((m.modified().ok()?, m.len())))
+        })
+        .collect()
+}
+
+/// 监听配置文件变化并自动热更新
+pub fn spawn_config_watcher(state: AppState) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let paths = watched_paths();
+        let mut last = fingerprint(&paths);
+        let mut ticker = tokio::time::interval(WATCH_INTERVAL);
+        ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            ticker.tick().await;
+            let current = fingerprint(&paths);
+            if current == last {
+                continue;
+            }
+            last = current;
+            match reload_from_disk(&state).await {
+                Ok(Some(report)) => debug!("[HotReload] 配置文件变化已应用: {:?}", report),
+                Ok(None) => {}
+                Err(e) => warn!("[HotReload] 配置文件重新加载失败，保留当前配置: {}", e),
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    #[test]
+    fn test_changed_sections_lists_proxy_fields() {
+        let old = AppConfig::new();
+        let mut new = old.clone();
+        new.proxy.port = old.proxy.port + 1;
+        new.proxy
+            .custom_mapping
+            .insert("gpt-4o".to_string(), "gemini-2.5-pro".to_string());
+        new.language = "ja".to_string();
+
+        let changed = changed_sections(&old, &new);
+        assert_eq!(
+            changed,
+            vec!["language", "proxy.custom_mapping", "proxy.port"]
+        );
+        assert!(changed_sections(&new, &new.clone()).is_empty());
+    }
+
+    async fn get(addr: SocketAddr) -> String {
+        let mut stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+        stream
+            .write_all(b"GET / HTTP/1.1\r\nHost: test\r\nConnection: close\r\n\r\n")
+            .await
+            .unwrap();
+        let mut body = String::new();
+        stream.read_to_string(&mut body).await.unwrap();
+        body
+    }
+
+    #[tokio::test]
+    async fn test_rebind_drains_in_flight_requests() {
+        let (release_tx, release_rx) = watch::channel(false);
+        let app = Router::new().route(
+            "/",
+            axum::routing::get(move || {
+                let mut release_rx = release_rx.clone();
+                async move {
+                    let _ = release_rx.wait_for(|r| *r).await;
+                    "done"
+                }
+            }),
+        );
+        let control = ListenerControl::new();
+        control.set_app(app);
+        control.bind("127.0.0.1:0").await.unwrap();
+        let old_addr = control.local_addr().await.unwrap();
+
+        // 旧监听器上有一个进行中的请求
+        let in_flight = tokio::spawn(get(old_addr));
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        control.bind("127.0.0.1:0").await.unwrap();
+        let new_addr = control.local_addr().await.unwrap();
+        assert_ne!(old_addr, new_addr);
+
+        // 旧地址不再接收新连接
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(tokio::net::TcpStream::connect(old_addr).await.is_err());
+
+        // 进行中的请求正常完成
+        release_tx.send(true).unwrap();
+        assert!(in_flight.await.unwrap().ends_with("done"));
+        assert!(get(new_addr).await.ends_with("done"));
+
+        control.stop().await;
+        assert!(control.local_addr().await.is_none());
+    }
+}
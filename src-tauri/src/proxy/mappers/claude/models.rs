@@ -12,6 +12,9 @@ pub struct ClaudeRequest {
     pub system: Option<SystemPrompt>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub tools: Option<Vec<Tool>>,
+    /// 工具选择策略 (auto / any / tool / none)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_choice: Option<ToolChoice>,
     #[serde(default)]
     pub stream: bool,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -36,6 +39,29 @@ pub struct ClaudeRequest {
     pub quality: Option<String>,
 }
 
+impl ClaudeRequest {
+    /// tool_choice 强制指定的客户端工具 (需带 input_schema)，其输入按 Schema 校验
+    pub fn forced_tool(&self) -> Option<&Tool> {
+        let Some(ToolChoice::Tool { name }) = &self.tool_choice else {
+            return None;
+        };
+        self.tools
+            .as_ref()?
+            .iter()
+            .find(|t| t.name.as_deref() == Some(name.as_str()) && t.input_schema.is_some())
+    }
+}
+
+/// Tool Choice
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ToolChoice {
+    Auto,
+    Any,
+    Tool { name: String },
+    None,
+}
+
 /// Thinking 配置
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ThinkingConfig {
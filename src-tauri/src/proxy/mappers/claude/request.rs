@@ -638,18 +638,31 @@ pub fn transform_claude_request_in(
     if let Some(tools_val) = tools {
         inner_request["tools"] = tools_val;
         // 显式设置工具配置模式为 VALIDATED 并开启 includeServerSideToolInvocations (同时支持 camelCase 与 snake_case 以对齐 Google v1internal 接口)
+        // [NEW] tool_choice: any/tool -> ANY (tool 时限定 allowedFunctionNames), none -> NONE
+        let (mode, allowed_names) = match &claude_req.tool_choice {
+            Some(ToolChoice::Any) => ("ANY", None),
+            Some(ToolChoice::Tool { name }) => ("ANY", Some(vec![name.clone()])),
+            Some(ToolChoice::None) => ("NONE", None),
+            Some(ToolChoice::Auto) | None => ("VALIDATED", None),
+        };
         inner_request["toolConfig"] = json!({
             "functionCallingConfig": {
-                "mode": "VALIDATED"
+                "mode": mode
             },
             "includeServerSideToolInvocations": true
         });
         inner_request["tool_config"] = json!({
             "function_calling_config": {
-                "mode": "VALIDATED"
+                "mode": mode
             },
             "include_server_side_tool_invocations": true
         });
+        if let Some(names) = allowed_names {
+            inner_request["toolConfig"]["functionCallingConfig"]["allowedFunctionNames"] =
+                json!(names);
+            inner_request["tool_config"]["function_calling_config"]["allowed_function_names"] =
+                json!(names);
+        }
     }
 
     // 深度清理 [undefined] 字符串 (Cherry Studio 等客户端常见注入)
@@ -2330,6 +2343,7 @@ mod tests {
             thinking: None,
             metadata: None,
             output_config: None,
+            tool_choice: None,
             size: None,
             quality: None,
         };
@@ -2343,6 +2357,35 @@ mod tests {
         assert!(body["requestId"].as_str().unwrap().starts_with("agent/"));
     }
 
+    #[test]
+    fn test_forced_tool_choice_maps_to_any_mode() {
+        let req: ClaudeRequest = serde_json::from_value(json!({
+            "model": "gemini-2.5-flash",
+            "messages": [{"role": "user", "content": "Extract the city"}],
+            "tools": [{
+                "name": "record_city",
+                "input_schema": {
+                    "type": "object",
+                    "properties": {"city": {"type": "string"}},
+                    "required": ["city"]
+                }
+            }],
+            "tool_choice": {"type": "tool", "name": "record_city", "disable_parallel_tool_use": true}
+        }))
+        .unwrap();
+        assert_eq!(
+            req.forced_tool().and_then(|t| t.name.as_deref()),
+            Some("record_city")
+        );
+
+        let body =
+            transform_claude_request_in(&req, "test-project", false, None, "test_session", None)
+                .unwrap();
+        let calling_config = &body["request"]["toolConfig"]["functionCallingConfig"];
+        assert_eq!(calling_config["mode"], "ANY");
+        assert_eq!(calling_config["allowedFunctionNames"], json!(["record_city"]));
+    }
+
     #[test]
     fn test_clean_json_schema() {
         let mut schema = json!({
@@ -2428,6 +2471,7 @@ mod tests {
             thinking: None,
             metadata: None,
             output_config: None,
+            tool_choice: None,
             size: None,
             quality: None,
         };
@@ -2499,6 +2543,7 @@ mod tests {
             thinking: None,
             metadata: None,
             output_config: None,
+            tool_choice: None,
             size: None,
             quality: None,
         };
@@ -2574,6 +2619,7 @@ mod tests {
             }),
             metadata: None,
             output_config: None,
+            tool_choice: None,
             size: None,
             quality: None,
         };
@@ -2625,6 +2671,7 @@ mod tests {
             thinking: None, // 未启用 thinking
             metadata: None,
             output_config: None,
+            tool_choice: None,
             size: None,
             quality: None,
         };
@@ -2682,6 +2729,7 @@ mod tests {
             }),
             metadata: None,
             output_config: None,
+            tool_choice: None,
             size: None,
             quality: None,
         };
@@ -2731,6 +2779,7 @@ mod tests {
             thinking: None,
             metadata: None,
             output_config: None,
+            tool_choice: None,
             size: None,
             quality: None,
         };
@@ -2923,6 +2972,7 @@ mod tests {
             thinking: None,
             metadata: None,
             output_config: None,
+            tool_choice: None,
             size: None,
             quality: None,
         };
@@ -2961,6 +3011,7 @@ mod tests {
             tools: None,
             metadata: None,
             output_config: None,
+            tool_choice: None,
             size: None,
             quality: None,
         };
@@ -2990,6 +3041,7 @@ mod tests {
             tools: None,
             metadata: None,
             output_config: None,
+            tool_choice: None,
             size: None,
             quality: None,
         };
@@ -3027,6 +3079,7 @@ mod tests {
             tools: None,
             metadata: None,
             output_config: None,
+            tool_choice: None,
             size: None,
             quality: None,
         };
@@ -3068,6 +3121,7 @@ mod tests {
             tools: None,
             metadata: None,
             output_config: None,
+            tool_choice: None,
             size: None,
             quality: None,
         };
@@ -3106,6 +3160,7 @@ mod tests {
             tools: None,
             metadata: None,
             output_config: None,
+            tool_choice: None,
             size: Some("1024x1024".to_string()),
             quality: Some("hd".to_string()),
         };
@@ -3157,6 +3212,7 @@ mod tests {
             tools: None,
             metadata: None,
             output_config: None,
+            tool_choice: None,
             size: None,
             quality: None,
         };
@@ -3213,6 +3269,7 @@ mod tests {
             thinking: None,
             metadata: None,
             output_config: None,
+            tool_choice: None,
             size: None,
             quality: None,
         };
@@ -3274,6 +3331,7 @@ mod tests {
             thinking: None,
             metadata: None,
             output_config: None,
+            tool_choice: None,
             size: None,
             quality: None,
         };
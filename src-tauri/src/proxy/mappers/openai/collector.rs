@@ -26,6 +26,8 @@ where
     let mut content_parts: Vec<String> = Vec::new();
     let mut reasoning_parts: Vec<String> = Vec::new();
     let mut finish_reason: Option<String> = None;
+    // Logprobs aggregation (None when upstream never returned logprobs)
+    let mut logprob_entries: Option<Vec<Value>> = None;
     // Tool calls aggregation: index -> (id, type, name, arguments_parts)
     let mut tool_calls_map: HashMap<u32, (String, String, String, Vec<String>)> = HashMap::new();
 
@@ -161,6 +163,16 @@ where
                             if let Some(fr) = choice.get("finish_reason").and_then(|v| v.as_str()) {
                                 finish_reason = Some(fr.to_string());
                             }
+
+                            if let Some(entries) = choice
+                                .get("logprobs")
+                                .and_then(|l| l.get("content"))
+                                .and_then(|c| c.as_array())
+                            {
+                                logprob_entries
+                                    .get_or_insert_with(Vec::new)
+                                    .extend(entries.iter().cloned());
+                            }
                         }
                     }
                 }
@@ -216,6 +228,7 @@ where
     response.choices.push(Choice {
         index: 0,
         message,
+        logprobs: logprob_entries.map(|content| serde_json::json!({ "content": content })),
         finish_reason: finish_reason.or(Some("stop".to_string())),
     });
 
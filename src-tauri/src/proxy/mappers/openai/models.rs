@@ -48,6 +48,73 @@ pub struct OpenAIRequest {
     // [NEW] Direct imageSize support (for Gemini native parameter)
     #[serde(default, rename = "imageSize")]
     pub image_size: Option<String>,
+    // [NEW] 流式选项 (include_usage: 结束前追加仅含 usage 的 chunk)
+    #[serde(default)]
+    pub stream_options: Option<StreamOptions>,
+    // [NEW] logprobs: Chat API 为 bool, 旧版 Completions API 为整数
+    #[serde(default)]
+    pub logprobs: Option<Value>,
+    #[serde(default)]
+    pub top_logprobs: Option<u32>,
+}
+
+/// OpenAI 单次请求允许的 top_logprobs 上限
+pub const MAX_TOP_LOGPROBS: u32 = 20;
+
+impl OpenAIRequest {
+    /// 流式响应是否需要在结束前追加 usage chunk
+    pub fn include_usage(&self) -> bool {
+        self.stream_options
+            .as_ref()
+            .is_some_and(|opts| opts.include_usage)
+    }
+
+    /// strict 模式下的 json_schema，非空时输出需按该 Schema 校验
+    pub fn strict_json_schema(&self) -> Option<&Value> {
+        let format = self.response_format.as_ref()?;
+        if format.r#type != "json_schema" {
+            return None;
+        }
+        let spec = format.json_schema.as_ref()?;
+        if spec.strict == Some(true) {
+            spec.schema.as_ref()
+        } else {
+            None
+        }
+    }
+
+    /// 解析 logprobs / top_logprobs 参数
+    /// 返回 None 表示未请求; Some(n) 表示请求 logprobs 且每个位置返回 n 个候选
+    pub fn requested_logprobs(&self) -> Result<Option<u32>, String> {
+        let top = match &self.logprobs {
+            None | Some(Value::Null) | Some(Value::Bool(false)) => {
+                if self.top_logprobs.is_some_and(|n| n > 0) {
+                    return Err("top_logprobs requires logprobs to be set to true".to_string());
+                }
+                return Ok(None);
+            }
+            Some(Value::Bool(true)) => self.top_logprobs.unwrap_or(0),
+            // 旧版 Completions API: logprobs 为整数, 表示候选数量
+            Some(Value::Number(n)) => match n.as_u64() {
+                Some(n) => n.min(u32::MAX as u64) as u32,
+                None => return Err("logprobs must be a non-negative integer".to_string()),
+            },
+            Some(_) => return Err("logprobs must be a boolean or an integer".to_string()),
+        };
+        if top > MAX_TOP_LOGPROBS {
+            return Err(format!(
+                "top_logprobs must be less than or equal to {}",
+                MAX_TOP_LOGPROBS
+            ));
+        }
+        Ok(Some(top))
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct StreamOptions {
+    #[serde(default)]
+    pub include_usage: bool,
 }
 
 /// Thinking 配置 (兼容 Anthropic 和 OpenAI 扩展协议)
@@ -64,6 +131,20 @@ pub struct ThinkingConfig {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ResponseFormat {
     pub r#type: String,
+    // [NEW] 结构化输出: type = "json_schema" 时携带
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub json_schema: Option<JsonSchemaFormat>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JsonSchemaFormat {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub schema: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub strict: Option<bool>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
@@ -158,6 +239,8 @@ pub struct OpenAIResponse {
 pub struct Choice {
     pub index: u32,
     pub message: OpenAIMessage,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub logprobs: Option<Value>,
     pub finish_reason: Option<String>,
 }
 
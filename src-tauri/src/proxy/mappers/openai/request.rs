@@ -180,6 +180,12 @@ pub fn extract_client_tool_names(tools: &Option<Vec<Value>>) -> std::collections
     names
 }
 
+/// 上游模型是否支持 responseLogprobs (仅 Gemini 文本模型)
+pub fn model_supports_logprobs(mapped_model: &str) -> bool {
+    let lower = mapped_model.to_lowercase();
+    lower.starts_with("gemini-") && !lower.contains("image")
+}
+
 pub fn transform_openai_request(
     request: &OpenAIRequest,
     project_id: &str,
@@ -738,6 +744,14 @@ pub fn transform_openai_request(
         gen_config["seed"] = json!(seed);
     }
 
+    // [NEW] logprobs -> responseLogprobs / logprobs (参数校验由 handler 负责)
+    if let Ok(Some(top_logprobs)) = request.requested_logprobs() {
+        gen_config["responseLogprobs"] = json!(true);
+        if top_logprobs > 0 {
+            gen_config["logprobs"] = json!(top_logprobs);
+        }
+    }
+
     // 为 thinking 模型注入 thinkingConfig (使用 thinkingBudget 而非 thinkingLevel)
     if actual_include_thinking {
         // [RESOLVE #1694] Check image thinking mode
@@ -875,6 +889,13 @@ pub fn transform_openai_request(
     if let Some(fmt) = &request.response_format {
         if fmt.r#type == "json_object" {
             gen_config["responseMimeType"] = json!("application/json");
+        } else if fmt.r#type == "json_schema" {
+            // [NEW] 结构化输出: json_schema -> responseSchema (展开 $ref/allOf)
+            gen_config["responseMimeType"] = json!("application/json");
+            if let Some(schema) = fmt.json_schema.as_ref().and_then(|s| s.schema.as_ref()) {
+                gen_config["responseSchema"] =
+                    crate::proxy::common::json_schema::build_response_schema(schema);
+            }
         }
     }
 
@@ -1802,4 +1823,83 @@ mod tests {
             "v1internal should avoid mixed Google Search when functionDeclarations present"
         );
     }
+
+    #[test]
+    fn test_logprobs_mapped_to_generation_config() {
+        let req: OpenAIRequest = serde_json::from_value(json!({
+            "model": "gemini-2.5-flash",
+            "messages": [{"role": "user", "content": "Hi"}],
+            "logprobs": true,
+            "top_logprobs": 3
+        }))
+        .unwrap();
+        assert_eq!(req.requested_logprobs(), Ok(Some(3)));
+
+        let (result, _, _, _) = transform_openai_request(&req, "proj", "gemini-2.5-flash", None);
+        let gen_config = &result["request"]["generationConfig"];
+        assert_eq!(gen_config["responseLogprobs"], true);
+        assert_eq!(gen_config["logprobs"], 3);
+    }
+
+    #[test]
+    fn test_logprobs_validation() {
+        let parse = |v: Value| serde_json::from_value::<OpenAIRequest>(v).unwrap();
+
+        // 旧版 Completions API 的整数形式
+        let legacy = parse(json!({"model": "m", "prompt": "p", "logprobs": 2}));
+        assert_eq!(legacy.requested_logprobs(), Ok(Some(2)));
+
+        let orphan = parse(json!({"model": "m", "top_logprobs": 2}));
+        assert!(orphan.requested_logprobs().is_err());
+
+        let too_many = parse(json!({"model": "m", "logprobs": true, "top_logprobs": 21}));
+        assert!(too_many.requested_logprobs().is_err());
+
+        let disabled = parse(json!({"model": "m", "logprobs": false}));
+        assert_eq!(disabled.requested_logprobs(), Ok(None));
+
+        assert!(model_supports_logprobs("gemini-2.5-flash"));
+        assert!(!model_supports_logprobs("gemini-3-pro-image"));
+        assert!(!model_supports_logprobs("claude-sonnet-4-5"));
+    }
+
+    #[test]
+    fn test_json_schema_response_format() {
+        let req: OpenAIRequest = serde_json::from_value(json!({
+            "model": "gemini-2.5-flash",
+            "messages": [{"role": "user", "content": "Extract"}],
+            "response_format": {
+                "type": "json_schema",
+                "json_schema": {
+                    "name": "person",
+                    "strict": true,
+                    "schema": {
+                        "type": "object",
+                        "properties": {"address": {"$ref": "#/$defs/Address"}},
+                        "required": ["address"],
+                        "additionalProperties": false,
+                        "$defs": {
+                            "Address": {
+                                "type": "object",
+                                "properties": {"city": {"type": "string"}},
+                                "required": ["city"]
+                            }
+                        }
+                    }
+                }
+            }
+        }))
+        .unwrap();
+        assert!(req.strict_json_schema().is_some());
+
+        let (result, _, _, _) = transform_openai_request(&req, "proj", "gemini-2.5-flash", None);
+        let gen_config = &result["request"]["generationConfig"];
+        assert_eq!(gen_config["responseMimeType"], "application/json");
+        let schema = &gen_config["responseSchema"];
+        assert!(schema.get("$defs").is_none());
+        assert_eq!(
+            schema["properties"]["address"]["properties"]["city"]["type"],
+            "string"
+        );
+    }
 }
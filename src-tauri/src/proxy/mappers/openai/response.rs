@@ -24,6 +24,81 @@ pub fn resolve_shell_tool_name(
         model_tool_name.to_string()
     }
 }
+/// 将 Gemini candidate 的 logprobsResult 转换为 OpenAI Chat 格式的 logprobs
+/// ({ "content": [{ token, logprob, bytes, top_logprobs }] })
+pub fn map_gemini_logprobs(candidate: &Value) -> Option<Value> {
+    let result = candidate.get("logprobsResult")?;
+    let chosen = result.get("chosenCandidates")?.as_array()?;
+    let top = result.get("topCandidates").and_then(|t| t.as_array());
+
+    let token_entry = |c: &Value| {
+        let token = c.get("token").and_then(|t| t.as_str()).unwrap_or("");
+        serde_json::json!({
+            "token": token,
+            "logprob": c.get("logProbability").and_then(|v| v.as_f64()).unwrap_or(0.0),
+            "bytes": token.as_bytes(),
+        })
+    };
+
+    let content: Vec<Value> = chosen
+        .iter()
+        .enumerate()
+        .map(|(i, c)| {
+            let mut entry = token_entry(c);
+            let alternatives: Vec<Value> = top
+                .and_then(|t| t.get(i))
+                .and_then(|t| t.get("candidates"))
+                .and_then(|c| c.as_array())
+                .map(|list| list.iter().map(token_entry).collect())
+                .unwrap_or_default();
+            entry["top_logprobs"] = Value::Array(alternatives);
+            entry
+        })
+        .collect();
+    Some(serde_json::json!({ "content": content }))
+}
+
+/// 将 OpenAI Chat 格式的 logprobs 转换为旧版 Completions API 格式
+/// ({ tokens, token_logprobs, top_logprobs, text_offset })，text_offset 按字符计数
+pub fn chat_logprobs_to_legacy(logprobs: &Value, initial_offset: usize) -> Value {
+    let entries = logprobs
+        .get("content")
+        .and_then(|c| c.as_array())
+        .cloned()
+        .unwrap_or_default();
+    let mut tokens = Vec::with_capacity(entries.len());
+    let mut token_logprobs = Vec::with_capacity(entries.len());
+    let mut top_logprobs = Vec::with_capacity(entries.len());
+    let mut text_offset = Vec::with_capacity(entries.len());
+    let mut offset = initial_offset;
+    for entry in &entries {
+        let token = entry.get("token").and_then(|t| t.as_str()).unwrap_or("");
+        tokens.push(Value::String(token.to_string()));
+        token_logprobs.push(entry.get("logprob").cloned().unwrap_or(Value::Null));
+        let alternatives: serde_json::Map<String, Value> = entry
+            .get("top_logprobs")
+            .and_then(|t| t.as_array())
+            .map(|list| {
+                list.iter()
+                    .filter_map(|alt| {
+                        let token = alt.get("token")?.as_str()?.to_string();
+                        Some((token, alt.get("logprob").cloned().unwrap_or(Value::Null)))
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        top_logprobs.push(Value::Object(alternatives));
+        text_offset.push(serde_json::json!(offset));
+        offset += token.chars().count();
+    }
+    serde_json::json!({
+        "tokens": tokens,
+        "token_logprobs": token_logprobs,
+        "top_logprobs": top_logprobs,
+        "text_offset": text_offset,
+    })
+}
+
 fn extract_apply_patch_input(args: &Value) -> String {
     if let Some(obj) = args.as_object() {
         if let Some(input) = obj.get("input").and_then(|v| v.as_str()) {
@@ -323,6 +398,7 @@ pub fn transform_openai_response(
                     name: None,
                     refusal: refusal_val,
                 },
+                logprobs: map_gemini_logprobs(candidate),
                 finish_reason: Some(finish_reason.to_string()),
             });
         }
@@ -347,6 +423,7 @@ pub fn transform_openai_response(
                     name: None,
                     refusal: Some(refusal_msg),
                 },
+                logprobs: None,
                 finish_reason: Some("content_filter".to_string()),
             });
         }
@@ -469,6 +546,35 @@ mod tests {
         assert_eq!(result.choices[0].finish_reason, Some("stop".to_string()));
     }
 
+    #[test]
+    fn test_logprobs_result_mapping() {
+        let gemini_resp = json!({
+            "candidates": [{
+                "content": {"parts": [{"text": "Yes"}]},
+                "finishReason": "STOP",
+                "logprobsResult": {
+                    "chosenCandidates": [{"token": "Yes", "logProbability": -0.1}],
+                    "topCandidates": [{"candidates": [
+                        {"token": "Yes", "logProbability": -0.1},
+                        {"token": "No", "logProbability": -2.4}
+                    ]}]
+                }
+            }]
+        });
+
+        let result = transform_openai_response(&gemini_resp, Some("session-123"), 1, None);
+        let logprobs = result.choices[0].logprobs.as_ref().expect("logprobs");
+        assert_eq!(logprobs["content"][0]["token"], "Yes");
+        assert_eq!(logprobs["content"][0]["bytes"], json!([89, 101, 115]));
+        assert_eq!(logprobs["content"][0]["top_logprobs"][1]["logprob"], -2.4);
+
+        let legacy = chat_logprobs_to_legacy(logprobs, 0);
+        assert_eq!(legacy["tokens"], json!(["Yes"]));
+        assert_eq!(legacy["token_logprobs"], json!([-0.1]));
+        assert_eq!(legacy["top_logprobs"][0]["No"], -2.4);
+        assert_eq!(legacy["text_offset"], json!([0]));
+    }
+
     #[test]
     fn test_usage_metadata_mapping() {
         let gemini_resp = json!({
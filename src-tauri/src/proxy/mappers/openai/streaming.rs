@@ -109,6 +109,7 @@ pub fn create_openai_sse_stream<S, E>(
     session_id: String,
     message_count: usize,
     client_tool_names: Option<std::collections::HashSet<String>>,
+    include_usage: bool,
 ) -> Pin<Box<dyn Stream<Item = Result<Bytes, String>> + Send>>
 where
     S: Stream<Item = Result<Bytes, E>> + Send + ?Sized + 'static,
@@ -124,6 +125,8 @@ where
     let stream = async_stream::stream! {
         let mut emitted_tool_calls = std::collections::HashSet::new();
         let mut final_usage: Option<super::models::OpenAIUsage> = None;
+        // [NEW] stream_options.include_usage: 最后一次 usageMetadata, 在 [DONE] 前单独发送
+        let mut trailing_usage: Option<super::models::OpenAIUsage> = None;
         let mut error_occurred = false;
         let mut tool_call_index = 0;
 
@@ -148,6 +151,9 @@ where
                                             let actual_data = if let Some(inner) = json.get_mut("response").map(|v| v.take()) { inner } else { json };
                                             if let Some(u) = actual_data.get("usageMetadata") {
                                                 final_usage = extract_usage_metadata(u);
+                                                if include_usage {
+                                                    trailing_usage = final_usage.clone();
+                                                }
                                             }
 
                                             if let Some(candidates) = actual_data.get("candidates").and_then(|c| c.as_array()) {
@@ -298,7 +304,8 @@ where
                                                         yield Ok::<Bytes, String>(Bytes::from(sse_out));
                                                     }
 
-                                                    if !content_out.is_empty() || finish_reason.is_some() {
+                                                    let logprobs = super::response::map_gemini_logprobs(candidate);
+                                                    if !content_out.is_empty() || finish_reason.is_some() || logprobs.is_some() {
                                                         let mut openai_chunk = json!({
                                                             "id": &stream_id,
                                                             "object": "chat.completion.chunk",
@@ -310,7 +317,11 @@ where
                                                                 "finish_reason": finish_reason
                                                             }]
                                                         });
-                                                        if finish_reason.is_some() {
+                                                        if let Some(logprobs) = logprobs {
+                                                            openai_chunk["choices"][0]["logprobs"] = logprobs;
+                                                        }
+                                                        // include_usage 时 usage 由结尾的独立 chunk 携带
+                                                        if finish_reason.is_some() && !include_usage {
                                                             if let Some(ref usage) = final_usage {
                                                                 openai_chunk["usage"] = serde_json::to_value(usage).unwrap();
                                                             }
@@ -365,6 +376,17 @@ where
         }
 
         if !error_occurred {
+            if let Some(usage) = trailing_usage {
+                let usage_chunk = json!({
+                    "id": &stream_id,
+                    "object": "chat.completion.chunk",
+                    "created": created_ts,
+                    "model": &model,
+                    "choices": [],
+                    "usage": usage
+                });
+                yield Ok::<Bytes, String>(Bytes::from(format!("data: {}\n\n", serde_json::to_string(&usage_chunk).unwrap_or_default())));
+            }
             yield Ok::<Bytes, String>(Bytes::from("data: [DONE]\n\n"));
         }
     };
@@ -376,6 +398,7 @@ pub fn create_legacy_sse_stream<S, E>(
     model: String,
     session_id: String,
     message_count: usize,
+    include_usage: bool,
 ) -> Pin<Box<dyn Stream<Item = Result<Bytes, String>> + Send>>
 where
     S: Stream<Item = Result<Bytes, E>> + Send + ?Sized + 'static,
@@ -395,7 +418,11 @@ where
 
     let stream = async_stream::stream! {
         let mut final_usage: Option<super::models::OpenAIUsage> = None;
+        // stream_options.include_usage: 最后一次 usageMetadata, 在 [DONE] 前单独发送
+        let mut trailing_usage: Option<super::models::OpenAIUsage> = None;
         let mut error_occurred = false;
+        // logprobs.text_offset 基于已输出文本的累计字符数
+        let mut text_offset = 0usize;
         let mut heartbeat_interval = tokio::time::interval(std::time::Duration::from_secs(15));
         heartbeat_interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
 
@@ -415,7 +442,12 @@ where
                                         if json_part == "[DONE]" { continue; }
                                         if let Ok(mut json) = serde_json::from_str::<Value>(json_part) {
                                             let actual_data = if let Some(inner) = json.get_mut("response").map(|v| v.take()) { inner } else { json };
-                                            if let Some(u) = actual_data.get("usageMetadata") { final_usage = extract_usage_metadata(u); }
+                                            if let Some(u) = actual_data.get("usageMetadata") {
+                                                final_usage = extract_usage_metadata(u);
+                                                if include_usage {
+                                                    trailing_usage = final_usage.clone();
+                                                }
+                                            }
 
                                             let mut content_out = String::new();
                                             if let Some(candidates) = actual_data.get("candidates").and_then(|c| c.as_array()) {
@@ -439,11 +471,19 @@ where
                                                 "STOP" => "stop", "MAX_TOKENS" => "length", "SAFETY" => "content_filter", _ => f,
                                             });
 
+                                            let logprobs = actual_data.get("candidates").and_then(|c| c.get(0)).and_then(super::response::map_gemini_logprobs).map(|l| {
+                                                let legacy = super::response::chat_logprobs_to_legacy(&l, text_offset);
+                                                text_offset += legacy["tokens"].as_array().map(|t| t.iter().filter_map(|v| v.as_str()).map(|s| s.chars().count()).sum()).unwrap_or(0);
+                                                legacy
+                                            });
                                             let mut legacy_chunk = json!({
                                                 "id": &stream_id, "object": "text_completion", "created": created_ts, "model": &model,
-                                                "choices": [{ "text": content_out, "index": 0, "logprobs": null, "finish_reason": finish_reason }]
+                                                "choices": [{ "text": content_out, "index": 0, "logprobs": logprobs, "finish_reason": finish_reason }]
                                             });
-                                            if let Some(ref usage) = final_usage { legacy_chunk["usage"] = serde_json::to_value(usage).unwrap(); }
+                                            // 与 Chat 流一致: 默认仅结束 chunk 携带 usage，include_usage 时由结尾的独立 chunk 携带
+                                            if finish_reason.is_some() && !include_usage {
+                                                if let Some(ref usage) = final_usage { legacy_chunk["usage"] = serde_json::to_value(usage).unwrap(); }
+                                            }
                                             if finish_reason.is_some() { final_usage = None; }
                                             yield Ok::<Bytes, String>(Bytes::from(format!("data: {}\n\n", serde_json::to_string(&legacy_chunk).unwrap_or_default())));
                                         }
@@ -471,6 +511,17 @@ where
             }
         }
         if !error_occurred {
+            if let Some(usage) = trailing_usage {
+                let usage_chunk = json!({
+                    "id": &stream_id,
+                    "object": "text_completion",
+                    "created": created_ts,
+                    "model": &model,
+                    "choices": [],
+                    "usage": usage
+                });
+                yield Ok::<Bytes, String>(Bytes::from(format!("data: {}\n\n", serde_json::to_string(&usage_chunk).unwrap_or_default())));
+            }
             yield Ok::<Bytes, String>(Bytes::from("data: [DONE]\n\n"));
         }
     };
@@ -1511,6 +1562,7 @@ mod tests {
             "test-session".to_string(),
             0,
             None,
+            false,
         );
 
         let mut chunks = Vec::new();
@@ -1560,6 +1612,131 @@ mod tests {
         assert!(found_finish, "Finish reason should be strictly 'stop'");
     }
 
+    #[tokio::test]
+    async fn test_openai_streaming_include_usage_trailing_chunk() {
+        let chunk_json = json!({
+            "candidates": [{
+                "finishReason": "STOP",
+                "content": { "parts": [{ "text": "Hi" }] },
+                "logprobsResult": {
+                    "chosenCandidates": [{ "token": "Hi", "logProbability": -0.25 }],
+                    "topCandidates": [{ "candidates": [
+                        { "token": "Hi", "logProbability": -0.25 },
+                        { "token": "Hello", "logProbability": -1.5 }
+                    ] }]
+                }
+            }],
+            "usageMetadata": {
+                "promptTokenCount": 5,
+                "candidatesTokenCount": 1,
+                "totalTokenCount": 6
+            }
+        });
+        let items: Vec<Result<Bytes, reqwest::Error>> =
+            vec![Ok(Bytes::from(format!("data: {}\n\n", chunk_json)))];
+
+        let mut openai_stream = create_openai_sse_stream(
+            Box::pin(stream::iter(items)),
+            "gemini-2.5-flash".to_string(),
+            "test-session".to_string(),
+            0,
+            None,
+            true,
+        );
+
+        let mut lines = Vec::new();
+        while let Some(Ok(bytes)) = openai_stream.next().await {
+            for line in String::from_utf8_lossy(&bytes).lines() {
+                if let Some(data) = line.strip_prefix("data: ") {
+                    lines.push(data.to_string());
+                }
+            }
+        }
+
+        assert_eq!(lines.last().map(String::as_str), Some("[DONE]"));
+        let chunks: Vec<Value> = lines[..lines.len() - 1]
+            .iter()
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect();
+
+        // 内容 chunk 不携带 usage，但带有映射后的 logprobs
+        let content_chunk = &chunks[0];
+        assert!(content_chunk.get("usage").is_none());
+        let logprobs = &content_chunk["choices"][0]["logprobs"]["content"][0];
+        assert_eq!(logprobs["token"], "Hi");
+        assert_eq!(logprobs["logprob"], -0.25);
+        assert_eq!(logprobs["top_logprobs"][1]["token"], "Hello");
+
+        // 最后一个 chunk 只包含 usage
+        let usage_chunk = chunks.last().unwrap();
+        assert_eq!(usage_chunk["choices"], json!([]));
+        assert_eq!(usage_chunk["usage"]["prompt_tokens"], 5);
+        assert_eq!(usage_chunk["usage"]["total_tokens"], 6);
+    }
+
+    #[tokio::test]
+    async fn test_legacy_streaming_usage_and_text_offset() {
+        let chunk = |text: &str, finish: Option<&str>| {
+            let mut candidate = json!({
+                "content": { "parts": [{ "text": text }] },
+                "logprobsResult": {
+                    "chosenCandidates": [{ "token": text, "logProbability": -0.5 }]
+                }
+            });
+            if let Some(reason) = finish {
+                candidate["finishReason"] = json!(reason);
+            }
+            json!({
+                "candidates": [candidate],
+                "usageMetadata": { "promptTokenCount": 3, "candidatesTokenCount": 2, "totalTokenCount": 5 }
+            })
+        };
+        let collect = |include_usage: bool| async move {
+            let items: Vec<Result<Bytes, reqwest::Error>> = vec![
+                Ok(Bytes::from(format!("data: {}\n\n", chunk("héllo", None)))),
+                Ok(Bytes::from(format!(
+                    "data: {}\n\n",
+                    chunk("!", Some("STOP"))
+                ))),
+            ];
+            let mut legacy_stream = create_legacy_sse_stream(
+                Box::pin(stream::iter(items)),
+                "gemini-2.5-flash".to_string(),
+                "test-session".to_string(),
+                0,
+                include_usage,
+            );
+            let mut chunks = Vec::new();
+            while let Some(Ok(bytes)) = legacy_stream.next().await {
+                for line in String::from_utf8_lossy(&bytes).lines() {
+                    if let Some(data) = line.strip_prefix("data: ") {
+                        if data != "[DONE]" {
+                            chunks.push(serde_json::from_str::<Value>(data).unwrap());
+                        }
+                    }
+                }
+            }
+            chunks
+        };
+
+        // 默认仅结束 chunk 携带 usage；text_offset 按字符累计 ("héllo" 为 5 个字符)
+        let chunks = collect(false).await;
+        assert_eq!(chunks.len(), 2);
+        assert!(chunks[0].get("usage").is_none());
+        assert_eq!(chunks[1]["usage"]["total_tokens"], 5);
+        assert_eq!(
+            chunks[1]["choices"][0]["logprobs"]["text_offset"],
+            json!([5])
+        );
+
+        // include_usage: 内容 chunk 不带 usage，结尾追加仅含 usage 的 chunk
+        let chunks = collect(true).await;
+        assert_eq!(chunks.len(), 3);
+        assert!(chunks[..2].iter().all(|c| c.get("usage").is_none()));
+        assert_eq!(chunks[2]["choices"], json!([]));
+        assert_eq!(chunks[2]["usage"]["prompt_tokens"], 3);
+    }
+
     #[tokio::test]
     async fn test_openai_streaming_reasoning_content() {
         // Chunk with thought part
@@ -1585,6 +1762,7 @@ mod tests {
             "test-session".to_string(),
             0,
             None,
+            false,
         );
 
         let mut chunks = Vec::new();
@@ -0,0 +1,421 @@
+//! Prometheus 指标导出 (/metrics)
+//!
+//! 请求计数与延迟直方图在 `ProxyMonitor::log_request` 中累计 (不受监控开关影响)，
+//! 上游端点降级次数在 `UpstreamClient` 中累计；限流锁定、账号配额与缓存命中率
+//! 在抓取时从 `TokenManager` / `CacheManager` 实时读取。输出为 Prometheus 文本格式 0.0.4。
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write as _;
+use std::sync::{Mutex, OnceLock};
+
+use crate::proxy::cache_manager::LayerStats;
+use crate::proxy::monitor::ProxyRequestLog;
+use crate::proxy::rate_limit::RateLimitSnapshot;
+
+pub const METRICS_CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+/// 延迟直方图桶 (毫秒)
+const LATENCY_BUCKETS_MS: [u64; 11] = [
+    100, 250, 500, 1_000, 2_500, 5_000, 10_000, 30_000, 60_000, 120_000, 300_000,
+];
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+struct RequestKey {
+    protocol: String,
+    model: String,
+    mapped_model: String,
+    account: String,
+    status: u16,
+}
+
+#[derive(Debug, Clone, Default)]
+struct LatencySeries {
+    count: u64,
+    sum_ms: u64,
+    /// 每个桶的非累积计数，渲染时再累加
+    buckets: [u64; LATENCY_BUCKETS_MS.len()],
+}
+
+impl LatencySeries {
+    fn observe(&mut self, duration_ms: u64) {
+        self.count += 1;
+        self.sum_ms += duration_ms;
+        if let Some(idx) = LATENCY_BUCKETS_MS.iter().position(|b| duration_ms <= *b) {
+            self.buckets[idx] += 1;
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+struct FallbackKey {
+    method: String,
+    endpoint: String,
+    status: String,
+}
+
+/// 进程级指标注册表
+#[derive(Default)]
+pub struct ProxyMetrics {
+    requests: Mutex<HashMap<RequestKey, LatencySeries>>,
+    fallbacks: Mutex<HashMap<FallbackKey, u64>>,
+}
+
+impl ProxyMetrics {
+    pub fn global() -> &'static ProxyMetrics {
+        static INSTANCE: OnceLock<ProxyMetrics> = OnceLock::new();
+        INSTANCE.get_or_init(ProxyMetrics::default)
+    }
+
+    /// 记录一次完成的代理请求
+    pub fn record_request(&self, log: &ProxyRequestLog) {
+        let key = RequestKey {
+            protocol: log.protocol.clone().unwrap_or_else(|| "other".to_string()),
+            model: log.model.clone().unwrap_or_default(),
+            mapped_model: log.mapped_model.clone().unwrap_or_default(),
+            account: log.account_email.clone().unwrap_or_default(),
+            status: log.status,
+        };
+        if let Ok(mut requests) = self.requests.lock() {
+            requests.entry(key).or_default().observe(log.duration);
+        }
+    }
+
+    /// 记录一次上游端点降级 (对应 `UpstreamCallResult::fallback_attempts` 中的一条)
+    pub fn record_fallback(&self, method: &str, endpoint: &str, status: Option<u16>) {
+        let key = FallbackKey {
+            method: method.to_string(),
+            endpoint: endpoint.to_string(),
+            status: status
+                .map(|s| s.to_string())
+                .unwrap_or_else(|| "network_error".to_string()),
+        };
+        if let Ok(mut fallbacks) = self.fallbacks.lock() {
+            *fallbacks.entry(key).or_insert(0) += 1;
+        }
+    }
+
+    /// 渲染所有指标
+    pub fn render(
+        &self,
+        rate_limits: &[RateLimitSnapshot],
+        account_emails: &HashMap<String, String>,
+        quotas: &[(String, HashMap<String, i32>)],
+        cache: &LayerStats,
+    ) -> String {
+        let mut out = String::new();
+        self.render_requests(&mut out);
+        self.render_fallbacks(&mut out);
+        render_rate_limits(&mut out, rate_limits, account_emails);
+        render_quotas(&mut out, quotas);
+        render_cache(&mut out, cache);
+        out
+    }
+
+    fn render_requests(&self, out: &mut String) {
+        let requests: BTreeMap<RequestKey, LatencySeries> = match self.requests.lock() {
+            Ok(r) => r.iter().map(|(k, v)| (k.clone(), v.clone())).collect(),
+            Err(_) => return,
+        };
+
+        out.push_str("# HELP antigravity_requests_total Total proxied requests.\n");
+        out.push_str("# TYPE antigravity_requests_total counter\n");
+        for (key, series) in &requests {
+            let _ = writeln!(
+                out,
+                "antigravity_requests_total{{{}}} {}",
+                request_labels(key),
+                series.count
+            );
+        }
+
+        out.push_str(
+            "# HELP antigravity_request_duration_seconds Time until the response headers were produced.\n",
+        );
+        out.push_str("# TYPE antigravity_request_duration_seconds histogram\n");
+        for (key, series) in &requests {
+            let labels = request_labels(key);
+            let mut cumulative = 0;
+            for (idx, bound) in LATENCY_BUCKETS_MS.iter().enumerate() {
+                cumulative += series.buckets[idx];
+                let _ = writeln!(
+                    out,
+                    "antigravity_request_duration_seconds_bucket{{{},le=\"{}\"}} {}",
+                    labels,
+                    format_seconds(*bound),
+                    cumulative
+                );
+            }
+            let _ = writeln!(
+                out,
+                "antigravity_request_duration_seconds_bucket{{{},le=\"+Inf\"}} {}",
+                labels, series.count
+            );
+            let _ = writeln!(
+                out,
+                "antigravity_request_duration_seconds_sum{{{}}} {}",
+                labels,
+                format_seconds(series.sum_ms)
+            );
+            let _ = writeln!(
+                out,
+                "antigravity_request_duration_seconds_count{{{}}} {}",
+                labels, series.count
+            );
+        }
+    }
+
+    fn render_fallbacks(&self, out: &mut String) {
+        let fallbacks: BTreeMap<FallbackKey, u64> = match self.fallbacks.lock() {
+            Ok(f) => f.iter().map(|(k, v)| (k.clone(), *v)).collect(),
+            Err(_) => return,
+        };
+
+        out.push_str(
+            "# HELP antigravity_upstream_fallbacks_total Upstream endpoint attempts that failed over to the next endpoint.\n",
+        );
+        out.push_str("# TYPE antigravity_upstream_fallbacks_total counter\n");
+        for (key, count) in &fallbacks {
+            let _ = writeln!(
+                out,
+                "antigravity_upstream_fallbacks_total{{method=\"{}\",endpoint=\"{}\",status=\"{}\"}} {}",
+                escape_label(&key.method),
+                escape_label(&key.endpoint),
+                escape_label(&key.status),
+                count
+            );
+        }
+    }
+}
+
+fn render_rate_limits(
+    out: &mut String,
+    rate_limits: &[RateLimitSnapshot],
+    account_emails: &HashMap<String, String>,
+) {
+    out.push_str("# HELP antigravity_rate_limit_lockouts Active rate-limit lockouts.\n");
+    out.push_str("# TYPE antigravity_rate_limit_lockouts gauge\n");
+    let _ = writeln!(out, "antigravity_rate_limit_lockouts {}", rate_limits.len());
+
+    out.push_str(
+        "# HELP antigravity_rate_limit_remaining_seconds Seconds until a lockout expires.\n",
+    );
+    out.push_str("# TYPE antigravity_rate_limit_remaining_seconds gauge\n");
+    for limit in rate_limits {
+        let account = account_emails
+            .get(&limit.account_id)
+            .map(String::as_str)
+            .unwrap_or(&limit.account_id);
+        let _ = writeln!(
+            out,
+            "antigravity_rate_limit_remaining_seconds{{account=\"{}\",model=\"{}\",reason=\"{}\"}} {}",
+            escape_label(account),
+            escape_label(limit.model.as_deref().unwrap_or("")),
+            limit.reason,
+            limit.remaining_sec
+        );
+    }
+}
+
+fn render_quotas(out: &mut String, quotas: &[(String, HashMap<String, i32>)]) {
+    out.push_str(
+        "# HELP antigravity_account_quota_remaining_percent Remaining model quota per account.\n",
+    );
+    out.push_str("# TYPE antigravity_account_quota_remaining_percent gauge\n");
+    for (email, models) in quotas {
+        let models: BTreeMap<&String, &i32> = models.iter().collect();
+        for (model, pct) in models {
+            let _ = writeln!(
+                out,
+                "antigravity_account_quota_remaining_percent{{account=\"{}\",model=\"{}\"}} {}",
+                escape_label(email),
+                escape_label(model),
+                pct
+            );
+        }
+    }
+}
+
+fn render_cache(out: &mut String, cache: &LayerStats) {
+    let layers = [
+        (
+            "si",
+            cache.si_hits,
+            cache.si_misses,
+            cache.active_si_entries,
+        ),
+        (
+            "tools",
+            cache.tools_hits,
+            cache.tools_misses,
+            cache.active_tools_entries,
+        ),
+        (
+            "prefix",
+            cache.prefix_hits,
+            cache.prefix_misses,
+            cache.active_prefix_entries,
+        ),
+    ];
+
+    out.push_str("# HELP antigravity_cache_lookups_total Context cache lookups by layer.\n");
+    out.push_str("# TYPE antigravity_cache_lookups_total counter\n");
+    for (layer, hits, misses, _) in &layers {
+        let _ = writeln!(
+            out,
+            "antigravity_cache_lookups_total{{layer=\"{}\",result=\"hit\"}} {}",
+            layer, hits
+        );
+        let _ = writeln!(
+            out,
+            "antigravity_cache_lookups_total{{layer=\"{}\",result=\"miss\"}} {}",
+            layer, misses
+        );
+    }
+
+    out.push_str("# HELP antigravity_cache_hit_ratio Context cache hit ratio by layer.\n");
+    out.push_str("# TYPE antigravity_cache_hit_ratio gauge\n");
+    for (layer, hits, misses, _) in &layers {
+        let total = hits + misses;
+        let ratio = if total == 0 {
+            0.0
+        } else {
+            *hits as f64 / total as f64
+        };
+        let _ = writeln!(
+            out,
+            "antigravity_cache_hit_ratio{{layer=\"{}\"}} {}",
+            layer, ratio
+        );
+    }
+
+    out.push_str("# HELP antigravity_cache_entries Active context cache entries by layer.\n");
+    out.push_str("# TYPE antigravity_cache_entries gauge\n");
+    for (layer, _, _, entries) in &layers {
+        let _ = writeln!(
+            out,
+            "antigravity_cache_entries{{layer=\"{}\"}} {}",
+            layer, entries
+        );
+    }
+}
+
+fn request_labels(key: &RequestKey) -> String {
+    format!(
+        "protocol=\"{}\",model=\"{}\",mapped_model=\"{}\",account=\"{}\",status=\"{}\"",
+        escape_label(&key.protocol),
+        escape_label(&key.model),
+        escape_label(&key.mapped_model),
+        escape_label(&key.account),
+        key.status
+    )
+}
+
+fn format_seconds(ms: u64) -> String {
+    format!("{}", ms as f64 / 1000.0)
+}
+
+/// Prometheus 标签值转义: 反斜杠、双引号、换行
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_log(status: u16, duration: u64) -> ProxyRequestLog {
+        ProxyRequestLog {
+            id: "id".to_string(),
+            timestamp: 0,
+            method: "POST".to_string(),
+            url: "/v1/chat/completions".to_string(),
+            status,
+            duration,
+            model: Some("gpt-4o".to_string()),
+            mapped_model: Some("gemini-3-flash".to_string()),
+            account_email: Some("a@example.com".to_string()),
+            client_ip: None,
+            error: None,
+            request_body: None,
+            response_body: None,
+            input_tokens: None,
+            output_tokens: None,
+            cached_tokens: None,
+            protocol: Some("openai".to_string()),
+            username: None,
+            reasoning_tokens: None,
+        }
+    }
+
+    #[test]
+    fn test_request_counter_and_histogram() {
+        let metrics = ProxyMetrics::default();
+        metrics.record_request(&sample_log(200, 80));
+        metrics.record_request(&sample_log(200, 1_200));
+        metrics.record_request(&sample_log(429, 50));
+
+        let out = metrics.render(&[], &HashMap::new(), &[], &LayerStats::default());
+        let labels = "protocol=\"openai\",model=\"gpt-4o\",mapped_model=\"gemini-3-flash\",account=\"a@example.com\"";
+        assert!(out.contains(&format!(
+            "antigravity_requests_total{{{},status=\"200\"}} 2",
+            labels
+        )));
+        assert!(out.contains(&format!(
+            "antigravity_requests_total{{{},status=\"429\"}} 1",
+            labels
+        )));
+        assert!(out.contains(&format!(
+            "antigravity_request_duration_seconds_bucket{{{},status=\"200\",le=\"0.1\"}} 1",
+            labels
+        )));
+        assert!(out.contains(&format!(
+            "antigravity_request_duration_seconds_bucket{{{},status=\"200\",le=\"2.5\"}} 2",
+            labels
+        )));
+        assert!(out.contains(&format!(
+            "antigravity_request_duration_seconds_sum{{{},status=\"200\"}} 1.28",
+            labels
+        )));
+    }
+
+    #[test]
+    fn test_gauges_and_escaping() {
+        let metrics = ProxyMetrics::default();
+        metrics.record_fallback("generateContent", "https://sandbox/v1internal", Some(503));
+        metrics.record_fallback("generateContent", "https://sandbox/v1internal", Some(503));
+        metrics.record_fallback("generateContent", "https://daily/v1internal", None);
+
+        let limits = vec![RateLimitSnapshot {
+            key: "acc1:claude".to_string(),
+            account_id: "acc1".to_string(),
+            model: Some("claude".to_string()),
+            reason: "QUOTA_EXHAUSTED",
+            reset_at: 0,
+            remaining_sec: 42,
+            detected_at: 0,
+            failure_count: 1,
+        }];
+        let emails = HashMap::from([("acc1".to_string(), "b@example.com".to_string())]);
+        let quotas = vec![(
+            "weird\"name".to_string(),
+            HashMap::from([("gemini-3-pro".to_string(), 35)]),
+        )];
+        let cache = LayerStats {
+            si_hits: 3,
+            si_misses: 1,
+            ..Default::default()
+        };
+
+        let out = metrics.render(&limits, &emails, &quotas, &cache);
+        assert!(out.contains("antigravity_upstream_fallbacks_total{method=\"generateContent\",endpoint=\"https://sandbox/v1internal\",status=\"503\"} 2"));
+        assert!(out.contains("status=\"network_error\"} 1"));
+        assert!(out.contains("antigravity_rate_limit_lockouts 1"));
+        assert!(out.contains("antigravity_rate_limit_remaining_seconds{account=\"b@example.com\",model=\"claude\",reason=\"QUOTA_EXHAUSTED\"} 42"));
+        assert!(out.contains("antigravity_account_quota_remaining_percent{account=\"weird\\\"name\",model=\"gemini-3-pro\"} 35"));
+        assert!(out.contains("antigravity_cache_hit_ratio{layer=\"si\"} 0.75"));
+        assert!(out.contains("antigravity_cache_hit_ratio{layer=\"tools\"} 0"));
+    }
+}
@@ -1,4 +1,6 @@
 // API Key 认证中间件
+use crate::proxy::hot_reload::ConfigSnapshot;
+use crate::proxy::{ProxyAuthMode, ProxySecurityConfig};
 use axum::{
     extract::Request,
     extract::State,
@@ -6,14 +8,11 @@ use axum::{
     middleware::Next,
     response::Response,
 };
-use std::sync::Arc;
-use tokio::sync::RwLock;
-
-use crate::proxy::{ProxyAuthMode, ProxySecurityConfig};
 
 /// API Key 认证中间件 (代理接口使用，遵循 auth_mode)
+#[tracing::instrument(name = "middleware.auth", skip_all)]
 pub async fn auth_middleware(
-    state: State<Arc<RwLock<ProxySecurityConfig>>>,
+    state: State<ConfigSnapshot>,
     request: Request,
     next: Next,
 ) -> Result<Response, StatusCode> {
@@ -22,7 +21,7 @@ pub async fn auth_middleware(
 
 /// 管理接口认证中间件 (管理接口使用，强制严格鉴权)
 pub async fn admin_auth_middleware(
-    state: State<Arc<RwLock<ProxySecurityConfig>>>,
+    state: State<ConfigSnapshot>,
     request: Request,
     next: Next,
 ) -> Result<Response, StatusCode> {
@@ -31,7 +30,7 @@ pub async fn admin_auth_middleware(
 
 /// 内部认证逻辑
 async fn auth_middleware_internal(
-    State(security): State<Arc<RwLock<ProxySecurityConfig>>>,
+    State(config): State<ConfigSnapshot>,
     request: Request,
     next: Next,
     force_strict: bool,
@@ -53,7 +52,7 @@ async fn auth_middleware_internal(
         return Ok(next.run(request).await);
     }
 
-    let security = security.read().await.clone();
+    let security = ProxySecurityConfig::from_proxy_config(&config.load().proxy);
     let effective_mode = security.effective_auth_mode();
 
     // 权限检查逻辑
@@ -185,6 +184,12 @@ async fn auth_middleware_internal(
                 if let Ok(Some(user_token)) =
                     crate::modules::user_token_db::get_token_by_value(token)
                 {
+                    // 令牌级限额 (模型白名单 / RPM / 日月 token 预算)，在分发前拦截
+                    let request = match enforce_token_limits(&user_token, request).await {
+                        Ok(request) => request,
+                        Err(response) => return Ok(response),
+                    };
+
                     let identity = UserTokenIdentity {
                         token_id: user_token.id,
                         token: user_token.token,
@@ -237,6 +242,130 @@ async fn auth_middleware_internal(
     }
 }
 
+/// 读取请求体大小上限 (与 monitor 中间件一致)
+const MAX_LIMIT_CHECK_BODY_SIZE: usize = 100 * 1024 * 1024;
+
+/// 检查 UserToken 限额，超限时返回协议对应格式的错误响应
+async fn enforce_token_limits(
+    user_token: &crate::modules::user_token_db::UserToken,
+    request: Request,
+) -> Result<Request, Response> {
+    let path = request.uri().path().to_string();
+
+    // 仅在配置了模型白名单时才需要解析请求体中的 model
+    let (request, model) = if user_token.allowed_models.is_empty() {
+        (request, None)
+    } else if let Some(model) = model_from_gemini_path(&path) {
+        (request, Some(model))
+    } else if request.method() == axum::http::Method::POST {
+        let (parts, body) = request.into_parts();
+        let bytes = axum::body::to_bytes(body, MAX_LIMIT_CHECK_BODY_SIZE)
+            .await
+            .map_err(|_| {
+                token_limit_response(
+                    &path,
+                    StatusCode::PAYLOAD_TOO_LARGE,
+                    "Request body too large",
+                    None,
+                )
+            })?;
+        let model = serde_json::from_slice::<serde_json::Value>(&bytes)
+            .ok()
+            .and_then(|v| {
+                v.get("model")
+                    .and_then(|m| m.as_str())
+                    .map(|s| s.to_string())
+            });
+        (
+            Request::from_parts(parts, axum::body::Body::from(bytes)),
+            model,
+        )
+    } else {
+        (request, None)
+    };
+
+    match crate::modules::user_token_db::check_token_limits(user_token, model.as_deref()) {
+        Ok(None) => Ok(request),
+        Ok(Some(violation)) => {
+            tracing::warn!(
+                "UserToken {} limit exceeded: {:?}",
+                user_token.username,
+                violation
+            );
+            let status = match violation {
+                crate::modules::user_token_db::TokenLimitViolation::ModelNotAllowed(_) => {
+                    StatusCode::FORBIDDEN
+                }
+                _ => StatusCode::TOO_MANY_REQUESTS,
+            };
+            Err(token_limit_response(
+                &path,
+                status,
+                &violation.message(),
+                violation.retry_after_secs(),
+            ))
+        }
+        Err(e) => {
+            // 统计查询失败时不阻断请求，避免数据库异常导致全部用户不可用
+            tracing::error!("UserToken limit check failed: {}", e);
+            Ok(request)
+        }
+    }
+}
+
+fn model_from_gemini_path(path: &str) -> Option<String> {
+    path.split("/v1beta/models/")
+        .nth(1)
+        .and_then(|s| s.split([':', '/']).next())
+        .filter(|s| !s.is_empty())
+        .map(|s| s.to_string())
+}
+
+/// 按客户端协议构造限额错误响应 (OpenAI / Anthropic / Gemini)
+fn token_limit_response(
+    path: &str,
+    status: StatusCode,
+    message: &str,
+    retry_after_secs: Option<i64>,
+) -> Response {
+    let is_quota = status == StatusCode::TOO_MANY_REQUESTS;
+    let body = if path.starts_with("/v1/messages") {
+        serde_json::json!({
+            "type": "error",
+            "error": {
+                "type": if is_quota { "rate_limit_error" } else { "permission_error" },
+                "message": message
+            }
+        })
+    } else if path.starts_with("/v1beta/") {
+        serde_json::json!({
+            "error": {
+                "code": status.as_u16(),
+                "message": message,
+                "status": if is_quota { "RESOURCE_EXHAUSTED" } else { "PERMISSION_DENIED" }
+            }
+        })
+    } else {
+        serde_json::json!({
+            "error": {
+                "message": message,
+                "type": if is_quota { "rate_limit_exceeded" } else { "permission_denied" },
+                "code": if is_quota { "token_limit_exceeded" } else { "model_not_allowed" }
+            }
+        })
+    };
+
+    let mut builder = axum::response::Response::builder()
+        .status(status)
+        .header("Content-Type", "application/json");
+    if let Some(secs) = retry_after_secs {
+        builder = builder.header("Retry-After", secs.to_string());
+    }
+    builder
+        .body(axum::body::Body::from(body.to_string()))
+        .unwrap()
+}
+
 /// 用户令牌身份信息 (传递给 Monitor 使用)
 #[derive(Clone, Debug)]
 pub struct UserTokenIdentity {
@@ -250,6 +379,8 @@ pub struct UserTokenIdentity {
 mod tests {
     use super::*;
     use crate::proxy::ProxyAuthMode;
+    use std::sync::Arc;
+    use tokio::sync::RwLock;
 
     #[tokio::test]
     async fn test_admin_auth_with_password() {
@@ -258,8 +389,6 @@ mod tests {
             api_key: "sk-api".to_string(),
             admin_password: Some("admin123".to_string()),
             allow_lan_access: true,
-            port: 8045,
-            security_monitor: crate::proxy::config::SecurityMonitorConfig::default(),
         }));
 
         // 模拟请求 - 管理接口使用正确的管理密码
@@ -277,4 +406,44 @@ mod tests {
     fn test_auth_placeholder() {
         assert!(true);
     }
+
+    #[test]
+    fn test_model_from_gemini_path() {
+        assert_eq!(
+            model_from_gemini_path("/v1beta/models/gemini-3-flash:generateContent"),
+            Some("gemini-3-flash".to_string())
+        );
+        assert_eq!(
+            model_from_gemini_path("/v1beta/models/gemini-3-pro/countTokens"),
+            Some("gemini-3-pro".to_string())
+        );
+        assert_eq!(model_from_gemini_path("/v1/chat/completions"), None);
+    }
+
+    #[tokio::test]
+    async fn test_token_limit_response_matches_protocol() {
+        let read = |resp: Response| async move {
+            let bytes = axum::body::to_bytes(resp.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            serde_json::from_slice::<serde_json::Value>(&bytes).unwrap()
+        };
+
+        let resp =
+            token_limit_response("/v1/messages", StatusCode::TOO_MANY_REQUESTS, "x", Some(30));
+        assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(resp.headers().get("Retry-After").unwrap(), "30");
+        assert_eq!(read(resp).await["error"]["type"], "rate_limit_error");
+
+        let resp = token_limit_response(
+            "/v1beta/models/gemini-3-flash:generateContent",
+            StatusCode::TOO_MANY_REQUESTS,
+            "x",
+            None,
+        );
+        assert_eq!(read(resp).await["error"]["status"], "RESOURCE_EXHAUSTED");
+
+        let resp = token_limit_response("/v1/chat/completions", StatusCode::FORBIDDEN, "x", None);
+        assert_eq!(read(resp).await["error"]["code"], "model_not_allowed");
+    }
 }
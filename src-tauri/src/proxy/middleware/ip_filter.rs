@@ -8,6 +8,7 @@ use axum::{
 };
 
 /// IP 黑白名单过滤中间件
+#[tracing::instrument(name = "middleware.ip_filter", skip_all)]
 pub async fn ip_filter_middleware(
     State(state): State<AppState>,
     request: Request,
@@ -18,7 +19,8 @@ pub async fn ip_filter_middleware(
 
     if let Some(ip) = &client_ip {
         // 读取安全配置
-        let security_config = state.security.read().await;
+        let config = state.config.load_full();
+        let security_config = &config.proxy;
 
         // 1. 检查白名单 (如果启用白名单模式,只允许白名单 IP)
         if security_config.security_monitor.whitelist.enabled {
@@ -7,9 +7,11 @@ pub mod logging;
 pub mod monitor;
 
 pub mod service_status;
+pub mod telemetry;
 
 pub use auth::{admin_auth_middleware, auth_middleware};
 pub use cors::cors_layer;
 pub use ip_filter::ip_filter_middleware;
 pub use monitor::monitor_middleware;
 pub use service_status::service_status_middleware;
+pub use telemetry::telemetry_middleware;
@@ -342,6 +342,52 @@ fn extract_output_tokens(usage: &Value) -> Option<u32> {
     }
 }
 
+/// 从非流式 JSON 响应中提取用量 (支持 OpenAI "usage" 或 Gemini "usageMetadata")
+fn apply_response_usage(log: &mut ProxyRequestLog, json: &Value) {
+    let Some(usage) = json
+        .get("usage")
+        .or(json.get("usageMetadata"))
+        .or(json.get("response").and_then(|r| r.get("usage")))
+        .or(json.get("response").and_then(|r| r.get("usageMetadata")))
+    else {
+        return;
+    };
+    log.input_tokens = extract_input_tokens(usage);
+    log.output_tokens = extract_output_tokens(usage);
+    log.cached_tokens = log.cached_tokens.or_else(|| extract_cached_tokens(usage));
+    log.reasoning_tokens = log
+        .reasoning_tokens
+        .or_else(|| extract_reasoning_tokens(usage));
+
+    if log.input_tokens.is_none() && log.output_tokens.is_none() {
+        log.output_tokens = usage
+            .get("total_tokens")
+            .or(usage.get("totalTokenCount"))
+            .and_then(|v| v.as_u64())
+            .map(|v| v as u32);
+    }
+}
+
+/// 记录未经过 HTTP 中间件、由进程内部发起的请求 (如批处理逐行执行)，
+/// 与 JSON 响应路径一致地写入请求日志、token 统计与 User Token 用量
+pub(crate) async fn log_internal_request(
+    state: &AppState,
+    user_token_identity: Option<UserTokenIdentity>,
+    mut log: ProxyRequestLog,
+    response: &Value,
+) {
+    apply_response_usage(&mut log, response);
+    log.username = user_token_identity
+        .as_ref()
+        .map(|identity| identity.username.clone());
+    if log.status >= 400 {
+        log.error = log.response_body.clone();
+    }
+    record_user_token_usage(&user_token_identity, &log, None);
+    state.monitor.log_request(log).await;
+}
+
+#[tracing::instrument(name = "middleware.monitor", skip_all)]
 pub async fn monitor_middleware(
     State(state): State<AppState>,
     request: Request,
@@ -352,7 +398,11 @@ pub async fn monitor_middleware(
     let method = request.method().to_string();
     let uri = request.uri().to_string();
 
-    if uri.contains("event_logging") || uri.contains("/api/") || uri.starts_with("/internal/") {
+    if uri.contains("event_logging")
+        || uri.contains("/api/")
+        || uri.starts_with("/internal/")
+        || uri == "/metrics"
+    {
         return next.run(request).await;
     }
 
@@ -511,6 +561,7 @@ pub async fn monitor_middleware(
         cached_tokens: None,
         protocol,
         username,
+        reasoning_tokens: None,
     };
 
     if content_type.contains("text/event-stream") {
@@ -776,6 +827,7 @@ pub async fn monitor_middleware(
                             log.cached_tokens = log.cached_tokens.or(cached_tokens);
                             reasoning_tokens =
                                 reasoning_tokens.or_else(|| extract_reasoning_tokens(usage));
+                            log.reasoning_tokens = reasoning_tokens;
 
                             if log.input_tokens.is_none() && log.output_tokens.is_none() {
                                 log.output_tokens = usage
@@ -911,6 +963,9 @@ pub async fn monitor_middleware(
                                     log.output_tokens = extract_output_tokens(usage);
                                     log.cached_tokens =
                                         log.cached_tokens.or_else(|| extract_cached_tokens(usage));
+                                    log.reasoning_tokens = log
+                                        .reasoning_tokens
+                                        .or_else(|| extract_reasoning_tokens(usage));
                                     break;
                                 }
                             }
@@ -939,26 +994,7 @@ pub async fn monitor_middleware(
             Ok(bytes) => {
                 if let Ok(s) = std::str::from_utf8(&bytes) {
                     if let Ok(json) = serde_json::from_str::<Value>(&s) {
-                        // 支持 OpenAI "usage" 或 Gemini "usageMetadata"
-                        if let Some(usage) = json
-                            .get("usage")
-                            .or(json.get("usageMetadata"))
-                            .or(json.get("response").and_then(|r| r.get("usage")))
-                            .or(json.get("response").and_then(|r| r.get("usageMetadata")))
-                        {
-                            log.input_tokens = extract_input_tokens(usage);
-                            log.output_tokens = extract_output_tokens(usage);
-                            log.cached_tokens =
-                                log.cached_tokens.or_else(|| extract_cached_tokens(usage));
-
-                            if log.input_tokens.is_none() && log.output_tokens.is_none() {
-                                log.output_tokens = usage
-                                    .get("total_tokens")
-                                    .or(usage.get("totalTokenCount"))
-                                    .and_then(|v| v.as_u64())
-                                    .map(|v| v as u32);
-                            }
-                        }
+                        apply_response_usage(&mut log, &json);
                     }
                     if is_image_route {
                         log.response_body = serde_json::from_str::<Value>(&s)
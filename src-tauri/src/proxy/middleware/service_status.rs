@@ -1,7 +1,7 @@
 use crate::proxy::server::AppState;
 use axum::{
     extract::{Request, State},
-    http::StatusCode,
+    http::{header, StatusCode},
     middleware::Next,
     response::{IntoResponse, Response},
 };
@@ -24,12 +24,17 @@ pub async fn service_status_middleware(
     };
 
     if !running {
+        let retry_after = state.config.load().proxy.shutdown.retry_after_secs;
         return (
             StatusCode::SERVICE_UNAVAILABLE,
+            [(header::RETRY_AFTER, retry_after.to_string())],
             "Proxy service is currently disabled".to_string(),
         )
             .into_response();
     }
 
-    next.run(request).await
+    // 登记进行中请求，停止时据此排空
+    let guard = state.drain.track();
+    let response = next.run(request).await;
+    crate::proxy::drain::attach(response, guard)
 }
@@ -0,0 +1,95 @@
+// 链路追踪中间件 - 为每个代理请求创建 OpenTelemetry 根 span
+use axum::{extract::Request, http::HeaderMap, middleware::Next, response::Response};
+use opentelemetry::propagation::Extractor;
+use opentelemetry::trace::TraceContextExt;
+use tracing::field::Empty;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+struct HeaderExtractor<'a>(&'a HeaderMap);
+
+impl Extractor for HeaderExtractor<'_> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).and_then(|v| v.to_str().ok())
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.0.keys().map(|k| k.as_str()).collect()
+    }
+}
+
+fn header_str<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers.get(name).and_then(|v| v.to_str().ok())
+}
+
+/// 请求级追踪中间件 (最外层)
+/// 创建根 span `proxy.request`，支持从 W3C `traceparent` 续接调用方链路，
+/// 响应阶段补全状态码以及 `X-Account-Email` / `X-Mapped-Model` 对应的账号与实际模型
+pub async fn telemetry_middleware(request: Request, next: Next) -> Response {
+    let method = request.method().to_string();
+    let path = request.uri().path().to_string();
+
+    let span = tracing::info_span!(
+        "proxy.request",
+        otel.name = %format!("{} {}", method, path),
+        otel.kind = "server",
+        otel.status_code = Empty,
+        http.request.method = %method,
+        url.path = %path,
+        http.response.status_code = Empty,
+        trace_id = Empty,
+        session_id = Empty,
+        account = Empty,
+        mapped_model = Empty,
+        retry.attempt = Empty,
+        compression.layer = Empty,
+    );
+
+    let parent = opentelemetry::global::get_text_map_propagator(|propagator| {
+        propagator.extract(&HeaderExtractor(request.headers()))
+    });
+    if parent.span().span_context().is_valid() {
+        let _ = span.set_parent(parent);
+    }
+
+    let response = crate::proxy::telemetry::in_request_scope(span.clone(), next.run(request)).await;
+
+    let status = response.status();
+    span.record("http.response.status_code", status.as_u16());
+    if status.is_server_error() {
+        span.record("otel.status_code", "ERROR");
+    }
+    if let Some(account) = header_str(response.headers(), "X-Account-Email") {
+        span.record("account", account);
+    }
+    if let Some(mapped) = header_str(response.headers(), "X-Mapped-Model") {
+        span.record("mapped_model", mapped);
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_header_extractor_reads_traceparent() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            "traceparent",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01"
+                .parse()
+                .unwrap(),
+        );
+        let propagator = opentelemetry_sdk::propagation::TraceContextPropagator::new();
+        let cx = opentelemetry::propagation::TextMapPropagator::extract(
+            &propagator,
+            &HeaderExtractor(&headers),
+        );
+        let span_context = cx.span().span_context().clone();
+        assert!(span_context.is_valid());
+        assert_eq!(
+            span_context.trace_id().to_string(),
+            "4bf92f3577b34da6a3ce929d0e0e4736"
+        );
+    }
+}
@@ -9,14 +9,19 @@ pub mod token_manager;
 
 // 新架构模块
 pub mod audio; // 音频处理模块
+pub mod batch_runner; // OpenAI Batch API 执行器
+pub mod batch_store; // Batch API 作业队列 (batches.db)
 pub mod cache_manager; // Context Cache 管理 (前缀哈希 → cache_id 映射)
 pub mod cli_sync; // CLI 配置同步 (v3.3.35)
 pub mod common; // 公共工具
 pub mod debug_logger;
+pub mod drain; // 优雅排空 (停止 / 退出 / 滚动升级)
 pub mod droid_sync; // Droid (Factory CLI) 配置同步
 pub mod handlers; // API 端点处理器
+pub mod hot_reload; // 配置热更新 (快照替换 / 文件监听 / 监听器切换)
 pub mod http_session_store; // HTTP多轮对话会话历史存储
 pub mod mappers; // 协议转换器
+pub mod metrics; // Prometheus 指标导出 (/metrics)
 pub mod middleware; // Axum 中间件
 pub mod model_specs; // 模型规格管理 (v4.1.29)
 pub mod monitor; // 监控
@@ -24,9 +29,11 @@ pub mod opencode_sync; // OpenCode 配置同步
 pub mod providers; // Extra upstream providers (z.ai, etc.)
 pub mod proxy_pool; // 代理池管理器
 pub mod rate_limit; // 限流跟踪
+pub mod rate_limit_store; // 限流状态持久化 (rate_limits.db)
 pub mod session_manager; // 会话指纹管理
 pub mod signature_cache; // Signature Cache (v3.3.16)
 pub mod sticky_config; // 粘性调度配置
+pub mod telemetry; // OpenTelemetry 链路追踪 (OTLP 导出)
 pub mod upstream; // 上游客户端
 pub mod zai_vision_mcp; // Built-in Vision MCP server state
 pub mod zai_vision_tools; // Built-in Vision MCP tools (z.ai vision API) // 调试日志
@@ -24,6 +24,8 @@ pub struct ProxyRequestLog {
     pub cached_tokens: Option<u32>,
     pub protocol: Option<String>, // 协议类型: "openai", "anthropic", "gemini"
     pub username: Option<String>, // User token username
+    #[serde(default)]
+    pub reasoning_tokens: Option<u32>, // 推理 (thinking) Token，计入 output_tokens
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
@@ -80,16 +82,25 @@ impl ProxyMonitor {
     }
 
     pub async fn log_request(&self, log: ProxyRequestLog) {
+        crate::proxy::metrics::ProxyMetrics::global().record_request(&log);
+
         if let (Some(account), Some(input), Some(output)) =
             (&log.account_email, log.input_tokens, log.output_tokens)
         {
-            let model = log.model.clone().unwrap_or_else(|| "unknown".to_string());
-            let account = account.clone();
-            let cached = log.cached_tokens.unwrap_or(0);
+            let record = crate::modules::token_stats::UsageRecord {
+                account_email: account.clone(),
+                model: log.model.clone().unwrap_or_else(|| "unknown".to_string()),
+                mapped_model: log.mapped_model.clone(),
+                username: log.username.clone(),
+                input_tokens: input,
+                output_tokens: output,
+                cached_tokens: log.cached_tokens.unwrap_or(0),
+                reasoning_tokens: log.reasoning_tokens.unwrap_or(0),
+                // Anthropic 口径的 input_tokens 不含缓存命中部分
+                input_includes_cached: log.protocol.as_deref() != Some("anthropic"),
+            };
             tokio::task::spawn_blocking(move || {
-                if let Err(e) = crate::modules::token_stats::record_usage(
-                    &account, &model, input, output, cached,
-                ) {
+                if let Err(e) = crate::modules::token_stats::record_usage_detail(&record) {
                     tracing::debug!("Failed to record token stats: {}", e);
                 }
             });
@@ -170,6 +181,7 @@ impl ProxyMonitor {
                 cached_tokens: log.cached_tokens,
                 protocol: log.protocol.clone(),
                 username: log.username.clone(),
+                reasoning_tokens: log.reasoning_tokens,
             };
             let _ = app.emit("proxy://request", &log_summary);
         }
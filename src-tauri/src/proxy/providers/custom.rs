@@ -0,0 +1,354 @@
+// 通用上游 Provider (OpenAI / Anthropic / Gemini 协议透传)
+// 与 z.ai 相同的调度语义: Exclusive / Pooled / Fallback，按入站协议匹配 Provider
+
+use axum::{
+    body::Body,
+    http::{header, HeaderMap, HeaderValue, Method, StatusCode},
+    response::{IntoResponse, Response},
+};
+use bytes::Bytes;
+use futures::StreamExt;
+use serde_json::Value;
+use std::sync::atomic::Ordering;
+
+use crate::proxy::config::{ProviderDispatchMode, ProviderProtocol, UpstreamProviderConfig};
+use crate::proxy::server::AppState;
+
+/// 模型名是否带有该 Provider 的显式路由前缀 (`<name>:<model>`)
+fn strip_provider_prefix<'a>(provider: &UpstreamProviderConfig, model: &'a str) -> Option<&'a str> {
+    let prefix = format!("{}:", provider.name);
+    match model.get(..prefix.len()) {
+        Some(head) if head.eq_ignore_ascii_case(&prefix) && model.len() > prefix.len() => {
+            Some(&model[prefix.len()..])
+        }
+        _ => None,
+    }
+}
+
+fn pattern_matches(pattern: &str, model: &str) -> bool {
+    let pattern = pattern.trim().to_lowercase();
+    let model = model.to_lowercase();
+    match pattern.strip_suffix('*') {
+        Some(prefix) => model.starts_with(prefix),
+        None => pattern == model,
+    }
+}
+
+/// Provider 是否可以服务该入站模型
+pub fn provider_serves_model(provider: &UpstreamProviderConfig, model: &str) -> bool {
+    strip_provider_prefix(provider, model).is_some()
+        || provider.model_mapping.contains_key(model)
+        || provider.models.is_empty()
+        || provider.models.iter().any(|p| pattern_matches(p, model))
+}
+
+/// 将入站模型名映射为上游模型名
+pub fn map_model_for_provider(provider: &UpstreamProviderConfig, model: &str) -> String {
+    let model = strip_provider_prefix(provider, model).unwrap_or(model);
+    if let Some(mapped) = provider.model_mapping.get(model) {
+        return mapped.clone();
+    }
+    if let Some(mapped) = provider.model_mapping.get(&model.to_lowercase()) {
+        return mapped.clone();
+    }
+    model.to_string()
+}
+
+/// 拼接上游地址；base_url 以 `/v1` 结尾且请求路径也以 `/v1/` 开头时去重
+pub fn build_provider_url(base_url: &str, path: &str, query: Option<&str>) -> String {
+    let base = base_url.trim().trim_end_matches('/');
+    let path = if path.starts_with('/') {
+        path.to_string()
+    } else {
+        format!("/{}", path)
+    };
+    let path = match (base.rsplit('/').next(), path.split('/').nth(1)) {
+        (Some(last), Some(first)) if !last.is_empty() && last == first => {
+            path[first.len() + 1..].to_string()
+        }
+        _ => path,
+    };
+    match query.filter(|q| !q.is_empty()) {
+        Some(q) => format!("{}{}?{}", base, path, q),
+        None => format!("{}{}", base, path),
+    }
+}
+
+/// Pooled 模式: Google 账号与 Provider 共享轮询槽位，返回命中的 Provider 下标
+pub fn pooled_slot(
+    google_accounts: usize,
+    pooled_providers: usize,
+    counter: usize,
+) -> Option<usize> {
+    if pooled_providers == 0 {
+        return None;
+    }
+    let total = google_accounts.saturating_add(pooled_providers);
+    let slot = counter % total;
+    (slot < pooled_providers).then_some(slot)
+}
+
+/// 为当前请求选择 Provider (未命中时返回 None，继续走 Google 账号池)
+///
+/// 优先级: 显式前缀 > Exclusive > Pooled > Fallback
+pub async fn select_provider(
+    state: &AppState,
+    protocol: ProviderProtocol,
+    model: &str,
+    trace_id: &str,
+) -> Option<UpstreamProviderConfig> {
+    let providers = state.config.load().proxy.providers.clone();
+    let active: Vec<UpstreamProviderConfig> = providers
+        .into_iter()
+        .filter(|p| p.enabled && p.protocol == protocol && !p.base_url.trim().is_empty())
+        .collect();
+    if active.is_empty() {
+        return None;
+    }
+
+    if let Some(p) = active
+        .iter()
+        .find(|p| strip_provider_prefix(p, model).is_some())
+    {
+        tracing::info!(
+            "[{}] Routing to provider '{}' (explicit prefix)",
+            trace_id,
+            p.name
+        );
+        return Some(p.clone());
+    }
+
+    let eligible: Vec<&UpstreamProviderConfig> = active
+        .iter()
+        .filter(|p| p.dispatch_mode != ProviderDispatchMode::Off)
+        .filter(|p| provider_serves_model(p, model))
+        .collect();
+
+    if let Some(p) = eligible
+        .iter()
+        .find(|p| p.dispatch_mode == ProviderDispatchMode::Exclusive)
+    {
+        tracing::info!(
+            "[{}] Routing to provider '{}' (exclusive)",
+            trace_id,
+            p.name
+        );
+        return Some((*p).clone());
+    }
+
+    let google_accounts = state.token_manager.len();
+    let pooled: Vec<&&UpstreamProviderConfig> = eligible
+        .iter()
+        .filter(|p| p.dispatch_mode == ProviderDispatchMode::Pooled)
+        .collect();
+    if !pooled.is_empty() {
+        let counter = state.provider_rr.fetch_add(1, Ordering::Relaxed);
+        if let Some(idx) = pooled_slot(google_accounts, pooled.len(), counter) {
+            tracing::info!(
+                "[{}] Routing to provider '{}' (pooled slot {})",
+                trace_id,
+                pooled[idx].name,
+                idx
+            );
+            return Some((**pooled[idx]).clone());
+        }
+    }
+
+    let fallback = eligible
+        .iter()
+        .find(|p| p.dispatch_mode == ProviderDispatchMode::Fallback)?;
+    let limit_key = crate::proxy::common::model_mapping::rate_limit_model_key(model);
+    if google_accounts == 0
+        || !state
+            .token_manager
+            .has_available_account("provider", &limit_key)
+            .await
+    {
+        tracing::info!(
+            "[{}] Google pool unavailable for {}, routing to fallback provider '{}'",
+            trace_id,
+            model,
+            fallback.name
+        );
+        return Some((*fallback).clone());
+    }
+    None
+}
+
+fn set_provider_auth(headers: &mut HeaderMap, provider: &UpstreamProviderConfig) {
+    let key = provider.api_key.trim();
+    if key.is_empty() {
+        return;
+    }
+    let (name, value) = match provider.protocol {
+        ProviderProtocol::Openai => (header::AUTHORIZATION.as_str(), format!("Bearer {}", key)),
+        ProviderProtocol::Anthropic => ("x-api-key", key.to_string()),
+        ProviderProtocol::Gemini => ("x-goog-api-key", key.to_string()),
+    };
+    if let Ok(v) = HeaderValue::from_str(&value) {
+        if let Ok(k) = header::HeaderName::from_bytes(name.as_bytes()) {
+            headers.insert(k, v);
+        }
+    }
+}
+
+/// 将请求透传给 Provider，并把响应 (含 SSE) 原样流回客户端
+///
+/// OpenAI / Anthropic 请求体中的 `model` 会按 Provider 映射改写；
+/// Gemini 协议的模型位于路径中，由调用方传入映射后的 path
+pub async fn forward_json(
+    state: &AppState,
+    provider: &UpstreamProviderConfig,
+    path: &str,
+    query: Option<&str>,
+    incoming_headers: &HeaderMap,
+    mut body: Value,
+    original_model: &str,
+) -> Response {
+    let mapped_model = map_model_for_provider(provider, original_model);
+    if body.get("model").is_some() {
+        body["model"] = Value::String(mapped_model.clone());
+    }
+
+    let url = build_provider_url(&provider.base_url, path, query);
+    let config = state.config.load_full();
+    let timeout_secs = config.proxy.request_timeout.max(5);
+    let upstream_proxy = config.proxy.upstream_proxy.clone();
+    let client = match super::zai_anthropic::build_client(Some(upstream_proxy), timeout_secs) {
+        Ok(c) => c,
+        Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, e).into_response(),
+    };
+
+    let mut headers = super::zai_anthropic::copy_passthrough_headers(incoming_headers);
+    set_provider_auth(&mut headers, provider);
+    headers.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static("application/json"),
+    );
+
+    tracing::debug!(
+        "Forwarding request to provider '{}' ({}): {}",
+        provider.name,
+        mapped_model,
+        url
+    );
+
+    let body_bytes = serde_json::to_vec(&body).unwrap_or_default();
+    let resp = match client
+        .request(Method::POST, &url)
+        .headers(headers)
+        .body(body_bytes)
+        .send()
+        .await
+    {
+        Ok(r) => r,
+        Err(e) => {
+            return (
+                StatusCode::BAD_GATEWAY,
+                format!("Provider '{}' request failed: {}", provider.name, e),
+            )
+                .into_response();
+        }
+    };
+
+    let status = StatusCode::from_u16(resp.status().as_u16()).unwrap_or(StatusCode::BAD_GATEWAY);
+    let mut out = Response::builder()
+        .status(status)
+        .header("X-Account-Email", format!("provider:{}", provider.name))
+        .header("X-Mapped-Model", mapped_model);
+    if let Some(ct) = resp.headers().get(header::CONTENT_TYPE) {
+        out = out.header(header::CONTENT_TYPE, ct.clone());
+    }
+
+    let stream = resp.bytes_stream().map(|chunk| match chunk {
+        Ok(b) => Ok::<Bytes, std::io::Error>(b),
+        Err(e) => Ok(Bytes::from(format!("Upstream stream error: {}", e))),
+    });
+
+    out.body(Body::from_stream(stream)).unwrap_or_else(|_| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Failed to build response",
+        )
+            .into_response()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn provider(models: Vec<&str>, mapping: Vec<(&str, &str)>) -> UpstreamProviderConfig {
+        UpstreamProviderConfig {
+            name: "vllm".to_string(),
+            enabled: true,
+            base_url: "http://127.0.0.1:8000/v1".to_string(),
+            api_key: "sk-test".to_string(),
+            protocol: ProviderProtocol::Openai,
+            dispatch_mode: ProviderDispatchMode::Pooled,
+            model_mapping: mapping
+                .into_iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect::<HashMap<_, _>>(),
+            models: models.into_iter().map(|m| m.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn test_model_matching_and_mapping() {
+        let p = provider(
+            vec!["qwen*", "llama-3-70b"],
+            vec![("gpt-4o", "qwen2.5-72b")],
+        );
+
+        assert!(provider_serves_model(&p, "qwen2.5-7b"));
+        assert!(provider_serves_model(&p, "LLaMA-3-70B"));
+        assert!(provider_serves_model(&p, "gpt-4o"));
+        assert!(provider_serves_model(&p, "vllm:anything"));
+        assert!(!provider_serves_model(&p, "gemini-3-flash"));
+        assert!(provider_serves_model(
+            &provider(vec![], vec![]),
+            "gemini-3-flash"
+        ));
+
+        assert_eq!(map_model_for_provider(&p, "gpt-4o"), "qwen2.5-72b");
+        assert_eq!(map_model_for_provider(&p, "vllm:gpt-4o"), "qwen2.5-72b");
+        assert_eq!(
+            map_model_for_provider(&p, "VLLM:custom-model"),
+            "custom-model"
+        );
+        assert_eq!(map_model_for_provider(&p, "llama-3-70b"), "llama-3-70b");
+    }
+
+    #[test]
+    fn test_build_provider_url_dedups_version_segment() {
+        assert_eq!(
+            build_provider_url("http://127.0.0.1:8000/v1/", "/v1/chat/completions", None),
+            "http://127.0.0.1:8000/v1/chat/completions"
+        );
+        assert_eq!(
+            build_provider_url("http://127.0.0.1:8080", "/v1/chat/completions", None),
+            "http://127.0.0.1:8080/v1/chat/completions"
+        );
+        assert_eq!(
+            build_provider_url(
+                "https://gw.example.com/gemini",
+                "/v1beta/models/m:streamGenerateContent",
+                Some("alt=sse")
+            ),
+            "https://gw.example.com/gemini/v1beta/models/m:streamGenerateContent?alt=sse"
+        );
+    }
+
+    #[test]
+    fn test_pooled_slot_shares_round_robin_with_google_pool() {
+        // 3 个 Google 账号 + 1 个 Provider: 每 4 次请求命中一次 Provider
+        let hits = (0..8).filter(|c| pooled_slot(3, 1, *c).is_some()).count();
+        assert_eq!(hits, 2);
+        // 没有 Google 账号时全部落到 Provider 上
+        assert!((0..4).all(|c| pooled_slot(0, 2, c).is_some()));
+        assert_eq!(pooled_slot(0, 2, 3), Some(1));
+        assert_eq!(pooled_slot(5, 0, 0), None);
+    }
+}
@@ -46,7 +46,7 @@ fn join_base_url(base: &str, path: &str) -> Result<String, String> {
     Ok(format!("{}{}", base, path))
 }
 
-fn build_client(
+pub(crate) fn build_client(
     upstream_proxy: Option<crate::proxy::config::UpstreamProxyConfig>,
     timeout_secs: u64,
 ) -> Result<reqwest::Client, String> {
@@ -67,7 +67,7 @@ fn build_client(
         .map_err(|e| format!("Failed to build HTTP client: {}", e))
 }
 
-fn copy_passthrough_headers(incoming: &HeaderMap) -> HeaderMap {
+pub(crate) fn copy_passthrough_headers(incoming: &HeaderMap) -> HeaderMap {
     // Only forward a conservative set of headers to avoid leaking the local proxy key or cookies.
     let mut out = HeaderMap::new();
 
@@ -141,7 +141,8 @@ pub async fn forward_anthropic_json(
     mut body: Value,
     message_count: usize, // [NEW v4.0.0] Pass message count for rewind detection
 ) -> Response {
-    let zai = state.zai.read().await.clone();
+    let config = state.config.load_full();
+    let zai = config.proxy.zai.clone();
     if !zai.enabled || zai.dispatch_mode == crate::proxy::ZaiDispatchMode::Off {
         return (StatusCode::BAD_REQUEST, "z.ai is disabled").into_response();
     }
@@ -174,8 +175,8 @@ pub async fn forward_anthropic_json(
         Err(e) => return (StatusCode::BAD_REQUEST, e).into_response(),
     };
 
-    let timeout_secs = state.request_timeout.max(5);
-    let upstream_proxy = state.upstream_proxy.read().await.clone();
+    let timeout_secs = config.proxy.request_timeout.max(5);
+    let upstream_proxy = config.proxy.upstream_proxy.clone();
     let client = match build_client(Some(upstream_proxy), timeout_secs) {
         Ok(c) => c,
         Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, e).into_response(),
@@ -5,7 +5,8 @@ use rquest::Client;
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
 use std::time::Duration;
-use tokio::sync::RwLock;
+
+use crate::proxy::hot_reload::ConfigSnapshot;
 
 use rquest_util::Emulation;
 use std::sync::OnceLock;
@@ -19,7 +20,7 @@ pub fn get_global_proxy_pool() -> Option<Arc<ProxyPoolManager>> {
 }
 
 /// 初始化全局代理池管理器
-pub fn init_global_proxy_pool(config: Arc<RwLock<ProxyPoolConfig>>) -> Arc<ProxyPoolManager> {
+pub fn init_global_proxy_pool(config: ConfigSnapshot) -> Arc<ProxyPoolManager> {
     let manager = Arc::new(ProxyPoolManager::new(config));
     let _ = GLOBAL_PROXY_POOL.set(manager.clone());
     manager
@@ -33,9 +34,23 @@ pub struct PoolProxyConfig {
     pub entry_id: String,
 }
 
+/// 单个代理的健康检查结果
+#[derive(Debug, Clone, Copy)]
+struct ProxyHealth {
+    is_healthy: bool,
+    latency: Option<u64>,
+    checked_at: i64,
+}
+
 /// 代理池管理器
+///
+/// 代理池配置始终从配置快照读取，健康检查结果与账号绑定作为运行时状态单独保存，
+/// 通过 `pool_config` 叠加到配置上
 pub struct ProxyPoolManager {
-    config: Arc<RwLock<ProxyPoolConfig>>,
+    config: ConfigSnapshot,
+
+    /// 健康检查结果 (proxy_id -> health)
+    health: Arc<DashMap<String, ProxyHealth>>,
 
     /// 代理使用计数 (proxy_id -> count)
     usage_counter: Arc<DashMap<String, usize>>,
@@ -48,13 +63,12 @@ pub struct ProxyPoolManager {
 }
 
 impl ProxyPoolManager {
-    pub fn new(config: Arc<RwLock<ProxyPoolConfig>>) -> Self {
+    pub fn new(config: ConfigSnapshot) -> Self {
         // 从配置中加载已保存的绑定关系
         let account_bindings = Arc::new(DashMap::new());
-
-        // 使用 blocking 方式读取配置（因为 new 不是 async）
-        // 注意：这里使用 try_read 避免死锁
-        if let Ok(cfg) = config.try_read() {
+        {
+            let app_config = config.load();
+            let cfg = &app_config.proxy.proxy_pool;
             for (account_id, proxy_id) in &cfg.account_bindings {
                 account_bindings.insert(account_id.clone(), proxy_id.clone());
             }
@@ -68,12 +82,27 @@ impl ProxyPoolManager {
 
         Self {
             config,
+            health: Arc::new(DashMap::new()),
             usage_counter: Arc::new(DashMap::new()),
             account_bindings,
             round_robin_index: Arc::new(AtomicUsize::new(0)),
         }
     }
 
+    /// 当前生效的代理池配置，叠加运行时的健康检查结果与账号绑定
+    pub fn pool_config(&self) -> ProxyPoolConfig {
+        let mut config = self.config.load().proxy.proxy_pool.clone();
+        for proxy in config.proxies.iter_mut() {
+            if let Some(health) = self.health.get(&proxy.id) {
+                proxy.is_healthy = health.is_healthy;
+                proxy.latency = health.latency;
+                proxy.last_check_time = Some(health.checked_at);
+            }
+        }
+        config.account_bindings = self.get_all_bindings_snapshot();
+        config
+    }
+
     /// [NEW] 为指定账号获取“最终生效”的 HttpClient
     /// 逻辑：
     /// 1. 账号显式绑定代理优先 (Account-Proxy Binding)
@@ -93,7 +122,7 @@ impl ProxyPoolManager {
             self.get_proxy_for_account(acc_id).await.ok().flatten()
         } else {
             // 没有 account_id 的通用请求，如果代理池启用，则默认从中选择节点作为出口
-            let config = self.config.read().await;
+            let config = self.pool_config();
             if config.enabled {
                 let res = self.select_proxy_from_pool(&config).await.ok().flatten();
                 if let Some(ref p) = res {
@@ -155,7 +184,7 @@ impl ProxyPoolManager {
             self.get_proxy_for_account(acc_id).await.ok().flatten()
         } else {
             // 没有 account_id 的通用请求，如果代理池启用，则默认从中选择节点作为出口
-            let config = self.config.read().await;
+            let config = self.pool_config();
             if config.enabled {
                 let res = self.select_proxy_from_pool(&config).await.ok().flatten();
                 if let Some(ref p) = res {
@@ -207,7 +236,7 @@ impl ProxyPoolManager {
         &self,
         account_id: &str,
     ) -> Result<Option<PoolProxyConfig>, String> {
-        let config = self.config.read().await;
+        let config = self.pool_config();
 
         if !config.enabled || config.proxies.is_empty() {
             return Ok(None);
@@ -370,7 +399,7 @@ impl ProxyPoolManager {
     ) -> Result<(), String> {
         // 检查代理是否存在
         {
-            let config = self.config.read().await;
+            let config = self.pool_config();
             if !config.proxies.iter().any(|p| p.id == proxy_id) {
                 return Err(format!("Proxy {} not found", proxy_id));
             }
@@ -436,13 +465,11 @@ impl ProxyPoolManager {
     }
 
     /// [HOT-RELOAD] Re-sync the in-memory DashMap from `config.account_bindings`.
-    /// Called after `update_proxy_pool` so that a wholesale ProxyPoolConfig
-    /// replacement (e.g. via `save_config`) does not leave the in-memory
-    /// bindings stale or empty.
+    /// Called after a new config snapshot is applied so that a wholesale
+    /// ProxyPoolConfig replacement (e.g. via `save_config`) does not leave the
+    /// in-memory bindings stale or empty.
     pub async fn sync_bindings_from_config(&self) {
-        let config = self.config.read().await;
-        let snapshot = config.account_bindings.clone();
-        drop(config);
+        let snapshot = self.config.load().proxy.proxy_pool.account_bindings.clone();
 
         // Reset the DashMap: clear old entries, then insert fresh ones.
         self.account_bindings.clear();
@@ -461,16 +488,9 @@ impl ProxyPoolManager {
         // 获取当前绑定快照
         let bindings = self.get_all_bindings_snapshot();
 
-        // 更新配置中的绑定关系
-        {
-            let mut config = self.config.write().await;
-            config.account_bindings = bindings;
-        }
-
-        // 保存到磁盘
+        // 保存到磁盘 (文件监听随后把新配置应用到快照)
         if let Ok(mut app_config) = crate::modules::config::load_app_config() {
-            let config = self.config.read().await;
-            app_config.proxy.proxy_pool = config.clone();
+            app_config.proxy.proxy_pool.account_bindings = bindings;
             if let Err(e) = crate::modules::config::save_app_config(&app_config) {
                 tracing::error!("[ProxyPool] Failed to persist bindings: {}", e);
             }
@@ -482,8 +502,10 @@ impl ProxyPoolManager {
         // 由于需要异步并发检查，且不能锁住 config 太久，
         // 我们先复制一份需要检查的代理列表
         let proxies_to_check: Vec<_> = {
-            let config = self.config.read().await;
+            let config = self.config.load();
             config
+                .proxy
+                .proxy_pool
                 .proxies
                 .iter()
                 .filter(|p| p.enabled)
@@ -517,13 +539,16 @@ impl ProxyPoolManager {
             .await;
 
         // 统一更新状态
-        let mut config = self.config.write().await;
+        let checked_at = chrono::Utc::now().timestamp();
         for (id, is_healthy, latency) in results {
-            if let Some(proxy) = config.proxies.iter_mut().find(|p| p.id == id) {
-                proxy.is_healthy = is_healthy;
-                proxy.latency = latency;
-                proxy.last_check_time = Some(chrono::Utc::now().timestamp());
-            }
+            self.health.insert(
+                id,
+                ProxyHealth {
+                    is_healthy,
+                    latency,
+                    checked_at,
+                },
+            );
         }
 
         Ok(())
@@ -592,7 +617,7 @@ impl ProxyPoolManager {
             tracing::info!("Starting proxy pool health check loop...");
             loop {
                 // Perform check only if enabled
-                let enabled = self.config.read().await.enabled;
+                let enabled = self.config.load().proxy.proxy_pool.enabled;
                 if enabled {
                     if let Err(e) = self.health_check().await {
                         tracing::error!("Proxy pool health check failed: {}", e);
@@ -601,7 +626,8 @@ impl ProxyPoolManager {
 
                 // Get interval and sleep AFTER check
                 let interval_secs = {
-                    let cfg = self.config.read().await;
+                    let app_config = self.config.load();
+                    let cfg = &app_config.proxy.proxy_pool;
                     if !cfg.enabled {
                         60 // check every minute if disabled
                     } else {
@@ -1,7 +1,11 @@
 use dashmap::DashMap;
 use regex::Regex;
+use serde::Serialize;
+use std::sync::OnceLock;
 use std::time::{Duration, SystemTime};
 
+use crate::proxy::rate_limit_store::RateLimitStore;
+
 /// 限流原因类型
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum RateLimitReason {
@@ -17,6 +21,30 @@ pub enum RateLimitReason {
     Unknown,
 }
 
+impl RateLimitReason {
+    /// 持久化用的稳定字符串标识
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RateLimitReason::QuotaExhausted => "QUOTA_EXHAUSTED",
+            RateLimitReason::RateLimitExceeded => "RATE_LIMIT_EXCEEDED",
+            RateLimitReason::ModelCapacityExhausted => "MODEL_CAPACITY_EXHAUSTED",
+            RateLimitReason::ServerError => "SERVER_ERROR",
+            RateLimitReason::Unknown => "UNKNOWN",
+        }
+    }
+
+    /// 从持久化字符串还原，无法识别时返回 Unknown
+    pub fn from_str_lossy(s: &str) -> Self {
+        match s {
+            "QUOTA_EXHAUSTED" => RateLimitReason::QuotaExhausted,
+            "RATE_LIMIT_EXCEEDED" => RateLimitReason::RateLimitExceeded,
+            "MODEL_CAPACITY_EXHAUSTED" => RateLimitReason::ModelCapacityExhausted,
+            "SERVER_ERROR" => RateLimitReason::ServerError,
+            _ => RateLimitReason::Unknown,
+        }
+    }
+}
+
 /// 限流信息
 #[allow(dead_code)]
 #[derive(Debug, Clone)]
@@ -38,6 +66,19 @@ pub struct RateLimitInfo {
     pub model: Option<String>,
 }
 
+/// 限流记录快照 (供管理接口展示)
+#[derive(Debug, Clone, Serialize)]
+pub struct RateLimitSnapshot {
+    pub key: String,
+    pub account_id: String,
+    pub model: Option<String>,
+    pub reason: &'static str,
+    pub reset_at: i64,
+    pub remaining_sec: u64,
+    pub detected_at: i64,
+    pub failure_count: u32,
+}
+
 /// 失败计数过期时间：1小时（超过此时间未失败则重置计数）
 const FAILURE_COUNT_EXPIRY_SECONDS: u64 = 3600;
 
@@ -46,6 +87,8 @@ pub struct RateLimitTracker {
     limits: DashMap<String, RateLimitInfo>,
     /// 连续失败计数（用于智能指数退避），带时间戳用于自动过期
     failure_counts: DashMap<String, (u32, SystemTime)>,
+    /// 可选的持久化存储 (rate_limits.db)，未挂载时仅保存在内存
+    store: OnceLock<RateLimitStore>,
 }
 
 impl RateLimitTracker {
@@ -53,6 +96,82 @@ impl RateLimitTracker {
         Self {
             limits: DashMap::new(),
             failure_counts: DashMap::new(),
+            store: OnceLock::new(),
+        }
+    }
+
+    /// 挂载持久化存储并恢复上次运行遗留的锁定记录与退避计数
+    ///
+    /// 只在首次挂载时恢复，返回恢复的锁定记录数量；已挂载时返回 0。
+    pub fn attach_store(&self, store: RateLimitStore) -> usize {
+        if self.store.set(store).is_err() {
+            return 0;
+        }
+        let Some(store) = self.store.get() else {
+            return 0;
+        };
+
+        let now = SystemTime::now();
+        if let Err(e) = store.delete_expired_limits(now) {
+            tracing::warn!("清理过期的持久化限流记录失败: {}", e);
+        }
+
+        let mut restored = 0;
+        match store.load_active_limits(now) {
+            Ok(limits) => {
+                for item in limits {
+                    self.limits.entry(item.key).or_insert_with(|| {
+                        restored += 1;
+                        item.info
+                    });
+                }
+            }
+            Err(e) => tracing::warn!("加载持久化限流记录失败: {}", e),
+        }
+
+        match store.load_failure_counts(now, Duration::from_secs(FAILURE_COUNT_EXPIRY_SECONDS)) {
+            Ok(counts) => {
+                for item in counts {
+                    self.failure_counts
+                        .entry(item.account_id)
+                        .or_insert((item.count, item.updated_at));
+                }
+            }
+            Err(e) => tracing::warn!("加载持久化退避计数失败: {}", e),
+        }
+
+        if restored > 0 {
+            tracing::info!("已从 rate_limits.db 恢复 {} 条限流记录", restored);
+        }
+        restored
+    }
+
+    /// 是否已挂载持久化存储
+    pub fn has_store(&self) -> bool {
+        self.store.get().is_some()
+    }
+
+    fn persist_limit(&self, key: &str, account_id: &str, info: &RateLimitInfo) {
+        if let Some(store) = self.store.get() {
+            if let Err(e) = store.save_limit(key, account_id, info) {
+                tracing::debug!("持久化限流记录 {} 失败: {}", key, e);
+            }
+        }
+    }
+
+    fn unpersist_limit(&self, key: &str) {
+        if let Some(store) = self.store.get() {
+            if let Err(e) = store.delete_limit(key) {
+                tracing::debug!("删除持久化限流记录 {} 失败: {}", key, e);
+            }
+        }
+    }
+
+    fn persist_failure_count(&self, account_id: &str, count: u32, updated_at: SystemTime) {
+        if let Some(store) = self.store.get() {
+            if let Err(e) = store.save_failure_count(account_id, count, updated_at) {
+                tracing::debug!("持久化账号 {} 的退避计数失败: {}", account_id, e);
+            }
         }
     }
 
@@ -106,9 +225,16 @@ impl RateLimitTracker {
     pub fn mark_success(&self, account_id: &str) {
         if self.failure_counts.remove(account_id).is_some() {
             tracing::debug!("账号 {} 请求成功，已重置失败计数", account_id);
+            if let Some(store) = self.store.get() {
+                if let Err(e) = store.delete_failure_count(account_id) {
+                    tracing::debug!("删除账号 {} 的持久化退避计数失败: {}", account_id, e);
+                }
+            }
         }
         // 清除账号级限流
-        self.limits.remove(account_id);
+        if self.limits.remove(account_id).is_some() {
+            self.unpersist_limit(account_id);
+        }
         // 注意：我们暂时无法清除该账号下的所有模型级锁，因为我们不知道哪些模型被锁了
         // 除非遍历 limits。考虑到模型级锁通常是 QuotaExhausted，让其自然过期也是可以接受的。
         // 或者我们可以引入索引，但为了简单，暂时只清除 Account 级锁。
@@ -154,6 +280,7 @@ impl RateLimitTracker {
         };
 
         let key = self.get_limit_key(account_id, model.as_deref());
+        self.persist_limit(&key, account_id, &info);
         self.limits.insert(key, info);
 
         if let Some(m) = &model {
@@ -291,7 +418,10 @@ impl RateLimitTracker {
                     }
                     entry.0 += 1;
                     entry.1 = now;
-                    entry.0
+                    let count = entry.0;
+                    drop(entry);
+                    self.persist_failure_count(account_id, count, now);
+                    count
                 } else {
                     // ServerError (5xx) 使用固定值 1，不累加，避免污染 429 的退避阶梯
                     1
@@ -388,6 +518,7 @@ impl RateLimitTracker {
             account_id.to_string()
         };
 
+        self.persist_limit(&key, account_id, &info);
         self.limits.insert(key, info.clone());
 
         tracing::warn!(
@@ -654,6 +785,11 @@ impl RateLimitTracker {
 
         if count > 0 {
             tracing::debug!("清除了 {} 个过期的限流记录", count);
+            if let Some(store) = self.store.get() {
+                if let Err(e) = store.delete_expired_limits(now) {
+                    tracing::debug!("清理过期的持久化限流记录失败: {}", e);
+                }
+            }
         }
 
         count
@@ -661,7 +797,60 @@ impl RateLimitTracker {
 
     /// 清除指定账号的限流记录
     pub fn clear(&self, account_id: &str) -> bool {
-        self.limits.remove(account_id).is_some()
+        let removed = self.limits.remove(account_id).is_some();
+        if removed {
+            self.unpersist_limit(account_id);
+        }
+        removed
+    }
+
+    /// 获取当前仍生效的限流记录快照 (按剩余时间降序)
+    pub fn snapshot(&self) -> Vec<RateLimitSnapshot> {
+        let now = SystemTime::now();
+        let to_unix = |t: SystemTime| {
+            t.duration_since(SystemTime::UNIX_EPOCH)
+                .map(|d| d.as_secs() as i64)
+                .unwrap_or(0)
+        };
+
+        let mut items: Vec<RateLimitSnapshot> = self
+            .limits
+            .iter()
+            .filter(|entry| entry.value().reset_time > now)
+            .map(|entry| {
+                let info = entry.value();
+                let account_id = match &info.model {
+                    Some(m) => entry
+                        .key()
+                        .strip_suffix(&format!(":{}", m))
+                        .unwrap_or(entry.key())
+                        .to_string(),
+                    None => entry.key().clone(),
+                };
+                let failure_count = self
+                    .failure_counts
+                    .get(&account_id)
+                    .map(|c| c.0)
+                    .unwrap_or(0);
+                RateLimitSnapshot {
+                    key: entry.key().clone(),
+                    model: info.model.clone(),
+                    reason: info.reason.as_str(),
+                    reset_at: to_unix(info.reset_time),
+                    remaining_sec: info
+                        .reset_time
+                        .duration_since(now)
+                        .map(|d| d.as_secs())
+                        .unwrap_or(0),
+                    detected_at: to_unix(info.detected_at),
+                    failure_count,
+                    account_id,
+                }
+            })
+            .collect();
+
+        items.sort_by_key(|item| std::cmp::Reverse(item.remaining_sec));
+        items
     }
 
     /// 清除所有限流记录 (乐观重置策略)
@@ -671,6 +860,11 @@ impl RateLimitTracker {
     pub fn clear_all(&self) {
         let count = self.limits.len();
         self.limits.clear();
+        if let Some(store) = self.store.get() {
+            if let Err(e) = store.clear_limits() {
+                tracing::debug!("清空持久化限流记录失败: {}", e);
+            }
+        }
         tracing::warn!(
             "🔄 Optimistic reset: Cleared all {} rate limit record(s)",
             count
@@ -820,4 +1014,73 @@ mod tests {
         let info = tracker.parse_from_error("acc2", 429, None, quota_body, None, &backoff_steps);
         assert_eq!(info.unwrap().retry_after_sec, 7200);
     }
+
+    #[test]
+    fn test_lockouts_survive_restart_via_store() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir
+            .path()
+            .join(crate::proxy::rate_limit_store::RATE_LIMIT_DB_FILE);
+        let backoff_steps = vec![60, 300, 1800, 7200];
+        let quota_body = r#"{"error":{"details":[{"reason":"QUOTA_EXHAUSTED"}]}}"#;
+
+        let tracker = RateLimitTracker::new();
+        tracker.attach_store(RateLimitStore::open(&db_path).unwrap());
+        tracker.parse_from_error(
+            "acc3",
+            429,
+            None,
+            quota_body,
+            Some("claude-opus-4-6-thinking".to_string()),
+            &backoff_steps,
+        );
+        tracker.parse_from_error("acc4", 429, Some("120"), "rate limit", None, &backoff_steps);
+        drop(tracker);
+
+        // 模拟重启：新的 tracker 从同一个库恢复
+        let restored = RateLimitTracker::new();
+        assert_eq!(
+            restored.attach_store(RateLimitStore::open(&db_path).unwrap()),
+            2
+        );
+        assert!(restored.is_rate_limited("acc3", Some("claude-opus-4-6-thinking")));
+        assert!(!restored.is_rate_limited("acc3", Some("gemini-3-flash")));
+        assert!(restored.is_rate_limited("acc4", None));
+
+        let snapshot = restored.snapshot();
+        let acc3 = snapshot.iter().find(|s| s.account_id == "acc3").unwrap();
+        assert_eq!(acc3.reason, "QUOTA_EXHAUSTED");
+        assert_eq!(acc3.model.as_deref(), Some("claude-opus-4-6-thinking"));
+        assert_eq!(acc3.failure_count, 1);
+
+        // 退避阶梯从上次的计数继续，而不是回到第一档
+        let info = restored
+            .parse_from_error("acc3", 429, None, quota_body, None, &backoff_steps)
+            .unwrap();
+        assert_eq!(info.retry_after_sec, 300);
+    }
+
+    #[test]
+    fn test_cleared_lockouts_are_not_restored() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir
+            .path()
+            .join(crate::proxy::rate_limit_store::RATE_LIMIT_DB_FILE);
+
+        let tracker = RateLimitTracker::new();
+        tracker.attach_store(RateLimitStore::open(&db_path).unwrap());
+        tracker.parse_from_error("acc5", 429, Some("60"), "", None, &[]);
+        tracker.parse_from_error("acc6", 429, Some("60"), "", None, &[]);
+        assert!(tracker.clear("acc5"));
+        tracker.mark_success("acc6");
+        drop(tracker);
+
+        let restored = RateLimitTracker::new();
+        assert_eq!(
+            restored.attach_store(RateLimitStore::open(&db_path).unwrap()),
+            0
+        );
+        assert!(!restored.is_rate_limited("acc5", None));
+        assert!(!restored.is_rate_limited("acc6", None));
+    }
 }
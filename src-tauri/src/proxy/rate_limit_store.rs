@@ -0,0 +1,240 @@
+//! 限流状态持久化 (rate_limits.db)
+//!
+//! `RateLimitTracker` 的锁定记录与退避计数原本只存在内存中，反代重启后会忘记
+//! 哪些账号已经 QUOTA_EXHAUSTED，从而再次打满 429。这里把它们写入与
+//! `proxy_logs.db` 同目录的小型 SQLite 库，并在 `TokenManager::load_accounts` 时恢复。
+
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use rusqlite::{params, Connection};
+
+use crate::modules::db_migrations::{self, Migration};
+use crate::proxy::rate_limit::{RateLimitInfo, RateLimitReason};
+
+pub const RATE_LIMIT_DB_FILE: &str = "rate_limits.db";
+
+const MIGRATIONS: &[Migration] = &[Migration {
+    version: 1,
+    description: "create rate_limits / failure_counts",
+    up: migrate_v1_create_tables,
+}];
+
+/// 对指定连接执行结构迁移 (备份恢复时用于校验并升级暂存副本)
+pub(crate) fn migrate(conn: &mut Connection) -> Result<u32, String> {
+    db_migrations::run_migrations(conn, RATE_LIMIT_DB_FILE, MIGRATIONS)
+}
+
+fn migrate_v1_create_tables(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS rate_limits (
+            limit_key TEXT PRIMARY KEY,
+            account_id TEXT NOT NULL,
+            model TEXT,
+            reason TEXT NOT NULL,
+            reset_at INTEGER NOT NULL,
+            retry_after_sec INTEGER NOT NULL,
+            detected_at INTEGER NOT NULL
+        );
+        CREATE INDEX IF NOT EXISTS idx_rate_limits_account ON rate_limits (account_id);
+        CREATE TABLE IF NOT EXISTS failure_counts (
+            account_id TEXT PRIMARY KEY,
+            count INTEGER NOT NULL,
+            updated_at INTEGER NOT NULL
+        );",
+    )
+}
+
+/// 从库中恢复出的一条锁定记录
+#[derive(Debug, Clone)]
+pub struct PersistedRateLimit {
+    pub key: String,
+    pub info: RateLimitInfo,
+}
+
+/// 从库中恢复出的退避计数 (account_id, count, updated_at)
+#[derive(Debug, Clone)]
+pub struct PersistedFailureCount {
+    pub account_id: String,
+    pub count: u32,
+    pub updated_at: SystemTime,
+}
+
+#[derive(Debug)]
+pub struct RateLimitStore {
+    conn: Mutex<Connection>,
+}
+
+impl RateLimitStore {
+    pub fn open(db_path: &Path) -> Result<Self, String> {
+        if let Some(parent) = db_path.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+        }
+        let mut conn = Connection::open(db_path).map_err(|e| e.to_string())?;
+
+        conn.pragma_update(None, "journal_mode", "WAL")
+            .map_err(|e| e.to_string())?;
+        conn.pragma_update(None, "busy_timeout", 5000)
+            .map_err(|e| e.to_string())?;
+        conn.pragma_update(None, "synchronous", "NORMAL")
+            .map_err(|e| e.to_string())?;
+
+        migrate(&mut conn)?;
+
+        Ok(Self {
+            conn: Mutex::new(conn),
+        })
+    }
+
+    pub fn save_limit(
+        &self,
+        key: &str,
+        account_id: &str,
+        info: &RateLimitInfo,
+    ) -> Result<(), String> {
+        let conn = self.conn.lock().map_err(|e| e.to_string())?;
+        conn.execute(
+            "INSERT OR REPLACE INTO rate_limits
+                (limit_key, account_id, model, reason, reset_at, retry_after_sec, detected_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![
+                key,
+                account_id,
+                info.model,
+                info.reason.as_str(),
+                to_unix(info.reset_time),
+                info.retry_after_sec as i64,
+                to_unix(info.detected_at),
+            ],
+        )
+        .map_err(|e| e.to_string())?;
+        Ok(())
+    }
+
+    pub fn delete_limit(&self, key: &str) -> Result<(), String> {
+        let conn = self.conn.lock().map_err(|e| e.to_string())?;
+        conn.execute("DELETE FROM rate_limits WHERE limit_key = ?1", [key])
+            .map_err(|e| e.to_string())?;
+        Ok(())
+    }
+
+    pub fn delete_expired_limits(&self, now: SystemTime) -> Result<usize, String> {
+        let conn = self.conn.lock().map_err(|e| e.to_string())?;
+        conn.execute(
+            "DELETE FROM rate_limits WHERE reset_at <= ?1",
+            [to_unix(now)],
+        )
+        .map_err(|e| e.to_string())
+    }
+
+    pub fn clear_limits(&self) -> Result<(), String> {
+        let conn = self.conn.lock().map_err(|e| e.to_string())?;
+        conn.execute("DELETE FROM rate_limits", [])
+            .map_err(|e| e.to_string())?;
+        Ok(())
+    }
+
+    /// 加载尚未过期的锁定记录
+    pub fn load_active_limits(&self, now: SystemTime) -> Result<Vec<PersistedRateLimit>, String> {
+        let conn = self.conn.lock().map_err(|e| e.to_string())?;
+        let mut stmt = conn
+            .prepare(
+                "SELECT limit_key, model, reason, reset_at, retry_after_sec, detected_at
+                 FROM rate_limits WHERE reset_at > ?1",
+            )
+            .map_err(|e| e.to_string())?;
+
+        let rows = stmt
+            .query_map([to_unix(now)], |row| {
+                let reason: String = row.get(2)?;
+                Ok(PersistedRateLimit {
+                    key: row.get(0)?,
+                    info: RateLimitInfo {
+                        reset_time: from_unix(row.get(3)?),
+                        retry_after_sec: row.get::<_, i64>(4)?.max(0) as u64,
+                        detected_at: from_unix(row.get(5)?),
+                        reason: RateLimitReason::from_str_lossy(&reason),
+                        model: row.get(1)?,
+                    },
+                })
+            })
+            .map_err(|e| e.to_string())?;
+
+        let mut limits = Vec::new();
+        for row in rows {
+            limits.push(row.map_err(|e| e.to_string())?);
+        }
+        Ok(limits)
+    }
+
+    pub fn save_failure_count(
+        &self,
+        account_id: &str,
+        count: u32,
+        updated_at: SystemTime,
+    ) -> Result<(), String> {
+        let conn = self.conn.lock().map_err(|e| e.to_string())?;
+        conn.execute(
+            "INSERT OR REPLACE INTO failure_counts (account_id, count, updated_at)
+             VALUES (?1, ?2, ?3)",
+            params![account_id, count, to_unix(updated_at)],
+        )
+        .map_err(|e| e.to_string())?;
+        Ok(())
+    }
+
+    pub fn delete_failure_count(&self, account_id: &str) -> Result<(), String> {
+        let conn = self.conn.lock().map_err(|e| e.to_string())?;
+        conn.execute(
+            "DELETE FROM failure_counts WHERE account_id = ?1",
+            [account_id],
+        )
+        .map_err(|e| e.to_string())?;
+        Ok(())
+    }
+
+    /// 加载仍在有效期内的退避计数 (超过 `expiry` 未更新的视为已重置)
+    pub fn load_failure_counts(
+        &self,
+        now: SystemTime,
+        expiry: Duration,
+    ) -> Result<Vec<PersistedFailureCount>, String> {
+        let conn = self.conn.lock().map_err(|e| e.to_string())?;
+        let cutoff = to_unix(now) - expiry.as_secs() as i64;
+        conn.execute(
+            "DELETE FROM failure_counts WHERE updated_at <= ?1",
+            [cutoff],
+        )
+        .map_err(|e| e.to_string())?;
+
+        let mut stmt = conn
+            .prepare("SELECT account_id, count, updated_at FROM failure_counts")
+            .map_err(|e| e.to_string())?;
+        let rows = stmt
+            .query_map([], |row| {
+                Ok(PersistedFailureCount {
+                    account_id: row.get(0)?,
+                    count: row.get(1)?,
+                    updated_at: from_unix(row.get(2)?),
+                })
+            })
+            .map_err(|e| e.to_string())?;
+
+        let mut counts = Vec::new();
+        for row in rows {
+            counts.push(row.map_err(|e| e.to_string())?);
+        }
+        Ok(counts)
+    }
+}
+
+fn to_unix(t: SystemTime) -> i64 {
+    t.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0)
+}
+
+fn from_unix(secs: i64) -> SystemTime {
+    UNIX_EPOCH + Duration::from_secs(secs.max(0) as u64)
+}
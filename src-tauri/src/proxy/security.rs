@@ -1,4 +1,4 @@
-use crate::proxy::config::{ProxyAuthMode, ProxyConfig, SecurityMonitorConfig};
+use crate::proxy::config::{ProxyAuthMode, ProxyConfig};
 
 #[derive(Debug, Clone)]
 pub struct ProxySecurityConfig {
@@ -6,8 +6,6 @@ pub struct ProxySecurityConfig {
     pub api_key: String,
     pub admin_password: Option<String>,
     pub allow_lan_access: bool,
-    pub port: u16,
-    pub security_monitor: SecurityMonitorConfig,
 }
 
 impl ProxySecurityConfig {
@@ -16,9 +14,8 @@ impl ProxySecurityConfig {
             auth_mode: config.auth_mode.clone(),
             api_key: config.api_key.clone(),
             admin_password: config.admin_password.clone(),
-            allow_lan_access: config.allow_lan_access,
-            port: config.port,
-            security_monitor: config.security_monitor.clone(),
+            // 以实际监听地址为准：bind_address 可能绕过 allow_lan_access 单独设置
+            allow_lan_access: config.allow_lan_access || config.is_lan_exposed(),
         }
     }
 
@@ -47,8 +44,6 @@ mod tests {
             api_key: "sk-test".to_string(),
             admin_password: None,
             allow_lan_access: false,
-            port: 8080,
-            security_monitor: crate::proxy::config::SecurityMonitorConfig::default(),
         };
         assert!(matches!(s.effective_auth_mode(), ProxyAuthMode::Off));
     }
@@ -60,12 +55,40 @@ mod tests {
             api_key: "sk-test".to_string(),
             admin_password: None,
             allow_lan_access: true,
-            port: 8080,
-            security_monitor: crate::proxy::config::SecurityMonitorConfig::default(),
         };
         assert!(matches!(
             s.effective_auth_mode(),
             ProxyAuthMode::AllExceptHealth
         ));
     }
+
+    #[test]
+    fn auto_mode_follows_effective_bind_address() {
+        let mut config = ProxyConfig {
+            auth_mode: ProxyAuthMode::Auto,
+            ..Default::default()
+        };
+        assert!(!config.allow_lan_access);
+        let local = ProxySecurityConfig::from_proxy_config(&config);
+        assert!(matches!(local.effective_auth_mode(), ProxyAuthMode::Off));
+
+        for addr in ["0.0.0.0", "::", "192.168.1.10", "my-host"] {
+            config.bind_address = Some(addr.to_string());
+            let s = ProxySecurityConfig::from_proxy_config(&config);
+            assert!(
+                matches!(s.effective_auth_mode(), ProxyAuthMode::AllExceptHealth),
+                "{}",
+                addr
+            );
+        }
+        for addr in ["127.0.0.1", "::1", "[::1]", "localhost"] {
+            config.bind_address = Some(addr.to_string());
+            let s = ProxySecurityConfig::from_proxy_config(&config);
+            assert!(
+                matches!(s.effective_auth_mode(), ProxyAuthMode::Off),
+                "{}",
+                addr
+            );
+        }
+    }
 }
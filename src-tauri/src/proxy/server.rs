@@ -1,5 +1,8 @@
 use crate::models::AppConfig;
-use crate::modules::{account, config, logger, migration, proxy_db, security_db, token_stats};
+use crate::modules::{
+    account, account_transfer, audit_export, chargeback, config, logger, migration, proxy_db,
+    quota_history, security_db, token_stats,
+};
 use crate::proxy::TokenManager;
 use axum::{
     extract::{DefaultBodyLimit, Path, Query, State},
@@ -89,36 +92,28 @@ pub fn take_pending_delete_accounts() -> Vec<String> {
 #[derive(Clone)]
 pub struct AppState {
     pub token_manager: Arc<TokenManager>,
-    pub custom_mapping: Arc<tokio::sync::RwLock<std::collections::HashMap<String, String>>>,
-    #[allow(dead_code)]
-    pub request_timeout: u64, // API 请求超时(秒)
     #[allow(dead_code)]
     pub thought_signature_map: Arc<tokio::sync::Mutex<std::collections::HashMap<String, String>>>, // 思维链签名映射 (ID -> Signature)
-    #[allow(dead_code)]
-    pub upstream_proxy: Arc<tokio::sync::RwLock<crate::proxy::config::UpstreamProxyConfig>>,
     pub upstream: Arc<crate::proxy::upstream::client::UpstreamClient>,
-    pub zai: Arc<RwLock<crate::proxy::ZaiConfig>>,
     pub provider_rr: Arc<AtomicUsize>,
     pub zai_vision_mcp: Arc<crate::proxy::zai_vision_mcp::ZaiVisionMcpState>,
     pub monitor: Arc<crate::proxy::monitor::ProxyMonitor>,
-    pub experimental: Arc<RwLock<crate::proxy::config::ExperimentalConfig>>,
-    pub debug_logging: Arc<RwLock<crate::proxy::config::DebugLoggingConfig>>,
     pub switching: Arc<RwLock<bool>>, // [NEW] 账号切换状态，用于防止并发切换
     pub integration: crate::modules::integration::SystemManager, // [NEW] 系统集成层实现
     pub account_service: Arc<crate::modules::account_service::AccountService>, // [NEW] 账号管理服务层
-    pub security: Arc<RwLock<crate::proxy::ProxySecurityConfig>>,              // [NEW] 安全配置状态
     pub cloudflared_state: Arc<crate::commands::cloudflared::CloudflaredState>, // [NEW] Cloudflared 插件状态
     pub is_running: Arc<RwLock<bool>>, // [NEW] 运行状态标识
-    pub port: u16,                     // [NEW] 本地监听端口 (v4.0.8 修复)
-    pub proxy_pool_state: Arc<tokio::sync::RwLock<crate::proxy::config::ProxyPoolConfig>>, // [FIX Web Mode]
     pub proxy_pool_manager: Arc<crate::proxy::proxy_pool::ProxyPoolManager>, // [FIX Web Mode]
-    pub only_raw_quota_models: Arc<tokio::sync::RwLock<bool>>, // [NEW] 是否只暴露真实配额模型
+    pub config: crate::proxy::hot_reload::ConfigSnapshot, // 当前生效配置快照 (原子替换，Handler 统一从此读取)
+    pub listener: Arc<crate::proxy::hot_reload::ListenerControl>, // 监听器 (支持热切换地址)
+    pub drain: Arc<crate::proxy::drain::DrainController>, // 进行中请求计数 (优雅排空)
+    pub batches: Arc<crate::proxy::batch_runner::BatchRunner>, // Batch API 作业队列
 }
 
-// 为 AppState 实现 FromRef，以便中间件提取 security 状态
-impl axum::extract::FromRef<AppState> for Arc<RwLock<crate::proxy::ProxySecurityConfig>> {
+// 为 AppState 实现 FromRef，以便鉴权中间件从配置快照读取安全配置
+impl axum::extract::FromRef<AppState> for crate::proxy::hot_reload::ConfigSnapshot {
     fn from_ref(state: &AppState) -> Self {
-        state.security.clone()
+        state.config.clone()
     }
 }
 
@@ -259,189 +254,101 @@ fn to_account_response(
 /// Axum 服务器实例
 #[derive(Clone)]
 pub struct AxumServer {
-    shutdown_tx: Arc<tokio::sync::Mutex<Option<oneshot::Sender<()>>>>,
-    custom_mapping: Arc<tokio::sync::RwLock<std::collections::HashMap<String, String>>>,
-    proxy_state: Arc<tokio::sync::RwLock<crate::proxy::config::UpstreamProxyConfig>>,
-    upstream: Arc<crate::proxy::upstream::client::UpstreamClient>,
-    security_state: Arc<RwLock<crate::proxy::ProxySecurityConfig>>,
-    zai_state: Arc<RwLock<crate::proxy::ZaiConfig>>,
-    experimental: Arc<RwLock<crate::proxy::config::ExperimentalConfig>>,
-    debug_logging: Arc<RwLock<crate::proxy::config::DebugLoggingConfig>>,
+    app_state: AppState,
     #[allow(dead_code)] // 预留给 cloudflared 运行状态查询与后续控制
     pub cloudflared_state: Arc<crate::commands::cloudflared::CloudflaredState>,
-    pub is_running: Arc<RwLock<bool>>,
     pub token_manager: Arc<TokenManager>, // [NEW] 暴露出 TokenManager 供反代服务复用
-    pub proxy_pool_state: Arc<tokio::sync::RwLock<crate::proxy::config::ProxyPoolConfig>>, // [NEW] 代理池配置状态
     pub proxy_pool_manager: Arc<crate::proxy::proxy_pool::ProxyPoolManager>, // [NEW] 暴露代理池管理器供命令调用
-    pub only_raw_quota_models: Arc<tokio::sync::RwLock<bool>>,
+    /// 配置文件监听任务 (停止服务时取消，避免重启后残留的监听器继续作用于旧状态)
+    config_watcher: Arc<tokio::task::JoinHandle<()>>,
 }
 
 impl AxumServer {
-    pub async fn update_only_raw_quota_models(&self, only_raw: bool) {
-        let mut r = self.only_raw_quota_models.write().await;
-        *r = only_raw;
-        tracing::debug!("only_raw_quota_models 已更新: {}", only_raw);
-    }
-
-    pub async fn update_mapping(&self, config: &crate::proxy::config::ProxyConfig) {
-        {
-            let mut m = self.custom_mapping.write().await;
-            *m = config.custom_mapping.clone();
-        }
-        tracing::debug!("模型映射 (Custom) 已全量热更新");
-    }
-
-    /// 更新代理配置
-    pub async fn update_proxy(&self, new_config: crate::proxy::config::UpstreamProxyConfig) {
-        {
-            let mut proxy = self.proxy_state.write().await;
-            *proxy = new_config.clone();
-        }
-        // [HOT-RELOAD] Rebuild default HTTP client with new upstream proxy
-        self.upstream.rebuild_default_client(Some(new_config)).await;
-        // Stale per-proxy clients may also be affected (e.g. fallback path)
-        self.upstream.clear_client_cache();
-        tracing::info!("Upstream proxy config hot-reloaded");
-    }
-
-    /// 更新代理池配置
-    pub async fn update_proxy_pool(&self, new_config: crate::proxy::config::ProxyPoolConfig) {
-        {
-            let mut pool = self.proxy_pool_state.write().await;
-            *pool = new_config;
-        }
-        // [HOT-RELOAD] Re-sync in-memory account↔proxy bindings from the new config
-        self.proxy_pool_manager.sync_bindings_from_config().await;
-        // [HOT-RELOAD] Drop cached per-proxy HTTP clients so they rebuild with new URLs/credentials
-        self.upstream.clear_client_cache();
-        tracing::info!("Proxy pool config hot-reloaded");
-    }
-
-    pub async fn update_security(&self, config: &crate::proxy::config::ProxyConfig) {
-        let mut sec = self.security_state.write().await;
-        *sec = crate::proxy::ProxySecurityConfig::from_proxy_config(config);
-        tracing::info!("反代服务安全配置已热更新");
-    }
-
-    pub async fn update_zai(&self, config: &crate::proxy::config::ProxyConfig) {
-        let mut zai = self.zai_state.write().await;
-        *zai = config.zai.clone();
-        tracing::info!("z.ai 配置已热更新");
-    }
-
-    pub async fn update_experimental(&self, config: &crate::proxy::config::ProxyConfig) {
-        let mut exp = self.experimental.write().await;
-        *exp = config.experimental.clone();
-        tracing::info!("实验性配置已热更新");
-    }
-
-    pub async fn update_debug_logging(&self, config: &crate::proxy::config::ProxyConfig) {
-        let mut dbg_cfg = self.debug_logging.write().await;
-        *dbg_cfg = config.debug_logging.clone();
-        tracing::info!("调试日志配置已热更新");
-    }
-
-    pub async fn update_user_agent(&self, config: &crate::proxy::config::ProxyConfig) {
-        self.upstream
-            .set_user_agent_override(config.user_agent_override.clone())
-            .await;
-        tracing::info!("User-Agent 配置已热更新: {:?}", config.user_agent_override);
-    }
-
     pub async fn set_running(&self, running: bool) {
-        let mut r = self.is_running.write().await;
-        *r = running;
+        crate::proxy::drain::set_running(&self.app_state, running).await;
         tracing::info!("反代服务运行状态更新为: {}", running);
     }
 
+    /// 进程退出前排空进行中的请求并停止监听
+    pub async fn drain_and_stop(&self) {
+        self.config_watcher.abort();
+        self.app_state.batches.stop();
+        crate::proxy::drain::shutdown(&self.app_state).await;
+    }
+
     /// 启动 Axum 服务器
     pub async fn start(
         host: String,
         port: u16,
         token_manager: Arc<TokenManager>,
-        custom_mapping: std::collections::HashMap<String, String>,
-        _request_timeout: u64,
-        upstream_proxy: crate::proxy::config::UpstreamProxyConfig,
-        user_agent_override: Option<String>,
-        security_config: crate::proxy::ProxySecurityConfig,
-        zai_config: crate::proxy::ZaiConfig,
         monitor: Arc<crate::proxy::monitor::ProxyMonitor>,
-        experimental_config: crate::proxy::config::ExperimentalConfig,
-        debug_logging: crate::proxy::config::DebugLoggingConfig,
-
         integration: crate::modules::integration::SystemManager,
         cloudflared_state: Arc<crate::commands::cloudflared::CloudflaredState>,
-        proxy_pool_config: crate::proxy::config::ProxyPoolConfig, // [NEW]
-        only_raw_quota_models: bool,
+        app_config: crate::models::AppConfig,
     ) -> Result<(Self, tokio::task::JoinHandle<()>), String> {
-        let custom_mapping_state = Arc::new(tokio::sync::RwLock::new(custom_mapping));
-        let proxy_state = Arc::new(tokio::sync::RwLock::new(upstream_proxy.clone()));
-        let proxy_pool_state = Arc::new(tokio::sync::RwLock::new(proxy_pool_config));
-        let proxy_pool_manager =
-            crate::proxy::proxy_pool::init_global_proxy_pool(proxy_pool_state.clone());
+        let proxy = app_config.proxy.clone();
+        let config = crate::proxy::hot_reload::new_snapshot(app_config);
+        let proxy_pool_manager = crate::proxy::proxy_pool::init_global_proxy_pool(config.clone());
 
         // Start health check loop
         proxy_pool_manager.clone().start_health_check_loop();
-        let security_state = Arc::new(RwLock::new(security_config));
-        let zai_state = Arc::new(RwLock::new(zai_config));
         let provider_rr = Arc::new(AtomicUsize::new(0));
         let zai_vision_mcp_state = Arc::new(crate::proxy::zai_vision_mcp::ZaiVisionMcpState::new());
-        let experimental_state = Arc::new(RwLock::new(experimental_config));
-        let debug_logging_state = Arc::new(RwLock::new(debug_logging));
+        crate::proxy::signature_cache::apply_persistence_config(
+            proxy.experimental.enable_persistent_signature_cache,
+        );
         let is_running_state = Arc::new(RwLock::new(false));
 
-        let only_raw_quota_models_state = Arc::new(tokio::sync::RwLock::new(only_raw_quota_models));
-
         let state = AppState {
             token_manager: token_manager.clone(),
-            custom_mapping: custom_mapping_state.clone(),
-            request_timeout: 300, // 5分钟超时
             thought_signature_map: Arc::new(tokio::sync::Mutex::new(
                 std::collections::HashMap::new(),
             )),
-            upstream_proxy: proxy_state.clone(),
             upstream: {
                 let u = Arc::new(crate::proxy::upstream::client::UpstreamClient::new(
-                    Some(upstream_proxy.clone()),
+                    Some(proxy.upstream_proxy.clone()),
                     Some(proxy_pool_manager.clone()),
                 ));
                 // 初始化 User-Agent 覆盖
-                if user_agent_override.is_some() {
-                    u.set_user_agent_override(user_agent_override).await;
+                if proxy.user_agent_override.is_some() {
+                    u.set_user_agent_override(proxy.user_agent_override.clone())
+                        .await;
                 }
+                // 初始化夹具录制 / 上游地址覆盖
+                u.set_debug_logging(proxy.debug_logging.clone()).await;
                 u
             },
-            zai: zai_state.clone(),
             provider_rr: provider_rr.clone(),
             zai_vision_mcp: zai_vision_mcp_state,
             monitor: monitor.clone(),
-            experimental: experimental_state.clone(),
-            debug_logging: debug_logging_state.clone(),
             switching: Arc::new(RwLock::new(false)),
             integration: integration.clone(),
             account_service: Arc::new(crate::modules::account_service::AccountService::new(
                 integration.clone(),
             )),
-            security: security_state.clone(),
             cloudflared_state: cloudflared_state.clone(),
-            is_running: is_running_state.clone(),
-            port,
-            proxy_pool_state: proxy_pool_state.clone(),
+            is_running: is_running_state,
             proxy_pool_manager: proxy_pool_manager.clone(),
-            only_raw_quota_models: only_raw_quota_models_state.clone(),
+            config,
+            listener: Arc::new(crate::proxy::hot_reload::ListenerControl::new()),
+            drain: Arc::new(crate::proxy::drain::DrainController::new()),
+            batches: Arc::new(crate::proxy::batch_runner::BatchRunner::open(
+                &crate::modules::account::get_data_dir()?,
+            )?),
         };
 
         // 构建路由 - 使用新架构的 handlers！
         use crate::proxy::handlers;
         use crate::proxy::middleware::{
             admin_auth_middleware, auth_middleware, cors_layer, ip_filter_middleware,
-            monitor_middleware, service_status_middleware,
+            monitor_middleware, service_status_middleware, telemetry_middleware,
         };
 
         // 1. 构建主 AI 代理路由 (遵循 auth_mode 配置)
         let proxy_routes = Router::new()
             .route("/health", get(health_check_handler))
             .route("/healthz", get(health_check_handler))
+            .route("/metrics", get(metrics_handler)) // Prometheus 指标 (遵循 auth_mode)
             // OpenAI Protocol
             .route("/v1/models", get(handlers::openai::handle_list_models))
             .route(
@@ -457,7 +364,15 @@ impl AxumServer {
                 post(handlers::openai::handle_completions)
                     .get(handlers::openai::handle_responses_websocket),
             ) // 兼容 Codex CLI
+            .route(
+                "/v1/responses/input_tokens",
+                post(handlers::openai::handle_responses_input_tokens),
+            )
             .route("/responses", post(handlers::openai::handle_completions))
+            .route(
+                "/responses/input_tokens",
+                post(handlers::openai::handle_responses_input_tokens),
+            )
             .route(
                 "/responses/compact",
                 post(handlers::openai::handle_completions),
@@ -474,6 +389,35 @@ impl AxumServer {
                 "/v1/audio/transcriptions",
                 post(handlers::audio::handle_audio_transcription),
             ) // 音频转录 API
+            .route(
+                "/v1/embeddings",
+                post(handlers::embeddings::handle_embeddings),
+            ) // Embeddings API
+            .route(
+                "/v1/files",
+                post(handlers::batch::handle_upload_file).get(handlers::batch::handle_list_files),
+            ) // Files API (Batch 输入 / 输出)
+            .route(
+                "/v1/files/:file_id",
+                get(handlers::batch::handle_get_file).delete(handlers::batch::handle_delete_file),
+            )
+            .route(
+                "/v1/files/:file_id/content",
+                get(handlers::batch::handle_get_file_content),
+            )
+            .route(
+                "/v1/batches",
+                post(handlers::batch::handle_create_batch)
+                    .get(handlers::batch::handle_list_batches),
+            ) // Batch API (本地作业队列)
+            .route(
+                "/v1/batches/:batch_id",
+                get(handlers::batch::handle_get_batch),
+            )
+            .route(
+                "/v1/batches/:batch_id/cancel",
+                post(handlers::batch::handle_cancel_batch),
+            )
             // Claude Protocol
             .route("/v1/messages", post(handlers::claude::handle_messages))
             .route(
@@ -514,9 +458,10 @@ impl AxumServer {
             .route("/v1/api/event_logging", post(silent_ok_handler))
             // 应用 AI 服务特定的层
             // 注意：Axum layer 执行顺序是从下往上（洋葱模型）
-            // 请求: ip_filter -> auth -> monitor -> handler
-            // 响应: handler -> monitor -> auth -> ip_filter
+            // 请求: telemetry -> ip_filter -> auth -> monitor -> handler
+            // 响应: handler -> monitor -> auth -> ip_filter -> telemetry
             // monitor 需要在 auth 之后执行才能获取 UserTokenIdentity
+            // telemetry 在最外层创建根 span，其余中间件与 handler 均为其子 span
             .layer(axum::middleware::from_fn_with_state(
                 state.clone(),
                 monitor_middleware,
@@ -528,7 +473,8 @@ impl AxumServer {
             .layer(axum::middleware::from_fn_with_state(
                 state.clone(),
                 ip_filter_middleware,
-            ));
+            ))
+            .layer(axum::middleware::from_fn(telemetry_middleware));
 
         // 2. 构建管理 API (强制鉴权)
         let admin_routes = Router::new()
@@ -573,6 +519,10 @@ impl AxumServer {
             .route("/accounts/import/v1", post(admin_import_v1_accounts))
             .route("/accounts/import/db", post(admin_import_from_db))
             .route("/accounts/import/db-custom", post(admin_import_custom_db))
+            .route(
+                "/accounts/import/encrypted",
+                post(admin_import_encrypted_accounts),
+            )
             .route("/accounts/sync/db", post(admin_sync_account_from_db))
             .route("/stats/summary", get(admin_get_token_stats_summary))
             .route("/stats/hourly", get(admin_get_token_stats_hourly))
@@ -625,7 +575,10 @@ impl AxumServer {
                 "/proxy/session-bindings/clear",
                 post(admin_clear_proxy_session_bindings),
             )
-            .route("/proxy/rate-limits", delete(admin_clear_all_rate_limits))
+            .route(
+                "/proxy/rate-limits",
+                get(admin_list_rate_limits).delete(admin_clear_all_rate_limits),
+            )
             .route(
                 "/proxy/rate-limits/:accountId",
                 delete(admin_clear_rate_limit),
@@ -660,10 +613,15 @@ impl AxumServer {
             .route("/proxy/cloudflared/start", post(admin_cloudflared_start))
             .route("/proxy/cloudflared/stop", post(admin_cloudflared_stop))
             .route("/system/open-folder", post(admin_open_folder))
+            .route("/system/backup", post(admin_create_backup))
+            .route("/system/restore", post(admin_restore_backup))
+            .route("/system/reload", post(admin_reload_from_disk))
+            .route("/system/shutdown", post(admin_shutdown))
             .route("/proxy/stats", get(admin_get_proxy_stats))
             .route("/logs", get(admin_get_proxy_logs_filtered))
             .route("/logs/count", get(admin_get_proxy_logs_count_filtered))
             .route("/logs/clear", post(admin_clear_proxy_logs))
+            .route("/logs/export", get(admin_export_audit_log))
             .route("/logs/:logId", get(admin_get_proxy_log_detail))
             // Debug Console (Log Bridge)
             .route("/debug/enable", post(admin_enable_debug_console))
@@ -697,8 +655,15 @@ impl AxumServer {
                 "/stats/token/account-trend/daily",
                 get(admin_get_token_stats_account_trend_daily),
             )
+            .route("/stats/token/cost", get(admin_get_token_cost_report))
+            .route("/stats/quota/history", get(admin_get_quota_history))
+            .route("/stats/quota/forecast", get(admin_get_quota_forecast))
             .route("/accounts/bulk-delete", post(admin_delete_accounts))
             .route("/accounts/export", post(admin_export_accounts))
+            .route(
+                "/accounts/export/encrypted",
+                post(admin_export_encrypted_accounts),
+            )
             .route("/accounts/reorder", post(admin_reorder_accounts))
             .route("/accounts/:accountId/quota", get(admin_fetch_account_quota))
             .route(
@@ -771,6 +736,7 @@ impl AxumServer {
             )
             .route("/user-tokens/summary", get(admin_get_user_token_summary))
             .route("/user-tokens/:id/renew", post(admin_renew_user_token))
+            .route("/user-tokens/:id/usage", get(admin_get_user_token_usage))
             .route(
                 "/user-tokens/:id",
                 delete(admin_delete_user_token).patch(admin_update_user_token),
@@ -816,93 +782,36 @@ impl AxumServer {
             app
         };
 
-        // 绑定地址
+        // 绑定地址 (监听器支持热切换，见 hot_reload)
         let addr = format!("{}:{}", host, port);
-        let listener = tokio::net::TcpListener::bind(&addr)
-            .await
-            .map_err(|e| format!("地址 {} 绑定失败: {}", addr, e))?;
+        state.listener.set_app(app);
+        state
+            .listener
+            .set_reuse_port(state.config.load().proxy.shutdown.reuse_port);
+        let handle = state.listener.bind(&addr).await?;
 
-        tracing::info!("反代服务器启动在 http://{}", addr);
-
-        // 创建关闭通道
-        let (shutdown_tx, mut shutdown_rx) = oneshot::channel::<()>();
+        // 监听配置文件变化，外部修改后自动热更新
+        let config_watcher = crate::proxy::hot_reload::spawn_config_watcher(state.clone());
+        // 恢复并执行 Batch API 作业
+        crate::proxy::batch_runner::spawn(state.clone());
 
         let server_instance = Self {
-            shutdown_tx: Arc::new(tokio::sync::Mutex::new(Some(shutdown_tx))),
-            custom_mapping: custom_mapping_state.clone(),
-            proxy_state,
-            upstream: state.upstream.clone(),
-            security_state,
-            zai_state,
-            experimental: experimental_state.clone(),
-            debug_logging: debug_logging_state.clone(),
+            app_state: state.clone(),
             cloudflared_state,
-            is_running: is_running_state,
             token_manager: token_manager.clone(),
-            proxy_pool_state,
             proxy_pool_manager,
-            only_raw_quota_models: only_raw_quota_models_state,
+            config_watcher: Arc::new(config_watcher),
         };
 
-        // 在新任务中启动服务器
-        let handle = tokio::spawn(async move {
-            use hyper::server::conn::http1;
-            use hyper_util::rt::TokioIo;
-            use hyper_util::service::TowerToHyperService;
-
-            loop {
-                tokio::select! {
-                    res = listener.accept() => {
-                        match res {
-                            Ok((stream, remote_addr)) => {
-                                let io = TokioIo::new(stream);
-
-                                // 注入 ConnectInfo (用于获取真实 IP)
-                                use tower::ServiceExt;
-                                use hyper::body::Incoming;
-                                let app_with_info = app.clone().map_request(move |mut req: axum::http::Request<Incoming>| {
-                                    req.extensions_mut().insert(axum::extract::ConnectInfo(remote_addr));
-                                    req
-                                });
-
-                                let service = TowerToHyperService::new(app_with_info);
-
-                                tokio::task::spawn(async move {
-                                    if let Err(err) = http1::Builder::new()
-                                        .serve_connection(io, service)
-                                        .with_upgrades() // 支持 WebSocket (如果以后需要)
-                                        .await
-                                    {
-                                        debug!("连接处理结束或出错: {:?}", err);
-                                    }
-                                });
-                            }
-                            Err(e) => {
-                                error!("接收连接失败: {:?}", e);
-                            }
-                        }
-                    }
-                    _ = &mut shutdown_rx => {
-                        tracing::info!("反代服务器停止监听");
-                        break;
-                    }
-                }
-            }
-        });
-
         Ok((server_instance, handle))
     }
 
-    /// 停止服务器
-    pub fn stop(&self) {
-        let tx_mutex = self.shutdown_tx.clone();
-        tokio::spawn(async move {
-            let mut lock = tx_mutex.lock().await;
-            if let Some(tx) = lock.take() {
-                let _ = tx.send(());
-                tracing::info!("Axum server 停止信号已发送");
-            }
-        });
+    /// 热更新完整配置 (原子替换快照，必要时重新绑定监听器)
+    pub async fn apply_config(
+        &self,
+        config: crate::models::AppConfig,
+    ) -> Result<crate::proxy::hot_reload::ReloadReport, String> {
+        crate::proxy::hot_reload::apply_config(&self.app_state, config).await
     }
 }
 
@@ -917,6 +826,37 @@ async fn health_check_handler() -> Response {
     .into_response()
 }
 
+/// Prometheus 指标处理器
+async fn metrics_handler(State(state): State<AppState>) -> Response {
+    let token_manager = &state.token_manager;
+    let rate_limits = token_manager.get_rate_limit_snapshot();
+    let account_emails: std::collections::HashMap<String, String> = rate_limits
+        .iter()
+        .filter_map(|limit| {
+            token_manager
+                .get_token_by_id(&limit.account_id)
+                .map(|t| (limit.account_id.clone(), t.email))
+        })
+        .collect();
+    let quotas = token_manager.get_quota_snapshot();
+    let cache = crate::proxy::cache_manager::global_cache_manager().get_layer_stats();
+
+    let body = crate::proxy::metrics::ProxyMetrics::global().render(
+        &rate_limits,
+        &account_emails,
+        &quotas,
+        &cache,
+    );
+    (
+        [(
+            axum::http::header::CONTENT_TYPE,
+            crate::proxy::metrics::METRICS_CONTENT_TYPE,
+        )],
+        body,
+    )
+        .into_response()
+}
+
 /// 静默成功处理器 (用于拦截遥测日志等)
 async fn silent_ok_handler() -> Response {
     StatusCode::OK.into_response()
@@ -1012,6 +952,34 @@ async fn admin_export_accounts(
     Ok(Json(response))
 }
 
+/// Export accounts as a passphrase-encrypted portable bundle (for moving between machines)
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ExportEncryptedAccountsRequest {
+    account_ids: Vec<String>,
+    passphrase: String,
+}
+
+async fn admin_export_encrypted_accounts(
+    Json(payload): Json<ExportEncryptedAccountsRequest>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+    let res = tokio::task::spawn_blocking(move || {
+        account_transfer::export_accounts(&payload.account_ids, &payload.passphrase)
+    })
+    .await;
+
+    match res {
+        Ok(Ok(data)) => Ok(Json(data)),
+        Ok(Err(e)) => Err((StatusCode::BAD_REQUEST, Json(ErrorResponse { error: e }))),
+        Err(e) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ErrorResponse {
+                error: e.to_string(),
+            }),
+        )),
+    }
+}
+
 async fn admin_get_current_account(
     State(state): State<AppState>,
 ) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
@@ -1429,7 +1397,13 @@ async fn admin_save_config(
     State(state): State<AppState>,
     Json(payload): Json<SaveConfigWrapper>,
 ) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
-    let new_config = payload.config;
+    // 托管字段 (环境变量 / 声明式配置文件) 不可通过 API 修改
+    let new_config = crate::modules::managed_config::apply(payload.config).map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ErrorResponse { error: e }),
+        )
+    })?;
     // 1. 持久化
     config::save_app_config(&new_config).map_err(|e| {
         (
@@ -1438,59 +1412,56 @@ async fn admin_save_config(
         )
     })?;
 
-    // 2. 热更新内存状态
-    // 这里我们直接复用内部组件的 update 方法
-    // 注意：AppState 本身持有各个组件的 Arc<RwLock> 或直接持有引用
-
-    // 我们需要一个方式获取到当前的 AxumServer 实例来进行热更新，
-    // 或者直接操作 AppState 里的各状态。
-    // 在本重构中，各个状态已经在 AppState 中了。
-
-    // 更新模型映射
-    {
-        let mut mapping = state.custom_mapping.write().await;
-        *mapping = new_config.clone().proxy.custom_mapping;
-    }
-
-    // 更新上游代理
-    {
-        let mut proxy = state.upstream_proxy.write().await;
-        *proxy = new_config.clone().proxy.upstream_proxy;
-    }
-
-    // 更新安全策略
-    {
-        let mut security = state.security.write().await;
-        *security = crate::proxy::ProxySecurityConfig::from_proxy_config(&new_config.proxy);
-    }
+    // 2. 热更新内存状态 (与桌面端 save_config 共用同一路径)
+    let report = crate::proxy::hot_reload::apply_config(&state, new_config)
+        .await
+        .map_err(|e| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ErrorResponse { error: e }),
+            )
+        })?;
 
-    // 更新 z.ai 配置
-    {
-        let mut zai = state.zai.write().await;
-        *zai = new_config.clone().proxy.zai;
-    }
+    Ok(Json(report))
+}
 
-    // 更新实验性配置
-    {
-        let mut exp = state.experimental.write().await;
-        *exp = new_config.clone().proxy.experimental;
-    }
+/// 流式导出审计日志 (JSONL / CSV)
+async fn admin_export_audit_log(
+    Query(query): Query<audit_export::ExportQuery>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+    // 先校验参数，错误以 400 返回而不是中断的流
+    let plan = audit_export::plan(&query)
+        .map_err(|e| (StatusCode::BAD_REQUEST, Json(ErrorResponse { error: e })))?;
+    let content_type = plan.content_type();
+    let disposition = format!("attachment; filename=\"{}\"", plan.file_name());
 
-    // 更新代理池配置（Web/Docker 保存配置时热更新）
-    {
-        let mut pool = state.proxy_pool_state.write().await;
-        *pool = new_config.clone().proxy.proxy_pool;
-    }
+    let (tx, rx) = tokio::sync::mpsc::channel(16);
+    tokio::task::spawn_blocking(move || {
+        let mut writer = audit_export::ChannelWriter::new(tx.clone());
+        match audit_export::write_export(&plan, &mut writer) {
+            Ok(count) => debug!("审计日志导出完成: {} 行", count),
+            Err(e) => {
+                error!("审计日志导出失败: {}", e);
+                // 以流错误结束响应，客户端可感知导出不完整
+                let _ = tx.blocking_send(Err(std::io::Error::other(e)));
+            }
+        }
+    });
 
-    Ok(StatusCode::OK)
+    Ok((
+        [
+            (axum::http::header::CONTENT_TYPE, content_type.to_string()),
+            (axum::http::header::CONTENT_DISPOSITION, disposition),
+        ],
+        axum::body::Body::from_stream(tokio_stream::wrappers::ReceiverStream::new(rx)),
+    ))
 }
 
 // [FIX Web Mode] Get proxy pool config
 async fn admin_get_proxy_pool_config(
     State(state): State<AppState>,
 ) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
-    let config = state.proxy_pool_state.read().await;
-    Ok(Json(config.clone()))
+    Ok(Json(state.proxy_pool_manager.pool_config()))
 }
 
 // [FIX Web Mode] Get all account proxy bindings
@@ -1565,7 +1536,7 @@ async fn admin_trigger_proxy_health_check(
     })?;
 
     // 返回更新后的代理池配置（包含健康状态）
-    let config = state.proxy_pool_state.read().await;
+    let config = state.proxy_pool_manager.pool_config();
     Ok(Json(serde_json::json!({
         "success": true,
         "message": "Health check completed",
@@ -1580,10 +1551,14 @@ async fn admin_get_proxy_status(
     let active_accounts = state.token_manager.len();
 
     let is_running = { *state.is_running.read().await };
+    let port = match state.listener.local_addr().await {
+        Some(addr) => addr.port(),
+        None => state.config.load().proxy.port,
+    };
     Ok(Json(serde_json::json!({
         "running": is_running,
-        "port": state.port,
-        "base_url": format!("http://127.0.0.1:{}", state.port),
+        "port": port,
+        "base_url": format!("http://127.0.0.1:{}", port),
         "active_accounts": active_accounts,
     })))
 }
@@ -1600,8 +1575,7 @@ async fn admin_start_proxy_service(State(state): State<AppState>) -> impl IntoRe
         logger::log_error(&format!("[API] 启用服务并加载账号失败: {}", e));
     }
 
-    let mut running = state.is_running.write().await;
-    *running = true;
+    crate::proxy::drain::set_running(&state, true).await;
     logger::log_info("[API] 反代服务功能已启用 (持久化已同步)");
     StatusCode::OK
 }
@@ -1613,8 +1587,7 @@ async fn admin_stop_proxy_service(State(state): State<AppState>) -> impl IntoRes
         let _ = crate::modules::config::save_app_config(&config);
     }
 
-    let mut running = state.is_running.write().await;
-    *running = false;
+    crate::proxy::drain::set_running(&state, false).await;
     logger::log_info("[API] 反代服务功能已禁用 (Axum 模式 / 持久化已同步)");
     StatusCode::OK
 }
@@ -1631,13 +1604,7 @@ async fn admin_update_model_mapping(
 ) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
     let config = payload.config;
 
-    // 1. 更新内存状态 (热更新)
-    {
-        let mut mapping = state.custom_mapping.write().await;
-        *mapping = config.custom_mapping.clone();
-    }
-
-    // 2. 持久化到硬盘 (修复 #1149)
+    // 1. 持久化到硬盘 (修复 #1149)
     // 加载当前配置，更新 mapping，然后保存
     let mut app_config = crate::modules::config::load_app_config().map_err(|e| {
         (
@@ -1647,6 +1614,7 @@ async fn admin_update_model_mapping(
     })?;
 
     app_config.proxy.custom_mapping = config.custom_mapping;
+    app_config.proxy.model_fallback_chains = config.model_fallback_chains;
 
     crate::modules::config::save_app_config(&app_config).map_err(|e| {
         (
@@ -1655,6 +1623,16 @@ async fn admin_update_model_mapping(
         )
     })?;
 
+    // 2. 热更新 (原子替换配置快照)
+    crate::proxy::hot_reload::apply_config(&state, app_config)
+        .await
+        .map_err(|e| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ErrorResponse { error: e }),
+            )
+        })?;
+
     logger::log_info("[API] 模型映射已通过 API 热更新并保存");
     Ok(StatusCode::OK)
 }
@@ -1670,6 +1648,29 @@ async fn admin_clear_proxy_session_bindings(State(state): State<AppState>) -> im
     StatusCode::OK
 }
 
+#[derive(Serialize)]
+struct RateLimitEntryResponse {
+    #[serde(flatten)]
+    limit: crate::proxy::rate_limit::RateLimitSnapshot,
+    email: Option<String>,
+}
+
+async fn admin_list_rate_limits(State(state): State<AppState>) -> impl IntoResponse {
+    let limits: Vec<RateLimitEntryResponse> = state
+        .token_manager
+        .get_rate_limit_snapshot()
+        .into_iter()
+        .map(|limit| RateLimitEntryResponse {
+            email: state
+                .token_manager
+                .get_token_by_id(&limit.account_id)
+                .map(|t| t.email),
+            limit,
+        })
+        .collect();
+    Json(limits)
+}
+
 async fn admin_clear_all_rate_limits(State(state): State<AppState>) -> impl IntoResponse {
     state.token_manager.clear_all_rate_limits();
     logger::log_info("[API] 已清除所有限流记录");
@@ -1948,6 +1949,15 @@ async fn admin_create_user_token(
     Ok(Json(token))
 }
 
+async fn admin_get_user_token_usage(
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+    let usage = crate::commands::user_token::get_user_token_usage(id)
+        .await
+        .map_err(|e| (StatusCode::NOT_FOUND, Json(ErrorResponse { error: e })))?;
+    Ok(Json(usage))
+}
+
 #[derive(Deserialize)]
 #[serde(rename_all = "camelCase")]
 struct RenewTokenRequest {
@@ -2293,6 +2303,87 @@ async fn admin_get_token_stats_account_trend_daily(
     }
 }
 
+// Quota History Handlers
+#[derive(Deserialize, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+struct QuotaHistoryQuery {
+    account_id: Option<String>,
+    hours: Option<i64>,
+}
+
+async fn admin_get_quota_history(
+    Query(p): Query<QuotaHistoryQuery>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+    let hours = p.hours.unwrap_or(24);
+    let res = tokio::task::spawn_blocking(move || {
+        quota_history::get_history(p.account_id.as_deref(), hours)
+    })
+    .await;
+
+    match res {
+        Ok(Ok(history)) => Ok(Json(history)),
+        Ok(Err(e)) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ErrorResponse { error: e }),
+        )),
+        Err(e) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ErrorResponse {
+                error: e.to_string(),
+            }),
+        )),
+    }
+}
+
+async fn admin_get_quota_forecast(
+    Query(p): Query<QuotaHistoryQuery>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+    // 默认取最近 7 天样本，覆盖 weekly 窗口的完整周期
+    let hours = p.hours.unwrap_or(168);
+    let res = tokio::task::spawn_blocking(move || {
+        quota_history::get_forecasts(p.account_id.as_deref(), hours)
+    })
+    .await;
+
+    match res {
+        Ok(Ok(forecasts)) => Ok(Json(forecasts)),
+        Ok(Err(e)) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ErrorResponse { error: e }),
+        )),
+        Err(e) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ErrorResponse {
+                error: e.to_string(),
+            }),
+        )),
+    }
+}
+
+async fn admin_get_token_cost_report(
+    State(state): State<AppState>,
+    Query(query): Query<chargeback::CostQuery>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+    let plan = chargeback::plan(&query)
+        .map_err(|e| (StatusCode::BAD_REQUEST, Json(ErrorResponse { error: e })))?;
+    let pricing = state.config.load().proxy.pricing.clone();
+    let res = tokio::task::spawn_blocking(move || chargeback::cost_report(&plan, &pricing)).await;
+
+    match res {
+        Ok(Ok(report)) => Ok(Json(report)),
+        Ok(Err(e)) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ErrorResponse { error: e }),
+        )),
+        Err(e) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ErrorResponse {
+                error: e.to_string(),
+            }),
+        )),
+    }
+}
+
 async fn admin_clear_token_stats() -> impl IntoResponse {
     let res = tokio::task::spawn_blocking(|| {
         // Clear databases (brute force)
@@ -2801,8 +2892,206 @@ async fn admin_open_folder() -> Result<impl IntoResponse, (StatusCode, Json<Erro
     Ok(StatusCode::OK)
 }
 
+// --- Backup Handlers ---
+
+/// 备份口令通过请求头传递，避免出现在访问日志的 URL 中
+const BACKUP_PASSPHRASE_HEADER: &str = "X-Backup-Passphrase";
+
+fn backup_passphrase(headers: &HeaderMap) -> Option<String> {
+    headers
+        .get(BACKUP_PASSPHRASE_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .filter(|v| !v.is_empty())
+        .map(|v| v.to_string())
+}
+
+async fn admin_create_backup(
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+    let passphrase = backup_passphrase(&headers);
+    let encrypted = passphrase.is_some();
+    let res = tokio::task::spawn_blocking(move || {
+        let data_dir = account::get_data_dir()?;
+        crate::modules::backup::create_backup_in(&data_dir, passphrase.as_deref())
+    })
+    .await;
+
+    let (archive, manifest) = match res {
+        Ok(Ok(result)) => result,
+        Ok(Err(e)) => {
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ErrorResponse { error: e }),
+            ))
+        }
+        Err(e) => {
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ErrorResponse {
+                    error: e.to_string(),
+                }),
+            ))
+        }
+    };
+    logger::log_info(&format!(
+        "[Backup] Created backup via admin API ({} databases, {} files, encrypted: {})",
+        manifest.databases.len(),
+        manifest.files.len(),
+        encrypted
+    ));
+
+    let content_type = if encrypted {
+        "application/octet-stream"
+    } else {
+        "application/gzip"
+    };
+    Ok((
+        [
+            (axum::http::header::CONTENT_TYPE, content_type.to_string()),
+            (
+                axum::http::header::CONTENT_DISPOSITION,
+                format!(
+                    "attachment; filename=\"{}\"",
+                    crate::modules::backup::default_file_name(encrypted)
+                ),
+            ),
+        ],
+        archive,
+    ))
+}
+
+async fn admin_restore_backup(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    body: axum::body::Bytes,
+) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+    let passphrase = backup_passphrase(&headers);
+    let res = tokio::task::spawn_blocking(move || {
+        let data_dir = account::get_data_dir()?;
+        crate::modules::backup::restore_backup_in(&data_dir, &body, passphrase.as_deref())
+    })
+    .await;
+
+    let manifest = match res {
+        Ok(Ok(manifest)) => manifest,
+        Ok(Err(e)) => {
+            return Err((StatusCode::BAD_REQUEST, Json(ErrorResponse { error: e })));
+        }
+        Err(e) => {
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ErrorResponse {
+                    error: e.to_string(),
+                }),
+            ))
+        }
+    };
+
+    logger::log_info(&format!(
+        "[Backup] Restored backup created by v{} at {}",
+        manifest.app_version, manifest.created_at
+    ));
+
+    // 恢复后立即热更新配置并重新加载账号，无需重启反代服务
+    let reload = crate::proxy::hot_reload::reload_from_disk(&state)
+        .await
+        .map_err(|e| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ErrorResponse {
+                    error: format!("Backup restored but config reload failed: {}", e),
+                }),
+            )
+        })?;
+    let _ = state.token_manager.load_accounts().await;
+
+    Ok(Json(serde_json::json!({
+        "manifest": manifest,
+        "reload": reload,
+    })))
+}
+
+/// 重新读取磁盘上的配置与账号 (供 CLI 在修改文件 / 数据库后通知运行中的实例)
+async fn admin_reload_from_disk(
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+    let reload = crate::proxy::hot_reload::reload_from_disk(&state)
+        .await
+        .map_err(|e| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ErrorResponse { error: e }),
+            )
+        })?;
+    let accounts = state.token_manager.load_accounts().await.map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ErrorResponse { error: e }),
+        )
+    })?;
+
+    Ok(Json(serde_json::json!({
+        "accounts": accounts,
+        "reload": reload,
+    })))
+}
+
+/// 优雅停止 headless 实例 (`proxy stop`，Windows 无 SIGTERM 可用)
+async fn admin_shutdown() -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+    if crate::modules::cli::request_shutdown() {
+        logger::log_info("[API] 收到停止请求，headless 实例即将退出");
+        Ok(StatusCode::ACCEPTED)
+    } else {
+        Err((
+            StatusCode::CONFLICT,
+            Json(ErrorResponse {
+                error: "Shutdown is only available in headless mode".to_string(),
+            }),
+        ))
+    }
+}
+
 // --- Import Handlers ---
 
+#[derive(Deserialize)]
+struct ImportEncryptedAccountsRequest {
+    data: String,
+    passphrase: String,
+}
+
+async fn admin_import_encrypted_accounts(
+    State(state): State<AppState>,
+    Json(payload): Json<ImportEncryptedAccountsRequest>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+    let res = tokio::task::spawn_blocking(move || {
+        account_transfer::import_accounts(&payload.data, &payload.passphrase)
+    })
+    .await;
+
+    let result = match res {
+        Ok(Ok(result)) => result,
+        Ok(Err(e)) => {
+            return Err((StatusCode::BAD_REQUEST, Json(ErrorResponse { error: e })));
+        }
+        Err(e) => {
+            return Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ErrorResponse {
+                    error: e.to_string(),
+                }),
+            ))
+        }
+    };
+
+    // 导入已写入代理池配置，重新加载配置以同步代理池与绑定，并立即加载导入的账号
+    if let Err(e) = crate::proxy::hot_reload::reload_from_disk(&state).await {
+        logger::log_warn(&format!("[API] 导入后重新加载配置失败: {}", e));
+    }
+    let _ = state.token_manager.load_accounts().await;
+
+    Ok(Json(result))
+}
+
 async fn admin_import_v1_accounts(
     State(state): State<AppState>,
 ) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
@@ -3072,7 +3361,7 @@ async fn handle_oauth_callback(
     let code = params.code;
 
     // Exchange token
-    let port = state.security.read().await.port;
+    let port = state.config.load().proxy.port;
     let host = headers.get("host").and_then(|h| h.to_str().ok());
     let proto = headers
         .get("x-forwarded-proto")
@@ -3195,7 +3484,7 @@ async fn admin_prepare_oauth_url_web(
     headers: HeaderMap,
     State(state): State<AppState>,
 ) -> Result<Json<serde_json::Value>, (StatusCode, Json<ErrorResponse>)> {
-    let port = state.security.read().await.port;
+    let port = state.config.load().proxy.port;
     let host = headers.get("host").and_then(|h| h.to_str().ok());
     let proto = headers
         .get("x-forwarded-proto")
@@ -3647,11 +3936,15 @@ async fn admin_update_security_config(
         )
     })?;
 
-    {
-        let mut sec = state.security.write().await;
-        *sec = crate::proxy::ProxySecurityConfig::from_proxy_config(&app_config.proxy);
-        tracing::info!("[Security] Runtime security config hot-reloaded via Web API");
-    }
+    crate::proxy::hot_reload::apply_config(&state, app_config)
+        .await
+        .map_err(|e| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ErrorResponse { error: e }),
+            )
+        })?;
+    tracing::info!("[Security] Runtime security config hot-reloaded via Web API");
 
     Ok(StatusCode::OK)
 }
@@ -1,6 +1,12 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::{Mutex, OnceLock};
-use std::time::{Duration, SystemTime};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use rusqlite::{params, Connection};
+
+use crate::modules::db_migrations::{self, Migration};
 
 // Node.js proxy uses 2 hours TTL
 const SIGNATURE_TTL: Duration = Duration::from_secs(2 * 60 * 60);
@@ -11,6 +17,17 @@ const TOOL_CACHE_LIMIT: usize = 500; // Layer 1: Tool-specific signatures
 const FAMILY_CACHE_LIMIT: usize = 200; // Layer 2: Model family mappings
 const SESSION_CACHE_LIMIT: usize = 1000; // Layer 3: Session-based signatures (largest)
 
+// Optional disk-backed layer (signature_cache.db), warmed on startup
+pub const SIGNATURE_CACHE_DB_FILE: &str = "signature_cache.db";
+const PERSISTED_ROW_LIMIT: usize = TOOL_CACHE_LIMIT + FAMILY_CACHE_LIMIT + 2 * SESSION_CACHE_LIMIT;
+// Re-apply TTL and row cap on disk every N writes so the table stays bounded at runtime
+const PRUNE_EVERY_WRITES: usize = 200;
+
+const LAYER_TOOL: &str = "tool";
+const LAYER_FAMILY: &str = "family";
+const LAYER_SESSION: &str = "session";
+const LAYER_REASONING: &str = "reasoning";
+
 /// Cache entry with timestamp for TTL
 #[derive(Clone, Debug)]
 struct CacheEntry<T> {
@@ -33,6 +50,10 @@ impl<T> CacheEntry<T> {
         }
     }
 
+    fn restored(data: T, timestamp: SystemTime) -> Self {
+        Self { data, timestamp }
+    }
+
     fn is_expired(&self) -> bool {
         self.timestamp.elapsed().unwrap_or(Duration::ZERO) > SIGNATURE_TTL
     }
@@ -63,6 +84,13 @@ pub struct SignatureCache {
     /// Key: session fingerprint
     /// Value: A vector of reasoning contents (index corresponds to assistant turn index)
     session_reasonings: Mutex<HashMap<String, CacheEntry<Vec<String>>>>,
+
+    /// Optional write-through SQLite layer so that signatures survive proxy restarts.
+    /// None = memory only (default).
+    db: Mutex<Option<Connection>>,
+
+    /// Writes since the persistent layer was attached (drives periodic pruning)
+    persisted_writes: AtomicUsize,
 }
 
 impl SignatureCache {
@@ -72,6 +100,8 @@ impl SignatureCache {
             thinking_families: Mutex::new(HashMap::new()),
             session_signatures: Mutex::new(HashMap::new()),
             session_reasonings: Mutex::new(HashMap::new()),
+            db: Mutex::new(None),
+            persisted_writes: AtomicUsize::new(0),
         }
     }
 
@@ -81,6 +111,146 @@ impl SignatureCache {
         INSTANCE.get_or_init(SignatureCache::new)
     }
 
+    // ===== Disk-backed layer =====
+
+    /// Attach the SQLite layer at `db_path` and warm the in-memory caches from it.
+    /// Returns the number of entries restored. Calling it again while attached is a no-op.
+    pub fn enable_persistence(&self, db_path: &Path) -> Result<usize, String> {
+        if self.is_persistent() {
+            return Ok(0);
+        }
+
+        let conn = init_db(db_path).map_err(|e| {
+            format!(
+                "signature_cache.db init failed at {}: {}",
+                db_path.display(),
+                e
+            )
+        })?;
+        let rows = load_persisted_rows(&conn).map_err(|e| e.to_string())?;
+        *self.db.lock().map_err(|e| e.to_string())? = Some(conn);
+
+        let restored = self.warm(rows);
+        tracing::info!(
+            "[SignatureCache] Persistent layer enabled at {}, restored {} entries",
+            db_path.display(),
+            restored
+        );
+        Ok(restored)
+    }
+
+    /// Detach the SQLite layer. In-memory entries are kept; the file is left on disk.
+    pub fn disable_persistence(&self) {
+        if let Ok(mut db) = self.db.lock() {
+            if db.take().is_some() {
+                tracing::info!("[SignatureCache] Persistent layer disabled");
+            }
+        }
+    }
+
+    pub fn is_persistent(&self) -> bool {
+        self.db.lock().map(|db| db.is_some()).unwrap_or(false)
+    }
+
+    fn warm(&self, rows: Vec<PersistedRow>) -> usize {
+        let mut restored = 0;
+        for row in rows {
+            let ok = match row.layer.as_str() {
+                LAYER_TOOL => self
+                    .tool_signatures
+                    .lock()
+                    .map(|mut cache| {
+                        cache.insert(row.key, CacheEntry::restored(row.value, row.timestamp));
+                    })
+                    .is_ok(),
+                LAYER_FAMILY => self
+                    .thinking_families
+                    .lock()
+                    .map(|mut cache| {
+                        cache.insert(row.key, CacheEntry::restored(row.value, row.timestamp));
+                    })
+                    .is_ok(),
+                LAYER_SESSION => {
+                    match serde_json::from_str::<BTreeMap<usize, String>>(&row.value) {
+                        Ok(map) => self
+                            .session_signatures
+                            .lock()
+                            .map(|mut cache| {
+                                let data = map
+                                    .into_iter()
+                                    .map(|(message_count, signature)| {
+                                        (
+                                            message_count,
+                                            SessionSignatureEntry {
+                                                signature,
+                                                message_count,
+                                            },
+                                        )
+                                    })
+                                    .collect();
+                                cache.insert(row.key, CacheEntry::restored(data, row.timestamp));
+                            })
+                            .is_ok(),
+                        Err(_) => false,
+                    }
+                }
+                LAYER_REASONING => match serde_json::from_str::<Vec<String>>(&row.value) {
+                    Ok(data) => self
+                        .session_reasonings
+                        .lock()
+                        .map(|mut cache| {
+                            cache.insert(row.key, CacheEntry::restored(data, row.timestamp));
+                        })
+                        .is_ok(),
+                    Err(_) => false,
+                },
+                _ => false,
+            };
+            if ok {
+                restored += 1;
+            }
+        }
+        restored
+    }
+
+    fn persist(&self, layer: &str, key: &str, value: &str) {
+        let Ok(mut guard) = self.db.lock() else {
+            return;
+        };
+        let Some(conn) = guard.as_mut() else {
+            return;
+        };
+        if let Err(e) = conn.execute(
+            "INSERT OR REPLACE INTO signature_cache (layer, cache_key, value, updated_unix) \
+             VALUES (?1, ?2, ?3, ?4)",
+            params![layer, key, value, unix_now()],
+        ) {
+            tracing::debug!("[SignatureCache] Failed to persist {} entry: {}", layer, e);
+        }
+
+        let writes = self.persisted_writes.fetch_add(1, Ordering::Relaxed) + 1;
+        if writes.is_multiple_of(PRUNE_EVERY_WRITES) {
+            if let Err(e) = prune_persisted_rows(conn) {
+                tracing::debug!("[SignatureCache] Failed to prune persisted rows: {}", e);
+            }
+        }
+    }
+
+    fn unpersist(&self, layer: &str, key: &str) {
+        let Ok(mut guard) = self.db.lock() else {
+            return;
+        };
+        let Some(conn) = guard.as_mut() else {
+            return;
+        };
+        if let Err(e) = conn.execute(
+            "DELETE FROM signature_cache WHERE layer = ?1 AND cache_key = ?2",
+            params![layer, key],
+        ) {
+            tracing::debug!("[SignatureCache] Failed to delete {} entry: {}", layer, e);
+        }
+    }
+
     /// Store a tool call signature
     pub fn cache_tool_signature(&self, tool_use_id: &str, signature: String) {
         if signature.len() < MIN_SIGNATURE_LENGTH {
@@ -92,7 +262,7 @@ impl SignatureCache {
                 "[SignatureCache] Caching tool signature for id: {}",
                 tool_use_id
             );
-            cache.insert(tool_use_id.to_string(), CacheEntry::new(signature));
+            cache.insert(tool_use_id.to_string(), CacheEntry::new(signature.clone()));
 
             // Clean up expired entries when limit is reached
             if cache.len() > TOOL_CACHE_LIMIT {
@@ -107,7 +277,10 @@ impl SignatureCache {
                     );
                 }
             }
+        } else {
+            return;
         }
+        self.persist(LAYER_TOOL, tool_use_id, &signature);
     }
 
     /// Retrieve a signature for a tool_use_id
@@ -138,7 +311,7 @@ impl SignatureCache {
                 signature.len(),
                 family
             );
-            cache.insert(signature, CacheEntry::new(family));
+            cache.insert(signature.clone(), CacheEntry::new(family.clone()));
 
             if cache.len() > FAMILY_CACHE_LIMIT {
                 let before = cache.len();
@@ -152,7 +325,10 @@ impl SignatureCache {
                     );
                 }
             }
+        } else {
+            return;
         }
+        self.persist(LAYER_FAMILY, &signature, &family);
     }
 
     /// Get model family for a signature
@@ -188,7 +364,7 @@ impl SignatureCache {
             return;
         }
 
-        if let Ok(mut cache) = self.session_signatures.lock() {
+        let snapshot = if let Ok(mut cache) = self.session_signatures.lock() {
             let entry = cache
                 .entry(session_id.to_string())
                 .or_insert_with(|| CacheEntry::new(HashMap::new()));
@@ -236,6 +412,13 @@ impl SignatureCache {
                 );
             }
 
+            let snapshot: BTreeMap<usize, &str> = entry
+                .data
+                .iter()
+                .map(|(mc, e)| (*mc, e.signature.as_str()))
+                .collect();
+            let snapshot = serde_json::to_string(&snapshot).ok();
+
             // Cleanup when limit is reached (Session cache has largest limit)
             if cache.len() > SESSION_CACHE_LIMIT {
                 let before = cache.len();
@@ -250,6 +433,13 @@ impl SignatureCache {
                     );
                 }
             }
+            snapshot
+        } else {
+            None
+        };
+
+        if let Some(value) = snapshot {
+            self.persist(LAYER_SESSION, session_id, &value);
         }
     }
 
@@ -308,7 +498,7 @@ impl SignatureCache {
             return;
         }
 
-        if let Ok(mut cache) = self.session_reasonings.lock() {
+        let snapshot = if let Ok(mut cache) = self.session_reasonings.lock() {
             let entry = cache
                 .entry(session_id.to_string())
                 .or_insert_with(|| CacheEntry::new(Vec::new()));
@@ -322,7 +512,8 @@ impl SignatureCache {
 
             // Only update if the new reasoning is longer to prevent overwriting with partial content
             let old_len = entry.data[turn_index].len();
-            if reasoning.len() > old_len {
+            let changed = reasoning.len() > old_len;
+            if changed {
                 tracing::debug!(
                     "[SignatureCache] Session {} (turn={}) -> caching reasoning text (len: {} -> {})",
                     session_id,
@@ -332,6 +523,11 @@ impl SignatureCache {
                 );
                 entry.data[turn_index] = reasoning;
             }
+            let snapshot = if changed {
+                serde_json::to_string(&entry.data).ok()
+            } else {
+                None
+            };
 
             // Session cache cleanup if limit exceeded
             if cache.len() > SESSION_CACHE_LIMIT {
@@ -346,6 +542,13 @@ impl SignatureCache {
                     );
                 }
             }
+            snapshot
+        } else {
+            None
+        };
+
+        if let Some(value) = snapshot {
+            self.persist(LAYER_REASONING, session_id, &value);
         }
     }
 
@@ -381,6 +584,7 @@ impl SignatureCache {
                 );
             }
         }
+        self.unpersist(LAYER_SESSION, session_id);
     }
 
     /// Clear all caches (for testing or manual reset)
@@ -398,7 +602,130 @@ impl SignatureCache {
         if let Ok(mut cache) = self.session_reasonings.lock() {
             cache.clear();
         }
+        if let Ok(mut guard) = self.db.lock() {
+            if let Some(conn) = guard.as_mut() {
+                if let Err(e) = conn.execute("DELETE FROM signature_cache", []) {
+                    tracing::debug!("[SignatureCache] Failed to clear persisted entries: {}", e);
+                }
+            }
+        }
+    }
+}
+
+/// Enable or disable the disk-backed layer of the global cache according to
+/// `ExperimentalConfig::enable_persistent_signature_cache`.
+pub fn apply_persistence_config(enabled: bool) {
+    let cache = SignatureCache::global();
+    if !enabled {
+        cache.disable_persistence();
+        return;
+    }
+    let db_path = match crate::modules::account::get_data_dir() {
+        Ok(dir) => dir.join(SIGNATURE_CACHE_DB_FILE),
+        Err(e) => {
+            tracing::warn!("[SignatureCache] Cannot resolve data dir: {}", e);
+            return;
+        }
+    };
+    if let Err(e) = cache.enable_persistence(&db_path) {
+        tracing::warn!("[SignatureCache] {} — falling back to in-memory only", e);
+    }
+}
+
+struct PersistedRow {
+    layer: String,
+    key: String,
+    value: String,
+    timestamp: SystemTime,
+}
+
+const MIGRATIONS: &[Migration] = &[Migration {
+    version: 1,
+    description: "create signature_cache",
+    up: migrate_v1_create_table,
+}];
+
+fn migrate_v1_create_table(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute_batch(
+        "
+        CREATE TABLE IF NOT EXISTS signature_cache (
+            layer TEXT NOT NULL,
+            cache_key TEXT NOT NULL,
+            value TEXT NOT NULL,
+            updated_unix INTEGER NOT NULL,
+            PRIMARY KEY (layer, cache_key)
+        );
+        CREATE INDEX IF NOT EXISTS idx_signature_cache_updated \
+            ON signature_cache(updated_unix);
+        ",
+    )
+}
+
+/// Run schema migrations on `conn` (also used to validate staged copies during restore).
+pub(crate) fn migrate(conn: &mut Connection) -> Result<u32, String> {
+    db_migrations::run_migrations(conn, SIGNATURE_CACHE_DB_FILE, MIGRATIONS)
+}
+
+fn init_db(db_path: &Path) -> Result<Connection, String> {
+    if let Some(parent) = db_path.parent() {
+        if let Err(e) = std::fs::create_dir_all(parent) {
+            tracing::warn!(
+                "[SignatureCache] create_dir_all({}) failed: {}",
+                parent.display(),
+                e
+            );
+        }
     }
+    let mut conn = Connection::open(db_path).map_err(|e| e.to_string())?;
+    conn.pragma_update(None, "journal_mode", "WAL")
+        .map_err(|e| e.to_string())?;
+    conn.pragma_update(None, "synchronous", "NORMAL")
+        .map_err(|e| e.to_string())?;
+    conn.busy_timeout(Duration::from_secs(5))
+        .map_err(|e| e.to_string())?;
+    migrate(&mut conn)?;
+    Ok(conn)
+}
+
+/// Drop expired rows and enforce the size cap (oldest first).
+fn prune_persisted_rows(conn: &Connection) -> rusqlite::Result<()> {
+    let cutoff = unix_now().saturating_sub(SIGNATURE_TTL.as_secs() as i64);
+    conn.execute(
+        "DELETE FROM signature_cache WHERE updated_unix <= ?1",
+        params![cutoff],
+    )?;
+    conn.execute(
+        "DELETE FROM signature_cache WHERE rowid NOT IN \
+         (SELECT rowid FROM signature_cache ORDER BY updated_unix DESC LIMIT ?1)",
+        params![PERSISTED_ROW_LIMIT as i64],
+    )?;
+    Ok(())
+}
+
+/// Prune the table and return what is left.
+fn load_persisted_rows(conn: &Connection) -> rusqlite::Result<Vec<PersistedRow>> {
+    prune_persisted_rows(conn)?;
+
+    let mut stmt = conn.prepare(
+        "SELECT layer, cache_key, value, updated_unix FROM signature_cache \
+         ORDER BY updated_unix ASC",
+    )?;
+    let rows = stmt.query_map([], |r| {
+        Ok(PersistedRow {
+            layer: r.get(0)?,
+            key: r.get(1)?,
+            value: r.get(2)?,
+            timestamp: UNIX_EPOCH + Duration::from_secs(r.get::<_, i64>(3)?.max(0) as u64),
+        })
+    })?;
+    rows.collect()
+}
+
+fn unix_now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0)
 }
 
 #[cfg(test)]
@@ -496,4 +823,108 @@ mod tests {
         assert!(cache.get_signature_family(&sig).is_none());
         assert!(cache.get_session_signature("sid-1").is_none());
     }
+
+    #[test]
+    fn test_persistent_layer_survives_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join(SIGNATURE_CACHE_DB_FILE);
+        let sig = "p".repeat(60);
+        let sig_late = "q".repeat(70);
+
+        let cache = SignatureCache::new();
+        assert_eq!(cache.enable_persistence(&db_path).unwrap(), 0);
+        cache.cache_tool_signature("toolu_persist", sig.clone());
+        cache.cache_thinking_family(sig.clone(), "claude-opus-4-6-thinking".to_string());
+        cache.cache_session_signature("sid-persist", sig.clone(), 2);
+        cache.cache_session_signature("sid-persist", sig_late.clone(), 4);
+        cache.cache_session_reasoning("sid-persist", "step by step".to_string(), 1);
+        drop(cache);
+
+        // Simulated restart: a fresh cache warmed from the same file
+        let restored = SignatureCache::new();
+        assert_eq!(restored.enable_persistence(&db_path).unwrap(), 4);
+        assert_eq!(
+            restored.get_tool_signature("toolu_persist"),
+            Some(sig.clone())
+        );
+        assert_eq!(
+            restored.get_signature_family(&sig),
+            Some("claude-opus-4-6-thinking".to_string())
+        );
+        assert_eq!(
+            restored.get_session_signature("sid-persist"),
+            Some(sig_late)
+        );
+        assert_eq!(
+            restored.get_session_signature_at("sid-persist", 2),
+            Some(sig)
+        );
+        assert_eq!(
+            restored.get_session_reasoning("sid-persist", 1),
+            Some("step by step".to_string())
+        );
+    }
+
+    #[test]
+    fn test_persistent_layer_respects_delete_and_clear() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join(SIGNATURE_CACHE_DB_FILE);
+        let sig = "r".repeat(60);
+
+        let cache = SignatureCache::new();
+        cache.enable_persistence(&db_path).unwrap();
+        cache.cache_session_signature("sid-gone", sig.clone(), 1);
+        cache.cache_tool_signature("toolu_gone", sig.clone());
+        cache.delete_session_signature("sid-gone");
+        drop(cache);
+
+        let restored = SignatureCache::new();
+        assert_eq!(restored.enable_persistence(&db_path).unwrap(), 1);
+        assert!(restored.get_session_signature("sid-gone").is_none());
+        restored.clear();
+        drop(restored);
+
+        let empty = SignatureCache::new();
+        assert_eq!(empty.enable_persistence(&db_path).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_persistent_layer_prunes_while_running() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join(SIGNATURE_CACHE_DB_FILE);
+        let cache = SignatureCache::new();
+        cache.enable_persistence(&db_path).unwrap();
+
+        let row_count = |cache: &SignatureCache| -> i64 {
+            let guard = cache.db.lock().unwrap();
+            guard
+                .as_ref()
+                .unwrap()
+                .query_row("SELECT COUNT(*) FROM signature_cache", [], |r| r.get(0))
+                .unwrap()
+        };
+
+        // An expired row left over on disk
+        {
+            let guard = cache.db.lock().unwrap();
+            guard
+                .as_ref()
+                .unwrap()
+                .execute(
+                    "INSERT INTO signature_cache (layer, cache_key, value, updated_unix) \
+                     VALUES ('tool', 'toolu_stale', 'x', 0)",
+                    [],
+                )
+                .unwrap();
+        }
+
+        let sig = "s".repeat(60);
+        for i in 0..PRUNE_EVERY_WRITES - 1 {
+            cache.cache_tool_signature(&format!("toolu_{}", i), sig.clone());
+        }
+        assert_eq!(row_count(&cache), PRUNE_EVERY_WRITES as i64);
+
+        cache.cache_tool_signature("toolu_last", sig);
+        assert_eq!(row_count(&cache), PRUNE_EVERY_WRITES as i64);
+    }
 }
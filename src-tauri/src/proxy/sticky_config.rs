@@ -9,6 +9,9 @@ pub enum SchedulingMode {
     Balance,
     /// 性能优先 (Performance-first): 纯轮询模式 (Round-robin)，账号负载最均衡，但不利用缓存
     PerformanceFirst,
+    /// 配额加权 (Quota-weighted): 按目标模型剩余配额、健康分与距刷新时间加权随机选号，
+    /// 即将刷新的账号优先消耗，刷新周期长的账号被保留；会话粘性与平衡模式一致
+    QuotaWeighted,
 }
 
 impl Default for SchedulingMode {
@@ -0,0 +1,269 @@
+//! OpenTelemetry 链路追踪 (OTLP 导出)
+//!
+//! 日志系统初始化时通过 `layer()` 常驻挂载 `tracing-opentelemetry` 层，其 TracerProvider 的
+//! 采样器与 span 处理器均可热切换：`apply_config()` 根据 `TelemetryConfig` 构建或关闭
+//! OTLP/HTTP 批量导出器，未启用时采样器直接丢弃所有 span。
+//!
+//! 每个代理请求由 `middleware::telemetry_middleware` 创建根 span `proxy.request`
+//! (支持从请求头的 W3C `traceparent` 续接上游链路)，中间件、模型路由、账号选择、
+//! 上下文压缩与上游调用各自为其子 span。根 span 上的统一属性：
+//! `trace_id` / `session_id` / `account` / `mapped_model` / `retry.attempt` / `compression.layer`，
+//! handler 通过 `record()` / `record_attempt()` 写入，`account` 与 `mapped_model`
+//! 在响应阶段从 `X-Account-Email` / `X-Mapped-Model` 头补全。
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{OnceLock, RwLock};
+use std::time::Duration;
+
+use opentelemetry::trace::{
+    Link, SamplingDecision, SamplingResult, SpanKind, TraceContextExt, TraceId, TracerProvider as _,
+};
+use opentelemetry::{Context, KeyValue};
+use opentelemetry_otlp::WithExportConfig;
+use opentelemetry_sdk::error::OTelSdkResult;
+use opentelemetry_sdk::trace::{
+    BatchSpanProcessor, Sampler, SdkTracer, SdkTracerProvider, ShouldSample, SpanData,
+    SpanProcessor,
+};
+use tracing::{Instrument, Span};
+use tracing_opentelemetry::OpenTelemetryLayer;
+
+use crate::proxy::config::TelemetryConfig;
+
+const TRACER_NAME: &str = "antigravity-proxy";
+const EXPORT_TIMEOUT_SECS: u64 = 10;
+
+static PROVIDER: OnceLock<SdkTracerProvider> = OnceLock::new();
+/// 当前采样器 (None 表示未启用，丢弃所有 span)
+static SAMPLER: RwLock<Option<Sampler>> = RwLock::new(None);
+/// 当前批量导出处理器
+static PROCESSOR: RwLock<Option<BatchSpanProcessor>> = RwLock::new(None);
+
+/// 委托给 `SAMPLER` 的采样器
+#[derive(Debug, Clone)]
+struct SwitchableSampler;
+
+impl ShouldSample for SwitchableSampler {
+    fn should_sample(
+        &self,
+        parent_context: Option<&Context>,
+        trace_id: TraceId,
+        name: &str,
+        span_kind: &SpanKind,
+        attributes: &[KeyValue],
+        links: &[Link],
+    ) -> SamplingResult {
+        if let Ok(guard) = SAMPLER.read() {
+            if let Some(sampler) = guard.as_ref() {
+                return sampler.should_sample(
+                    parent_context,
+                    trace_id,
+                    name,
+                    span_kind,
+                    attributes,
+                    links,
+                );
+            }
+        }
+        SamplingResult {
+            decision: SamplingDecision::Drop,
+            attributes: Vec::new(),
+            trace_state: parent_context
+                .map(|cx| cx.span().span_context().trace_state().clone())
+                .unwrap_or_default(),
+        }
+    }
+}
+
+/// 委托给 `PROCESSOR` 的 span 处理器
+#[derive(Debug)]
+struct SwitchableProcessor;
+
+impl SpanProcessor for SwitchableProcessor {
+    fn on_start(&self, span: &mut opentelemetry_sdk::trace::Span, cx: &Context) {
+        if let Ok(guard) = PROCESSOR.read() {
+            if let Some(processor) = guard.as_ref() {
+                processor.on_start(span, cx);
+            }
+        }
+    }
+
+    fn on_end(&self, span: SpanData) {
+        if let Ok(guard) = PROCESSOR.read() {
+            if let Some(processor) = guard.as_ref() {
+                processor.on_end(span);
+            }
+        }
+    }
+
+    fn force_flush(&self) -> OTelSdkResult {
+        match PROCESSOR.read() {
+            Ok(guard) => guard.as_ref().map_or(Ok(()), |p| p.force_flush()),
+            Err(_) => Ok(()),
+        }
+    }
+
+    fn shutdown_with_timeout(&self, timeout: Duration) -> OTelSdkResult {
+        match PROCESSOR.read() {
+            Ok(guard) => guard
+                .as_ref()
+                .map_or(Ok(()), |p| p.shutdown_with_timeout(timeout)),
+            Err(_) => Ok(()),
+        }
+    }
+}
+
+/// 单个请求的追踪上下文 (根 span + 当前重试序号)
+struct RequestTrace {
+    span: Span,
+    attempt: AtomicUsize,
+}
+
+tokio::task_local! {
+    static REQUEST_TRACE: RequestTrace;
+}
+
+/// 创建挂载到全局 subscriber 的 OpenTelemetry 层 (导出由 `apply_config` 控制)
+pub fn layer<S>() -> OpenTelemetryLayer<S, SdkTracer>
+where
+    S: tracing::Subscriber + for<'a> tracing_subscriber::registry::LookupSpan<'a>,
+{
+    opentelemetry::global::set_text_map_propagator(
+        opentelemetry_sdk::propagation::TraceContextPropagator::new(),
+    );
+    let provider = PROVIDER.get_or_init(|| {
+        SdkTracerProvider::builder()
+            .with_sampler(SwitchableSampler)
+            .with_span_processor(SwitchableProcessor)
+            .build()
+    });
+    tracing_opentelemetry::layer().with_tracer(provider.tracer(TRACER_NAME))
+}
+
+fn build_processor(config: &TelemetryConfig) -> Result<BatchSpanProcessor, String> {
+    let exporter = opentelemetry_otlp::SpanExporter::builder()
+        .with_http()
+        .with_endpoint(config.otlp_endpoint.clone())
+        .with_timeout(Duration::from_secs(EXPORT_TIMEOUT_SECS))
+        .build()
+        .map_err(|e| format!("创建 OTLP exporter 失败: {}", e))?;
+
+    let mut processor = BatchSpanProcessor::builder(exporter).build();
+    processor.set_resource(
+        &opentelemetry_sdk::Resource::builder()
+            .with_service_name(config.service_name.clone())
+            .build(),
+    );
+    Ok(processor)
+}
+
+/// 应用 (或热更新) 链路追踪配置
+pub fn apply_config(config: &TelemetryConfig) {
+    let processor = if config.enabled {
+        match build_processor(config) {
+            Ok(processor) => Some(processor),
+            Err(e) => {
+                tracing::warn!("[Telemetry] {}", e);
+                None
+            }
+        }
+    } else {
+        None
+    };
+    let enabled = processor.is_some();
+
+    let sampler = enabled.then(|| {
+        Sampler::ParentBased(Box::new(Sampler::TraceIdRatioBased(
+            config.sample_ratio.clamp(0.0, 1.0),
+        )))
+    });
+    if let Ok(mut guard) = SAMPLER.write() {
+        *guard = sampler;
+    }
+    let previous = match PROCESSOR.write() {
+        Ok(mut guard) => std::mem::replace(&mut *guard, processor),
+        Err(_) => None,
+    };
+    if let Some(previous) = previous {
+        // shutdown 会阻塞等待批量导出完成，放到独立线程避免卡住异步运行时
+        std::thread::spawn(move || {
+            if let Err(e) = previous.shutdown() {
+                tracing::debug!("[Telemetry] 关闭旧导出器: {}", e);
+            }
+        });
+    }
+
+    if enabled {
+        tracing::info!(
+            "[Telemetry] OTLP 导出已启用: {} (service: {}, sample: {})",
+            config.otlp_endpoint,
+            config.service_name,
+            config.sample_ratio
+        );
+    } else {
+        tracing::debug!("[Telemetry] OTLP 导出已关闭");
+    }
+}
+
+/// 立即导出缓冲中的 span (阻塞调用，异步上下文中请放入 `spawn_blocking`)
+pub fn force_flush() {
+    if let Some(provider) = PROVIDER.get() {
+        if let Err(e) = provider.force_flush() {
+            tracing::debug!("[Telemetry] force_flush 失败: {}", e);
+        }
+    }
+}
+
+/// 在请求上下文中运行 `fut`: 根 span 作为其父 span，并允许 `record()` 写入根 span 属性
+pub async fn in_request_scope<F: std::future::Future>(span: Span, fut: F) -> F::Output {
+    let trace = RequestTrace {
+        span: span.clone(),
+        attempt: AtomicUsize::new(0),
+    };
+    REQUEST_TRACE.scope(trace, fut.instrument(span)).await
+}
+
+/// 在当前请求的根 span 上记录属性 (不在请求上下文中时忽略)
+pub fn record<V: tracing::Value>(field: &'static str, value: V) {
+    let _ = REQUEST_TRACE.try_with(|trace| {
+        trace.span.record(field, value);
+    });
+}
+
+/// 记录当前重试序号 (从 0 开始)，后续的账号选择与上游调用 span 会带上该序号
+pub fn record_attempt(attempt: usize) {
+    let _ = REQUEST_TRACE.try_with(|trace| {
+        trace.attempt.store(attempt, Ordering::Relaxed);
+        trace.span.record("retry.attempt", attempt);
+    });
+}
+
+/// 当前请求的重试序号
+pub fn current_attempt() -> Option<usize> {
+    REQUEST_TRACE
+        .try_with(|trace| trace.attempt.load(Ordering::Relaxed))
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_record_outside_request_is_noop() {
+        record("trace_id", "req_1");
+        record_attempt(2);
+        assert_eq!(current_attempt(), None);
+    }
+
+    #[tokio::test]
+    async fn test_attempt_is_scoped_to_request() {
+        in_request_scope(Span::none(), async {
+            assert_eq!(current_attempt(), Some(0));
+            record_attempt(3);
+            assert_eq!(current_attempt(), Some(3));
+        })
+        .await;
+        assert_eq!(current_attempt(), None);
+    }
+}
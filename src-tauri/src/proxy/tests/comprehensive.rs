@@ -36,6 +36,7 @@ mod tests {
             }),
             metadata: None,
             output_config: None,
+            tool_choice: None,
             size: None,
             quality: None,
         };
@@ -0,0 +1,150 @@
+//! Model Fallback Chain Tests
+//!
+//! 主目标模型在所有账号上被锁定时，handler 应沿降级链切换到下一个仍有可用账号的模型，
+//! 并通过 `X-Mapped-Model` 报告实际服务的模型。
+
+use std::collections::HashMap;
+
+use axum::{
+    extract::State,
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use serde_json::json;
+
+use super::mock_upstream::MockUpstream;
+use super::replay_tests::{build_state, sse_fixture, update_proxy_config};
+use crate::proxy::common::model_mapping::{resolve_model_route, resolve_model_route_with_fallback};
+
+const PRIMARY: &str = "gemini-3-pro-high";
+const FALLBACK: &str = "gemini-3-flash";
+
+/// 仅 QUOTA_EXHAUSTED 会产生模型级锁 (其他原因锁定整个账号)
+const QUOTA_EXHAUSTED_BODY: &str = r#"{"error":{"details":[{"reason":"QUOTA_EXHAUSTED"}]}}"#;
+
+fn chains() -> HashMap<String, Vec<String>> {
+    let mut chains = HashMap::new();
+    chains.insert(PRIMARY.to_string(), vec![FALLBACK.to_string()]);
+    chains
+}
+
+#[tokio::test]
+async fn test_fallback_chain_skips_locked_model() {
+    let mock = MockUpstream::new().start().await.unwrap();
+    let state = build_state(&mock).await;
+    let custom = HashMap::new();
+
+    let primary_target = resolve_model_route(PRIMARY, &custom);
+
+    // 未锁定时保持主目标
+    let resolved =
+        resolve_model_route_with_fallback(PRIMARY, &custom, &chains(), &state.token_manager).await;
+    assert_eq!(resolved, primary_target);
+
+    // 锁定主目标的模型级限流后切换到备选
+    state
+        .token_manager
+        .mark_rate_limited_async(
+            "replay@test.com",
+            429,
+            Some("60"),
+            QUOTA_EXHAUSTED_BODY,
+            Some(PRIMARY),
+        )
+        .await;
+    let resolved =
+        resolve_model_route_with_fallback(PRIMARY, &custom, &chains(), &state.token_manager).await;
+    assert_eq!(resolved, FALLBACK);
+
+    // 无降级链的模型不受影响
+    let resolved =
+        resolve_model_route_with_fallback(PRIMARY, &custom, &HashMap::new(), &state.token_manager)
+            .await;
+    assert_eq!(resolved, primary_target);
+}
+
+#[tokio::test]
+async fn test_fallback_chain_reports_served_model() {
+    let mock = MockUpstream::new()
+        .with_fixture(sse_fixture())
+        .start()
+        .await
+        .unwrap();
+    let state = build_state(&mock).await;
+    update_proxy_config(&state, |proxy| proxy.model_fallback_chains = chains());
+    state
+        .token_manager
+        .mark_rate_limited_async(
+            "replay@test.com",
+            429,
+            Some("60"),
+            QUOTA_EXHAUSTED_BODY,
+            Some(PRIMARY),
+        )
+        .await;
+
+    let body = json!({
+        "model": PRIMARY,
+        "messages": [{ "role": "user", "content": "hi" }]
+    });
+    let response = crate::proxy::handlers::openai::handle_chat_completions(
+        State(state),
+        HeaderMap::new(),
+        Json(body),
+    )
+    .await
+    .into_response();
+
+    let status = response.status();
+    let served = response
+        .headers()
+        .get("X-Mapped-Model")
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.to_string());
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    assert_eq!(
+        status,
+        StatusCode::OK,
+        "body: {}",
+        String::from_utf8_lossy(&bytes)
+    );
+    assert_eq!(served.as_deref(), Some(FALLBACK));
+    let received = mock.received();
+    assert_eq!(received.len(), 1);
+    assert_eq!(received[0].body["model"], FALLBACK);
+}
+
+#[tokio::test]
+async fn test_fallback_chain_advances_for_claude_model() {
+    const CLAUDE_PRIMARY: &str = "claude-opus-4-6-thinking";
+    let mock = MockUpstream::new().start().await.unwrap();
+    let state = build_state(&mock).await;
+    let custom = HashMap::new();
+    let mut chains = HashMap::new();
+    chains.insert(CLAUDE_PRIMARY.to_string(), vec![FALLBACK.to_string()]);
+
+    let primary_target = resolve_model_route(CLAUDE_PRIMARY, &custom);
+    let resolved =
+        resolve_model_route_with_fallback(CLAUDE_PRIMARY, &custom, &chains, &state.token_manager)
+            .await;
+    assert_eq!(resolved, primary_target);
+
+    // 与 Claude handler 一致：以映射后的模型名记录限流
+    state
+        .token_manager
+        .mark_rate_limited_async(
+            "replay@test.com",
+            429,
+            Some("60"),
+            QUOTA_EXHAUSTED_BODY,
+            Some(&primary_target),
+        )
+        .await;
+    let resolved =
+        resolve_model_route_with_fallback(CLAUDE_PRIMARY, &custom, &chains, &state.token_manager)
+            .await;
+    assert_eq!(resolved, FALLBACK);
+}
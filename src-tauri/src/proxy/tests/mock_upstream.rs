@@ -0,0 +1,245 @@
+//! Mock Upstream (v1internal 回放服务器)
+//!
+//! 在本地随机端口启动一个 Axum 服务，按 `UpstreamFixture` 回放 v1internal 响应。
+//! 将 `DebugLoggingConfig::upstream_base_url` 指向 `MockUpstreamHandle::base_url()`
+//! 即可让完整的 handler 链路 (含 SSE 流式转换) 离线运行。
+//!
+//! ## 夹具匹配规则
+//! - 按 v1internal 方法名 (`generateContent` / `streamGenerateContent` ...) 匹配
+//! - 夹具带 `model` 时还需与请求体中的 `model` 一致，否则匹配任意模型
+//! - 同一请求命中多个夹具时按注册顺序依次消费，最后一个会被重复使用
+//! - 未命中时返回 404 (Google 风格错误体)
+
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use axum::{
+    body::{Body, Bytes},
+    extract::State,
+    http::{header, StatusCode, Uri},
+    response::{IntoResponse, Response},
+    Router,
+};
+use serde_json::Value;
+
+use crate::proxy::debug_logger::UpstreamFixture;
+
+/// Mock 上游收到的一次请求
+#[derive(Debug, Clone)]
+pub struct MockRequest {
+    pub method: String,
+    pub query: Option<String>,
+    pub body: Value,
+}
+
+#[derive(Clone, Default)]
+struct MockState {
+    fixtures: Arc<Mutex<Vec<UpstreamFixture>>>,
+    received: Arc<Mutex<Vec<MockRequest>>>,
+}
+
+#[derive(Default)]
+pub struct MockUpstream {
+    fixtures: Vec<UpstreamFixture>,
+}
+
+impl MockUpstream {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_fixture(mut self, fixture: UpstreamFixture) -> Self {
+        self.fixtures.push(fixture);
+        self
+    }
+
+    /// 加载录制模式输出的夹具目录 (按文件名排序，即录制时间顺序)
+    pub fn load_dir(mut self, dir: &Path) -> Result<Self, String> {
+        let mut paths: Vec<_> = std::fs::read_dir(dir)
+            .map_err(|e| format!("读取夹具目录失败: {}", e))?
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|p| p.extension().and_then(|s| s.to_str()) == Some("json"))
+            .collect();
+        paths.sort();
+
+        for path in paths {
+            let content = std::fs::read_to_string(&path)
+                .map_err(|e| format!("读取夹具失败 {:?}: {}", path, e))?;
+            let fixture: UpstreamFixture = serde_json::from_str(&content)
+                .map_err(|e| format!("解析夹具失败 {:?}: {}", path, e))?;
+            self.fixtures.push(fixture);
+        }
+        Ok(self)
+    }
+
+    /// 在 127.0.0.1 随机端口启动回放服务器
+    pub async fn start(self) -> Result<MockUpstreamHandle, String> {
+        let state = MockState {
+            fixtures: Arc::new(Mutex::new(self.fixtures)),
+            received: Arc::new(Mutex::new(Vec::new())),
+        };
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+            .await
+            .map_err(|e| format!("绑定 Mock 端口失败: {}", e))?;
+        let addr = listener
+            .local_addr()
+            .map_err(|e| format!("获取 Mock 地址失败: {}", e))?;
+
+        // v1internal 的路径形如 `/v1internal:method`，统一走 fallback 自行解析
+        let app = Router::new()
+            .fallback(handle_mock_request)
+            .with_state(state.clone());
+        let task = tokio::spawn(async move {
+            let _ = axum::serve(listener, app).await;
+        });
+
+        Ok(MockUpstreamHandle {
+            base_url: format!("http://{}/v1internal", addr),
+            received: state.received,
+            task,
+        })
+    }
+}
+
+pub struct MockUpstreamHandle {
+    base_url: String,
+    received: Arc<Mutex<Vec<MockRequest>>>,
+    task: tokio::task::JoinHandle<()>,
+}
+
+impl MockUpstreamHandle {
+    /// 可直接写入 `DebugLoggingConfig::upstream_base_url` 的地址
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    /// 已收到的请求 (按到达顺序)
+    pub fn received(&self) -> Vec<MockRequest> {
+        self.received.lock().unwrap().clone()
+    }
+}
+
+impl Drop for MockUpstreamHandle {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// 从队列中挑选匹配的夹具 (多个命中时消费第一个，仅剩一个时重复使用)
+pub fn take_fixture(
+    fixtures: &mut Vec<UpstreamFixture>,
+    method: &str,
+    model: Option<&str>,
+) -> Option<UpstreamFixture> {
+    let matches: Vec<usize> = fixtures
+        .iter()
+        .enumerate()
+        .filter(|(_, f)| f.method == method)
+        .filter(|(_, f)| match (f.model.as_deref(), model) {
+            (Some(expected), Some(actual)) => expected == actual,
+            (Some(_), None) => false,
+            (None, _) => true,
+        })
+        .map(|(idx, _)| idx)
+        .collect();
+
+    match matches.as_slice() {
+        [] => None,
+        [only] => Some(fixtures[*only].clone()),
+        [first, ..] => Some(fixtures.remove(*first)),
+    }
+}
+
+/// 将 SSE 文本按事件切分，模拟上游逐块推送
+fn split_sse_events(body: &str) -> Vec<Bytes> {
+    body.split_inclusive("\n\n")
+        .map(|chunk| Bytes::from(chunk.to_string()))
+        .collect()
+}
+
+async fn handle_mock_request(State(state): State<MockState>, uri: Uri, body: Bytes) -> Response {
+    let method = uri
+        .path()
+        .rsplit(':')
+        .next()
+        .unwrap_or_default()
+        .to_string();
+    let body: Value = serde_json::from_slice(&body).unwrap_or(Value::Null);
+    let model = body
+        .get("model")
+        .and_then(|m| m.as_str())
+        .map(|m| m.to_string());
+
+    state.received.lock().unwrap().push(MockRequest {
+        method: method.clone(),
+        query: uri.query().map(|q| q.to_string()),
+        body,
+    });
+
+    let fixture = take_fixture(
+        &mut state.fixtures.lock().unwrap(),
+        &method,
+        model.as_deref(),
+    );
+    let Some(fixture) = fixture else {
+        let error = serde_json::json!({
+            "error": {
+                "code": 404,
+                "message": format!("No mock fixture for method={} model={:?}", method, model),
+                "status": "NOT_FOUND"
+            }
+        });
+        return (StatusCode::NOT_FOUND, axum::Json(error)).into_response();
+    };
+
+    let status = StatusCode::from_u16(fixture.status).unwrap_or(StatusCode::OK);
+    let body = if fixture.content_type.starts_with("text/event-stream") {
+        let events = split_sse_events(&fixture.body);
+        Body::from_stream(futures::stream::iter(
+            events.into_iter().map(Ok::<_, std::io::Error>),
+        ))
+    } else {
+        Body::from(fixture.body)
+    };
+
+    (status, [(header::CONTENT_TYPE, fixture.content_type)], body).into_response()
+}
+
+fn fixture(method: &str, model: Option<&str>, body: &str) -> UpstreamFixture {
+    UpstreamFixture {
+        method: method.to_string(),
+        query: None,
+        model: model.map(|m| m.to_string()),
+        request: Value::Null,
+        status: 200,
+        content_type: "application/json".to_string(),
+        body: body.to_string(),
+    }
+}
+
+#[test]
+fn test_take_fixture_matches_method_and_model() {
+    let mut fixtures = vec![
+        fixture("generateContent", Some("gemini-2.5-pro"), "pro"),
+        fixture("generateContent", None, "any"),
+        fixture("streamGenerateContent", None, "stream"),
+    ];
+
+    let hit = take_fixture(&mut fixtures, "generateContent", Some("gemini-2.5-pro")).unwrap();
+    assert_eq!(hit.body, "pro");
+    // 只剩一个匹配项时重复使用
+    let hit = take_fixture(&mut fixtures, "generateContent", Some("gemini-2.5-pro")).unwrap();
+    assert_eq!(hit.body, "any");
+    let hit = take_fixture(&mut fixtures, "generateContent", Some("gemini-2.5-pro")).unwrap();
+    assert_eq!(hit.body, "any");
+
+    assert!(take_fixture(&mut fixtures, "countTokens", None).is_none());
+}
+
+#[test]
+fn test_split_sse_events_keeps_boundaries() {
+    let chunks = split_sse_events("data: {\"a\":1}\n\ndata: {\"b\":2}\n\n");
+    assert_eq!(chunks.len(), 2);
+    assert_eq!(&chunks[1][..], b"data: {\"b\":2}\n\n");
+}
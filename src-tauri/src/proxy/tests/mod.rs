@@ -1,7 +1,13 @@
 pub mod comprehensive;
+pub mod fallback_chain_tests;
+pub mod mock_upstream;
+pub mod provider_routing_tests;
 pub mod quota_protection;
+pub mod quota_weighted_tests;
 pub mod rate_limit_404_tests;
+pub mod replay_tests;
 pub mod retry_strategy_tests;
 pub mod security_integration_tests;
 pub mod security_ip_tests;
+pub mod telemetry_tests;
 pub mod ultra_priority_tests;
@@ -0,0 +1,274 @@
+//! Custom Provider Routing Tests
+//!
+//! 启动本地 OpenAI / Gemini 兼容的回显服务作为 Provider，验证 handler 的路由、
+//! 模型映射、鉴权头与路径拼接。
+
+use std::collections::HashMap;
+
+use axum::{
+    extract::{Path, State},
+    http::{HeaderMap, StatusCode, Uri},
+    response::IntoResponse,
+    routing::post,
+    Json, Router,
+};
+use serde_json::{json, Value};
+
+use super::mock_upstream::MockUpstream;
+use super::replay_tests::{build_state, update_proxy_config};
+use crate::proxy::config::{ProviderDispatchMode, ProviderProtocol, UpstreamProviderConfig};
+
+/// 回显收到的路径、鉴权头与请求体
+async fn echo(uri: Uri, headers: HeaderMap, Json(body): Json<Value>) -> Json<Value> {
+    let header = |name: &str| {
+        headers
+            .get(name)
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or_default()
+            .to_string()
+    };
+    Json(json!({
+        "path": uri.path(),
+        "query": uri.query(),
+        "authorization": header("authorization"),
+        "x_goog_api_key": header("x-goog-api-key"),
+        "model": body.get("model"),
+    }))
+}
+
+async fn start_echo_server() -> String {
+    let app = Router::new()
+        .route("/v1/chat/completions", post(echo))
+        .fallback(echo);
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        let _ = axum::serve(listener, app).await;
+    });
+    format!("http://{}", addr)
+}
+
+fn provider(
+    base_url: String,
+    protocol: ProviderProtocol,
+    dispatch_mode: ProviderDispatchMode,
+) -> UpstreamProviderConfig {
+    UpstreamProviderConfig {
+        name: "local".to_string(),
+        enabled: true,
+        base_url,
+        api_key: "sk-local".to_string(),
+        protocol,
+        dispatch_mode,
+        model_mapping: HashMap::from([("gpt-4o".to_string(), "qwen2.5-72b".to_string())]),
+        models: vec!["gpt-4o".to_string(), "gemini-*".to_string()],
+    }
+}
+
+async fn read_json(response: axum::response::Response) -> (StatusCode, HeaderMap, Value) {
+    let status = response.status();
+    let headers = response.headers().clone();
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    (
+        status,
+        headers,
+        serde_json::from_slice(&bytes).unwrap_or(Value::Null),
+    )
+}
+
+#[tokio::test]
+async fn test_openai_exclusive_provider_forwards_with_mapping() {
+    let mock = MockUpstream::new().start().await.unwrap();
+    let state = build_state(&mock).await;
+    let base_url = format!("{}/v1", start_echo_server().await);
+    update_proxy_config(&state, |proxy| {
+        proxy.providers = vec![provider(
+            base_url,
+            ProviderProtocol::Openai,
+            ProviderDispatchMode::Exclusive,
+        )];
+    });
+
+    let response = crate::proxy::handlers::openai::handle_chat_completions(
+        State(state),
+        HeaderMap::new(),
+        Json(json!({
+            "model": "gpt-4o",
+            "messages": [{ "role": "user", "content": "hi" }]
+        })),
+    )
+    .await
+    .into_response();
+
+    let (status, headers, body) = read_json(response).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["path"], "/v1/chat/completions");
+    assert_eq!(body["model"], "qwen2.5-72b");
+    assert_eq!(body["authorization"], "Bearer sk-local");
+    assert_eq!(headers["x-account-email"], "provider:local");
+    assert_eq!(headers["x-mapped-model"], "qwen2.5-72b");
+    // 请求未到达 Google 上游
+    assert!(mock.received().is_empty());
+}
+
+#[tokio::test]
+async fn test_gemini_provider_explicit_prefix_builds_stream_path() {
+    let mock = MockUpstream::new().start().await.unwrap();
+    let state = build_state(&mock).await;
+    let base_url = start_echo_server().await;
+    // dispatch_mode = Off: 只有显式前缀才会路由到该 Provider
+    update_proxy_config(&state, |proxy| {
+        proxy.providers = vec![provider(
+            base_url,
+            ProviderProtocol::Gemini,
+            ProviderDispatchMode::Off,
+        )];
+    });
+
+    let response = crate::proxy::handlers::gemini::handle_generate(
+        State(state),
+        Path("local:gemma-3-27b:streamGenerateContent".to_string()),
+        HeaderMap::new(),
+        Json(json!({ "contents": [{ "role": "user", "parts": [{ "text": "hi" }] }] })),
+    )
+    .await
+    .into_response();
+
+    let (status, _, body) = read_json(response).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        body["path"],
+        "/v1beta/models/gemma-3-27b:streamGenerateContent"
+    );
+    assert_eq!(body["query"], "alt=sse");
+    assert_eq!(body["x_goog_api_key"], "sk-local");
+}
+
+/// 以 Chat Completion 格式回复，正文回显上游收到的模型与 stream 标志
+async fn chat_reply(Json(body): Json<Value>) -> Json<Value> {
+    let text = format!("model={} stream={}", body["model"], body["stream"]);
+    Json(json!({
+        "id": "chatcmpl-provider",
+        "object": "chat.completion",
+        "created": 1,
+        "model": body["model"],
+        "choices": [{
+            "index": 0,
+            "message": { "role": "assistant", "content": text },
+            "finish_reason": "stop"
+        }],
+        "usage": { "prompt_tokens": 3, "completion_tokens": 4, "total_tokens": 7 }
+    }))
+}
+
+#[tokio::test]
+async fn test_openai_provider_serves_responses_and_legacy_completions() {
+    use crate::proxy::handlers::openai::handle_completions;
+
+    let mock = MockUpstream::new().start().await.unwrap();
+    let state = build_state(&mock).await;
+    let app = Router::new().route("/v1/chat/completions", post(chat_reply));
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let base_url = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move {
+        let _ = axum::serve(listener, app).await;
+    });
+    update_proxy_config(&state, |proxy| {
+        proxy.providers = vec![provider(
+            base_url,
+            ProviderProtocol::Openai,
+            ProviderDispatchMode::Exclusive,
+        )];
+    });
+    let uri = |path: &str| axum::extract::OriginalUri(path.parse().unwrap());
+
+    // Responses API (非流式)
+    let response = handle_completions(
+        uri("/v1/responses"),
+        State(state.clone()),
+        HeaderMap::new(),
+        Json(json!({ "model": "gpt-4o", "input": "hi" })),
+    )
+    .await;
+    let (status, headers, body) = read_json(response).await;
+    assert_eq!(status, StatusCode::OK, "body: {}", body);
+    assert_eq!(headers["x-account-email"], "provider:local");
+    assert_eq!(body["object"], "response");
+    assert_eq!(
+        body["output"][0]["content"][0]["text"],
+        "model=\"qwen2.5-72b\" stream=false"
+    );
+    assert_eq!(body["usage"]["total_tokens"], 7);
+
+    // Legacy Completions (流式)：Provider 以非流式调用，回复转换为 text_completion SSE
+    let response = handle_completions(
+        uri("/v1/completions"),
+        State(state),
+        HeaderMap::new(),
+        Json(json!({ "model": "gpt-4o", "prompt": "hi", "stream": true })),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    let text = String::from_utf8_lossy(&bytes);
+    assert!(text.contains("text_completion"), "body: {}", text);
+    assert!(text.contains("stream=false"), "body: {}", text);
+    assert!(mock.received().is_empty());
+}
+
+async fn reasoning_reply(Json(body): Json<Value>) -> Json<Value> {
+    Json(json!({
+        "id": "chatcmpl-provider",
+        "object": "chat.completion",
+        "created": 1,
+        "model": body["model"],
+        "choices": [{
+            "index": 0,
+            "message": { "role": "assistant", "content": "héllo", "reasoning_content": "思考中" },
+            "logprobs": { "content": [
+                { "token": "hé", "logprob": -0.1, "top_logprobs": [] },
+                { "token": "llo", "logprob": -0.2, "top_logprobs": [] }
+            ] },
+            "finish_reason": "stop"
+        }]
+    }))
+}
+
+#[tokio::test]
+async fn test_legacy_completions_logprobs_offset_counts_reasoning_chars() {
+    use crate::proxy::handlers::openai::handle_completions;
+
+    let mock = MockUpstream::new().start().await.unwrap();
+    let state = build_state(&mock).await;
+    let app = Router::new().route("/v1/chat/completions", post(reasoning_reply));
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let base_url = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move {
+        let _ = axum::serve(listener, app).await;
+    });
+    update_proxy_config(&state, |proxy| {
+        proxy.providers = vec![provider(
+            base_url,
+            ProviderProtocol::Openai,
+            ProviderDispatchMode::Exclusive,
+        )];
+    });
+
+    let response = handle_completions(
+        axum::extract::OriginalUri("/v1/completions".parse().unwrap()),
+        State(state),
+        HeaderMap::new(),
+        Json(json!({ "model": "gpt-4o", "prompt": "hi", "logprobs": 1 })),
+    )
+    .await;
+    let (status, _, body) = read_json(response).await;
+    assert_eq!(status, StatusCode::OK, "body: {}", body);
+    let choice = &body["choices"][0];
+    assert_eq!(choice["text"], "思考中\n\nhéllo");
+    // 偏移量按字符计："思考中\n\n" 为 5 个字符 (字节长度为 11)
+    assert_eq!(choice["logprobs"]["text_offset"], json!([5, 7]));
+}
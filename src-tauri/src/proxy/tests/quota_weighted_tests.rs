@@ -0,0 +1,158 @@
+//! Quota-Weighted Scheduling Tests
+//!
+//! 验证 `SchedulingMode::QuotaWeighted` 的权重计算与加权随机选号逻辑。
+//!
+//! ## 测试覆盖
+//! - `test_weight_scales_with_quota_and_health`: 权重随剩余配额与健康分线性变化
+//! - `test_reset_soon_accounts_weigh_more`: 即将刷新的账号权重更高
+//! - `test_roll_maps_to_weighted_buckets`: roll 值按权重区间映射到账号
+//! - `test_skips_attempted_protected_and_empty`: 跳过已尝试 / 受保护 / 无配额账号
+//! - `test_all_zero_weights_fall_back_to_first`: 权重全为 0 时退化为首个可用账号
+
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+
+use crate::proxy::token_manager::{ProxyToken, TokenManager};
+
+const MODEL: &str = "gemini-3-flash";
+const NOW: i64 = 1_700_000_000;
+
+/// 创建测试用的 ProxyToken
+fn create_test_token(
+    email: &str,
+    health_score: f32,
+    reset_time: Option<i64>,
+    model_quota: Option<i32>,
+) -> ProxyToken {
+    let mut model_quotas = HashMap::new();
+    if let Some(q) = model_quota {
+        model_quotas.insert(MODEL.to_string(), q);
+    }
+
+    ProxyToken {
+        account_id: email.to_string(),
+        access_token: "test_token".to_string(),
+        refresh_token: "test_refresh".to_string(),
+        expires_in: 3600,
+        timestamp: NOW + 3600,
+        email: email.to_string(),
+        account_path: PathBuf::from("/tmp/test"),
+        project_id: None,
+        subscription_tier: None,
+        remaining_quota: model_quota,
+        protected_models: HashSet::new(),
+        health_score,
+        reset_time,
+        validation_blocked: false,
+        validation_blocked_until: 0,
+        validation_url: None,
+        model_quotas,
+        model_limits: HashMap::new(),
+    }
+}
+
+#[test]
+fn test_weight_scales_with_quota_and_health() {
+    let full = create_test_token("full@test.com", 1.0, None, Some(100));
+    let half = create_test_token("half@test.com", 1.0, None, Some(50));
+    let sick = create_test_token("sick@test.com", 0.5, None, Some(100));
+
+    let w_full = TokenManager::quota_weight(&full, MODEL, NOW);
+    let w_half = TokenManager::quota_weight(&half, MODEL, NOW);
+    let w_sick = TokenManager::quota_weight(&sick, MODEL, NOW);
+
+    assert!((w_full - 1.0).abs() < 1e-9);
+    assert!((w_half - 0.5).abs() < 1e-9);
+    assert!((w_sick - 0.5).abs() < 1e-9);
+
+    // 未知模型配额视为 0
+    assert_eq!(TokenManager::quota_weight(&full, "unknown-model", NOW), 0.0);
+}
+
+#[test]
+fn test_reset_soon_accounts_weigh_more() {
+    let soon = create_test_token("soon@test.com", 1.0, Some(NOW + 300), Some(50));
+    let later = create_test_token("later@test.com", 1.0, Some(NOW + 5 * 24 * 3600), Some(50));
+    let past = create_test_token("past@test.com", 1.0, Some(NOW - 60), Some(50));
+
+    let w_soon = TokenManager::quota_weight(&soon, MODEL, NOW);
+    let w_later = TokenManager::quota_weight(&later, MODEL, NOW);
+    let w_past = TokenManager::quota_weight(&past, MODEL, NOW);
+
+    assert!(w_soon > w_later, "soon={} later={}", w_soon, w_later);
+    // 已过刷新时间的账号获得最大加成 (×4)
+    assert!((w_past - 2.0).abs() < 1e-9);
+    // 刷新周期很长的账号接近无加成
+    assert!(w_later < 0.55);
+}
+
+#[test]
+fn test_roll_maps_to_weighted_buckets() {
+    // 权重: a = 0.25, b = 0.75
+    let candidates = vec![
+        create_test_token("a@test.com", 1.0, None, Some(25)),
+        create_test_token("b@test.com", 1.0, None, Some(75)),
+    ];
+    let attempted = HashSet::new();
+
+    let pick = |roll: f64| {
+        TokenManager::select_quota_weighted(&candidates, &attempted, MODEL, false, NOW, roll)
+            .map(|t| t.email.clone())
+    };
+
+    assert_eq!(pick(0.0).as_deref(), Some("a@test.com"));
+    assert_eq!(pick(0.2).as_deref(), Some("a@test.com"));
+    assert_eq!(pick(0.3).as_deref(), Some("b@test.com"));
+    assert_eq!(pick(0.999).as_deref(), Some("b@test.com"));
+    // 越界 roll 被钳制
+    assert_eq!(pick(1.5).as_deref(), Some("b@test.com"));
+}
+
+#[test]
+fn test_skips_attempted_protected_and_empty() {
+    let mut protected = create_test_token("protected@test.com", 1.0, None, Some(100));
+    protected.protected_models.insert(MODEL.to_string());
+
+    let candidates = vec![
+        create_test_token("attempted@test.com", 1.0, None, Some(100)),
+        protected,
+        create_test_token("empty@test.com", 1.0, None, Some(0)),
+        create_test_token("ok@test.com", 1.0, None, Some(10)),
+    ];
+    let mut attempted = HashSet::new();
+    attempted.insert("attempted@test.com".to_string());
+
+    for roll in [0.0, 0.5, 0.99] {
+        let selected =
+            TokenManager::select_quota_weighted(&candidates, &attempted, MODEL, true, NOW, roll);
+        assert_eq!(selected.map(|t| t.email.as_str()), Some("ok@test.com"));
+    }
+
+    // 关闭配额保护后，受保护账号重新参与选择
+    let selected =
+        TokenManager::select_quota_weighted(&candidates, &attempted, MODEL, false, NOW, 0.0);
+    assert_eq!(
+        selected.map(|t| t.email.as_str()),
+        Some("protected@test.com")
+    );
+}
+
+#[test]
+fn test_all_zero_weights_fall_back_to_first() {
+    let candidates = vec![
+        create_test_token("first@test.com", 1.0, None, None),
+        create_test_token("second@test.com", 1.0, None, Some(0)),
+    ];
+    let attempted = HashSet::new();
+
+    let selected =
+        TokenManager::select_quota_weighted(&candidates, &attempted, MODEL, false, NOW, 0.7);
+    assert_eq!(selected.map(|t| t.email.as_str()), Some("first@test.com"));
+
+    let mut all = HashSet::new();
+    all.insert("first@test.com".to_string());
+    all.insert("second@test.com".to_string());
+    assert!(
+        TokenManager::select_quota_weighted(&candidates, &all, MODEL, false, NOW, 0.7).is_none()
+    );
+}
@@ -0,0 +1,699 @@
+//! Record / Replay Integration Tests
+//!
+//! 将 `UpstreamClient` 指向本地 `MockUpstream`，离线跑通完整的 handler 链路：
+//! OpenAI (`handle_chat_completions`)、Claude (`handle_messages`)、
+//! Gemini (`handle_generate`)，包括 SSE 流式转换，以及录制模式的夹具往返。
+
+use std::path::PathBuf;
+use std::sync::atomic::AtomicUsize;
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, State},
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use serde_json::{json, Value};
+use tokio::sync::RwLock;
+
+use super::mock_upstream::{MockUpstream, MockUpstreamHandle};
+use crate::proxy::config::{DebugLoggingConfig, ProxyConfig};
+use crate::proxy::debug_logger::UpstreamFixture;
+use crate::proxy::server::AppState;
+use crate::proxy::upstream::client::UpstreamClient;
+use crate::proxy::TokenManager;
+
+const MODEL: &str = "gemini-3-flash";
+
+pub fn sse_fixture() -> UpstreamFixture {
+    let chunk = |text: &str, finish: Option<&str>| {
+        let mut candidate = json!({
+            "content": { "role": "model", "parts": [{ "text": text }] }
+        });
+        if let Some(reason) = finish {
+            candidate["finishReason"] = json!(reason);
+        }
+        json!({
+            "response": {
+                "candidates": [candidate],
+                "usageMetadata": {
+                    "promptTokenCount": 5,
+                    "candidatesTokenCount": 3,
+                    "totalTokenCount": 8
+                },
+                "modelVersion": MODEL,
+                "responseId": "mock-response"
+            }
+        })
+    };
+
+    UpstreamFixture {
+        method: "streamGenerateContent".to_string(),
+        query: Some("alt=sse".to_string()),
+        model: None,
+        request: Value::Null,
+        status: 200,
+        content_type: "text/event-stream".to_string(),
+        body: format!(
+            "data: {}\r\n\r\ndata: {}\r\n\r\n",
+            chunk("Hello from ", None),
+            chunk("mock upstream", Some("STOP"))
+        )
+        .replace("\r\n", "\n"),
+    }
+}
+
+fn json_fixture() -> UpstreamFixture {
+    UpstreamFixture {
+        method: "generateContent".to_string(),
+        query: None,
+        model: None,
+        request: Value::Null,
+        status: 200,
+        content_type: "application/json".to_string(),
+        body: json!({
+            "response": {
+                "candidates": [{
+                    "content": {
+                        "role": "model",
+                        "parts": [{ "text": "Hello from mock upstream" }]
+                    },
+                    "finishReason": "STOP"
+                }],
+                "usageMetadata": {
+                    "promptTokenCount": 5,
+                    "candidatesTokenCount": 3,
+                    "totalTokenCount": 8
+                },
+                "modelVersion": MODEL,
+                "responseId": "mock-response"
+            }
+        })
+        .to_string(),
+    }
+}
+
+/// 创建带一个测试账号的数据目录
+fn create_data_dir() -> PathBuf {
+    let root =
+        std::env::temp_dir().join(format!("antigravity-replay-test-{}", uuid::Uuid::new_v4()));
+    let accounts_dir = root.join("accounts");
+    std::fs::create_dir_all(&accounts_dir).unwrap();
+
+    let now = chrono::Utc::now().timestamp();
+    let account = json!({
+        "id": "replay-acc",
+        "email": "replay@test.com",
+        "token": {
+            "access_token": "mock-access-token",
+            "refresh_token": "mock-refresh-token",
+            "expires_in": 3600,
+            "expiry_timestamp": now + 3600,
+            "project_id": "mock-project"
+        },
+        "quota": {
+            "models": [{ "name": MODEL, "percentage": 100 }]
+        },
+        "disabled": false,
+        "proxy_disabled": false,
+        "created_at": now,
+        "last_used": now
+    });
+    std::fs::write(
+        accounts_dir.join("replay-acc.json"),
+        serde_json::to_string_pretty(&account).unwrap(),
+    )
+    .unwrap();
+    root
+}
+
+/// 构建指向 Mock 上游的完整 AppState (其他集成测试复用)
+pub async fn build_state(mock: &MockUpstreamHandle) -> AppState {
+    let data_dir = create_data_dir();
+    let batches = Arc::new(crate::proxy::batch_runner::BatchRunner::open(&data_dir).unwrap());
+    let token_manager = Arc::new(TokenManager::new(data_dir));
+    token_manager.load_accounts().await.unwrap();
+
+    let config = ProxyConfig {
+        request_timeout: 30,
+        debug_logging: DebugLoggingConfig {
+            upstream_base_url: Some(mock.base_url().to_string()),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let upstream = Arc::new(UpstreamClient::new(None, None));
+    upstream
+        .set_debug_logging(config.debug_logging.clone())
+        .await;
+
+    let integration = crate::modules::integration::SystemManager::Headless;
+    let snapshot = crate::proxy::hot_reload::new_snapshot(crate::models::AppConfig {
+        proxy: config,
+        ..crate::models::AppConfig::new()
+    });
+
+    AppState {
+        token_manager,
+        thought_signature_map: Arc::new(tokio::sync::Mutex::new(Default::default())),
+        upstream,
+        provider_rr: Arc::new(AtomicUsize::new(0)),
+        zai_vision_mcp: Arc::new(crate::proxy::zai_vision_mcp::ZaiVisionMcpState::new()),
+        monitor: Arc::new(crate::proxy::monitor::ProxyMonitor::new(10, None)),
+        switching: Arc::new(RwLock::new(false)),
+        integration: integration.clone(),
+        account_service: Arc::new(crate::modules::account_service::AccountService::new(
+            integration,
+        )),
+        cloudflared_state: Arc::new(crate::commands::cloudflared::CloudflaredState::new()),
+        is_running: Arc::new(RwLock::new(true)),
+        proxy_pool_manager: Arc::new(crate::proxy::proxy_pool::ProxyPoolManager::new(
+            snapshot.clone(),
+        )),
+        config: snapshot,
+        listener: Arc::new(crate::proxy::hot_reload::ListenerControl::new()),
+        drain: Arc::new(crate::proxy::drain::DrainController::new()),
+        batches,
+    }
+}
+
+/// 修改测试 AppState 的配置快照 (模拟一次热更新)
+pub fn update_proxy_config(state: &AppState, update: impl FnOnce(&mut ProxyConfig)) {
+    let mut config = (**state.config.load()).clone();
+    update(&mut config.proxy);
+    state.config.store(Arc::new(config));
+}
+
+async fn start_mock() -> MockUpstreamHandle {
+    MockUpstream::new()
+        .with_fixture(sse_fixture())
+        .with_fixture(json_fixture())
+        .start()
+        .await
+        .unwrap()
+}
+
+async fn read_body(response: axum::response::Response) -> (StatusCode, String) {
+    let status = response.status();
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    (status, String::from_utf8_lossy(&bytes).to_string())
+}
+
+#[tokio::test]
+async fn test_replay_openai_chat_completions() {
+    let mock = start_mock().await;
+    let state = build_state(&mock).await;
+
+    let body = json!({
+        "model": MODEL,
+        "messages": [{ "role": "user", "content": "hi" }]
+    });
+    let response = crate::proxy::handlers::openai::handle_chat_completions(
+        State(state),
+        HeaderMap::new(),
+        Json(body),
+    )
+    .await
+    .into_response();
+
+    let (status, text) = read_body(response).await;
+    assert_eq!(status, StatusCode::OK, "body: {}", text);
+    let parsed: Value = serde_json::from_str(&text).unwrap();
+    assert_eq!(
+        parsed["choices"][0]["message"]["content"].as_str(),
+        Some("Hello from mock upstream")
+    );
+    assert!(!mock.received().is_empty());
+}
+
+#[tokio::test]
+async fn test_replay_openai_chat_completions_stream() {
+    let mock = start_mock().await;
+    let state = build_state(&mock).await;
+
+    let body = json!({
+        "model": MODEL,
+        "stream": true,
+        "messages": [{ "role": "user", "content": "hi" }]
+    });
+    let response = crate::proxy::handlers::openai::handle_chat_completions(
+        State(state),
+        HeaderMap::new(),
+        Json(body),
+    )
+    .await
+    .into_response();
+
+    let (status, text) = read_body(response).await;
+    assert_eq!(status, StatusCode::OK, "body: {}", text);
+    assert!(text.contains("chat.completion.chunk"), "body: {}", text);
+    assert!(text.contains("Hello from "), "body: {}", text);
+    assert!(text.contains("mock upstream"), "body: {}", text);
+    assert!(text.contains("[DONE]"), "body: {}", text);
+}
+
+#[tokio::test]
+async fn test_replay_completions_background_task_reply_is_local() {
+    use crate::proxy::config::{BackgroundTaskAction, BackgroundTaskRule, RulePattern};
+    use crate::proxy::handlers::openai::handle_completions;
+
+    let mock = start_mock().await;
+    let state = build_state(&mock).await;
+    update_proxy_config(&state, |proxy| {
+        proxy.background_tasks.rules = vec![BackgroundTaskRule {
+            name: "title".to_string(),
+            enabled: true,
+            protocols: vec!["openai".to_string()],
+            client_adapter: None,
+            user_agent: None,
+            min_message_chars: None,
+            max_message_chars: None,
+            last_user_message: RulePattern::new("(?i)title").ok(),
+            system_prompt: None,
+            has_tools: None,
+            action: BackgroundTaskAction::Reply {
+                text: "Local title".to_string(),
+            },
+        }];
+    });
+    let uri = |path: &str| axum::extract::OriginalUri(path.parse().unwrap());
+
+    // Responses API (非流式)
+    let body = json!({ "model": MODEL, "input": "Generate a title for this chat" });
+    let response = handle_completions(
+        uri("/v1/responses"),
+        State(state.clone()),
+        HeaderMap::new(),
+        Json(body),
+    )
+    .await;
+    let (status, text) = read_body(response).await;
+    assert_eq!(status, StatusCode::OK, "body: {}", text);
+    let parsed: Value = serde_json::from_str(&text).unwrap();
+    assert_eq!(parsed["object"], "response");
+    assert_eq!(parsed["output"][0]["content"][0]["text"], "Local title");
+
+    // Legacy Completions (流式)
+    let body = json!({ "model": MODEL, "prompt": "Generate a title", "stream": true });
+    let response = handle_completions(
+        uri("/v1/completions"),
+        State(state.clone()),
+        HeaderMap::new(),
+        Json(body),
+    )
+    .await;
+    let (status, text) = read_body(response).await;
+    assert_eq!(status, StatusCode::OK, "body: {}", text);
+    assert!(text.contains("text_completion"), "body: {}", text);
+    assert!(text.contains("Local title"), "body: {}", text);
+
+    // 本地回复不访问上游
+    assert!(mock.received().is_empty());
+}
+
+#[tokio::test]
+async fn test_replay_claude_messages_stream() {
+    let mock = start_mock().await;
+    let state = build_state(&mock).await;
+
+    let body = json!({
+        "model": MODEL,
+        "max_tokens": 256,
+        "stream": true,
+        "messages": [{ "role": "user", "content": "hi" }]
+    });
+    let response =
+        crate::proxy::handlers::claude::handle_messages(State(state), HeaderMap::new(), Json(body))
+            .await;
+
+    let (status, text) = read_body(response).await;
+    assert_eq!(status, StatusCode::OK, "body: {}", text);
+    assert!(text.contains("message_start"), "body: {}", text);
+    assert!(text.contains("content_block_delta"), "body: {}", text);
+    assert!(text.contains("mock upstream"), "body: {}", text);
+    assert!(text.contains("message_stop"), "body: {}", text);
+}
+
+#[tokio::test]
+async fn test_replay_stream_with_validated_output_is_accepted() {
+    let mock = start_mock().await;
+    let state = build_state(&mock).await;
+
+    let body = json!({
+        "model": MODEL,
+        "stream": true,
+        "messages": [{ "role": "user", "content": "hi" }],
+        "response_format": {
+            "type": "json_schema",
+            "json_schema": {
+                "name": "answer",
+                "strict": true,
+                "schema": { "type": "object", "properties": { "a": { "type": "string" } } }
+            }
+        }
+    });
+    let response = crate::proxy::handlers::openai::handle_chat_completions(
+        State(state.clone()),
+        HeaderMap::new(),
+        Json(body),
+    )
+    .await
+    .into_response();
+    let (status, text) = read_body(response).await;
+    assert_eq!(status, StatusCode::OK, "body: {}", text);
+    assert!(text.contains("mock upstream"), "body: {}", text);
+
+    let body = json!({
+        "model": MODEL,
+        "max_tokens": 256,
+        "stream": true,
+        "messages": [{ "role": "user", "content": "hi" }],
+        "tools": [{
+            "name": "answer",
+            "input_schema": { "type": "object", "properties": { "a": { "type": "string" } } }
+        }],
+        "tool_choice": { "type": "tool", "name": "answer" }
+    });
+    let response =
+        crate::proxy::handlers::claude::handle_messages(State(state), HeaderMap::new(), Json(body))
+            .await;
+    let (status, text) = read_body(response).await;
+    assert_eq!(status, StatusCode::OK, "body: {}", text);
+    assert!(text.contains("mock upstream"), "body: {}", text);
+
+    // 流式请求不做输出校验，每个请求只转发一次
+    assert_eq!(mock.received().len(), 2);
+}
+
+#[tokio::test]
+async fn test_replay_gemini_generate() {
+    let mock = start_mock().await;
+    let state = build_state(&mock).await;
+
+    let body = json!({
+        "contents": [{ "role": "user", "parts": [{ "text": "hi" }] }]
+    });
+    let response = crate::proxy::handlers::gemini::handle_generate(
+        State(state),
+        Path(format!("{}:generateContent", MODEL)),
+        HeaderMap::new(),
+        Json(body),
+    )
+    .await
+    .into_response();
+
+    let (status, text) = read_body(response).await;
+    assert_eq!(status, StatusCode::OK, "body: {}", text);
+    assert!(text.contains("Hello from"), "body: {}", text);
+}
+
+#[tokio::test]
+async fn test_record_fixture_round_trip() {
+    let mock = start_mock().await;
+    let output_dir =
+        std::env::temp_dir().join(format!("antigravity-fixture-test-{}", uuid::Uuid::new_v4()));
+
+    let upstream = UpstreamClient::new(None, None);
+    upstream
+        .set_debug_logging(DebugLoggingConfig {
+            output_dir: Some(output_dir.to_string_lossy().to_string()),
+            record_fixtures: true,
+            upstream_base_url: Some(mock.base_url().to_string()),
+            ..Default::default()
+        })
+        .await;
+
+    let request = json!({ "model": MODEL, "project": "mock-project", "request": {} });
+    let result = upstream
+        .call_v1_internal(
+            "streamGenerateContent",
+            "mock-access-token",
+            request.clone(),
+            Some("alt=sse"),
+            None,
+        )
+        .await
+        .unwrap();
+    assert_eq!(result.response.status().as_u16(), 200);
+    let streamed = result.response.text().await.unwrap();
+    assert_eq!(streamed, sse_fixture().body);
+
+    // 录制输出可直接被 MockUpstream 加载
+    let fixture_dir = output_dir.join("upstream_fixtures");
+    let replay = MockUpstream::new().load_dir(&fixture_dir).unwrap();
+    let replay = replay.start().await.unwrap();
+    let client = rquest::Client::new();
+    let replayed = client
+        .post(format!(
+            "{}:streamGenerateContent?alt=sse",
+            replay.base_url()
+        ))
+        .json(&request)
+        .send()
+        .await
+        .unwrap();
+    assert!(replayed
+        .headers()
+        .get("content-type")
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default()
+        .starts_with("text/event-stream"));
+    assert_eq!(replayed.text().await.unwrap(), sse_fixture().body);
+
+    let received = replay.received();
+    assert_eq!(received.len(), 1);
+    assert_eq!(received[0].method, "streamGenerateContent");
+    assert_eq!(received[0].query.as_deref(), Some("alt=sse"));
+    assert_eq!(received[0].body["model"], MODEL);
+
+    let _ = std::fs::remove_dir_all(&output_dir);
+}
+
+fn count_tokens_fixture(total: i64) -> UpstreamFixture {
+    UpstreamFixture {
+        method: "countTokens".to_string(),
+        query: None,
+        model: None,
+        request: Value::Null,
+        status: 200,
+        content_type: "application/json".to_string(),
+        body: json!({ "totalTokens": total }).to_string(),
+    }
+}
+
+#[tokio::test]
+async fn test_replay_claude_count_tokens_uses_upstream() {
+    let mock = MockUpstream::new()
+        .with_fixture(count_tokens_fixture(1234))
+        .start()
+        .await
+        .unwrap();
+    let state = build_state(&mock).await;
+
+    let body = json!({
+        "model": MODEL,
+        "system": "You are a helpful assistant.",
+        "messages": [{ "role": "user", "content": "hi" }]
+    });
+    let response = crate::proxy::handlers::claude::handle_count_tokens(
+        State(state),
+        HeaderMap::new(),
+        Json(body),
+    )
+    .await
+    .into_response();
+
+    let (status, text) = read_body(response).await;
+    assert_eq!(status, StatusCode::OK, "body: {}", text);
+    let parsed: Value = serde_json::from_str(&text).unwrap();
+    assert_eq!(parsed["input_tokens"], 1234);
+
+    // countTokens 只接受 request.contents，系统提示并入 contents 计数
+    let received = mock.received();
+    assert_eq!(received.len(), 1);
+    assert_eq!(received[0].method, "countTokens");
+    let sent = received[0].body.as_object().unwrap();
+    assert_eq!(sent.keys().collect::<Vec<_>>(), vec!["request"]);
+    let contents = received[0].body["request"]["contents"].to_string();
+    assert!(contents.contains("You are a helpful assistant."));
+    assert!(contents.contains("hi"));
+}
+
+#[tokio::test]
+async fn test_replay_count_tokens_falls_back_to_estimate() {
+    // 没有 countTokens 夹具时上游返回 404，回退到校准后的本地估算
+    let mock = MockUpstream::new().start().await.unwrap();
+    let state = build_state(&mock).await;
+
+    let body = json!({
+        "model": MODEL,
+        "messages": [{ "role": "user", "content": "hello world, count these tokens please" }]
+    });
+    let response = crate::proxy::handlers::claude::handle_count_tokens(
+        State(state.clone()),
+        HeaderMap::new(),
+        Json(body),
+    )
+    .await
+    .into_response();
+    let (status, text) = read_body(response).await;
+    assert_eq!(status, StatusCode::OK, "body: {}", text);
+    let parsed: Value = serde_json::from_str(&text).unwrap();
+    assert!(parsed["input_tokens"].as_u64().unwrap() > 0);
+
+    let body = json!({
+        "model": MODEL,
+        "instructions": "Be brief.",
+        "input": "hello world, count these tokens please"
+    });
+    let response =
+        crate::proxy::handlers::openai::handle_responses_input_tokens(State(state), Json(body))
+            .await
+            .into_response();
+    let (status, text) = read_body(response).await;
+    assert_eq!(status, StatusCode::OK, "body: {}", text);
+    let parsed: Value = serde_json::from_str(&text).unwrap();
+    assert_eq!(parsed["object"], "response.input_tokens");
+    assert!(parsed["input_tokens"].as_u64().unwrap() > 0);
+}
+
+#[tokio::test]
+async fn test_replay_responses_input_tokens_uses_upstream() {
+    let mock = MockUpstream::new()
+        .with_fixture(count_tokens_fixture(42))
+        .start()
+        .await
+        .unwrap();
+    let state = build_state(&mock).await;
+
+    let body = json!({
+        "model": MODEL,
+        "input": [{ "type": "message", "role": "user", "content": [{ "type": "input_text", "text": "hi" }] }]
+    });
+    let response =
+        crate::proxy::handlers::openai::handle_responses_input_tokens(State(state), Json(body))
+            .await
+            .into_response();
+    let (status, text) = read_body(response).await;
+    assert_eq!(status, StatusCode::OK, "body: {}", text);
+    let parsed: Value = serde_json::from_str(&text).unwrap();
+    assert_eq!(parsed["input_tokens"], 42);
+    assert_eq!(mock.received()[0].method, "countTokens");
+}
+
+#[tokio::test]
+async fn test_replay_batch_job_writes_output_file() {
+    use crate::proxy::batch_store::FileObject;
+    use crate::proxy::handlers::batch;
+
+    let mock = start_mock().await;
+    let state = build_state(&mock).await;
+
+    let line = |custom_id: &str, body: Value| {
+        json!({
+            "custom_id": custom_id,
+            "method": "POST",
+            "url": "/v1/chat/completions",
+            "body": body
+        })
+        .to_string()
+    };
+    let content = [
+        line(
+            "ok",
+            json!({ "model": MODEL, "stream": true, "messages": [{ "role": "user", "content": "hi" }] }),
+        ),
+        line("bad", json!({ "model": MODEL, "messages": "oops" })),
+    ]
+    .join("\n");
+    let input = FileObject::new(
+        "input.jsonl".to_string(),
+        "batch".to_string(),
+        content.len() as u64,
+        chrono::Utc::now().timestamp(),
+        None,
+    );
+    state.batches.save_file(&input, content.as_bytes()).unwrap();
+
+    let response = batch::handle_create_batch(
+        State(state.clone()),
+        None,
+        Json(
+            serde_json::from_value(json!({
+                "input_file_id": input.id,
+                "endpoint": "/v1/chat/completions",
+                "completion_window": "24h"
+            }))
+            .unwrap(),
+        ),
+    )
+    .await;
+    let (status, text) = read_body(response).await;
+    assert_eq!(status, StatusCode::OK, "body: {}", text);
+    let created: Value = serde_json::from_str(&text).unwrap();
+    let batch_id = created["id"].as_str().unwrap().to_string();
+    assert_eq!(created["status"], "in_progress");
+    assert_eq!(created["request_counts"]["total"], 2);
+
+    // 其他令牌看不到该作业；执行中的作业仍引用输入文件，不能删除
+    let other = Some(axum::extract::Extension(
+        crate::proxy::middleware::auth::UserTokenIdentity {
+            token_id: "tok-other".to_string(),
+            token: "sk-other".to_string(),
+            username: "other".to_string(),
+        },
+    ));
+    let response =
+        batch::handle_get_batch(State(state.clone()), other, Path(batch_id.clone())).await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    let response =
+        batch::handle_delete_file(State(state.clone()), None, Path(input.id.clone())).await;
+    assert_eq!(response.status(), StatusCode::CONFLICT);
+
+    crate::proxy::batch_runner::spawn(state.clone());
+    let finished = tokio::time::timeout(std::time::Duration::from_secs(20), async {
+        loop {
+            let batch = state
+                .batches
+                .store()
+                .get_batch(&batch_id, None)
+                .unwrap()
+                .unwrap();
+            if batch.is_terminal() {
+                return batch;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        }
+    })
+    .await
+    .expect("batch did not finish");
+    state.batches.stop();
+
+    assert_eq!(finished.status, "completed");
+    assert_eq!(finished.request_counts.completed, 1);
+    assert_eq!(finished.request_counts.failed, 1);
+
+    let output_id = finished.output_file_id.clone().unwrap();
+    let response =
+        batch::handle_get_file_content(State(state.clone()), None, Path(output_id)).await;
+    let (status, text) = read_body(response).await;
+    assert_eq!(status, StatusCode::OK);
+    let output: Value = serde_json::from_str(text.trim()).unwrap();
+    assert_eq!(output["custom_id"], "ok");
+    assert_eq!(output["response"]["status_code"], 200);
+    assert_eq!(
+        output["response"]["body"]["choices"][0]["message"]["content"].as_str(),
+        Some("Hello from mock upstream")
+    );
+
+    let errors = state
+        .batches
+        .read_file(finished.error_file_id.as_deref().unwrap())
+        .unwrap();
+    let error: Value = serde_json::from_slice(&errors).unwrap();
+    assert_eq!(error["custom_id"], "bad");
+    assert!(error["response"]["status_code"].as_u64().unwrap() >= 400);
+}
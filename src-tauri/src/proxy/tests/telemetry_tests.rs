@@ -0,0 +1,106 @@
+//! OpenTelemetry Export Tests
+//!
+//! 启动本地 OTLP/HTTP 收集端，验证 `telemetry_middleware` 的根 span 会续接调用方的
+//! `traceparent` 并被导出，以及关闭后不再导出。
+
+use std::sync::{Arc, Mutex};
+
+use axum::{
+    body::{Body, Bytes},
+    extract::State,
+    http::{Request, StatusCode},
+    routing::{get, post},
+    Router,
+};
+use tower::ServiceExt;
+use tracing_subscriber::layer::SubscriberExt;
+
+use crate::proxy::config::TelemetryConfig;
+use crate::proxy::middleware::telemetry_middleware;
+
+const TRACE_ID_HEX: &str = "4bf92f3577b34da6a3ce929d0e0e4736";
+
+type Received = Arc<Mutex<Vec<Bytes>>>;
+
+async fn collect(State(received): State<Received>, body: Bytes) -> StatusCode {
+    received.lock().unwrap().push(body);
+    StatusCode::OK
+}
+
+async fn start_collector() -> (String, Received) {
+    let received: Received = Arc::new(Mutex::new(Vec::new()));
+    let app = Router::new()
+        .route("/v1/traces", post(collect))
+        .with_state(received.clone());
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        let _ = axum::serve(listener, app).await;
+    });
+    (format!("http://{}/v1/traces", addr), received)
+}
+
+fn trace_id_bytes() -> Vec<u8> {
+    (0..TRACE_ID_HEX.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&TRACE_ID_HEX[i..i + 2], 16).unwrap())
+        .collect()
+}
+
+async fn send_traced_request() {
+    let app = Router::new()
+        .route(
+            "/v1/ping",
+            get(|| async { ([("X-Account-Email", "otel@test.com")], "pong") }),
+        )
+        .layer(axum::middleware::from_fn(telemetry_middleware));
+    let request = Request::builder()
+        .uri("/v1/ping")
+        .header(
+            "traceparent",
+            format!("00-{}-00f067aa0ba902b7-01", TRACE_ID_HEX),
+        )
+        .body(Body::empty())
+        .unwrap();
+    let response = app.oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+async fn flush() {
+    tokio::task::spawn_blocking(|| {
+        crate::proxy::telemetry::force_flush();
+    })
+    .await
+    .unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_request_span_exported_with_remote_parent() {
+    let (endpoint, received) = start_collector().await;
+    let subscriber = tracing_subscriber::registry().with(crate::proxy::telemetry::layer());
+    let _guard = tracing::subscriber::set_default(subscriber);
+
+    crate::proxy::telemetry::apply_config(&TelemetryConfig {
+        enabled: true,
+        otlp_endpoint: endpoint,
+        ..Default::default()
+    });
+    send_traced_request().await;
+    flush().await;
+
+    let exported = received.lock().unwrap().concat();
+    let trace_id = trace_id_bytes();
+    assert!(
+        exported.windows(trace_id.len()).any(|w| w == trace_id),
+        "exported payload should carry the caller's trace id"
+    );
+    assert!(exported.windows(12).any(|w| w == b"GET /v1/ping"));
+    assert!(exported.windows(13).any(|w| w == b"otel@test.com"));
+
+    // 关闭后不再导出
+    crate::proxy::telemetry::apply_config(&TelemetryConfig::default());
+    received.lock().unwrap().clear();
+    send_traced_request().await;
+    flush().await;
+    assert!(received.lock().unwrap().is_empty());
+}
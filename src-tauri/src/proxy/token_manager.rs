@@ -6,7 +6,8 @@ use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
 use tokio_util::sync::CancellationToken;
 
-use crate::proxy::rate_limit::RateLimitTracker;
+use crate::proxy::rate_limit::{RateLimitSnapshot, RateLimitTracker};
+use crate::proxy::rate_limit_store::{RateLimitStore, RATE_LIMIT_DB_FILE};
 use crate::proxy::sticky_config::StickySessionConfig;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -132,6 +133,19 @@ impl TokenManager {
             return Err(format!("账号目录不存在: {:?}", accounts_dir));
         }
 
+        // 首次加载时挂载 rate_limits.db，恢复重启前的限流锁定与退避计数
+        if !self.rate_limit_tracker.has_store() {
+            let db_path = self.data_dir.join(RATE_LIMIT_DB_FILE);
+            match RateLimitStore::open(&db_path) {
+                Ok(store) => {
+                    self.rate_limit_tracker.attach_store(store);
+                }
+                Err(e) => {
+                    tracing::warn!("打开限流持久化库失败 {:?}: {}", db_path, e);
+                }
+            }
+        }
+
         // Reload should reflect current on-disk state (accounts can be added/removed/disabled).
         self.tokens.clear();
         self.current_index.store(0, Ordering::SeqCst);
@@ -1050,6 +1064,121 @@ impl TokenManager {
     /// P2C 算法的候选池大小 - 从前 N 个最优候选中随机选择
     const P2C_POOL_SIZE: usize = 5;
 
+    /// 配额加权模式下，距刷新时间的参考窗口 (秒)
+    const QUOTA_WEIGHT_RESET_WINDOW_SECS: f64 = 3600.0;
+    /// 即将刷新账号的最大加成倍数 (reset_time 临近时权重最多放大 1 + 3 = 4 倍)
+    const QUOTA_WEIGHT_RESET_BOOST: f64 = 3.0;
+
+    /// 计算账号在配额加权模式下的权重
+    ///
+    /// weight = 剩余配额比例 × 健康分 × 刷新加成
+    /// 刷新加成 = 1 + BOOST × WINDOW / (WINDOW + 距刷新秒数)，未知刷新时间不加成
+    pub(crate) fn quota_weight(token: &ProxyToken, normalized_target: &str, now: i64) -> f64 {
+        let quota = token
+            .model_quotas
+            .get(normalized_target)
+            .copied()
+            .unwrap_or(0)
+            .clamp(0, 100) as f64
+            / 100.0;
+        if quota <= 0.0 {
+            return 0.0;
+        }
+
+        let health = (token.health_score as f64).clamp(0.05, 1.0);
+
+        let reset_boost = match token.reset_time {
+            Some(reset) => {
+                let secs_left = (reset - now).max(0) as f64;
+                1.0 + Self::QUOTA_WEIGHT_RESET_BOOST * Self::QUOTA_WEIGHT_RESET_WINDOW_SECS
+                    / (Self::QUOTA_WEIGHT_RESET_WINDOW_SECS + secs_left)
+            }
+            None => 1.0,
+        };
+
+        quota * health * reset_boost
+    }
+
+    /// 配额加权随机选择 (`roll` ∈ [0, 1)，由调用方传入以便测试)
+    ///
+    /// 所有候选权重均为 0 时退化为第一个可用账号
+    pub(crate) fn select_quota_weighted<'a>(
+        candidates: &'a [ProxyToken],
+        attempted: &HashSet<String>,
+        normalized_target: &str,
+        quota_protection_enabled: bool,
+        now: i64,
+        roll: f64,
+    ) -> Option<&'a ProxyToken> {
+        let available: Vec<(&ProxyToken, f64)> = candidates
+            .iter()
+            .filter(|t| !attempted.contains(&t.account_id))
+            .filter(|t| {
+                !quota_protection_enabled || !t.protected_models.contains(normalized_target)
+            })
+            .map(|t| (t, Self::quota_weight(t, normalized_target, now)))
+            .collect();
+
+        let total: f64 = available.iter().map(|(_, w)| w).sum();
+        if total <= 0.0 {
+            return available.first().map(|(t, _)| *t);
+        }
+
+        let mut threshold = roll.clamp(0.0, 1.0) * total;
+        for (token, weight) in &available {
+            if *weight <= 0.0 {
+                continue;
+            }
+            if threshold < *weight {
+                return Some(*token);
+            }
+            threshold -= weight;
+        }
+        // 浮点误差兜底: 返回最后一个有权重的账号
+        available
+            .iter()
+            .rev()
+            .find(|(_, w)| *w > 0.0)
+            .map(|(t, _)| *t)
+    }
+
+    /// 按调度模式选择候选账号 (QuotaWeighted 使用加权随机，其余模式使用 P2C)
+    fn select_for_mode<'a>(
+        &self,
+        mode: crate::proxy::sticky_config::SchedulingMode,
+        candidates: &'a [ProxyToken],
+        attempted: &HashSet<String>,
+        normalized_target: &str,
+        quota_protection_enabled: bool,
+    ) -> Option<&'a ProxyToken> {
+        if mode != crate::proxy::sticky_config::SchedulingMode::QuotaWeighted {
+            return self.select_with_p2c(
+                candidates,
+                attempted,
+                normalized_target,
+                quota_protection_enabled,
+            );
+        }
+
+        use rand::Rng;
+        let selected = Self::select_quota_weighted(
+            candidates,
+            attempted,
+            normalized_target,
+            quota_protection_enabled,
+            chrono::Utc::now().timestamp(),
+            rand::thread_rng().gen::<f64>(),
+        );
+        if let Some(t) = selected {
+            tracing::debug!(
+                "⚖️ [QuotaWeighted] Selected {} (weight={:.3})",
+                t.email,
+                Self::quota_weight(t, normalized_target, chrono::Utc::now().timestamp())
+            );
+        }
+        selected
+    }
+
     /// Power of 2 Choices (P2C) 选择算法
     /// 从前 5 个候选中随机选 2 个，选择配额更高的 -> 避免热点
     /// 返回选中的索引
@@ -1172,6 +1301,16 @@ impl TokenManager {
     /// 参数 `force_rotate` 为 true 时将忽略锁定，强制切换账号
     /// 参数 `session_id` 用于跨请求维持会话粘性
     /// 参数 `target_model` 用于检查配额保护 (Issue #621)
+    #[tracing::instrument(
+        name = "account.select",
+        skip_all,
+        fields(
+            quota_group,
+            model = target_model,
+            force_rotate,
+            retry.attempt = crate::proxy::telemetry::current_attempt()
+        )
+    )]
     pub async fn get_token(
         &self,
         quota_group: &str,
@@ -1672,7 +1811,8 @@ impl TokenManager {
                         }
                     }
 
-                    if let Some(selected) = self.select_with_p2c(
+                    if let Some(selected) = self.select_for_mode(
+                        scheduling.mode,
                         &non_limited,
                         &attempted,
                         &normalized_target,
@@ -1711,7 +1851,8 @@ impl TokenManager {
                     }
                 }
 
-                if let Some(selected) = self.select_with_p2c(
+                if let Some(selected) = self.select_for_mode(
+                    scheduling.mode,
                     &non_limited,
                     &attempted,
                     &normalized_target,
@@ -2312,6 +2453,22 @@ impl TokenManager {
         self.rate_limit_tracker.clear(account_id)
     }
 
+    /// 获取当前生效的限流记录 (包含重启后恢复的记录)
+    pub fn get_rate_limit_snapshot(&self) -> Vec<RateLimitSnapshot> {
+        self.rate_limit_tracker.snapshot()
+    }
+
+    /// 各账号的模型剩余配额快照 (email, model -> 剩余百分比)，用于 /metrics 导出
+    pub fn get_quota_snapshot(&self) -> Vec<(String, HashMap<String, i32>)> {
+        let mut quotas: Vec<(String, HashMap<String, i32>)> = self
+            .tokens
+            .iter()
+            .map(|entry| (entry.email.clone(), entry.model_quotas.clone()))
+            .collect();
+        quotas.sort_by(|a, b| a.0.cmp(&b.0));
+        quotas
+    }
+
     /// 清除所有限流记录
     pub fn clear_all_rate_limits(&self) {
         self.rate_limit_tracker.clear_all();
@@ -2355,8 +2512,11 @@ impl TokenManager {
         for entry in self.tokens.iter() {
             let token = entry.value();
 
-            // 1. 检查是否被限流
-            if self.is_rate_limited(&token.account_id, None).await {
+            // 1. 检查是否被限流 (账号级锁 + 目标模型的模型级锁)
+            if self
+                .is_rate_limited(&token.account_id, Some(target_model))
+                .await
+            {
                 tracing::debug!(
                     "[Fallback Check] Account {} is rate-limited, skipping",
                     token.email
@@ -2437,10 +2597,9 @@ impl TokenManager {
         model: Option<String>,
     ) -> bool {
         // [FIX #2209] 统一归一化模型名称
-        let normalized_model = model
+        let model_to_lock = model
             .as_deref()
-            .and_then(|m| crate::proxy::common::model_mapping::normalize_to_standard_id(m));
-        let model_to_lock = normalized_model.or(model);
+            .map(crate::proxy::common::model_mapping::rate_limit_model_key);
 
         if let Some(reset_time_str) = self.get_quota_reset_time(account_id) {
             tracing::info!("找到账号 {} 的配额刷新时间: {}", account_id, reset_time_str);
@@ -2524,10 +2683,9 @@ impl TokenManager {
                     );
 
                     // [FIX #2209] 统一归一化模型名称
-                    let normalized_model = model.as_deref().and_then(|m| {
-                        crate::proxy::common::model_mapping::normalize_to_standard_id(m)
-                    });
-                    let model_to_lock = normalized_model.or(model);
+                    let model_to_lock = model
+                        .as_deref()
+                        .map(crate::proxy::common::model_mapping::rate_limit_model_key);
 
                     // [FIX] 使用 account_id 作为 key，与 is_rate_limited 检查一致
                     self.rate_limit_tracker.set_lockout_until_iso(
@@ -2571,9 +2729,8 @@ impl TokenManager {
         model: Option<&str>, // 🆕 新增模型参数
     ) {
         // [FIX #2209] 统一归一化模型名称，确保锁定 Key 与负载均衡检查 Key 一致
-        let normalized_model =
-            model.and_then(|m| crate::proxy::common::model_mapping::normalize_to_standard_id(m));
-        let model_to_track = normalized_model.as_deref().or(model);
+        let model_key = model.map(crate::proxy::common::model_mapping::rate_limit_model_key);
+        let model_to_track = model_key.as_deref();
 
         // [NEW] 检查熔断是否启用
         let config = self.circuit_breaker_config.read().await.clone();
@@ -7,6 +7,7 @@ use serde_json::Value;
 use std::sync::Arc;
 use tokio::sync::RwLock;
 use tokio::time::Duration;
+use tracing::Instrument;
 
 /// 端点降级尝试的记录信息
 #[derive(Debug, Clone)]
@@ -74,11 +75,16 @@ const V1_INTERNAL_BASE_URL_FALLBACKS: [&str; 3] = [
     V1_INTERNAL_BASE_URL_PROD,    // 优先级 3: Prod (仅作为兜底)
 ];
 
+// Gemini public API (embedding 模型仅在此提供)
+const GEMINI_PUBLIC_BASE_URL: &str = "https://generativelanguage.googleapis.com/v1beta";
+
 pub struct UpstreamClient {
     default_client: RwLock<Client>,
     proxy_pool: Option<Arc<crate::proxy::proxy_pool::ProxyPoolManager>>,
     client_cache: DashMap<String, Client>, // proxy_id -> Client
     user_agent_override: RwLock<Option<String>>,
+    // 调试配置: 夹具录制开关与上游地址覆盖 (mock / 回放)
+    debug_logging: RwLock<crate::proxy::config::DebugLoggingConfig>,
 }
 
 impl UpstreamClient {
@@ -111,6 +117,7 @@ impl UpstreamClient {
             proxy_pool,
             client_cache: DashMap::new(),
             user_agent_override: RwLock::new(None),
+            debug_logging: RwLock::new(crate::proxy::config::DebugLoggingConfig::default()),
         }
     }
 
@@ -223,6 +230,53 @@ impl UpstreamClient {
         tracing::debug!("UpstreamClient User-Agent override updated: {:?}", lock);
     }
 
+    /// [HOT-RELOAD] 更新调试配置 (夹具录制 / 上游地址覆盖)
+    pub async fn set_debug_logging(&self, cfg: crate::proxy::config::DebugLoggingConfig) {
+        if let Some(url) = cfg.upstream_base_url.as_deref().filter(|u| !u.is_empty()) {
+            tracing::warn!("UpstreamClient v1internal base URL overridden: {}", url);
+        }
+        let mut lock = self.debug_logging.write().await;
+        *lock = cfg;
+    }
+
+    /// 当前生效的 v1internal 端点列表 (配置了覆盖地址时仅使用该地址，不再降级)
+    async fn v1_internal_base_urls(&self) -> Vec<String> {
+        let cfg = self.debug_logging.read().await;
+        match cfg
+            .upstream_base_url
+            .as_deref()
+            .map(|u| u.trim().trim_end_matches('/'))
+            .filter(|u| !u.is_empty())
+        {
+            Some(url) => vec![url.to_string()],
+            None => V1_INTERNAL_BASE_URL_FALLBACKS
+                .iter()
+                .map(|u| u.to_string())
+                .collect(),
+        }
+    }
+
+    /// 录制模式下包装响应，流结束后写入回放夹具；未开启时原样返回
+    async fn maybe_record(
+        &self,
+        method: &str,
+        query_string: Option<&str>,
+        body: &Value,
+        response: Response,
+    ) -> Response {
+        let cfg = self.debug_logging.read().await.clone();
+        if !cfg.record_fixtures {
+            return response;
+        }
+        crate::proxy::debug_logger::record_upstream_fixture(
+            cfg,
+            method,
+            query_string,
+            body,
+            response,
+        )
+    }
+
     /// Get current User-Agent
     pub async fn get_user_agent(&self) -> String {
         let ua_override = self.user_agent_override.read().await;
@@ -318,6 +372,15 @@ impl UpstreamClient {
 
     /// [FIX #765] 调用 v1internal API，支持透传额外的 Headers
     /// [ENHANCED] 返回 UpstreamCallResult，包含降级尝试记录，用于 debug 日志
+    #[tracing::instrument(
+        name = "upstream.call",
+        skip_all,
+        fields(
+            rpc.method = method,
+            account_id,
+            retry.attempt = crate::proxy::telemetry::current_attempt()
+        )
+    )]
     pub async fn call_v1_internal_with_headers(
         &self,
         method: &str,
@@ -378,12 +441,12 @@ impl UpstreamClient {
 
         // [NEW] 深度解析 body 中的 project_id 并注入 Header
         // 只有当 Body 包含 project 字段且非测试项目时，注入 x-goog-user-project
-        if let Some(proj) = body.get("project").and_then(|v| v.as_str()) {
-            if !proj.is_empty() && proj != "test-project" && proj != "project-id" {
-                if let Ok(hv) = header::HeaderValue::from_str(proj) {
-                    headers.insert("x-goog-user-project", hv);
-                }
-            }
+        if let Some(hv) = body
+            .get("project")
+            .and_then(|v| v.as_str())
+            .and_then(user_project_header)
+        {
+            headers.insert("x-goog-user-project", hv);
         }
 
         // 注入额外的 Headers (如 anthropic-beta)
@@ -399,6 +462,7 @@ impl UpstreamClient {
         tracing::debug!(?headers, "Final Upstream Request Headers");
 
         let mut has_triggered_downgrade = false;
+        let base_urls = self.v1_internal_base_urls().await;
 
         // [TEMPORARY FIX #3074] 针对 403 SERVICE_DISABLED 的自动降级重试逻辑
         // 我们包装一层循环，以便在检测到特定错误时移除 Header 并重试
@@ -408,9 +472,9 @@ impl UpstreamClient {
             let mut should_retry_without_header = false;
 
             // 遍历所有端点，失败时自动切换
-            for (idx, base_url) in V1_INTERNAL_BASE_URL_FALLBACKS.iter().enumerate() {
+            for (idx, base_url) in base_urls.iter().enumerate() {
                 let url = Self::build_url(base_url, method, query_string);
-                let has_next = idx + 1 < V1_INTERNAL_BASE_URL_FALLBACKS.len();
+                let has_next = idx + 1 < base_urls.len();
 
                 let body_bytes = serde_json::to_vec(&body).map_err(|e| e.to_string())?;
 
@@ -428,7 +492,16 @@ impl UpstreamClient {
                     req_builder = req_builder.body(body_bytes.clone());
                 }
 
-                let response = req_builder.send().await;
+                let endpoint_span = tracing::info_span!(
+                    "upstream.endpoint",
+                    url.full = %url,
+                    endpoint.index = idx,
+                    http.response.status_code = tracing::field::Empty,
+                );
+                let response = req_builder.send().instrument(endpoint_span.clone()).await;
+                if let Ok(resp) = &response {
+                    endpoint_span.record("http.response.status_code", resp.status().as_u16());
+                }
 
                 match response {
                     Ok(resp) => {
@@ -439,7 +512,7 @@ impl UpstreamClient {
                                     "✓ Upstream fallback succeeded | Endpoint: {} | Status: {} | Next endpoints available: {}",
                                     base_url,
                                     status,
-                                    V1_INTERNAL_BASE_URL_FALLBACKS.len() - idx - 1
+                                    base_urls.len() - idx - 1
                                 );
                             } else {
                                 tracing::debug!(
@@ -449,7 +522,9 @@ impl UpstreamClient {
                                 );
                             }
                             return Ok(UpstreamCallResult {
-                                response: resp,
+                                response: self
+                                    .maybe_record(method, query_string, &body, resp)
+                                    .await,
                                 fallback_attempts,
                             });
                         }
@@ -478,6 +553,11 @@ impl UpstreamClient {
                                 method
                             );
                             // [NEW] 记录降级尝试
+                            crate::proxy::metrics::ProxyMetrics::global().record_fallback(
+                                method,
+                                base_url,
+                                Some(status.as_u16()),
+                            );
                             fallback_attempts.push(FallbackAttemptLog {
                                 endpoint_url: url.clone(),
                                 status: Some(status.as_u16()),
@@ -489,7 +569,7 @@ impl UpstreamClient {
 
                         // 不可重试的错误或已是最后一个端点，直接返回
                         return Ok(UpstreamCallResult {
-                            response: resp,
+                            response: self.maybe_record(method, query_string, &body, resp).await,
                             fallback_attempts,
                         });
                     }
@@ -497,6 +577,8 @@ impl UpstreamClient {
                         let msg = format!("HTTP request failed at {}: {}", base_url, e);
                         tracing::debug!("{}", msg);
                         // [NEW] 记录网络错误的降级尝试
+                        crate::proxy::metrics::ProxyMetrics::global()
+                            .record_fallback(method, base_url, None);
                         fallback_attempts.push(FallbackAttemptLog {
                             endpoint_url: url.clone(),
                             status: None,
@@ -571,6 +653,57 @@ impl UpstreamClient {
             .map_err(|e| format!("Parse json failed: {}", e))?;
         Ok(json)
     }
+
+    /// 调用 Gemini 公共 API 的 embedding 方法 (embedContent / batchEmbedContents)
+    ///
+    /// v1internal 不提供 embedding 端点，这里直接使用账号的 OAuth Token 访问
+    /// generativelanguage.googleapis.com，并沿用账号绑定的代理。
+    /// OAuth Token 调用公共 API 需要计费项目，通过 x-goog-user-project 传入账号解析出的
+    /// project_id；与 v1internal 一致，403 时去掉该 Header 重试一次
+    pub async fn call_gemini_embedding(
+        &self,
+        model: &str,
+        method: &str,
+        access_token: &str,
+        project_id: &str,
+        body: Value,
+        account_id: Option<&str>,
+    ) -> Result<Response, String> {
+        let url = format!("{}/models/{}:{}", GEMINI_PUBLIC_BASE_URL, model, method);
+        let client = self.get_client(account_id).await;
+        let mut project = user_project_header(project_id);
+        loop {
+            let mut request = client
+                .post(&url)
+                .bearer_auth(access_token)
+                .header(header::CONTENT_TYPE, "application/json");
+            if let Some(hv) = &project {
+                request = request.header("x-goog-user-project", hv.clone());
+            }
+            let response = request
+                .json(&body)
+                .send()
+                .await
+                .map_err(|e| format!("Embedding request failed: {}", e))?;
+
+            if response.status() == StatusCode::FORBIDDEN && project.take().is_some() {
+                tracing::warn!(
+                    "Embedding request got 403 with project header, retrying WITHOUT x-goog-user-project (Account: {:?})",
+                    account_id
+                );
+                continue;
+            }
+            return Ok(response);
+        }
+    }
+}
+
+/// 账号的真实项目 ID 转为 x-goog-user-project Header (占位项目不发送)
+fn user_project_header(project_id: &str) -> Option<header::HeaderValue> {
+    if project_id.is_empty() || project_id == "test-project" || project_id == "project-id" {
+        return None;
+    }
+    header::HeaderValue::from_str(project_id).ok()
 }
 
 #[cfg(test)]
@@ -593,4 +726,15 @@ mod tests {
             "https://cloudcode-pa.googleapis.com/v1internal:streamGenerateContent?alt=sse"
         );
     }
+
+    #[test]
+    fn test_user_project_header_skips_placeholders() {
+        assert_eq!(
+            user_project_header("bamboo-precept-lgxtn").unwrap(),
+            "bamboo-precept-lgxtn"
+        );
+        assert!(user_project_header("").is_none());
+        assert!(user_project_header("test-project").is_none());
+        assert!(user_project_header("project-id").is_none());
+    }
 }
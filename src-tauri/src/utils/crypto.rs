@@ -11,6 +11,11 @@ const LEGACY_FIXED_NONCE: &[u8; 12] = b"antigravsalt";
 const ENCRYPTED_PREFIX: &str = "ag_enc_";
 const ENCRYPTED_V2_PREFIX: &str = "ag_enc_v2_";
 
+/// 口令加密数据的文件头 (与机器无关，可跨主机解密)
+const PASSPHRASE_MAGIC: &[u8] = b"AGPASS1\0";
+const PASSPHRASE_SALT_LEN: usize = 16;
+const PASSPHRASE_NONCE_LEN: usize = 12;
+
 fn get_encryption_key() -> [u8; 32] {
     let device_id = machine_uid::get().unwrap_or_else(|_| "default".to_string());
     let mut key = [0u8; 32];
@@ -119,6 +124,11 @@ fn decrypt_string_v2(encrypted: &str) -> Result<String, String> {
     String::from_utf8(plaintext).map_err(|e| format!("UTF-8 conversion failed: {}", e))
 }
 
+/// 判断字符串是否为 `encrypt_string` 的输出 (本机密钥加密)
+pub fn is_machine_encrypted(value: &str) -> bool {
+    value.starts_with(ENCRYPTED_PREFIX)
+}
+
 pub fn decrypt_string(encrypted: &str) -> Result<String, String> {
     if encrypted.starts_with(ENCRYPTED_V2_PREFIX) {
         decrypt_string_v2(&encrypted[ENCRYPTED_V2_PREFIX.len()..])
@@ -129,6 +139,60 @@ pub fn decrypt_string(encrypted: &str) -> Result<String, String> {
     }
 }
 
+fn derive_passphrase_key(passphrase: &str, salt: &[u8]) -> Result<[u8; 32], String> {
+    let mut key = [0u8; 32];
+    argon2::Argon2::default()
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|e| format!("Key derivation failed: {}", e))?;
+    Ok(key)
+}
+
+/// 使用口令加密任意数据 (Argon2id 派生密钥 + AES-256-GCM)，不依赖本机 machine id
+pub fn encrypt_with_passphrase(plaintext: &[u8], passphrase: &str) -> Result<Vec<u8>, String> {
+    if passphrase.is_empty() {
+        return Err("Passphrase must not be empty".to_string());
+    }
+    let mut salt = [0u8; PASSPHRASE_SALT_LEN];
+    let mut nonce_bytes = [0u8; PASSPHRASE_NONCE_LEN];
+    OsRng.fill_bytes(&mut salt);
+    OsRng.fill_bytes(&mut nonce_bytes);
+
+    let key = derive_passphrase_key(passphrase, &salt)?;
+    let cipher = Aes256Gcm::new(&key.into());
+    let ciphertext = cipher
+        .encrypt(Nonce::from_slice(&nonce_bytes), plaintext)
+        .map_err(|e| format!("Encryption failed: {}", e))?;
+
+    let mut out = Vec::with_capacity(
+        PASSPHRASE_MAGIC.len() + salt.len() + nonce_bytes.len() + ciphertext.len(),
+    );
+    out.extend_from_slice(PASSPHRASE_MAGIC);
+    out.extend_from_slice(&salt);
+    out.extend_from_slice(&nonce_bytes);
+    out.extend_from_slice(&ciphertext);
+    Ok(out)
+}
+
+/// 判断数据是否为 `encrypt_with_passphrase` 的输出
+pub fn is_passphrase_encrypted(data: &[u8]) -> bool {
+    data.starts_with(PASSPHRASE_MAGIC)
+}
+
+pub fn decrypt_with_passphrase(data: &[u8], passphrase: &str) -> Result<Vec<u8>, String> {
+    let header_len = PASSPHRASE_MAGIC.len() + PASSPHRASE_SALT_LEN + PASSPHRASE_NONCE_LEN;
+    if !is_passphrase_encrypted(data) || data.len() < header_len {
+        return Err("Invalid passphrase-encrypted payload".to_string());
+    }
+    let salt = &data[PASSPHRASE_MAGIC.len()..PASSPHRASE_MAGIC.len() + PASSPHRASE_SALT_LEN];
+    let nonce_bytes = &data[PASSPHRASE_MAGIC.len() + PASSPHRASE_SALT_LEN..header_len];
+
+    let key = derive_passphrase_key(passphrase, salt)?;
+    let cipher = Aes256Gcm::new(&key.into());
+    cipher
+        .decrypt(Nonce::from_slice(nonce_bytes), &data[header_len..])
+        .map_err(|_| "Decryption failed: wrong passphrase or corrupted data".to_string())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -170,4 +234,19 @@ mod tests {
         let decrypted = decrypt_string(&legacy_encrypted).unwrap();
         assert_eq!(password, decrypted);
     }
+
+    #[test]
+    fn test_passphrase_round_trip() {
+        let data = b"portable secret payload";
+        let encrypted = encrypt_with_passphrase(data, "correct horse").unwrap();
+        assert!(is_passphrase_encrypted(&encrypted));
+        assert_ne!(&encrypted[PASSPHRASE_MAGIC.len()..], data.as_slice());
+
+        assert_eq!(
+            decrypt_with_passphrase(&encrypted, "correct horse").unwrap(),
+            data
+        );
+        assert!(decrypt_with_passphrase(&encrypted, "wrong").is_err());
+        assert!(decrypt_with_passphrase(data, "correct horse").is_err());
+    }
 }
@@ -1787,7 +1787,7 @@ print(response.choices[0].message.content)`;
                                                 </div>
                                             </div>
                                             <div className="grid grid-cols-1 gap-2">
-                                                {(['CacheFirst', 'Balance', 'PerformanceFirst'] as const).map(mode => (
+                                                {(['CacheFirst', 'Balance', 'PerformanceFirst', 'QuotaWeighted'] as const).map(mode => (
                                                     <label
                                                         key={mode}
                                                         className={`flex items-start gap-3 p-3 rounded-xl border cursor-pointer transition-all duration-200 ${(appConfig.proxy.scheduling?.mode || 'Balance') === mode
@@ -1809,7 +1809,8 @@ print(response.choices[0].message.content)`;
                                                                 {t(`proxy.config.scheduling.modes_desc.${mode}`, {
                                                                     defaultValue: mode === 'CacheFirst' ? 'Binds session to account, waits precisely if limited (Maximizes Prompt Cache hits).' :
                                                                         mode === 'Balance' ? 'Binds session, auto-switches to available account if limited (Balanced cache & availability).' :
-                                                                            'No session binding, pure round-robin rotation (Best for high concurrency).'
+                                                                            mode === 'PerformanceFirst' ? 'No session binding, pure round-robin rotation (Best for high concurrency).' :
+                                                                                'Weighted by remaining model quota, health and reset time (Drains soon-to-reset accounts first).'
                                                                 })}
                                                             </div>
                                                         </div>
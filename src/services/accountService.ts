@@ -216,6 +216,23 @@ export async function exportAccounts(accountIds: string[]): Promise<ExportAccoun
     return await invoke('export_accounts', { accountIds });
 }
 
+// 口令加密的便携导出 (跨设备迁移)
+export interface EncryptedImportResult {
+    formatVersion: number;
+    exportedAt: number;
+    accountIds: string[];
+    proxiesAdded: number;
+    bindingsRestored: number;
+}
+
+export async function exportAccountsEncrypted(accountIds: string[], passphrase: string): Promise<string> {
+    return await invoke('export_accounts_encrypted', { accountIds, passphrase });
+}
+
+export async function importAccountsEncrypted(data: string, passphrase: string): Promise<EncryptedImportResult> {
+    return await invoke('import_accounts_encrypted', { data, passphrase });
+}
+
 // 自定义标签相关
 export async function updateAccountLabel(accountId: string, label: string): Promise<void> {
     return await invoke('update_account_label', { accountId, label });
@@ -6,17 +6,25 @@ export interface UpstreamProxyConfig {
 export interface ProxyConfig {
     enabled: boolean;
     allow_lan_access?: boolean;
+    bind_address?: string;
     auth_mode?: 'off' | 'strict' | 'all_except_health' | 'auto';
     port: number;
     api_key: string;
     admin_password?: string;
     auto_start: boolean;
     custom_mapping?: Record<string, string>;
+    model_fallback_chains?: Record<string, string[]>; // requested model (or wildcard) -> ordered fallback models
     request_timeout: number;
     enable_logging: boolean;
     debug_logging?: DebugLoggingConfig;
+    telemetry?: TelemetryConfig;
+    shutdown?: ShutdownConfig;
+    pricing?: PricingConfig;
+    batch?: BatchConfig;
+    background_tasks?: BackgroundTaskConfig;
     upstream_proxy: UpstreamProxyConfig;
     zai?: ZaiConfig;
+    providers?: UpstreamProviderConfig[];
     scheduling?: StickySessionConfig;
     experimental?: ExperimentalConfig;
     user_agent_override?: string;
@@ -63,9 +71,67 @@ export interface GlobalSystemPromptConfig {
 export interface DebugLoggingConfig {
     enabled: boolean;
     output_dir?: string;
+    record_fixtures?: boolean;
+    upstream_base_url?: string;
 }
 
-export type SchedulingMode = 'CacheFirst' | 'Balance' | 'PerformanceFirst';
+export interface TelemetryConfig {
+    enabled: boolean;
+    otlp_endpoint: string;
+    service_name: string;
+    sample_ratio: number;
+}
+
+export interface ShutdownConfig {
+    drain_timeout_secs: number;
+    retry_after_secs: number;
+    reuse_port: boolean;
+}
+
+// Per-million-token list prices used by the cost report
+export interface ModelPrice {
+    input: number;
+    output: number;
+    cached_input?: number;
+    reasoning?: number;
+}
+
+export interface PricingConfig {
+    currency: string;
+    models: Record<string, ModelPrice>; // model name (or wildcard) -> price
+}
+
+// Local execution of OpenAI Batch API jobs
+export interface BatchConfig {
+    max_concurrency: number;
+    max_retries: number;
+}
+
+export type BackgroundTaskAction =
+    | { type: 'route'; model: string }
+    | { type: 'reply'; text: string };
+
+export interface BackgroundTaskRule {
+    name: string;
+    enabled: boolean;
+    protocols: Array<'claude' | 'openai' | 'gemini'>;
+    client_adapter?: string;
+    user_agent?: string; // 正则
+    min_message_chars?: number;
+    max_message_chars?: number;
+    last_user_message?: string; // 正则
+    system_prompt?: string; // 正则
+    has_tools?: boolean;
+    action: BackgroundTaskAction;
+}
+
+export interface BackgroundTaskConfig {
+    enabled: boolean;
+    dry_run: boolean; // 仅记录将会命中的规则
+    rules: BackgroundTaskRule[];
+}
+
+export type SchedulingMode = 'CacheFirst' | 'Balance' | 'PerformanceFirst' | 'QuotaWeighted';
 
 export interface StickySessionConfig {
     mode: SchedulingMode;
@@ -97,6 +163,19 @@ export interface ZaiConfig {
     mcp: ZaiMcpConfig;
 }
 
+export type ProviderProtocol = 'openai' | 'anthropic' | 'gemini';
+
+export interface UpstreamProviderConfig {
+    name: string;
+    enabled: boolean;
+    base_url: string;
+    api_key: string;
+    protocol: ProviderProtocol;
+    dispatch_mode: ZaiDispatchMode;
+    model_mapping?: Record<string, string>;
+    models?: string[];
+}
+
 export interface ScheduledWarmupConfig {
     enabled: boolean;
     monitored_models: string[];
@@ -148,6 +227,7 @@ export interface AppConfig {
     circuit_breaker: CircuitBreakerConfig; // [NEW] 熔断器配置
     proxy: ProxyConfig;
     cloudflared: CloudflaredConfig; // [NEW] Cloudflared 配置
+    managed_fields?: string[]; // 由环境变量 / 声明式配置文件托管的字段 (只读)
 }
 
 // ============================================================================